@@ -0,0 +1,4 @@
+Copy the input to the output until its end
+(the end of the input leaves the cell unchanged or at zero)
+
+,[.[-],]
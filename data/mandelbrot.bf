@@ -0,0 +1,260 @@
+Mandelbrot set
+with numbers in units of 1/32 on 8 bit cells / each number being
+a pair of cells holding its positive and negative parts
+
+the table from cell 36 holds for each n in 7 cells: two cells of
+workspace / n squared over 32 for n below 64 / 1 for n from 64 /
+n squared over 64 for n below 128 / 1 for n from 128 / and the
+character drawn with n iterations left
+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>++++++++[->>>>>>++++++++<<<<<<]>>>>>
+>>++++++[->>>>>>++++++<<<<<<]>>>>>>>++++++[->>>>>>++++++<<<<<<]>>>>>>>++
++++[->>>>>>+++++++<<<<<<]>>>>>>>>>+<<+++++[->>>>>>+++++++<<<<<<]>>>>>>>>
+>+<<++++++[->>>>>>++++++<<<<<<]>>>>>>+>>>+>>+<<<<++++++[->>>>>>++++++<<<
+<<<]>>>>>>+>>>++>>+<<<<++++++[->>>>>>+++++++<<<<<<]>>>>>>>>>++>>+<<<<+++
++++[->>>>>>+++++++<<<<<<]>>>>>>>>>+++>>+<<<<++++++[->>>>>>+++++++<<<<<<]
+>>>>>>+>>>+++>>++<<<<++++++[->>>>>>+++++++<<<<<<]>>>>>>+>>>++++>>++<<<<+
++++++[->>>>>>++++++++++<<<<<<]>>>>>>+>>>+++++>>++<<<<++++++[->>>>>>+++++
++++++<<<<<<]>>>>>>+>>>+++++>>+++<<<<+++++[->>>>>>+++++++++<<<<<<]>>>>>>>
+>>++++++>>+++<<<<+++++[->>>>>>+++++++++<<<<<<]>>>>>>>>>+++++++>>++++<<<<
++++++++[->>>>>>++++++++<<<<<<]>>>>>>+++>>>++++++++>>++++<<<<+++++++[->>>
+>>>++++++++<<<<<<]>>>>>>+++>>>+++++++++>>+++++<<<<+++++++[->>>>>>+++++++
++<<<<<<]>>>>>>++>>>++++++++++>>+++++<<<<+++++++[->>>>>>++++++++<<<<<<]>>
+>>>>++>>>+++++++++++>>++++++<<<<++++[->>>>>>+++++++++++<<<<<<]>>>>>>>>>+
+++++++++++++>>++++++<<<<++++[->>>>>>+++++++++++<<<<<<]>>>>>>>>>+++++++++
++++++>>+++++++<<<<+++++[->>>>>>+++++++++<<<<<<]>>>>>>+>>>+++++++++++++++
+>>++++++++<<<<+++++[->>>>>>+++++++++<<<<<<]>>>>>>+>++++[->>++++<<]>>+>>+
++++++++<<<<++++[->>>>>>++++++++<<<<<<]>>>>>>>+++[->>++++++<<]>>>>+++++++
+++<<<<++++[->>>>>>++++++++<<<<<<]>>>>>>>++++[->>+++++<<]>>>>++++++++++>>
+>+++[->>+++++++<<]>>>>+++++++++++>>>+++[->>+++++++<<]>>++>>+++++++++++>>
+>+++++[->>+++++<<]>>>>++++++++++++>>>+++++[->>+++++<<]>>+>>+++++++++++++
+>>>++++[->>+++++++<<]>>>>++++++++++++++>>>+++++[->>++++++<<]>>>>++++++++
++++++++>>>++++[->>++++++++<<]++++[->>>>++++<<<<]>>>>>>>++++[->>++++++++<
+<]>>++<<++++[->>>>++++<<<<]>>>>+>>>++++++[->>++++++<<]+++[->>>>++++++<<<
+<]>>>>>>>++++++[->>++++++<<]>>++<<+++[->>>>++++++<<<<]>>>>+>>>+++++[->>+
++++++++<<]>>+<<++++[->>>>+++++<<<<]>>>>>>>++++++[->>+++++++<<]>>+<<+++[-
+>>>>+++++++<<<<]>>>>>>>+++++[->>+++++++++<<]+++[->>>>+++++++<<<<]>>>>++>
+>>++++++[->>++++++++<<]++++[->>>>++++++<<<<]>>>>>>>+++++[->>++++++++++<<
+]+++++[->>>>+++++<<<<]>>>>>>>++++[->>+++++++++++++<<]>>+<<+++++[->>>>+++
+++<<<<]>>>>+>>>+++++[->>+++++++++++<<]++++[->>>>+++++++<<<<]>>>>>>>+++++
+++[->>++++++++<<]>>++<<++++[->>>>+++++++<<<<]>>>>+>>>++++++[->>+++++++++
++<<]>>+<<+++++[->>>>++++++<<<<]>>>>>>>+++++++[->>+++++++++<<]++++[->>>>+
++++++++<<<<]>>>>>>>++++++[->>+++++++++++<<]++++[->>>>++++++++<<<<]>>>>+>
+>>++++++[->>+++++++++++<<]>>+++<<+++++[->>>>+++++++<<<<]>>>>>>>++++++++[
+->>+++++++++<<]++++++[->>>>++++++<<<<]>>>>>>>+++++[->>+++++++++++++++<<]
+++++++[->>>>++++++<<<<]>>>>++>>>++++++[->>+++++++++++++<<]++++++[->>>>++
+++++<<<<]>>>>+++>>>+++++++++[->>+++++++++<<]+++++[->>>>++++++++<<<<]>>>>
++>>>+++++++[->>++++++++++++<<]>>+<<++++++[->>>>+++++++<<<<]>>>>>>>++++++
+++[->>+++++++++++<<]++++[->>>>+++++++++++<<<<]>>>>>>>+++++++[->>++++++++
++++++<<]+++++[->>>>+++++++++<<<<]>>>>+>>>+++++[->>+++++++++++++++++++<<]
++++++[->>>>+++++++++<<<<]>>>>++>>>+++++++[->>++++++++++++++<<]+++++++[->
+>>>+++++++<<<<]>>>>>>>++++++++++[->>++++++++++<<]>>++<<+++++[->>>>++++++
+++++<<<<]>>>>+>>>+++++++[->>+++++++++++++++<<]++++[->>>>+++++++++++++<<<
+<]>>>>+>>>+++++++++[->>++++++++++++<<]>>+<<++++++[->>>>+++++++++<<<<]>>>
+>>>>++++++++[->>++++++++++++++<<]>>+<<+++++++[->>>>++++++++<<<<]>>>>>>>+
++++++++[->>++++++++++++++<<]>>++++<<+++++++[->>>>++++++++<<<<]>>>>++>>>+
++++++++++[->>++++++++++++<<]++++++[->>>>++++++++++<<<<]>>>>>>>++++++++++
++[->>+++++++++++<<]>>+++<<++++++[->>>>++++++++++<<<<]>>>>++>>>>>>+<<<+++
++++++[->>>>++++++++<<<<]>>>>>>>>>>+<<<++++++[->>>>+++++++++++<<<<]>>>>>>
+>>>>+<<<++++++[->>>>+++++++++++<<<<]>>>>++>>>>>>+<<<+++++++[->>>>+++++++
++++<<<<]>>>>>>>>>>+<<<++++++++[->>>>+++++++++<<<<]>>>>>>>>>>+<<<++++++++
+[->>>>+++++++++<<<<]>>>>++>>>>>>+<<<+++++++[->>>>+++++++++++<<<<]>>>>>>>
+>>>+<<<++++++[->>>>+++++++++++++<<<<]>>>>+>>>>>>+<<<+++++++++[->>>>+++++
+++++<<<<]>>>>>>>>>>+<<<+++++++++[->>>>+++++++++<<<<]>>>>++>>>>>>+<<<++++
++++[->>>>++++++++++++<<<<]>>>>++>>>>>>+<<<++++++++[->>>>+++++++++++<<<<]
+>>>>>>>>>>+<<<+++++++++[->>>>++++++++++<<<<]>>>>>>>>>>+<<<+++++++[->>>>+
+++++++++++++<<<<]>>>>++>>>>>>+<<<+++++[->>>>+++++++++++++++++++<<<<]>>>>
+>>>>>>+<<<+++++++[->>>>++++++++++++++<<<<]>>>>>>>>>>+<<<++++++++++[->>>>
+++++++++++<<<<]>>>>>>>>>>+<<<++++++++++[->>>>++++++++++<<<<]>>>>+++>>>>>
+>+<<<+++++++[->>>>+++++++++++++++<<<<]>>>>>>>>>>+<<<+++++++++[->>>>+++++
++++++++<<<<]>>>>>>>>>>+<<<++++++++++[->>>>+++++++++++<<<<]>>>>>>>>>>+<<<
+++++++++[->>>>++++++++++++++<<<<]>>>>+>>>>>>+<<<++++++++[->>>>++++++++++
+++++<<<<]>>>>++++>>>>>>+<<<+++++++++[->>>>+++++++++++++<<<<]>>>>+>>>>>>+
+<<<+++++++++++[->>>>+++++++++++<<<<]>>>>>>>>>>+<<<+++++++++++[->>>>+++++
+++++++<<<<]>>>>+++>>>>>>+<<<+++++++++[->>>>++++++++++++++<<<<]>>>>+>>>>>
+>+<<<++++++++[->>>>++++++++++++++++<<<<]>>>>+>>>>>>+<<<+++++++++++[->>>>
+++++++++++++<<<<]>>>>>>>>>>+<<<+++++++++[->>>>+++++++++++++++<<<<]>>>>>>
+>>>>+<<<++++++++[->>>>+++++++++++++++++<<<<]>>>>++>>>>>>+<<<++++++++++[-
+>>>>++++++++++++++<<<<]>>>>+>>>>>>+<<<++++++++++++[->>>>++++++++++++<<<<
+]>>>>>>>>>>+<<<++++++++++++[->>>>++++++++++++<<<<]>>>>+++>>>>>>+<<<+++++
++++++[->>>>+++++++++++++++<<<<]>>>>>>>>>>+<<<+++++++++[->>>>++++++++++++
++++++<<<<]>>>>>>>>>>+<<<++++++++++++[->>>>+++++++++++++<<<<]>>>>>>>>>>+<
+<<++++++++++++[->>>>+++++++++++++<<<<]>>>>+++>>>>>>+<<<+++++++++[->>>>++
+++++++++++++++++<<<<]>>>>+>>>>>>+<<<+++++++++++[->>>>+++++++++++++++<<<<
+]>>>>+>>>>>>+<<<+++++++++++++[->>>>+++++++++++++<<<<]>>>>>>>>>>+<<<+++++
+++++[->>>>+++++++++++++++++++<<<<]>>>>+>>>>>>+<<<+++++++++++[->>>>++++++
+++++++++++<<<<]>>>>>>>>>>+<<<+++++++++++[->>>>++++++++++++++++<<<<]>>>>+
+++>>>>>>+<<<+++++++++++++[->>>>++++++++++++++<<<<]>>>>>>>>>>+<<<++++++++
++++++[->>>>++++++++++++++<<<<]>>>>++++>>>>>>+<<<+++++++++[->>>>+++++++++
+++++++++++++<<<<]>>>>>>>>>>+<<<++++++++++++[->>>>++++++++++++++++<<<<]>>
+>>+>>>>>>+<<<++++++++++++++[->>>>++++++++++++++<<<<]>>>>>>>>>>+<<<++++++
+++++[->>>>++++++++++++++++++++<<<<]>>>>>>>>>>+<<<++++++++++[->>>>+++++++
++++++++++++++<<<<]>>>>+++>>>>>>+<<<+++++++++[->>>>++++++++++++++++++++++
++<<<<]>>>>>>>>>>+<<<++++++++++++++[->>>>+++++++++++++++<<<<]>>>>>>>>>>+<
+<<++++++++++++++[->>>>+++++++++++++++<<<<]>>>>++++>>>>>>+<<<++++++++++++
+[->>>>++++++++++++++++++<<<<]>>>>++>>>>>>+<<<+++++++++++++[->>>>++++++++
++++++++++<<<<]>>>>>>>>>>+<<<+++++++++++++++[->>>>+++++++++++++++<<<<]>>>
+>>>>>>>+<<<++++++++++++[->>>>+++++++++++++++++++<<<<]>>>>+>>>>>>+<<<++++
++++++++[->>>>+++++++++++++++++++++<<<<]>>>>++>>>>>>+<<<+++++++++++++[->>
+>>++++++++++++++++++<<<<]>>>>++>>>>>>+<<<+++++++++++++++[->>>>++++++++++
+++++++<<<<]>>>>>>>>>>+<<<+++++++++++[->>>>++++++++++++++++++++++<<<<]>>>
+>++>>>>>>+<<<+++++++++++++[->>>>+++++++++++++++++++<<<<]>>>>+>>>>>>+<<<+
++++++++++++++[->>>>++++++++++++++++++<<<<]>>>>>>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>
+>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+>>>>>+>>+
+
+rows
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<<<<<++++++++++++++++++++++++++++++++++++>>>>>>>>>>>>>>>>+++
+++++++++++++++++++++++++++++++++++[
+columns
+<<<<<<<<<<<<<<<<<<++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++>>>>>>>>>>>>>>>>>>>+++++++++++++++++++++++++++++++++++++++++++
++++++++++++++++++++++++++++++++++++++[
+iterate z from 0 while it stays within 2
+<<<<<<<++++++++++++++++++++++++<+[
+absolute values and signs
+<<<<<<<<<<<<<<<<[->>>>>>>>>>+>>>>>>>>+<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>
+>>>[-<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<[->>>>>>>>>+
+>>>>>>>>+<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<+>>>>>>>>
+>>>>>>>>>]<<<<<<<<<<<<<<<<<[->>>>>>>>>>>+>>>>>>+<<<<<<<<<<<<<<<<<]>>>>>>
+>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>]<<<<<<[[-]>>>>>>+<<<<<<
+]>>>>>>[-<<<<<<+>>>>>>]<<<<<<<<<<<<<<<<[->>>>>>>>>+>>>>>>>+<<<<<<<<<<<<<
+<<<]>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<[
+->>>>>>>>+>>>>>>>+<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<+>>>>>
+>>>>>>>>>>]<<<<<<<<<<<<<<<[->>>>>>>>>>+>>>>>+<<<<<<<<<<<<<<<]>>>>>>>>>>>
+>>>>[-<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>]<<<<<[[-]>>>>>+<<<<<]>>>>>[-<<<<<+
+>>>>>]
+escape when a part reaches 2
+<<<<<<<<[->>>>>>>>>+<+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]>[->>>>>>>>>>
+>>>>>+<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-[->>>>>>>+<<<<<<<]+>>>>>>>]>>>[-<
+<+<+>>>]<<<[->>>+<<<]<<<<<<<[->>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<<<<<<<<<]
+>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<
+<<<<[->>>>>>>>+<+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]>[->>>>>>>>>>>>>>>+<<<
+<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-[->>>>>>>+<<<<<<<]+>>>>>>>]>>>[-<<+<+>>>]<
+<<[->>>+<<<]<<<<<<<[->>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<<<<<<<<<]>>>>>>>>[
+-<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>]
+squares and escape when their sum reaches 4
+<<<<<<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>+<+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>
+>>>]>[->>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-[->>>>>>>+<<<<<<
+<]+>>>>>>>]>>[-<+<+>>]<<[->>+<<]<<<<<<<[->>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<
+<<<<<<<<<<]>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>
+>>>>]<<<<<<<<<<<<<<<<<<<<<<<<[->>>>>>>>+<+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>
+>>]>[->>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-[->>>>>>>+<<<<<<<
+]+>>>>>>>]>>[-<+<+>>]<<[->>+<<]<<<<<<<[->>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<
+<<<<<<<<<]>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>
+>]<<<<<<<<<<<<<<<<<<<<<<<<<<<[->>>>>>+>>>>+<<<<<<<<<<]>>>>>>>>>>[-<<<<<<
+<<<<+>>>>>>>>>>]<<<<<<<<<[->>>>>+>>>>+<<<<<<<<<]>>>>>>>>>[-<<<<<<<<<+>>>
+>>>>>>]<<<<[->>>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>
+>>>[-[->>>>>>>+<<<<<<<]+>>>>>>>]>>>>>[-<<<<+<+>>>>>]<<<<<[->>>>>+<<<<<]<
+<<<<<<[->>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<<<<<<<<<]>>>>>>>>[-<<<<<<<<<<<<
+<<<<<<<<+>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<+<<<<<<<<<<<[>[-]>>>>>>>>>>[-]<<<
+<<<<<<<<<<<<<<<[-]>[-]>[-]>[-]>[-]>[-]>>[-]]>>>>>>>>>>>[
+count the iteration
+<<<<<<<<<-[->>+>+<<<]>>>[-<<<+>>>]+<[>-<[-]]>[<<<<[-]>>>>[-]]
+twice the product of the parts from the squares of their sum and difference
+<<<<<<<<<<<<<<<<<<<<[-]>[-]>[-]>[-]>>>>>>>[->>>>+>>>>+<<<<<<<<]>>>>>>>>[
+-<<<<<<<<+>>>>>>>>]<<<<<<<[->>>+>>>>+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<
+<<[->>>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>[-[->>
+>>>>>+<<<<<<<]+>>>>>>>]>>>>[-<<<+<+>>>>]<<<<[->>>>+<<<<]<<<<<<<[->>>>>>>
+>[-<<<<<<<+>>>>>>>]<<<<<<<<<<<<<<<]>>>>>>>>[-<<<<<<<<<<+>>>>>>>>>>]<<<<<
+<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>+>+<<<<<<<<<<<<]>>>>>>>>>>>>[-<<<<<<<<<
+<<<+>>>>>>>>>>>>]<[<<<<<<<<<<[->>>>>>>>>>>+<<+<<<<<<<<<]>>>>>>>>>[-<<<<<
+<<<<+>>>>>>>>>]>>[<<+>>[-]]<[-]]<[<<<<<<<<<<->->>>>>>>>>[-]<<<<<<<<<<[->
+>>>>>>>>>>+>+<<<<<<<<<<<<]>>>>>>>>>>>>[-<<<<<<<<<<<<+>>>>>>>>>>>>]<[<<<<
+<<<<<<[->>>>>>>>>>>+<<+<<<<<<<<<]>>>>>>>>>[-<<<<<<<<<+>>>>>>>>>]>>[<<+>>
+[-]]<[-]]<]<<<<<<<<<[-<+>]<[->>>>>>>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<<<
+<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>[-[->>>>>>>+<<<<<<<]+>>>>>>>]>>>>[-<<<+<+
+>>>>]<<<<[->>>>+<<<<]<<<<<<<[->>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<<<<<<<<<]
+>>>>>>>>[-<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<[->>>>>>-<<<
+<<<]
+with the sign of the product
+<<<<<<[-<+>]<[->>>>>>>+<+<<<<<<]>>>>>>[-<<<<<<+>>>>>>]+>[<->[-]]+<<<<<<<
+[->>>>>>>-<<<<<<<]>>>>>>>[>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>
+>>>>>>>>>]<<<<<<[-]]>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>
+>]<<<<<<<[-]
+add c
+<<<<<<<<<<<<[-<<<<+>>>>>>>>>>>>>>>>+<<<<<<<<<<<<]>>>>>>>>>>>>[-<<<<<<<<<
+<<<+>>>>>>>>>>>>]<<<<<<<<<<<[-<<<<+>>>>>>>>>>>>>>>+<<<<<<<<<<<]>>>>>>>>>
+>>[-<<<<<<<<<<<+>>>>>>>>>>>]<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>+>+<<<<
+<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>
+>>>>>>>>]<[<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>+<<+<<<<<<<<<<<<<<<<<]
+>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>]>>[<<+>>[-]]<[-]]
+<[<<<<<<<<<<<<<<<<<<->->>>>>>>>>>>>>>>>>[-]<<<<<<<<<<<<<<<<<<[->>>>>>>>>
+>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<
+<<<<+>>>>>>>>>>>>>>>>>>>>]<[<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>+<<+<
+<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>]
+>>[<<+>>[-]]<[-]]<]<<<<<<<<<<<<[-<<<<<<<<+>>>>>>>>]>[-<<<<<<<<+>>>>>>>>]
+<<<<<[-<<<<+>>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<]>>>>>>>>>>>>>>[-<<<<<<<<<<
+<<<<+>>>>>>>>>>>>>>]<<<<<<<<<<<<<[-<<<<+>>>>>>>>>>>>>>>>>+<<<<<<<<<<<<<]
+>>>>>>>>>>>>>[-<<<<<<<<<<<<<+>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<[->>>>>>>>>
+>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<
+<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>]<[<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>
+>>>>>>>>>+<<+<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<
+<+>>>>>>>>>>>>>>>>>>>]>>[<<+>>[-]]<[-]]<[<<<<<<<<<<<<<<<<<<<<->->>>>>>>>
+>>>>>>>>>>>[-]<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<
+<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>
+>>>>>>>>]<[<<<<<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>>>>>+<<+<<<<<<<<<<<<<<
+<<<<<]>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>]>>[<<
++>>[-]]<[-]]<]>>>>>>[-]]<<<<<<<<<<]
+draw the character of the iterations left
+<<<<<<<<<<<<<<<<[-]>[-]>[-]>[-]>>>>>>>>>>>>>>[->>>>>>>>>>>>>>>>>+<<<<<<<
+<<<<<<<<<<]>>>>>>>>>>>>>>>>>[-[->>>>>>>+<<<<<<<]+>>>>>>>]>>>>>>[-<<<<<+<
++>>>>>>]<<<<<<[->>>>>>+<<<<<<]<<<<<<<[->>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<<
+<<<<<<<<]>>>>>>>>[-<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<.[-
+]
+next column
+<<<<<<<<<<<<<<<+[->>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>
+>>>[-<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>]<[<<<<<<<<<<<<<<<<[->>>>>>>>>
+>>>>>>>>+<<+<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<+>>>>>>>>>>>
+>>>>]>>[<<+>>[-]]<[-]]<[<<<<<<<<<<<<<<<<->->>>>>>>>>>>>>>>[-]<<<<<<<<<<<
+<<<<<[->>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>[-<<<<<<
+<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>]<[<<<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>+<<
++<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>]>>[<<+
+>>[-]]<[-]]<]>>>>-]
+next row
+<<<<<<<<<<<<<<<<<<<<[-]>[-]>>>>>>>>>>>>>>++++++++++.[-]<<<<<<<<<<<<<++[-
+>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<+>>
+>>>>>>>>>>>>>>]<[<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>+<<+<<<<<<<<<<<<<]>>>>>>
+>>>>>>>[-<<<<<<<<<<<<<+>>>>>>>>>>>>>]>>[<<+>>[-]]<[-]]<[<<<<<<<<<<<<<<->
+->>>>>>>>>>>>>[-]<<<<<<<<<<<<<<[->>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<]>>>>
+>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>]<[<<<<<<<<<<<<<<[->>>>>>
+>>>>>>>>>+<<+<<<<<<<<<<<<<]>>>>>>>>>>>>>[-<<<<<<<<<<<<<+>>>>>>>>>>>>>]>>
+[<<+>>[-]]<[-]]<]>>>-]
@@ -0,0 +1,8 @@
+Sierpinski triangle
+by Daniel B Cristofani (brainfuck dot org)
+
+++++++++[>+>++++<<-]>++>>+<[-[>>+<<-]+>>]>+[
+    -<<<[
+        ->[+[-]+>++>>>-<<]<[<]>>++++++[<<+++++>>-]+<<++.[-]<<
+    ]>.>+[>>]>+
+]
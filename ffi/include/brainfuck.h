@@ -0,0 +1,143 @@
+/*
+ * C interface of the brainfuck compiler and interpreter, implemented by
+ * libbrainfuck_ffi
+ *
+ * Kept by hand in the layout cbindgen writes for ffi/src/lib.rs.
+ */
+
+#ifndef BRAINFUCK_H
+#define BRAINFUCK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The program ended */
+#define BF_DONE 0
+/* The program goes on after this step */
+#define BF_RUNNING 1
+/* The pointer left the tape */
+#define BF_OUT_OF_TAPE -1
+/* The program wrote more than its limit */
+#define BF_OUTPUT_LIMIT -2
+/* A procedure of pbrain was called before being defined */
+#define BF_UNKNOWN_PROCEDURE -3
+/* The input or the output failed */
+#define BF_IO_ERROR -4
+/* A pointer given to the function was null */
+#define BF_INVALID_ARGUMENT -5
+/* The loops and the calls of procedures nested too deeply */
+#define BF_TOO_DEEP -6
+
+/* "," leaves the cell unchanged at the end of the input */
+#define BF_EOF_UNCHANGED 0
+/* "," sets the cell to 0 at the end of the input */
+#define BF_EOF_ZERO 1
+/* "," sets the cell to -1 at the end of the input */
+#define BF_EOF_MINUS_ONE 2
+
+/* A compiled program, and its run */
+typedef struct BfProgram BfProgram;
+
+/*
+ * Function reading a byte for ",", returning it, -1 at the end of the
+ * input, or -2 on an error
+ */
+typedef int (*BfReadCallback)(void *user_data);
+
+/* Function writing the byte of ".", returning 0, or -1 on an error */
+typedef int (*BfWriteCallback)(void *user_data, uint8_t byte);
+
+/*
+ * Compile a NUL-terminated brainfuck source at some optimization level,
+ * ready to run on an empty input
+ *
+ * Returns null on an invalid source, storing in `error_offset`, when it
+ * isn't null, the byte offset of the first unmatched bracket, of the first
+ * one nested too deeply, or of the first invalid UTF-8 sequence.
+ */
+BfProgram *bf_compile(const char *source, int opt_level, size_t *error_offset);
+
+/*
+ * Start a program over on a blank tape, for `bf_step`, reading
+ * `input_length` bytes of `input`
+ */
+int bf_reset(BfProgram *program, const uint8_t *input, size_t input_length);
+
+/*
+ * Read the input of a program, and write its output, through functions of
+ * the caller from its next start, each one being null to keep the buffer
+ * it replaces
+ *
+ * The functions get `user_data` as their first argument.
+ */
+int bf_set_io(BfProgram *program,
+              BfReadCallback read,
+              BfWriteCallback write,
+              void *user_data);
+
+/*
+ * Set what "," does at the end of the input from the next start of a
+ * program, one of the `BF_EOF_*` modes
+ */
+int bf_set_eof(BfProgram *program, int eof);
+
+/*
+ * Set the cells of the tape from the next start of a program, 30000 by
+ * default
+ */
+int bf_set_tape_size(BfProgram *program, size_t cells);
+
+/*
+ * Run a program to its end on a blank tape, reading `input_length` bytes
+ * of `input`
+ *
+ * Returns `BF_DONE`, or the error stopping the program.
+ */
+int bf_run(BfProgram *program, const uint8_t *input, size_t input_length);
+
+/*
+ * Run the next node of a program
+ *
+ * Returns `BF_RUNNING` while the program goes on, then `BF_DONE`, or the
+ * error stopping the program.
+ */
+int bf_step(BfProgram *program);
+
+/*
+ * Output of the program since the previous call, or since it started,
+ * storing its length in `length`
+ *
+ * The output is taken out of the buffer of the program, each byte being
+ * given once. The bytes stay valid until the next call on the program.
+ */
+const uint8_t *bf_output(BfProgram *program, size_t *length);
+
+/*
+ * Tape of a program, storing its length in `length` and the index of the
+ * pointer in `index`
+ *
+ * The cells stay valid until the next call on the program.
+ */
+const uint32_t *bf_tape(BfProgram *program, size_t *length, size_t *index);
+
+/*
+ * Allocate `length` bytes, for the hosts which can only hand over data in
+ * the memory of the library, such as WebAssembly
+ */
+uint8_t *bf_alloc(size_t length);
+
+/* Free bytes allocated by `bf_alloc` */
+void bf_dealloc(uint8_t *data, size_t length);
+
+/* Free a program */
+void bf_free(BfProgram *program);
+
+#ifdef __cplusplus
+} /* extern "C" */
+#endif
+
+#endif /* BRAINFUCK_H */
@@ -0,0 +1,67 @@
+// Types of the brainfuck WebAssembly package
+
+/** Source or input, as a string encoded in UTF-8 or as bytes */
+export type Bytes = string | Uint8Array | ArrayBuffer;
+
+export interface CompileOptions {
+  /** Optimization level, from 0 to 3 */
+  optLevel?: number;
+  /** What "," does at the end of the input, leaving the cell unchanged by default */
+  eof?: "unchanged" | "zero" | "minus-one";
+  /** Cells of the tape, 30000 by default */
+  tapeSize?: number;
+}
+
+export interface RunOptions {
+  /** Output of the program as it comes */
+  onOutput?: (output: Uint8Array) => void;
+  /** Steps run between two calls of onOutput */
+  chunk?: number;
+}
+
+/** A source with an unmatched bracket */
+export class ParseError extends Error {
+  readonly offset: number;
+}
+
+/** An error stopping a program */
+export class RunError extends Error {
+  readonly kind:
+    | "OutOfTape"
+    | "OutputLimit"
+    | "UnknownProcedure"
+    | "Io"
+    | "InvalidArgument";
+}
+
+/** A compiled program, and its run */
+export class Program {
+  /** Start the program over on a blank tape, for `step` */
+  reset(input?: Bytes): void;
+  /** Run the next node, returning whether the program goes on */
+  step(): boolean;
+  /** Run the program to its end on a blank tape, returning its output */
+  run(input?: Bytes, options?: RunOptions): Uint8Array;
+  /** Output of the program since it started */
+  readonly output: Uint8Array;
+  /** Cells of the tape, and index of the pointer */
+  tape(): { cells: Uint32Array; index: number };
+  /** Free the program, before the garbage collector does */
+  free(): void;
+}
+
+/** The library, loaded in a WebAssembly instance */
+export class Brainfuck {
+  /** Compile a brainfuck source */
+  compile(source: Bytes, options?: CompileOptions): Program;
+  /** Run a brainfuck source on some input, returning its output */
+  run(source: Bytes, input?: Bytes, options?: CompileOptions & RunOptions): Uint8Array;
+}
+
+/**
+ * Load the library, from brainfuck_ffi.wasm next to this module by default,
+ * or from a URL, a response, bytes or a compiled module
+ */
+export function load(
+  wasm?: URL | string | Response | BufferSource | WebAssembly.Module,
+): Promise<Brainfuck>;
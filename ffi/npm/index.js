@@ -0,0 +1,242 @@
+// Brainfuck compiler and interpreter, through the C interface of
+// brainfuck_ffi.wasm
+
+const DONE = 0;
+const RUNNING = 1;
+const ERRORS = {
+  [-1]: "OutOfTape",
+  [-2]: "OutputLimit",
+  [-3]: "UnknownProcedure",
+  [-4]: "Io",
+  [-5]: "InvalidArgument",
+  [-6]: "TooDeep",
+};
+const EOFS = { unchanged: 0, zero: 1, "minus-one": 2 };
+
+/** A source with an unmatched bracket, or brackets nested too deeply */
+export class ParseError extends Error {
+  constructor(offset) {
+    super(`invalid bracket at byte ${offset}`);
+    this.name = "ParseError";
+    this.offset = offset;
+  }
+}
+
+/** An error stopping a program */
+export class RunError extends Error {
+  constructor(status) {
+    super(ERRORS[status] ?? `status ${status}`);
+    this.name = "RunError";
+    this.kind = ERRORS[status];
+  }
+}
+
+const encoder = new TextEncoder();
+
+/** The library, loaded in a WebAssembly instance */
+export class Brainfuck {
+  constructor(instance) {
+    this.exports = instance.exports;
+    this.programs = new FinalizationRegistry((program) =>
+      this.exports.bf_free(program),
+    );
+  }
+
+  /** Compile a brainfuck source, as a string or bytes */
+  compile(source, { optLevel = 2, eof = "unchanged", tapeSize = 30000 } = {}) {
+    const bytes =
+      typeof source === "string" ? encoder.encode(source) : new Uint8Array(source);
+    const text = this.copyIn(new Uint8Array(bytes.length + 1));
+    this.view(Uint8Array, text.ptr, bytes.length).set(bytes);
+    const offset = this.exports.bf_alloc(4);
+    try {
+      const program = this.exports.bf_compile(text.ptr, optLevel, offset);
+      if (program === 0) {
+        throw new ParseError(this.word(offset));
+      }
+      if (
+        this.exports.bf_set_eof(program, EOFS[eof] ?? -1) < DONE ||
+        this.exports.bf_set_tape_size(program, tapeSize) < DONE
+      ) {
+        this.exports.bf_free(program);
+        throw new RangeError(`invalid eof ${eof} or tape size ${tapeSize}`);
+      }
+      return new Program(this, program);
+    } finally {
+      this.exports.bf_dealloc(offset, 4);
+      text.free();
+    }
+  }
+
+  /** Run a brainfuck source on some input, returning its output */
+  run(source, input = new Uint8Array(), options = {}) {
+    const program = this.compile(source, options);
+    try {
+      return program.run(input, options);
+    } finally {
+      program.free();
+    }
+  }
+
+  // View of the memory of the instance, which is replaced when it grows
+  view(type, ptr, length) {
+    return new type(this.exports.memory.buffer, ptr, length);
+  }
+
+  // A size_t of the memory of the instance, 32 bits wide on wasm32
+  word(ptr) {
+    return new DataView(this.exports.memory.buffer).getUint32(ptr, true);
+  }
+
+  // Bytes copied in the memory of the instance
+  copyIn(bytes) {
+    const ptr = this.exports.bf_alloc(bytes.length);
+    this.view(Uint8Array, ptr, bytes.length).set(bytes);
+    return {
+      ptr,
+      length: bytes.length,
+      free: () => this.exports.bf_dealloc(ptr, bytes.length),
+    };
+  }
+
+  // Output of a function filling a length, and maybe an index, in the
+  // memory of the instance
+  withLengths(fill) {
+    const lengths = this.exports.bf_alloc(8);
+    try {
+      const ptr = fill(lengths, lengths + 4);
+      return { ptr, length: this.word(lengths), index: this.word(lengths + 4) };
+    } finally {
+      this.exports.bf_dealloc(lengths, 8);
+    }
+  }
+}
+
+/** A compiled program, and its run */
+export class Program {
+  constructor(brainfuck, ptr) {
+    this.brainfuck = brainfuck;
+    this.ptr = ptr;
+    this.written = new Uint8Array(); // Output taken out of the library so far
+    brainfuck.programs.register(this, ptr, this);
+  }
+
+  check(status) {
+    if (status < DONE) {
+      throw new RunError(status);
+    }
+    return status;
+  }
+
+  withInput(input, call) {
+    const bytes =
+      typeof input === "string" ? encoder.encode(input) : new Uint8Array(input);
+    const data = this.brainfuck.copyIn(bytes);
+    try {
+      return call(data.ptr, data.length);
+    } finally {
+      data.free();
+    }
+  }
+
+  /** Start the program over on a blank tape, for `step` */
+  reset(input = new Uint8Array()) {
+    const { exports } = this.brainfuck;
+    this.written = new Uint8Array();
+    this.withInput(input, (ptr, length) => exports.bf_reset(this.ptr, ptr, length));
+  }
+
+  /** Run the next node, returning whether the program goes on */
+  step() {
+    return this.check(this.brainfuck.exports.bf_step(this.ptr)) === RUNNING;
+  }
+
+  /**
+   * Run the program to its end on a blank tape, returning its output
+   *
+   * With `onOutput`, the program runs by `chunk` steps, giving their output
+   * as it comes.
+   */
+  run(input = new Uint8Array(), { onOutput, chunk = 65536 } = {}) {
+    const { exports } = this.brainfuck;
+    if (onOutput === undefined) {
+      this.written = new Uint8Array();
+      this.withInput(input, (ptr, length) =>
+        this.check(exports.bf_run(this.ptr, ptr, length)),
+      );
+      return this.output;
+    }
+
+    this.reset(input);
+    let running = true;
+    while (running) {
+      for (let i = 0; running && i < chunk; i++) {
+        running = this.step();
+      }
+      const output = this.takeOutput();
+      if (output.length > 0) {
+        onOutput(output);
+      }
+    }
+    return this.output;
+  }
+
+  /** Output of the program since it started */
+  get output() {
+    this.takeOutput();
+    return this.written.slice();
+  }
+
+  // Output written since it was last taken out of the library
+  takeOutput() {
+    const { exports } = this.brainfuck;
+    const { ptr, length } = this.brainfuck.withLengths((length) =>
+      exports.bf_output(this.ptr, length),
+    );
+    const output = this.brainfuck.view(Uint8Array, ptr, length).slice();
+    const written = new Uint8Array(this.written.length + output.length);
+    written.set(this.written);
+    written.set(output, this.written.length);
+    this.written = written;
+    return output;
+  }
+
+  /** Cells of the tape, and index of the pointer */
+  tape() {
+    const { exports } = this.brainfuck;
+    const { ptr, length, index } = this.brainfuck.withLengths((length, index) =>
+      exports.bf_tape(this.ptr, length, index),
+    );
+    return { cells: this.brainfuck.view(Uint32Array, ptr, length).slice(), index };
+  }
+
+  /** Free the program, before the garbage collector does */
+  free() {
+    if (this.ptr !== 0) {
+      this.brainfuck.programs.unregister(this);
+      this.brainfuck.exports.bf_free(this.ptr);
+      this.ptr = 0;
+    }
+  }
+}
+
+/**
+ * Load the library, from brainfuck_ffi.wasm next to this module by default,
+ * or from a URL, a response, bytes or a compiled module
+ */
+export async function load(wasm = new URL("./brainfuck_ffi.wasm", import.meta.url)) {
+  if (wasm instanceof URL && wasm.protocol === "file:") {
+    const { readFile } = await import("node:fs/promises");
+    wasm = await readFile(wasm);
+  } else if (wasm instanceof URL || typeof wasm === "string") {
+    wasm = await fetch(wasm);
+  }
+  if (typeof Response !== "undefined" && wasm instanceof Response) {
+    wasm = await wasm.arrayBuffer();
+  }
+  const instance =
+    wasm instanceof WebAssembly.Module
+      ? await WebAssembly.instantiate(wasm)
+      : (await WebAssembly.instantiate(wasm)).instance;
+  return new Brainfuck(instance);
+}
@@ -0,0 +1,50 @@
+// Smoke test of the package, run by `npm test` once the library is built
+
+import assert from "node:assert/strict";
+import { load, ParseError, RunError } from "./index.js";
+
+const decoder = new TextDecoder();
+const brainfuck = await load();
+
+// A run to the end, on some input
+const output = brainfuck.run(",.,.[-]++++++++[>++++<-]>+.", "ok");
+assert.equal(decoder.decode(output), "ok!");
+
+// The output as it comes
+const program = brainfuck.compile("++++++++[>++++++++<-]>+.+.+.");
+const chunks = [];
+const written = program.run("", {
+  onOutput: (chunk) => chunks.push(decoder.decode(chunk)),
+  chunk: 1,
+});
+assert.deepEqual(chunks, ["A", "B", "C"]);
+assert.equal(decoder.decode(written), "ABC");
+program.free();
+
+// A node at a time, then the tape
+const stepped = brainfuck.compile(",[.,]+");
+stepped.reset("hi\0");
+while (stepped.step()) {}
+assert.equal(decoder.decode(stepped.output), "hi");
+const { cells, index } = stepped.tape();
+assert.deepEqual([cells[0], cells.length, index], [1, 30000, 0]);
+stepped.free();
+
+// The settings of the tape
+const configured = brainfuck.compile(",+.", { eof: "minus-one", tapeSize: 16 });
+assert.deepEqual([...configured.run()], [0]);
+assert.equal(configured.tape().cells.length, 16);
+configured.free();
+assert.throws(() => brainfuck.compile("", { eof: "never" }), RangeError);
+
+// The errors
+assert.throws(
+  () => brainfuck.compile("+[.]]"),
+  (error) => error instanceof ParseError && error.offset === 4,
+);
+assert.throws(
+  () => brainfuck.run("<", "", { optLevel: 0 }),
+  (error) => error instanceof RunError && error.kind === "OutOfTape",
+);
+
+console.log("ok");
@@ -0,0 +1,418 @@
+//! C interface of the brainfuck compiler and interpreter, declared by
+//! `include/brainfuck.h`
+//!
+//! A `BfProgram` owns a compiled program and the tape, the input and the
+//! output of its run, which goes to the end with `bf_run` or a node at a
+//! time with `bf_step`.
+
+use std::ffi::CStr;
+use std::io::{self, Read, Write};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::slice;
+
+use brainfuck::lexer::ParseError;
+use brainfuck::vm::{run_ast, Eof, RuntimeError, SharedBuffer, State, Stepper};
+use brainfuck::{Compiler, Program};
+
+/// The program ended
+pub const BF_DONE: c_int = 0;
+/// The program goes on after this step
+pub const BF_RUNNING: c_int = 1;
+/// The pointer left the tape
+pub const BF_OUT_OF_TAPE: c_int = -1;
+/// The program wrote more than its limit
+pub const BF_OUTPUT_LIMIT: c_int = -2;
+/// A procedure of pbrain was called before being defined
+pub const BF_UNKNOWN_PROCEDURE: c_int = -3;
+/// The input or the output failed
+pub const BF_IO_ERROR: c_int = -4;
+/// A pointer given to the function was null
+pub const BF_INVALID_ARGUMENT: c_int = -5;
+/// The loops and the calls of procedures nested too deeply
+pub const BF_TOO_DEEP: c_int = -6;
+
+/// "," leaves the cell unchanged at the end of the input
+pub const BF_EOF_UNCHANGED: c_int = 0;
+/// "," sets the cell to 0 at the end of the input
+pub const BF_EOF_ZERO: c_int = 1;
+/// "," sets the cell to -1 at the end of the input
+pub const BF_EOF_MINUS_ONE: c_int = 2;
+
+/// Function reading a byte for ",", returning it, -1 at the end of the
+/// input, or -2 on an error
+pub type BfReadCallback = extern "C" fn(user_data: *mut c_void) -> c_int;
+
+/// Function writing the byte of ".", returning 0, or -1 on an error
+pub type BfWriteCallback = extern "C" fn(user_data: *mut c_void, byte: u8) -> c_int;
+
+/// Input and output of a program handed to the functions of the caller
+#[derive(Clone, Copy)]
+struct Callbacks {
+    read: Option<BfReadCallback>,
+    write: Option<BfWriteCallback>,
+    user_data: *mut c_void,
+}
+
+impl Read for Callbacks {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = match (self.read, buf.is_empty()) {
+            (Some(read), false) => read,
+            _ => return Ok(0),
+        };
+        match read(self.user_data) {
+            -1 => Ok(0),
+            byte @ 0..=255 => {
+                buf[0] = byte as u8;
+                Ok(1)
+            }
+            _ => Err(io::Error::other("read callback failed")),
+        }
+    }
+}
+
+impl Write for Callbacks {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let write = match self.write {
+            Some(write) => write,
+            None => return Ok(buf.len()),
+        };
+        for byte in buf {
+            if write(self.user_data, *byte) != 0 {
+                return Err(io::Error::other("write callback failed"));
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A compiled program, and its run
+pub struct BfProgram {
+    program: Program,
+    state: State,
+    stepper: Stepper,
+    output: SharedBuffer,
+    written: Vec<u8>,             // Output taken by the last bf_output
+    callbacks: Option<Callbacks>, // Input and output replacing the buffers
+}
+
+impl BfProgram {
+    /// Start the program over, on a blank tape reading some input
+    ///
+    /// The callbacks given to `bf_set_io` take the place of the input, and
+    /// of the buffered output.
+    fn reset(&mut self, input: &[u8]) {
+        self.output = SharedBuffer::default();
+        self.state.reset();
+        self.state.memory.resize(self.program.config.tape_size, 0);
+        self.state.input = match self.callbacks {
+            Some(callbacks) if callbacks.read.is_some() => Box::new(callbacks),
+            _ => Box::new(io::Cursor::new(input.to_vec())),
+        };
+        self.state.output = match self.callbacks {
+            Some(callbacks) if callbacks.write.is_some() => Box::new(callbacks),
+            _ => Box::new(self.output.clone()),
+        };
+        self.stepper = Stepper::new(&self.program.ast);
+    }
+}
+
+/// Status code of an error of the VM
+fn status(error: RuntimeError) -> c_int {
+    match error {
+        RuntimeError::OutOfTape(_) => BF_OUT_OF_TAPE,
+        RuntimeError::OutputLimit(_) => BF_OUTPUT_LIMIT,
+        RuntimeError::UnknownProcedure(_) => BF_UNKNOWN_PROCEDURE,
+        RuntimeError::Io(_) => BF_IO_ERROR,
+        RuntimeError::TooDeep(_) => BF_TOO_DEEP,
+        RuntimeError::Interrupted(_) | RuntimeError::StepLimit(_) | RuntimeError::Timeout(_) => {
+            unreachable!("the stepper of the C API checks no interrupt flag nor limit of the run")
+        }
+    }
+}
+
+/// Bytes of a pointer and a length given by C, a null pointer being empty
+unsafe fn bytes<'a>(data: *const u8, length: usize) -> &'a [u8] {
+    if data.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(data, length)
+    }
+}
+
+/// Compile a NUL-terminated brainfuck source at some optimization level,
+/// ready to run on an empty input
+///
+/// Returns null on an invalid source, storing in `error_offset`, when it
+/// isn't null, the byte offset of the first unmatched bracket, of the first
+/// one nested too deeply, or of the first invalid UTF-8 sequence.
+///
+/// # Safety
+///
+/// `source` must be a NUL-terminated string, and `error_offset` null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn bf_compile(
+    source: *const c_char,
+    opt_level: c_int,
+    error_offset: *mut usize,
+) -> *mut BfProgram {
+    if source.is_null() {
+        return ptr::null_mut();
+    }
+    let fail = |offset: usize| {
+        if !error_offset.is_null() {
+            *error_offset = offset;
+        }
+        ptr::null_mut()
+    };
+    let source = match CStr::from_ptr(source).to_str() {
+        Ok(source) => source,
+        Err(error) => return fail(error.valid_up_to()),
+    };
+    let program = match Compiler::new()
+        .opt_level(opt_level.clamp(0, 3) as u8)
+        .compile(source)
+    {
+        Ok(program) => program,
+        Err(ParseError::Unmatched(offset) | ParseError::TooDeep(offset)) => return fail(offset),
+    };
+
+    let mut program = BfProgram {
+        state: State::new(&program.config),
+        stepper: Stepper::new(&program.ast),
+        program,
+        output: SharedBuffer::default(),
+        written: Vec::new(),
+        callbacks: None,
+    };
+    program.reset(&[]);
+    Box::into_raw(Box::new(program))
+}
+
+/// Start a program over on a blank tape, for `bf_step`, reading
+/// `input_length` bytes of `input`
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`, and `input` must be null or
+/// point to `input_length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bf_reset(
+    program: *mut BfProgram,
+    input: *const u8,
+    input_length: usize,
+) -> c_int {
+    match program.as_mut() {
+        Some(program) => {
+            program.reset(bytes(input, input_length));
+            BF_RUNNING
+        }
+        None => BF_INVALID_ARGUMENT,
+    }
+}
+
+/// Read the input of a program, and write its output, through functions of
+/// the caller from its next start, each one being null to keep the buffer
+/// it replaces
+///
+/// The functions get `user_data` as their first argument.
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`, and the functions must accept
+/// `user_data` until the program is freed or its functions are set again.
+#[no_mangle]
+pub unsafe extern "C" fn bf_set_io(
+    program: *mut BfProgram,
+    read: Option<BfReadCallback>,
+    write: Option<BfWriteCallback>,
+    user_data: *mut c_void,
+) -> c_int {
+    match program.as_mut() {
+        Some(program) => {
+            program.callbacks = Some(Callbacks {
+                read,
+                write,
+                user_data,
+            });
+            BF_DONE
+        }
+        None => BF_INVALID_ARGUMENT,
+    }
+}
+
+/// Set what "," does at the end of the input from the next start of a
+/// program, one of the `BF_EOF_*` modes
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_set_eof(program: *mut BfProgram, eof: c_int) -> c_int {
+    let eof = match eof {
+        BF_EOF_UNCHANGED => Eof::Unchanged,
+        BF_EOF_ZERO => Eof::Zero,
+        BF_EOF_MINUS_ONE => Eof::MinusOne,
+        _ => return BF_INVALID_ARGUMENT,
+    };
+    match program.as_mut() {
+        Some(program) => {
+            program.program.config.eof = eof;
+            BF_DONE
+        }
+        None => BF_INVALID_ARGUMENT,
+    }
+}
+
+/// Set the cells of the tape from the next start of a program, 30000 by
+/// default
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_set_tape_size(program: *mut BfProgram, cells: usize) -> c_int {
+    match program.as_mut() {
+        Some(program) if cells > 0 => {
+            program.program.config.tape_size = cells;
+            BF_DONE
+        }
+        _ => BF_INVALID_ARGUMENT,
+    }
+}
+
+/// Run a program to its end on a blank tape, reading `input_length` bytes
+/// of `input`
+///
+/// Returns `BF_DONE`, or the error stopping the program.
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`, and `input` must be null or
+/// point to `input_length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bf_run(
+    program: *mut BfProgram,
+    input: *const u8,
+    input_length: usize,
+) -> c_int {
+    let program = match program.as_mut() {
+        Some(program) => program,
+        None => return BF_INVALID_ARGUMENT,
+    };
+    program.reset(bytes(input, input_length));
+    let BfProgram { program, state, .. } = program;
+    match run_ast(&program.ast, state, &program.config) {
+        Ok(()) => BF_DONE,
+        Err(error) => status(error),
+    }
+}
+
+/// Run the next node of a program
+///
+/// Returns `BF_RUNNING` while the program goes on, then `BF_DONE`, or the
+/// error stopping the program.
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`.
+#[no_mangle]
+pub unsafe extern "C" fn bf_step(program: *mut BfProgram) -> c_int {
+    let program = match program.as_mut() {
+        Some(program) => program,
+        None => return BF_INVALID_ARGUMENT,
+    };
+    match program
+        .stepper
+        .step(&mut program.state, &program.program.config)
+    {
+        Ok(true) => BF_RUNNING,
+        Ok(false) => BF_DONE,
+        Err(error) => status(error),
+    }
+}
+
+/// Output of the program since the previous call, or since it started,
+/// storing its length in `length`
+///
+/// The output is taken out of the buffer of the program, each byte being
+/// given once. The bytes stay valid until the next call on the program.
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`, and `length` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn bf_output(program: *mut BfProgram, length: *mut usize) -> *const u8 {
+    let program = match (program.as_mut(), length.is_null()) {
+        (Some(program), false) => program,
+        _ => return ptr::null(),
+    };
+    program.written = program.output.take();
+    *length = program.written.len();
+    program.written.as_ptr()
+}
+
+/// Tape of a program, storing its length in `length` and the index of
+/// the pointer in `index`
+///
+/// The cells stay valid until the next call on the program.
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`, and `length` and `index` must be
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn bf_tape(
+    program: *mut BfProgram,
+    length: *mut usize,
+    index: *mut usize,
+) -> *const u32 {
+    let program = match (program.as_mut(), length.is_null() || index.is_null()) {
+        (Some(program), false) => program,
+        _ => return ptr::null(),
+    };
+    *length = program.state.memory.len();
+    *index = program.state.index;
+    program.state.memory.as_ptr()
+}
+
+/// Allocate `length` bytes, for the hosts which can only hand over data
+/// in the memory of the library, such as WebAssembly
+#[no_mangle]
+pub extern "C" fn bf_alloc(length: usize) -> *mut u8 {
+    let mut data = Vec::with_capacity(length);
+    let ptr = data.as_mut_ptr();
+    std::mem::forget(data);
+    ptr
+}
+
+/// Free bytes allocated by `bf_alloc`
+///
+/// # Safety
+///
+/// `data` must come from `bf_alloc` with the same `length`, or be null, and
+/// isn't used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bf_dealloc(data: *mut u8, length: usize) {
+    if !data.is_null() {
+        drop(Vec::from_raw_parts(data, 0, length));
+    }
+}
+
+/// Free a program
+///
+/// # Safety
+///
+/// `program` must come from `bf_compile`, or be null, and isn't used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bf_free(program: *mut BfProgram) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
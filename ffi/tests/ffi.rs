@@ -0,0 +1,195 @@
+use std::env;
+use std::ffi::{CString, OsString};
+use std::fs;
+use std::io::{self, Write};
+use std::os::raw::{c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::ptr;
+use std::slice;
+
+use brainfuck_ffi::*;
+
+unsafe fn output(program: *mut BfProgram) -> Vec<u8> {
+    let mut length = 0;
+    let bytes = bf_output(program, &mut length);
+    slice::from_raw_parts(bytes, length).to_vec()
+}
+
+#[test]
+fn programs_run_through_the_c_interface() {
+    unsafe {
+        let source = CString::new(",.,.[-]++++++++[>++++<-]>+.").unwrap();
+        let program = bf_compile(source.as_ptr(), 2, ptr::null_mut());
+        assert!(!program.is_null());
+        assert_eq!(bf_run(program, b"ok".as_ptr(), 2), BF_DONE);
+        assert_eq!(output(program), b"ok!");
+
+        // Stepping gives the same output, a node at a time
+        assert_eq!(bf_reset(program, b"hi".as_ptr(), 2), BF_RUNNING);
+        let mut steps = 0;
+        while bf_step(program) == BF_RUNNING {
+            steps += 1;
+        }
+        assert!(steps > 3);
+        assert_eq!(output(program), b"hi!");
+        // Each byte of the output is given once
+        assert_eq!(output(program), b"");
+        let (mut length, mut index) = (0, 0);
+        let tape = bf_tape(program, &mut length, &mut index);
+        assert_eq!(slice::from_raw_parts(tape, 2), [0, 33]);
+        assert_eq!((length, index), (30000, 1));
+        bf_free(program);
+
+        // Level 3 runs the start up to the input at compile time
+        let source = CString::new("++++++++[>++++<-]>+.,.").unwrap();
+        let program = bf_compile(source.as_ptr(), 3, ptr::null_mut());
+        assert_eq!(bf_reset(program, b"x".as_ptr(), 1), BF_RUNNING);
+        let mut steps = 0;
+        while bf_step(program) == BF_RUNNING {
+            steps += 1;
+        }
+        assert!(steps < 8);
+        assert_eq!(output(program), b"!x");
+        bf_free(program);
+
+        let source = CString::new("<").unwrap();
+        let program = bf_compile(source.as_ptr(), 0, ptr::null_mut());
+        assert_eq!(bf_run(program, ptr::null(), 0), BF_OUT_OF_TAPE);
+        bf_free(program);
+    }
+}
+
+#[test]
+fn eof_and_tape_size_are_set_for_the_next_start() {
+    unsafe {
+        let source = CString::new(",+.>>>>.").unwrap();
+        let program = bf_compile(source.as_ptr(), 0, ptr::null_mut());
+        assert_eq!(bf_set_eof(program, BF_EOF_MINUS_ONE), BF_DONE);
+        assert_eq!(bf_run(program, ptr::null(), 0), BF_DONE);
+        assert_eq!(output(program), b"\0\0");
+        assert_eq!(bf_set_eof(program, BF_EOF_ZERO), BF_DONE);
+        assert_eq!(bf_run(program, ptr::null(), 0), BF_DONE);
+        assert_eq!(output(program), b"\x01\0");
+
+        assert_eq!(bf_set_tape_size(program, 4), BF_DONE);
+        assert_eq!(bf_run(program, ptr::null(), 0), BF_OUT_OF_TAPE);
+        let (mut length, mut index) = (0, 0);
+        bf_tape(program, &mut length, &mut index);
+        assert_eq!(length, 4);
+
+        assert_eq!(bf_set_eof(program, 3), BF_INVALID_ARGUMENT);
+        assert_eq!(bf_set_tape_size(program, 0), BF_INVALID_ARGUMENT);
+        bf_free(program);
+    }
+}
+
+#[test]
+fn invalid_sources_give_their_error_offset() {
+    unsafe {
+        let source = CString::new("+[.]]").unwrap();
+        let mut offset = 0;
+        assert!(bf_compile(source.as_ptr(), 2, &mut offset).is_null());
+        assert_eq!(offset, 4);
+        assert_eq!(bf_step(ptr::null_mut()), BF_INVALID_ARGUMENT);
+        bf_free(ptr::null_mut());
+    }
+}
+
+extern "C" fn read_byte(user_data: *mut c_void) -> c_int {
+    let input = unsafe { &mut *(user_data as *mut Vec<u8>) };
+    input.pop().map_or(-1, c_int::from)
+}
+
+extern "C" fn write_byte(user_data: *mut c_void, byte: u8) -> c_int {
+    let output = unsafe { &mut *(user_data as *mut Vec<u8>) };
+    output.push(byte.to_ascii_uppercase());
+    0
+}
+
+#[test]
+fn callbacks_take_the_place_of_the_buffers() {
+    unsafe {
+        let source = CString::new(",.,.,.").unwrap();
+        let program = bf_compile(source.as_ptr(), 2, ptr::null_mut());
+        let mut data = b"cba".to_vec();
+        let user_data = &mut data as *mut Vec<u8> as *mut c_void;
+        assert_eq!(
+            bf_set_io(program, Some(read_byte), None, user_data),
+            BF_DONE
+        );
+        assert_eq!(bf_run(program, ptr::null(), 0), BF_DONE);
+        assert_eq!(output(program), b"abc");
+
+        data = b"cba".to_vec();
+        let user_data = &mut data as *mut Vec<u8> as *mut c_void;
+        bf_set_io(program, None, Some(write_byte), user_data);
+        assert_eq!(bf_run(program, b"xyz".as_ptr(), 3), BF_DONE);
+        assert_eq!(output(program), b"");
+        assert_eq!(data, b"cbaXYZ");
+        bf_free(program);
+    }
+}
+
+/// Tell that a test is skipped, past the capture of the output of the tests
+fn skip(test: &str, reason: &str) {
+    let _ = writeln!(io::stderr(), "{} skipped: {}", test, reason);
+}
+
+/// Whether the standard library of wasm32 is installed
+fn wasm32_installed() -> bool {
+    let output = Command::new("rustc").args(["--print", "sysroot"]).output();
+    match output {
+        Ok(output) => Path::new(String::from_utf8_lossy(&output.stdout).trim())
+            .join("lib/rustlib/wasm32-unknown-unknown")
+            .exists(),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn npm_package_runs_in_node() {
+    if !wasm32_installed() {
+        return skip("npm_package_runs_in_node", "the wasm32 target is missing");
+    }
+    // The package, with the library built for wasm32 as by `npm run build`
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("brainfuck-npm");
+    let npm = Path::new(env!("CARGO_MANIFEST_DIR")).join("npm");
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    let build = Command::new(cargo)
+        .args(["build", "--release", "--target", "wasm32-unknown-unknown"])
+        .arg("--manifest-path")
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(dir.join("target"))
+        .output()
+        .unwrap();
+    assert!(
+        build.status.success(),
+        "the library doesn't build for wasm32:\n{}",
+        String::from_utf8_lossy(&build.stderr)
+    );
+    let package = dir.join("package");
+    fs::create_dir_all(&package).unwrap();
+    for file in ["package.json", "index.js", "test.js"].iter() {
+        fs::copy(npm.join(file), package.join(file)).unwrap();
+    }
+    fs::copy(
+        dir.join("target/wasm32-unknown-unknown/release/brainfuck_ffi.wasm"),
+        package.join("brainfuck_ffi.wasm"),
+    )
+    .unwrap();
+
+    let output = match Command::new("node").arg(package.join("test.js")).output() {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return skip("npm_package_runs_in_node", "node is missing");
+        }
+        output => output.unwrap(),
+    };
+    assert!(
+        output.status.success(),
+        "the smoke test of the package fails:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, b"ok\n");
+}
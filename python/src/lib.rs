@@ -0,0 +1,313 @@
+//! The brainfuck compiler and interpreter as a Python module, through PyO3
+//!
+//! ```python
+//! >>> import brainfuck
+//! >>> brainfuck.run(",.,.", b"hi")
+//! b'hi'
+//! ```
+//!
+//! `compile` parses and optimizes a source into a `Program`, which runs to
+//! its end with `run` or a node at a time with `step`, its input and output
+//! being bytes or Python functions.
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use brainfuck::lexer;
+use brainfuck::vm::{run_ast, Eof, RuntimeError, SharedBuffer, State, Stepper};
+use brainfuck::Compiler;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+create_exception!(
+    brainfuck,
+    ParseError,
+    PyValueError,
+    "A source with an unmatched bracket, brackets nested too deeply, or which isn't UTF-8"
+);
+create_exception!(
+    brainfuck,
+    RunError,
+    PyRuntimeError,
+    "An error stopping a program"
+);
+create_exception!(brainfuck, OutOfTape, RunError, "The pointer left the tape");
+create_exception!(
+    brainfuck,
+    OutputLimit,
+    RunError,
+    "The program wrote more than its limit"
+);
+create_exception!(
+    brainfuck,
+    UnknownProcedure,
+    RunError,
+    "A procedure of pbrain was called before being defined"
+);
+create_exception!(
+    brainfuck,
+    TooDeep,
+    RunError,
+    "The loops and the calls of procedures nested too deeply"
+);
+
+/// ParseError of a source, with the byte offset of the error
+fn parse_error(py: Python<'_>, offset: usize) -> PyErr {
+    let error = ParseError::new_err(format!("invalid bracket at byte {}", offset));
+    match error.value(py).setattr("offset", offset) {
+        Ok(()) => error,
+        Err(error) => error,
+    }
+}
+
+/// Python function reading or writing the bytes of a program
+///
+/// The exception it raises is kept in `error`, to be raised again once the
+/// VM stops on the failed input or output.
+struct Callback {
+    function: Py<PyAny>,
+    error: Rc<RefCell<Option<PyErr>>>,
+}
+
+impl Callback {
+    /// Keep the exception of the function, failing the input or the output
+    fn fail(&self, error: PyErr) -> io::Error {
+        *self.error.borrow_mut() = Some(error);
+        io::Error::other("Python callback failed")
+    }
+}
+
+impl Read for Callback {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        Python::attach(|py| {
+            let byte = self
+                .function
+                .call0(py)
+                .and_then(|byte| byte.extract::<Option<i64>>(py))
+                .map_err(|error| self.fail(error))?;
+            Ok(match byte {
+                Some(byte) => {
+                    buf[0] = (byte & 0xFF) as u8;
+                    1
+                }
+                None => 0,
+            })
+        })
+    }
+}
+
+impl Write for Callback {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Python::attach(|py| {
+            for byte in buf {
+                self.function
+                    .call1(py, (*byte,))
+                    .map_err(|error| self.fail(error))?;
+            }
+            Ok(buf.len())
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A compiled program, and its run
+///
+/// Its input and output are bytes, or functions: `read()` returning a byte
+/// or None at the end of the input, and `write(byte)`. At the end of the
+/// input, "," does what `eof` tells: "unchanged", "zero" or "minus-one".
+#[pyclass(unsendable, module = "brainfuck")]
+pub struct Program {
+    program: brainfuck::Program,
+    state: State,
+    stepper: Stepper,
+    output: SharedBuffer,
+    error: Rc<RefCell<Option<PyErr>>>, // Exception of the last callback
+}
+
+impl Program {
+    /// Start the program over, on a blank tape reading some input, or
+    /// through the functions given in place of the input and the output
+    fn start(&mut self, input: &[u8], read: Option<Py<PyAny>>, write: Option<Py<PyAny>>) {
+        self.output = SharedBuffer::default();
+        self.error.borrow_mut().take();
+        self.state.reset();
+        self.state.memory.resize(self.program.config.tape_size, 0);
+        self.state.input = match read {
+            Some(function) => Box::new(Callback {
+                function,
+                error: self.error.clone(),
+            }),
+            None => Box::new(io::Cursor::new(input.to_vec())),
+        };
+        self.state.output = match write {
+            Some(function) => Box::new(Callback {
+                function,
+                error: self.error.clone(),
+            }),
+            None => Box::new(self.output.clone()),
+        };
+        self.stepper = Stepper::new(&self.program.ast);
+    }
+
+    /// Exception of an error of the VM, the one of a callback first
+    fn run_error(&self, error: RuntimeError) -> PyErr {
+        if let Some(error) = self.error.borrow_mut().take() {
+            return error;
+        }
+        let message = error.to_string();
+        match error {
+            RuntimeError::OutOfTape(_) => OutOfTape::new_err(message),
+            RuntimeError::OutputLimit(_) => OutputLimit::new_err(message),
+            RuntimeError::UnknownProcedure(_) => UnknownProcedure::new_err(message),
+            RuntimeError::TooDeep(_) => TooDeep::new_err(message),
+            RuntimeError::Io(_) => RunError::new_err(message),
+            RuntimeError::Interrupted(_)
+            | RuntimeError::StepLimit(_)
+            | RuntimeError::Timeout(_) => {
+                unreachable!("the module checks no interrupt flag nor limit of the run")
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl Program {
+    #[new]
+    #[pyo3(signature = (source, opt_level=2, eof="unchanged", tape_size=30000))]
+    fn new(
+        py: Python<'_>,
+        source: &Bound<'_, PyAny>,
+        opt_level: u8,
+        eof: &str,
+        tape_size: usize,
+    ) -> PyResult<Self> {
+        let bytes;
+        let source = match source.cast::<PyBytes>() {
+            Ok(source) => {
+                bytes = source.as_bytes();
+                std::str::from_utf8(bytes).map_err(|error| parse_error(py, error.valid_up_to()))?
+            }
+            Err(_) => source.extract::<&str>()?,
+        };
+        let mut program = Compiler::new()
+            .opt_level(opt_level.min(3))
+            .compile(source)
+            .map_err(|error| match error {
+                lexer::ParseError::Unmatched(offset) | lexer::ParseError::TooDeep(offset) => {
+                    parse_error(py, offset)
+                }
+            })?;
+        program.config.eof = match eof {
+            "unchanged" => Eof::Unchanged,
+            "zero" => Eof::Zero,
+            "minus-one" => Eof::MinusOne,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "invalid eof {:?}, expected unchanged, zero or minus-one",
+                    eof
+                )))
+            }
+        };
+        if tape_size == 0 {
+            return Err(PyValueError::new_err("the tape needs at least a cell"));
+        }
+        program.config.tape_size = tape_size;
+
+        let mut program = Program {
+            state: State::new(&program.config),
+            stepper: Stepper::new(&program.ast),
+            program,
+            output: SharedBuffer::default(),
+            error: Rc::default(),
+        };
+        program.start(&[], None, None);
+        Ok(program)
+    }
+
+    /// Start the program over on a blank tape, for `step`
+    #[pyo3(signature = (input=b"".as_slice(), read=None, write=None))]
+    fn reset(&mut self, input: &[u8], read: Option<Py<PyAny>>, write: Option<Py<PyAny>>) {
+        self.start(input, read, write);
+    }
+
+    /// Run the next node, returning whether the program goes on
+    fn step(&mut self) -> PyResult<bool> {
+        self.stepper
+            .step(&mut self.state, &self.program.config)
+            .map_err(|error| self.run_error(error))
+    }
+
+    /// Run the program to its end on a blank tape, returning its output
+    #[pyo3(signature = (input=b"".as_slice(), read=None, write=None))]
+    fn run<'py>(
+        &mut self,
+        py: Python<'py>,
+        input: &[u8],
+        read: Option<Py<PyAny>>,
+        write: Option<Py<PyAny>>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        self.start(input, read, write);
+        run_ast(&self.program.ast, &mut self.state, &self.program.config)
+            .map_err(|error| self.run_error(error))?;
+        Ok(self.output(py))
+    }
+
+    /// Output of the program since it started, without the one given to
+    /// `write`
+    #[getter]
+    fn output<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.output.contents())
+    }
+}
+
+/// Compile a brainfuck source, as `str` or `bytes`
+#[pyfunction]
+#[pyo3(signature = (source, opt_level=2, eof="unchanged", tape_size=30000))]
+fn compile(
+    py: Python<'_>,
+    source: &Bound<'_, PyAny>,
+    opt_level: u8,
+    eof: &str,
+    tape_size: usize,
+) -> PyResult<Program> {
+    Program::new(py, source, opt_level, eof, tape_size)
+}
+
+/// Run a brainfuck source on some input, returning its output
+#[pyfunction]
+#[pyo3(signature = (source, input=b"".as_slice(), opt_level=2, eof="unchanged", tape_size=30000))]
+fn run<'py>(
+    py: Python<'py>,
+    source: &Bound<'_, PyAny>,
+    input: &[u8],
+    opt_level: u8,
+    eof: &str,
+    tape_size: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    Program::new(py, source, opt_level, eof, tape_size)?.run(py, input, None, None)
+}
+
+/// Brainfuck compiler and interpreter
+#[pymodule(name = "brainfuck")]
+fn brainfuck_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add("ParseError", py.get_type::<ParseError>())?;
+    module.add("RunError", py.get_type::<RunError>())?;
+    module.add("OutOfTape", py.get_type::<OutOfTape>())?;
+    module.add("OutputLimit", py.get_type::<OutputLimit>())?;
+    module.add("UnknownProcedure", py.get_type::<UnknownProcedure>())?;
+    module.add("TooDeep", py.get_type::<TooDeep>())?;
+    module.add_class::<Program>()?;
+    module.add_function(wrap_pyfunction!(compile, module)?)?;
+    module.add_function(wrap_pyfunction!(run, module)?)?;
+    Ok(())
+}
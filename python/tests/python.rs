@@ -0,0 +1,53 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Tell that a test is skipped, past the capture of the output of the tests
+fn skip(test: &str, reason: &str) {
+    let _ = writeln!(io::stderr(), "{} skipped: {}", test, reason);
+}
+
+/// The module built by cargo along with the tests, in the directory above
+/// the one of their executables
+fn built_module() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    let dir = exe.parent().unwrap().parent().unwrap();
+    let name = format!(
+        "{}brainfuck_python{}",
+        env::consts::DLL_PREFIX,
+        env::consts::DLL_SUFFIX
+    );
+    dir.join(name)
+}
+
+#[test]
+fn module_runs_in_python() {
+    // The module as Python imports it, next to its tests
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("brainfuck-python");
+    fs::create_dir_all(&dir).unwrap();
+    let suffix = if cfg!(windows) { "pyd" } else { "so" };
+    fs::copy(built_module(), dir.join(format!("brainfuck.{}", suffix))).unwrap();
+    fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test_brainfuck.py"),
+        dir.join("test_brainfuck.py"),
+    )
+    .unwrap();
+
+    let output = match Command::new("python3")
+        .args(["-m", "unittest", "-v", "test_brainfuck"])
+        .current_dir(&dir)
+        .output()
+    {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return skip("module_runs_in_python", "python3 is missing");
+        }
+        output => output.unwrap(),
+    };
+    assert!(
+        output.status.success(),
+        "the tests of the module fail:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
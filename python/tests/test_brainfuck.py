@@ -0,0 +1,54 @@
+import unittest
+
+import brainfuck
+
+
+class BrainfuckTest(unittest.TestCase):
+    def test_bytes_in_bytes_out(self):
+        self.assertEqual(brainfuck.run(",.,.", b"hi"), b"hi")
+        program = brainfuck.compile(b"++++++++[>++++++++<-]>+.")
+        self.assertEqual(program.run(), b"A")
+        self.assertEqual(program.run(), b"A")
+
+    def test_steps(self):
+        program = brainfuck.compile(",.+++.", opt_level=0)
+        program.reset(b"x")
+        steps = 1
+        while program.step():
+            steps += 1
+        self.assertEqual(steps, 6)
+        self.assertEqual(program.output, b"x{")
+
+    def test_callbacks(self):
+        input = iter(b"abc")
+        written = []
+        program = brainfuck.compile(",[.,]")
+        program.run(read=lambda: next(input, 0), write=written.append)
+        self.assertEqual(bytes(written), b"abc")
+        self.assertEqual(program.output, b"")
+
+        def fail():
+            raise KeyError("no input")
+
+        with self.assertRaises(KeyError):
+            program.run(read=fail)
+        self.assertEqual(program.run(b"\x01\x00"), b"\x01")
+
+    def test_settings(self):
+        self.assertEqual(brainfuck.run(",+.", eof="zero"), b"\x01")
+        self.assertEqual(brainfuck.run(",+.", eof="minus-one"), b"\x00")
+        with self.assertRaises(brainfuck.OutOfTape):
+            brainfuck.run(">" * 16, tape_size=16, opt_level=0)
+        with self.assertRaises(ValueError):
+            brainfuck.compile("", eof="never")
+
+    def test_errors(self):
+        with self.assertRaises(brainfuck.ParseError) as error:
+            brainfuck.compile("+[.]]")
+        self.assertEqual(error.exception.offset, 4)
+        with self.assertRaises(brainfuck.OutOfTape):
+            brainfuck.run("<")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,387 @@
+//! Syntax tree of the programs, and locations in their sources
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::lexer::{parse_source, ParseError, Token, MAX_NESTING};
+use crate::prelude::*;
+
+/// A range of bytes of the source code
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The bytes from `start` up to `end`, excluded
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Smallest span covering both spans
+    pub fn merge(self, other: Span) -> Self {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// The instruction of a node of an Abstract Syntax Tree
+#[derive(Clone, Debug)]
+pub enum NodeKind {
+    Incr(isize),              // Increment instruction
+    Move(isize),              // Move instruction
+    Write,                    // Write instruction
+    Read,                     // Read instruction
+    Clear,                    // Clear loop instruction
+    Mul(Vec<(isize, isize)>), // Multiply loop instruction, as (offset, factor) pairs
+    Loop(Box<Node>),          // Loop instruction
+    Block(Vec<Node>),         // A container for nodes
+    Procedure(Arc<Node>),     // Procedure of the value of the cell, in pbrain
+    Call,                     // Call of the procedure of the value of the cell, in pbrain
+    Extended(Extended),       // Command of Extended Brainfuck Type I on the cell and the storage
+}
+
+/// A command of Extended Brainfuck Type I, between the cell and a storage
+/// register
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Extended {
+    Store,      // "$": copy the cell into the storage
+    Load,       // "!": copy the storage into the cell
+    ShiftRight, // "}": shift the cell one bit to the right
+    ShiftLeft,  // "{": shift the cell one bit to the left
+    Not,        // "~": flip the bits of the cell
+    Xor,        // "^": exclusive or of the cell with the storage
+    And,        // "&": and of the cell with the storage
+    Or,         // "|": or of the cell with the storage
+}
+
+impl Extended {
+    /// The commands, in the order of their characters in `$!}{~^&|`
+    pub const ALL: [Extended; 8] = [
+        Extended::Store,
+        Extended::Load,
+        Extended::ShiftRight,
+        Extended::ShiftLeft,
+        Extended::Not,
+        Extended::Xor,
+        Extended::And,
+        Extended::Or,
+    ];
+
+    /// Character of the command in the sources
+    pub fn text(self) -> &'static str {
+        match self {
+            Extended::Store => "$",
+            Extended::Load => "!",
+            Extended::ShiftRight => "}",
+            Extended::ShiftLeft => "{",
+            Extended::Not => "~",
+            Extended::Xor => "^",
+            Extended::And => "&",
+            Extended::Or => "|",
+        }
+    }
+
+    /// Name of the command, in the listings
+    pub fn name(self) -> &'static str {
+        match self {
+            Extended::Store => "store",
+            Extended::Load => "load",
+            Extended::ShiftRight => "shift right",
+            Extended::ShiftLeft => "shift left",
+            Extended::Not => "not",
+            Extended::Xor => "xor",
+            Extended::And => "and",
+            Extended::Or => "or",
+        }
+    }
+
+    /// The command of a character, if it is one
+    pub fn from_char(c: char) -> Option<Self> {
+        Extended::ALL
+            .iter()
+            .copied()
+            .find(|command| command.text().starts_with(c))
+    }
+}
+
+/// A node of an Abstract Syntax Tree, with the part of the source it comes from
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub span: Span,
+}
+
+impl Node {
+    /// A node of the source code of a span
+    pub fn new(kind: NodeKind, span: Span) -> Self {
+        Node { kind, span }
+    }
+}
+
+/// Write a run of increments or moves, as `val` times `positive` or `negative`
+fn write_run(f: &mut fmt::Formatter, val: isize, positive: char, negative: char) -> fmt::Result {
+    let c = if val < 0 { negative } else { positive };
+    (0..val.abs()).try_for_each(|_| write!(f, "{}", c))
+}
+
+/// The canonical brainfuck of a node, without comments or formatting, which
+/// `parse` reads back
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            NodeKind::Incr(val) => write_run(f, *val, '+', '-'),
+            NodeKind::Move(val) => write_run(f, *val, '>', '<'),
+            NodeKind::Write => f.write_str("."),
+            NodeKind::Read => f.write_str(","),
+            NodeKind::Clear => f.write_str("[-]"),
+            NodeKind::Mul(targets) => {
+                f.write_str("[-")?;
+                let mut position = 0;
+                for (offset, factor) in targets.iter() {
+                    write_run(f, offset - position, '>', '<')?;
+                    write_run(f, *factor, '+', '-')?;
+                    position = *offset;
+                }
+                write_run(f, -position, '>', '<')?;
+                f.write_str("]")
+            }
+            NodeKind::Loop(node) => write!(f, "[{}]", node),
+            NodeKind::Block(nodes) => nodes.iter().try_for_each(|node| write!(f, "{}", node)),
+            NodeKind::Procedure(node) => write!(f, "({})", node),
+            NodeKind::Call => f.write_str(":"),
+            NodeKind::Extended(command) => f.write_str(command.text()),
+        }
+    }
+}
+
+/// The AST of a classic brainfuck source, unoptimized
+impl FromStr for Node {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, ParseError> {
+        build_ast(parse_source(source))
+    }
+}
+
+/// An analysis of an AST, visiting each node before its children
+pub trait Visitor {
+    /// Visit a node, by default walking into its children
+    fn visit(&mut self, node: &Node) {
+        walk(self, node);
+    }
+}
+
+/// Visit the children of a node, in order
+pub fn walk<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    match &node.kind {
+        NodeKind::Loop(body) => visitor.visit(body),
+        NodeKind::Procedure(body) => visitor.visit(body),
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                visitor.visit(node);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A rewrite of an AST, each node being rewritten after its children
+pub trait Folder {
+    /// Rewrite a node, by default rebuilding it out of its rewritten children
+    fn fold(&mut self, node: &Node) -> Node {
+        fold_children(self, node)
+    }
+}
+
+/// Rebuild a node out of its rewritten children
+pub fn fold_children<F: Folder + ?Sized>(folder: &mut F, node: &Node) -> Node {
+    let kind = match &node.kind {
+        NodeKind::Loop(body) => NodeKind::Loop(Box::new(folder.fold(body))),
+        NodeKind::Procedure(body) => NodeKind::Procedure(Arc::new(folder.fold(body))),
+        NodeKind::Block(nodes) => {
+            NodeKind::Block(nodes.iter().map(|node| folder.fold(node)).collect())
+        }
+        kind => kind.clone(),
+    };
+    Node::new(kind, node.span)
+}
+
+/// The node, with its spans and those of its children moved `offset` bytes
+/// further in the source
+pub fn shift_spans(node: &Node, offset: usize) -> Node {
+    struct Shift(usize);
+    impl Folder for Shift {
+        fn fold(&mut self, node: &Node) -> Node {
+            let node = fold_children(self, node);
+            let span = Span::new(node.span.start + self.0, node.span.end + self.0);
+            Node::new(node.kind, span)
+        }
+    }
+
+    Shift(offset).fold(node)
+}
+
+/// Build the AST of the tokens of a source, or locate one of its unmatched
+/// brackets
+pub fn build_ast(tokens: impl IntoIterator<Item = (usize, Token)>) -> Result<Node, ParseError> {
+    build_ast_from(tokens.into_iter().map(Ok))
+}
+
+/// Build the AST of tokens coming with their errors, such as the ones read
+/// from a stream, in a single pass over them
+///
+/// The first unmatched bracket is the one `lexer::check_tokens` gives, and the
+/// loops and the procedures nest up to `lexer::MAX_NESTING`.
+pub fn build_ast_from<E: From<ParseError>>(
+    tokens: impl IntoIterator<Item = Result<(usize, Token), E>>,
+) -> Result<Node, E> {
+    let mut operations = vec![];
+    let mut stack: Vec<(usize, Token, Vec<Node>)> = vec![];
+    let mut end = 0;
+    for token in tokens {
+        let (offset, token) = token?;
+        let span = Span::new(offset, offset + 1);
+        end = offset + 1;
+        match token {
+            Token::Decr => {
+                operations.push(Node::new(NodeKind::Incr(-1), span));
+            }
+            Token::Incr => {
+                operations.push(Node::new(NodeKind::Incr(1), span));
+            }
+            Token::MoveLeft => {
+                operations.push(Node::new(NodeKind::Move(-1), span));
+            }
+            Token::MoveRight => {
+                operations.push(Node::new(NodeKind::Move(1), span));
+            }
+            Token::Write => {
+                operations.push(Node::new(NodeKind::Write, span));
+            }
+            Token::Read => {
+                operations.push(Node::new(NodeKind::Read, span));
+            }
+            Token::Call => {
+                operations.push(Node::new(NodeKind::Call, span));
+            }
+            Token::Extended(command) => {
+                operations.push(Node::new(NodeKind::Extended(command), span));
+            }
+            Token::LoopBegin | Token::ProcedureBegin if stack.len() == MAX_NESTING => {
+                return Err(ParseError::TooDeep(offset).into());
+            }
+            Token::LoopBegin | Token::ProcedureBegin => {
+                stack.push((offset, token, operations));
+                operations = vec![];
+            }
+            Token::LoopEnd | Token::ProcedureEnd => {
+                let opening = match token {
+                    Token::LoopEnd => Token::LoopBegin,
+                    _ => Token::ProcedureBegin,
+                };
+                let (begin, parent) = match stack.pop() {
+                    Some((begin, token, parent)) if token == opening => (begin, parent),
+                    _ => return Err(ParseError::Unmatched(offset).into()),
+                };
+                let body = Node::new(NodeKind::Block(operations), Span::new(begin + 1, offset));
+                let kind = match token {
+                    Token::LoopEnd => NodeKind::Loop(Box::new(body)),
+                    _ => NodeKind::Procedure(Arc::new(body)),
+                };
+                operations = parent;
+                operations.push(Node::new(kind, Span::new(begin, offset + 1)));
+            }
+        }
+    }
+
+    if let Some((offset, _, _)) = stack.last() {
+        return Err(ParseError::Unmatched(*offset).into());
+    }
+
+    // Optimize output
+    if operations.len() == 1 {
+        Ok(operations[0].clone())
+    } else {
+        Ok(Node::new(NodeKind::Block(operations), Span::new(0, end)))
+    }
+}
+
+fn tape_usage_ast(ast: &Node, position: &mut isize, range: &mut (isize, isize)) -> bool {
+    let mut touch = |offset: isize| {
+        range.0 = range.0.min(offset);
+        range.1 = range.1.max(offset);
+    };
+    match &ast.kind {
+        NodeKind::Incr(_)
+        | NodeKind::Write
+        | NodeKind::Read
+        | NodeKind::Clear
+        | NodeKind::Extended(_) => touch(*position),
+        NodeKind::Move(val) => *position += val,
+        NodeKind::Mul(targets) => {
+            touch(*position);
+            for (offset, _) in targets.iter() {
+                touch(*position + offset);
+            }
+        }
+        // Loops that don't come back to their starting cell may go anywhere
+        NodeKind::Loop(node) => {
+            touch(*position);
+            let start = *position;
+            if !tape_usage_ast(node, position, range) || *position != start {
+                return false;
+            }
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                if !tape_usage_ast(node, position, range) {
+                    return false;
+                }
+            }
+        }
+        // Procedures are looked up by the value of a cell, only known at
+        // runtime
+        NodeKind::Procedure(_) | NodeKind::Call => return false,
+    }
+
+    true
+}
+
+/// Number of cells used by a program, when it can be known without running it
+pub fn tape_usage(ast: &Node) -> Option<usize> {
+    let mut range = (0, 0);
+    if !tape_usage_ast(ast, &mut 0, &mut range) || range.0 < 0 {
+        return None;
+    }
+    Some(range.1 as usize + 1)
+}
+
+/// Line map of a source file, to locate byte offsets
+#[derive(Clone, Debug)]
+pub struct SourceMap {
+    pub path: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    /// The map of the lines of a source
+    pub fn new(path: &str, source: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(offset, _)| offset + 1));
+        SourceMap {
+            path: String::from(path),
+            line_starts,
+        }
+    }
+
+    /// Line and column, starting at 1, of a byte offset
+    pub fn location(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+}
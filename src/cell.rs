@@ -0,0 +1,146 @@
+//! Values of the cells of the tape, which the VM is generic over
+//!
+//! `u32` cells hold any width of `vm::Cell`, chosen at runtime, while `u8`
+//! and `u16` cells have their own width, and the `BigUint` cells of
+//! num-bigint don't wrap.
+
+use core::fmt;
+
+pub use num_bigint::BigUint;
+
+use crate::ast::Extended;
+use crate::vm::Cell;
+
+/// Value of a cell of the tape
+pub trait CellValue: Clone + Default + fmt::Debug + 'static {
+    /// Whether the value wraps around, instead of stopping at 0 and growing
+    /// without bounds, which some rewrites of the optimizer rely on
+    const WRAPS: bool = true;
+
+    /// Add a signed amount, wrapping around the width of the cell
+    fn add(&mut self, amount: isize);
+
+    /// Add a multiple of another value, for the multiplication loops
+    fn add_mul(&mut self, other: &Self, factor: isize);
+
+    /// Whether the value is 0, ending the loops
+    fn is_zero(&self) -> bool;
+
+    /// Byte written by "."
+    fn to_byte(&self) -> u8;
+
+    /// Value of a byte read by ","
+    fn from_byte(byte: u8) -> Self;
+
+    /// Low 32 bits of the value, identifying the procedures of pbrain
+    fn to_u32(&self) -> u32;
+
+    /// Wrap the value around the width of the configuration, when the cell
+    /// is wider than it
+    fn truncate(&mut self, _width: Cell) {}
+
+    /// Apply a shift or a bitwise command of Extended Brainfuck, with the
+    /// value of the storage, leaving the value alone on the others
+    fn bitwise(&mut self, command: Extended, storage: &Self);
+}
+
+macro_rules! impl_cell_value {
+    ($($ty:ty),*) => {$(
+        impl CellValue for $ty {
+            fn add(&mut self, amount: isize) {
+                *self = self.wrapping_add(amount as $ty);
+            }
+
+            fn add_mul(&mut self, other: &Self, factor: isize) {
+                *self = self.wrapping_add(other.wrapping_mul(factor as $ty));
+            }
+
+            fn is_zero(&self) -> bool {
+                *self == 0
+            }
+
+            fn to_byte(&self) -> u8 {
+                *self as u8
+            }
+
+            fn from_byte(byte: u8) -> Self {
+                byte as $ty
+            }
+
+            fn to_u32(&self) -> u32 {
+                *self as u32
+            }
+
+            fn truncate(&mut self, width: Cell) {
+                *self &= width.max() as $ty;
+            }
+
+            fn bitwise(&mut self, command: Extended, storage: &Self) {
+                *self = match command {
+                    Extended::ShiftRight => *self >> 1,
+                    Extended::ShiftLeft => *self << 1,
+                    Extended::Not => !*self,
+                    Extended::Xor => *self ^ storage,
+                    Extended::And => *self & storage,
+                    Extended::Or => *self | storage,
+                    Extended::Store | Extended::Load => *self,
+                };
+            }
+        }
+    )*};
+}
+
+impl_cell_value!(u8, u16, u32);
+
+/// Going below 0 leaves the cells at 0.
+impl CellValue for BigUint {
+    const WRAPS: bool = false;
+
+    fn add(&mut self, amount: isize) {
+        add_signed(self, BigUint::from(amount.unsigned_abs()), amount < 0);
+    }
+
+    fn add_mul(&mut self, other: &Self, factor: isize) {
+        add_signed(self, other * factor.unsigned_abs(), factor < 0);
+    }
+
+    fn is_zero(&self) -> bool {
+        self.bits() == 0
+    }
+
+    fn to_byte(&self) -> u8 {
+        self.to_u32() as u8
+    }
+
+    fn from_byte(byte: u8) -> Self {
+        BigUint::from(byte)
+    }
+
+    fn to_u32(&self) -> u32 {
+        self.iter_u32_digits().next().unwrap_or(0)
+    }
+
+    /// Without a width, "~" flips the bits up to the highest one set
+    fn bitwise(&mut self, command: Extended, storage: &Self) {
+        match command {
+            Extended::ShiftRight => *self >>= 1,
+            Extended::ShiftLeft => *self <<= 1,
+            Extended::Not => *self ^= (BigUint::from(1u8) << self.bits()) - 1u8,
+            Extended::Xor => *self ^= storage,
+            Extended::And => *self &= storage,
+            Extended::Or => *self |= storage,
+            Extended::Store | Extended::Load => {}
+        }
+    }
+}
+
+/// Add or subtract a magnitude, stopping at 0
+fn add_signed(number: &mut BigUint, magnitude: BigUint, negative: bool) {
+    if !negative {
+        *number += magnitude;
+    } else if *number <= magnitude {
+        *number = BigUint::default();
+    } else {
+        *number -= magnitude;
+    }
+}
@@ -0,0 +1,258 @@
+//! The animations of run --animate, the tape sampled during the run and
+//! drawn as an SVG or GIF image
+
+/// Frames kept by run --animate, the later ones being left out
+pub const ANIMATION_FRAMES: usize = 1000;
+
+/// Cells shown by run --animate at most, unless --animate-cells is given
+const ANIMATION_CELLS: usize = 32;
+
+/// Time each frame of an animation is shown, in hundredths of a second
+const ANIMATION_DELAY: u16 = 20;
+
+/// The tape sampled during a run, for run --animate
+pub struct Animation {
+    every: u64,           // Instructions between the frames
+    cells: Option<usize>, // Cells shown, up to the highest one used when None
+    recorded: usize,      // Cells kept in the frames
+    steps: u64,           // Instructions run so far
+    highest: usize,       // Highest cell the pointer reached
+    frames: Vec<Frame>,
+    pub truncated: bool, // Frames were left out
+}
+
+/// The tape before an instruction of a run
+struct Frame {
+    step: u64,
+    pointer: usize,
+    cells: Vec<u32>,
+}
+
+/// Pixels of the digits in the GIF animations, three per row, top row first
+const DIGITS: [[u8; 5]; 10] = [
+    [7, 5, 5, 5, 7],
+    [2, 6, 2, 2, 7],
+    [7, 1, 7, 4, 7],
+    [7, 1, 7, 1, 7],
+    [5, 5, 7, 1, 1],
+    [7, 4, 7, 1, 7],
+    [7, 4, 7, 5, 7],
+    [7, 1, 1, 1, 1],
+    [7, 5, 7, 5, 7],
+    [7, 5, 7, 1, 7],
+];
+
+impl Animation {
+    pub fn new(every: u64, cells: Option<usize>, tape_size: usize) -> Self {
+        Animation {
+            every,
+            cells,
+            recorded: cells.unwrap_or(ANIMATION_CELLS).min(tape_size),
+            steps: 0,
+            highest: 0,
+            frames: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// Count an instruction, sampling the tape before it every `every` ones
+    pub fn step(&mut self, memory: &[u32], index: usize) {
+        self.highest = self.highest.max(index);
+        if self.steps.is_multiple_of(self.every) {
+            self.record(memory, index);
+        }
+        self.steps += 1;
+    }
+
+    /// Sample the tape at the end of the run
+    pub fn finish(&mut self, memory: &[u32], index: usize) {
+        if self
+            .frames
+            .last()
+            .is_none_or(|frame| frame.step != self.steps)
+        {
+            self.record(memory, index);
+        }
+    }
+
+    fn record(&mut self, memory: &[u32], index: usize) {
+        if self.frames.len() == ANIMATION_FRAMES {
+            self.truncated = true;
+            return;
+        }
+        self.frames.push(Frame {
+            step: self.steps,
+            pointer: index,
+            cells: memory[..self.recorded].to_vec(),
+        });
+    }
+
+    /// Number of cells shown
+    fn shown(&self) -> usize {
+        match self.cells {
+            Some(cells) => cells.min(self.recorded),
+            None => (self.highest + 1).min(self.recorded),
+        }
+    }
+
+    /// An SVG image showing each frame in turn, with the value of the cells
+    /// and the step, the pointer being highlighted
+    pub fn svg(&self) -> String {
+        let (size, margin) = (48, 8);
+        let shown = self.shown();
+        let (width, height) = (shown * size + 2 * margin, size + 3 * margin + 16);
+        let duration = self.frames.len() as f64 * ANIMATION_DELAY as f64 / 100.0;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             font-family=\"monospace\" font-size=\"14\" text-anchor=\"middle\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+            width, height
+        );
+        let count = self.frames.len() as f64;
+        for (i, frame) in self.frames.iter().enumerate() {
+            // Each frame is only visible during its part of the loop
+            svg.push_str(&format!(
+                "<g visibility=\"hidden\"><animate attributeName=\"visibility\" \
+                 values=\"hidden;visible;hidden\" keyTimes=\"0;{:.6};{:.6}\" dur=\"{}s\" \
+                 calcMode=\"discrete\" repeatCount=\"indefinite\"/>\n",
+                i as f64 / count,
+                (i + 1) as f64 / count,
+                duration
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"start\">step {}</text>\n",
+                margin,
+                margin + 12,
+                frame.step
+            ));
+            let top = 2 * margin + 16;
+            for (index, value) in frame.cells[..shown].iter().enumerate() {
+                let fill = if index == frame.pointer {
+                    "#fd6"
+                } else {
+                    "#eee"
+                };
+                let x = margin + index * size;
+                svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" \
+                     stroke=\"#444\"/><text x=\"{}\" y=\"{}\">{}</text>\n",
+                    x,
+                    top,
+                    size,
+                    size,
+                    fill,
+                    x + size / 2,
+                    top + size / 2 + 5,
+                    value
+                ));
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// A looping GIF showing each frame in turn, with the value of the
+    /// cells, the pointer being highlighted
+    pub fn gif(&self) -> Vec<u8> {
+        const SCALE: usize = 2;
+        let shown = self.shown();
+        let digits = self
+            .frames
+            .iter()
+            .flat_map(|frame| frame.cells[..shown].iter())
+            .map(|value| value.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let cell_width = (digits * 4 + 3) * SCALE;
+        let cell_height = 9 * SCALE;
+        let (width, height) = (shown * cell_width + 1, cell_height + 1);
+
+        // White, black, the highlight of the pointer and gray borders
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&(width as u16).to_le_bytes());
+        gif.extend_from_slice(&(height as u16).to_le_bytes());
+        gif.extend_from_slice(&[0xf6, 0, 0]);
+        let mut palette = [0; 128 * 3];
+        palette[..12].copy_from_slice(&[255, 255, 255, 0, 0, 0, 255, 221, 102, 68, 68, 68]);
+        gif.extend_from_slice(&palette);
+        gif.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        for frame in self.frames.iter() {
+            let mut pixels = vec![0; width * height];
+            for (index, value) in frame.cells[..shown].iter().enumerate() {
+                let left = index * cell_width;
+                for y in 0..height {
+                    for x in left..=left + cell_width {
+                        let border =
+                            y == 0 || y == height - 1 || x == left || x == left + cell_width;
+                        pixels[y * width + x] = match border {
+                            true => 3,
+                            false if index == frame.pointer => 2,
+                            false => pixels[y * width + x],
+                        };
+                    }
+                }
+                let text = value.to_string();
+                let start = left + (cell_width - (text.len() * 4 - 1) * SCALE) / 2;
+                for (position, digit) in text.bytes().enumerate() {
+                    let rows = DIGITS[(digit - b'0') as usize];
+                    for (row, bits) in rows.iter().enumerate() {
+                        for column in 0..3 {
+                            if bits & (4 >> column) == 0 {
+                                continue;
+                            }
+                            for (dy, dx) in
+                                (0..SCALE).flat_map(|dy| (0..SCALE).map(move |dx| (dy, dx)))
+                            {
+                                let y = (2 + row) * SCALE + dy;
+                                let x = start + (position * 4 + column) * SCALE + dx;
+                                pixels[y * width + x] = 1;
+                            }
+                        }
+                    }
+                }
+            }
+
+            gif.extend_from_slice(&[0x21, 0xf9, 4, 0]);
+            gif.extend_from_slice(&ANIMATION_DELAY.to_le_bytes());
+            gif.extend_from_slice(&[0, 0, 0x2c, 0, 0, 0, 0]);
+            gif.extend_from_slice(&(width as u16).to_le_bytes());
+            gif.extend_from_slice(&(height as u16).to_le_bytes());
+            gif.extend_from_slice(&[0, 7]);
+            for block in lzw_literals(&pixels).chunks(255) {
+                gif.push(block.len() as u8);
+                gif.extend_from_slice(block);
+            }
+            gif.push(0);
+        }
+        gif.push(0x3b);
+        gif
+    }
+}
+
+/// The LZW data of a GIF image of 128 colors, each pixel being a code of its
+/// own, with a clear code before the table grows past 8-bit codes
+fn lzw_literals(pixels: &[u8]) -> Vec<u8> {
+    const CLEAR: u32 = 128;
+    const END: u32 = 129;
+    let mut data = Vec::new();
+    let (mut bits, mut count) = (0u32, 0);
+    let mut push = |code: u32| {
+        bits |= code << count;
+        count += 8;
+        while count >= 8 {
+            data.push(bits as u8);
+            bits >>= 8;
+            count -= 8;
+        }
+    };
+    for chunk in pixels.chunks(125) {
+        push(CLEAR);
+        for &pixel in chunk.iter() {
+            push(pixel as u32);
+        }
+    }
+    push(END);
+    data
+}
@@ -0,0 +1,1213 @@
+//! The subcommands of the command line and their options, parsed by clap
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use clap::builder::PossibleValue;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::codegen::{BinaryFormat, PrettyBf};
+use crate::lexer::{Language, DIALECTS};
+use crate::vm::{Bounds, Cell, Eof, Input};
+use crate::Config;
+
+use super::{JSON_MESSAGES, PROMPT, VERBOSE};
+
+/// The command line, a subcommand and its options
+#[derive(Parser)]
+#[command(
+    name = "brainfuck",
+    about = "A brainfuck compiler",
+    disable_help_subcommand = true,
+    after_help = "without a command, the arguments are given to run, so that a source\n\
+        starting with #!/usr/bin/env brainfuck can be made executable; a source\n\
+        followed by an output, as in brainfuck hello.bf hello, is still built\n\
+        as by build, but this form is deprecated\n\
+        \n\
+        options missing from the command line are read from the first bf.toml\n\
+        found from the directory of the source, as lines of <option> = <value>,\n\
+        with the long name of the option, such as tape-size = 1000\n\
+        \n\
+        exit status: 2 for a usage error, 3 for an invalid program, 4 when\n\
+        the program leaves the tape, 5 when a file can't be read or written,\n\
+        and 6 when the program reaches a limit such as --max-output"
+)]
+pub(super) struct Cli {
+    /// format of the errors and warnings: human (default), or json for a
+    /// JSON object per line
+    #[arg(
+        long,
+        global = true,
+        value_name = "format",
+        hide_possible_values = true
+    )]
+    pub(super) message_format: Option<MessageFormat>,
+
+    #[command(subcommand)]
+    pub(super) command: Commands,
+}
+
+#[derive(Subcommand)]
+pub(super) enum Commands {
+    /// run a program in the interpreter
+    #[command(
+        after_help = "[source] is a brainfuck program, or an AST exported as .json, read\n\
+            from the standard input when it is - or missing and -c isn't given;\n\
+            [sources...] follow it with --link, and the errors are located in\n\
+            their sources joined by newlines"
+    )]
+    Run(Box<RunArgs>),
+    /// run a program at each optimization level, and compare the runs
+    #[command(
+        after_help = "the program runs from -O0 to -O3 on the same input, read whole\n\
+            first, and must give the same output and errors; the instructions\n\
+            executed and the run time at each level are printed"
+    )]
+    Compare(CompareArgs),
+    /// print the loops of a program as an indented tree
+    #[command(
+        after_help = "each loop, or procedure, is given with its lines and columns, its\n\
+            depth, the cell it starts on and the moves of the pointer at each\n\
+            iteration, when they are known without running the program"
+    )]
+    Outline(OutlineArgs),
+    /// find inputs leaving the tape or entering the loops
+    #[command(
+        after_help = "the program runs on symbolic input, each byte read standing for all\n\
+            its values, and forks where they lead to different paths; each\n\
+            move of the pointer leaving the tape is printed with an input doing\n\
+            it, the input ending after it, and each loop with an input\n\
+            entering it, if any within the limits; the exit code is 1 when the\n\
+            pointer can leave the tape"
+    )]
+    Symbolic(SymbolicArgs),
+    /// show the state of a failed run, written by run --core
+    #[command(
+        after_help = "the error is located in the source, followed by the instructions\n\
+            run up to it, with the pointer and its cell before each, and by a\n\
+            hexdump of the tape"
+    )]
+    Inspect(InspectArgs),
+    /// run a program again on a recorded trace or input
+    #[command(
+        after_help = "with --trace, the bytes read are those found in the trace, which\n\
+            must have been written at the same optimization level; each step\n\
+            is checked against it, and the first divergence is printed with the\n\
+            exit code 1; --to-step then opens the line debugger of debug, with\n\
+            its commands read on the standard input"
+    )]
+    Replay(ReplayArgs),
+    /// find where two traces of run --trace diverge
+    #[command(
+        after_help = "the traces are aligned on the offsets of their instructions, those\n\
+            merged by the optimizations standing for the ones they cover, so\n\
+            the runs of a program at two optimization levels can be compared;\n\
+            the first divergence is printed, and the exit code is 1"
+    )]
+    TraceDiff(TraceDiffArgs),
+    /// compile a program to another language
+    #[command(
+        after_help = "[source] is a brainfuck program, or an AST exported as .json, read\n\
+            from the standard input when it is - or missing, and [output] a file,\n\
+            or - for the standard output; with --out-dir, each argument is a\n\
+            source, or a directory of .bf sources"
+    )]
+    Build(BuildArgs),
+    /// check a program for errors and likely bugs, without running it
+    #[command(
+        after_help = "[source] is read from the standard input when it is - or missing and\n\
+            -c isn't given; besides unmatched brackets, it reports the lints:\n\
+            dead-loop, endless-loop, nonterminating-loop,\n\
+            pointer-underflow-possible, pointer-overflow-possible,\n\
+            useless-sequence, unused-cells, and unbalanced-suspects which is\n\
+            allowed by default; --fix keeps the comments of the source, and the\n\
+            text of the dead loops"
+    )]
+    Check(CheckArgs),
+    /// format a program
+    #[command(
+        after_help = "[source] is read from the standard input when it is - or missing, and\n\
+            [output] is a file, the standard output by default"
+    )]
+    Fmt(FmtArgs),
+    /// run lines of brainfuck on a tape kept between them
+    #[command(
+        after_help = "lines starting with a colon are commands: :tape prints the tape, :ptr\n\
+            the pointer, :reset clears both, and :load <file> runs a source"
+    )]
+    Repl(ReplArgs),
+    /// step through a program
+    #[command(
+        after_help = "[source] is required, unless -c is given, as the debugger reads step,\n\
+            continue, break <byte offset>, watch <cell>, tape and quit on the\n\
+            standard input;\n\
+            with --tui, it reads single keys, and the program reads nothing\n\
+            unless --input or --input-str is given; a comment \"@label: name\"\n\
+            names the command after it, for the breakpoints"
+    )]
+    Debug(DebugArgs),
+    /// debug programs from an editor, with the Debug Adapter Protocol
+    #[cfg(feature = "server")]
+    #[command(
+        after_help = "the messages are read on the standard input and written on the\n\
+            standard output; the launch request takes the path of the program\n\
+            in \"program\", the text it reads in \"input\", and \"stopOnEntry\""
+    )]
+    Dap(DapArgs),
+    /// check and format programs in an editor, with the Language Server Protocol
+    #[cfg(feature = "server")]
+    #[command(
+        after_help = "the messages are read on the standard input and written on the\n\
+            standard output; the server reports the unmatched brackets and the\n\
+            lints, highlights the matching brackets, tells the nesting depth and\n\
+            the effect of the commands on hover, and formats as fmt does"
+    )]
+    Lsp(LspArgs),
+    /// run the cells of Jupyter notebooks, as a kernel
+    #[cfg(feature = "server")]
+    #[command(
+        after_help = "[connection file] is given by Jupyter, which starts the kernel with\n\
+            the kernel.json of --kernelspec, put in a directory brainfuck of\n\
+            the kernels of Jupyter; the output of each cell is shown under it,\n\
+            and each line read by the cell is asked to the notebook; an\n\
+            interrupt stops the running cell"
+    )]
+    JupyterKernel(JupyterKernelArgs),
+    /// run a program for each client of a WebSocket
+    #[cfg(feature = "server")]
+    #[command(
+        after_help = "each client runs the program on its own tape; the output is sent in\n\
+            binary messages, a line at a time and before each read, the\n\
+            messages of the client are the bytes read, and the errors are sent\n\
+            in a text message before the close, with the status 1011; each run\n\
+            stops after 1000000000 steps unless --max-steps is given"
+    )]
+    Serve(ServeArgs),
+    /// run programs, each reading the output of the previous one
+    #[command(
+        after_help = "the programs run concurrently: the first one reads the standard\n\
+            input and the last one writes to the standard output"
+    )]
+    Pipe(PipeArgs),
+    /// list, run or export the example programs
+    #[command(
+        after_help = "[action] is list (default), run <name>, show <name> to print its\n\
+            source, or export [directory] to write each example as <name>.bf"
+    )]
+    Examples(ExamplesArgs),
+    /// check the interpreter and the backends on a corpus of programs
+    #[command(
+        after_help = "the corpus covers wrapping cells, the modes of end of input, and deep\n\
+            nesting; each target running on this system is built and run with\n\
+            its usual tools, $CC for C and the assembly, $RUSTC for Rust, llc,\n\
+            qbe, node, python3, go, lua, javac and java, mcs and mono, and is\n\
+            skipped when they can't run"
+    )]
+    Selftest,
+    /// run or build a program again each time it changes
+    #[command(
+        after_help = "the program is run in the interpreter, or built into [output] when\n\
+            it is given, until interrupted"
+    )]
+    Watch(WatchArgs),
+}
+
+/// The program of a subcommand, from a source or -c
+#[derive(Args)]
+pub(super) struct ProgramArgs {
+    /// brainfuck program, instead of a source file
+    #[arg(
+        short,
+        long,
+        value_name = "code",
+        conflicts_with = "source",
+        allow_hyphen_values = true
+    )]
+    pub(super) code: Option<String>,
+
+    /// brainfuck source, - for the standard input
+    #[arg(value_name = "source")]
+    pub(super) source: Option<String>,
+}
+
+#[derive(Args)]
+pub(super) struct DialectArgs {
+    /// language of the sources: classic (default), pbrain, extended
+    /// (Extended Brainfuck Type I), or one of the dialects replacing the
+    /// commands by words: ook, blub, alphuck, pikalang
+    #[arg(long, value_name = "name", value_parser = parse_dialect)]
+    pub(super) dialect: Option<Language>,
+}
+
+impl DialectArgs {
+    pub(super) fn apply(&self, config: &mut Config) {
+        if let Some(language) = self.dialect {
+            config.language = language;
+        }
+    }
+}
+
+#[derive(Args)]
+pub(super) struct CompileArgs {
+    /// optimization level: 0 (none), 1 (merge the increments and moves), 2
+    /// (default, also replace the clear and multiply loops), 3 (also gather
+    /// the increments by offset, and run the start of the program at compile
+    /// time, up to its input)
+    #[arg(
+        short = 'O',
+        long,
+        value_name = "level",
+        value_parser = clap::value_parser!(u8).range(0..=3)
+    )]
+    pub(super) opt_level: Option<u8>,
+
+    /// print the duration of each stage, and the size of the program after
+    /// it, on the standard error
+    #[arg(short, long)]
+    pub(super) verbose: bool,
+}
+
+impl CompileArgs {
+    pub(super) fn apply(&self, config: &mut Config) {
+        if let Some(level) = self.opt_level {
+            config.opt_level = level;
+        }
+        VERBOSE.store(self.verbose, Ordering::Relaxed);
+    }
+}
+
+#[derive(Args)]
+pub(super) struct TapeArgs {
+    /// value of the cell when reading past the end of the input: unchanged
+    /// (default), zero, minus-one; 6502 and the executables of build only
+    /// leave it unchanged
+    #[arg(long, value_name = "mode", hide_possible_values = true)]
+    pub(super) eof: Option<Eof>,
+
+    /// length of the tape (default 30000)
+    #[arg(long, value_name = "cells")]
+    pub(super) tape_size: Option<NonZeroUsize>,
+
+    /// width of the cells: 8 (default), 16, 32
+    #[arg(long, value_name = "bits", hide_possible_values = true)]
+    pub(super) cell_width: Option<Cell>,
+
+    /// behavior past the ends of the tape: unchecked (default), wrap; the
+    /// wat, wasm, qbe, class, assembly and executable targets only take 8-bit
+    /// cells on an unchecked tape
+    #[arg(long, value_name = "mode", hide_possible_values = true)]
+    pub(super) bounds: Option<Bounds>,
+}
+
+impl TapeArgs {
+    /// Settings of the VM and the backends with these options, the others
+    /// being the defaults
+    pub(super) fn config(&self) -> Config {
+        let mut config = Config::default();
+        if let Some(eof) = self.eof {
+            config.eof = eof;
+        }
+        if let Some(size) = self.tape_size {
+            config.tape_size = size.get();
+        }
+        if let Some(cell) = self.cell_width {
+            config.cell = cell;
+        }
+        if let Some(bounds) = self.bounds {
+            config.bounds = bounds;
+        }
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct InputArgs {
+    /// file read by the program, instead of the standard input
+    #[arg(long, value_name = "file")]
+    pub(super) input: Option<String>,
+
+    /// string read by the program, instead of the standard input
+    #[arg(
+        long,
+        value_name = "string",
+        conflicts_with = "input",
+        allow_hyphen_values = true
+    )]
+    pub(super) input_str: Option<String>,
+}
+
+impl InputArgs {
+    /// Input of the program
+    pub(super) fn input(&self) -> Input {
+        match (&self.input, &self.input_str) {
+            (Some(path), _) => Input::File(path.into()),
+            (None, Some(string)) => Input::Bytes(string.as_bytes().to_vec()),
+            (None, None) => Input::Stdin,
+        }
+    }
+}
+
+#[derive(Args)]
+pub(super) struct LimitArgs {
+    /// stop the program with an error when it writes more bytes (unlimited by
+    /// default)
+    #[arg(long, value_name = "bytes")]
+    pub(super) max_output: Option<u64>,
+
+    /// stop the program with an error when it runs more instructions
+    /// (unlimited by default)
+    #[arg(long, value_name = "steps")]
+    pub(super) max_steps: Option<u64>,
+
+    /// stop the program with an error when it runs longer (unlimited by
+    /// default)
+    #[arg(long, value_name = "seconds", value_parser = parse_seconds)]
+    pub(super) timeout: Option<Duration>,
+}
+
+impl LimitArgs {
+    pub(super) fn apply(&self, config: &mut Config) {
+        config.max_output = self.max_output;
+        config.max_steps = self.max_steps;
+        config.timeout = self.timeout;
+    }
+}
+
+#[derive(Args, Default)]
+pub(super) struct LintArgs {
+    /// report these lints, separated by commas, as warnings
+    #[arg(long, value_name = "lints")]
+    pub(super) warn: Option<String>,
+
+    /// don't report these lints
+    #[arg(long, value_name = "lints")]
+    pub(super) allow: Option<String>,
+
+    /// report these lints as errors, failing with the status of an invalid
+    /// program
+    #[arg(long, value_name = "lints")]
+    pub(super) deny: Option<String>,
+
+    /// deny all the lints reported as warnings
+    #[arg(long)]
+    pub(super) deny_warnings: bool,
+}
+
+#[derive(Args)]
+pub(super) struct FormatArgs {
+    /// width of the formatted brainfuck (default 80)
+    #[arg(long, value_name = "columns")]
+    pub(super) line_width: Option<usize>,
+
+    /// follow the formatted runs with their length
+    #[arg(long)]
+    pub(super) annotate_runs: bool,
+}
+
+impl FormatArgs {
+    /// Layout of the formatted brainfuck
+    pub(super) fn pretty(&self) -> PrettyBf {
+        let default = PrettyBf::default();
+        PrettyBf {
+            line_width: self.line_width.unwrap_or(default.line_width),
+            annotate_runs: self.annotate_runs,
+        }
+    }
+}
+
+#[derive(Args)]
+pub(super) struct RunArgs {
+    #[command(flatten)]
+    pub(super) program: ProgramArgs,
+
+    /// sources run after it, with --link
+    #[arg(value_name = "sources")]
+    pub(super) sources: Vec<String>,
+
+    /// run the sources one after the other, as a single program on the same
+    /// tape
+    #[arg(long)]
+    pub(super) link: bool,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) input: InputArgs,
+
+    /// compile with the system C (or Rust, with -t rs) compiler and run the
+    /// program
+    #[arg(long)]
+    pub(super) run_native: bool,
+
+    /// language compiled by --run-native: c (default), rs
+    #[arg(short, long, value_name = "target")]
+    pub(super) target: Option<String>,
+
+    /// give each key pressed on a terminal to the program at once, without
+    /// echo
+    #[arg(long)]
+    pub(super) raw_input: bool,
+
+    /// shown on the standard error when the program reads a new line of a
+    /// terminal ("> " by default)
+    #[arg(long, value_name = "text", allow_hyphen_values = true)]
+    pub(super) prompt: Option<String>,
+
+    /// run the program this many times after a warmup, on the same input and
+    /// without output, and print statistics of the run times
+    #[arg(long, value_name = "runs")]
+    pub(super) bench: Option<NonZeroUsize>,
+
+    /// print the statistics of --bench as JSON
+    #[arg(long)]
+    pub(super) bench_json: bool,
+
+    /// print the numbers of instructions, iterations, bytes read and written,
+    /// the limits hit and the run time on the standard error after the run
+    #[arg(long)]
+    pub(super) stats: bool,
+
+    /// print the pointer and a hexdump of the tape on the standard error
+    /// after the run
+    #[arg(long)]
+    pub(super) dump_memory: bool,
+
+    /// cells dumped by --dump-memory, up to the highest one used by default
+    #[arg(long, value_name = "start>..<end")]
+    pub(super) dump_range: Option<String>,
+
+    /// file written by the program, instead of the standard output
+    #[arg(long, value_name = "file")]
+    pub(super) output_data: Option<String>,
+
+    /// print each read and write of these cells, separated by commas, with
+    /// the command doing it, on the standard error
+    #[arg(long, value_name = "cells")]
+    pub(super) watch_cell: Option<String>,
+
+    /// write the number of executions of each command to this file after the
+    /// run, as an annotated copy of the source, or as JSON when the file ends
+    /// with .json; the program runs unoptimized
+    #[arg(long, value_name = "file")]
+    pub(super) coverage: Option<String>,
+
+    /// write the instructions executed in each chain of nested loops to this
+    /// file, as the folded stacks of flamegraph and inferno
+    #[arg(long, value_name = "file")]
+    pub(super) flamegraph: Option<String>,
+
+    /// write an animation of the tape and the pointer over the run to this
+    /// file, as SVG, or as GIF when the file ends with .gif
+    #[arg(long, value_name = "file")]
+    pub(super) animate: Option<String>,
+
+    /// instructions between the frames of --animate (1)
+    #[arg(long, value_name = "steps", value_parser = clap::value_parser!(u64).range(1..))]
+    pub(super) animate_every: Option<u64>,
+
+    /// cells shown by --animate, up to the highest one used by default, at
+    /// most 32
+    #[arg(long, value_name = "cells")]
+    pub(super) animate_cells: Option<NonZeroUsize>,
+
+    /// print the lowest and the highest cells reached by the pointer in each
+    /// loop and in the whole run, on the standard error
+    #[arg(long)]
+    pub(super) audit_bounds: bool,
+
+    /// warn when --audit-bounds finds the pointer this close to the end of
+    /// the tape (16)
+    #[arg(
+        long,
+        value_name = "cells",
+        default_value_t = 16,
+        hide_default_value = true
+    )]
+    pub(super) audit_margin: usize,
+
+    /// when the program fails, write its tape and its last instructions to
+    /// this file, for inspect
+    #[arg(long, value_name = "file")]
+    pub(super) core: Option<String>,
+
+    /// write the offset of each instruction, with the pointer and its cell
+    /// before it, to this file as JSON lines, for trace-diff and replay
+    #[arg(long, value_name = "file")]
+    pub(super) trace: Option<String>,
+
+    #[command(flatten)]
+    pub(super) limits: LimitArgs,
+}
+
+impl RunArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        self.compile.apply(&mut config);
+        self.limits.apply(&mut config);
+        if let Some(prompt) = &self.prompt {
+            let _ = PROMPT.set(prompt.clone());
+        }
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct CompareArgs {
+    #[command(flatten)]
+    pub(super) program: ProgramArgs,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) input: InputArgs,
+}
+
+impl CompareArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct OutlineArgs {
+    #[command(flatten)]
+    pub(super) program: ProgramArgs,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) input: InputArgs,
+
+    /// run the program first, counting the runs and the iterations of each
+    /// loop
+    #[arg(long)]
+    pub(super) profile: bool,
+}
+
+impl OutlineArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct SymbolicArgs {
+    #[command(flatten)]
+    pub(super) program: ProgramArgs,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    /// bytes read at most, the input ending after them (4)
+    #[arg(long, value_name = "bytes")]
+    pub(super) max_input: Option<usize>,
+
+    /// values of the bytes read, both included (0..255)
+    #[arg(long, value_name = "first>..<last")]
+    pub(super) byte_range: Option<String>,
+
+    /// runs explored at most (10000)
+    #[arg(long, value_name = "paths")]
+    pub(super) max_paths: Option<usize>,
+
+    /// instructions of each run at most (100000)
+    #[arg(long, value_name = "steps")]
+    pub(super) max_steps: Option<u64>,
+}
+
+impl SymbolicArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct InspectArgs {
+    /// core written by run --core
+    #[arg(value_name = "core")]
+    pub(super) core: String,
+
+    /// cells dumped, the 32 around the pointer by default
+    #[arg(long, value_name = "start>..<end")]
+    pub(super) range: Option<String>,
+
+    /// print all the instructions kept in the core, instead of the last 8
+    #[arg(long)]
+    pub(super) history: bool,
+}
+
+#[derive(Args)]
+pub(super) struct ReplayArgs {
+    #[command(flatten)]
+    pub(super) program: ProgramArgs,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) input: InputArgs,
+
+    /// trace written by run --trace, giving the bytes read, and checked at
+    /// each step
+    #[arg(long, value_name = "file")]
+    pub(super) trace: Option<String>,
+
+    /// stop in the line debugger before this step
+    #[arg(long, value_name = "step", value_parser = clap::value_parser!(i64).range(1..))]
+    pub(super) to_step: Option<i64>,
+}
+
+impl ReplayArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        self.compile.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct TraceDiffArgs {
+    /// traces written by run --trace
+    #[arg(value_name = "trace", num_args = 2, required = true)]
+    pub(super) traces: Vec<String>,
+
+    /// compare the pointer and the cell at each step only, for edited sources
+    #[arg(long)]
+    pub(super) ignore_offsets: bool,
+}
+
+/// The options of build choosing the target and the settings of the
+/// generated code
+#[derive(Args, Default)]
+pub(super) struct BuildOptions {
+    /// build each source into this directory, with the extension of the
+    /// target
+    #[arg(long, value_name = "dir")]
+    pub(super) out_dir: Option<String>,
+
+    /// output language, guessed from the output file extension by default
+    /// (bin when it has none): bf, bf-pretty, bf-obfuscated, ook, blub,
+    /// alphuck, pikalang, c, rs, ll, wat, wasm, asm-x86_64, asm-riscv64, 6502
+    /// (ca65), elf, pe (Windows), macho (macOS), bin (executable of
+    /// --binary-format), js, py, go, lua, java, class, cs, qbe, c-lib (C
+    /// function bf_run), so (shared library exporting bf_run), avr (C for an
+    /// Arduino Uno, with I/O on the UART), rs-nostd (no_std Rust crate
+    /// exporting run), dot (graph of the optimized program), ast.json
+    /// (optimized AST), html (highlighted source, with the counts of
+    /// --profile), tokens, ast (parsed AST) and ir (optimized AST) dumps
+    #[arg(short, long, visible_alias = "emit", value_name = "target")]
+    pub(super) target: Option<String>,
+
+    /// run the program first, counting the executions to shade the dot and
+    /// html outputs
+    #[arg(long)]
+    pub(super) profile: bool,
+
+    /// output obfuscated brainfuck, same as --target bf-obfuscated
+    #[arg(long)]
+    pub(super) obfuscate: bool,
+
+    /// seed of the obfuscator (default 0)
+    #[arg(long, value_name = "seed")]
+    pub(super) seed: Option<u64>,
+
+    /// map generated C back to the source with #line
+    #[arg(long)]
+    pub(super) line_directives: bool,
+
+    /// stop the generated C when the pointer leaves the tape, with the offset
+    /// in the source
+    #[arg(long)]
+    pub(super) codegen_checks: bool,
+
+    /// format of the executables of the bin target: elf, pe, macho (default:
+    /// the one of this system)
+    #[arg(long, value_name = "format", hide_possible_values = true)]
+    pub(super) binary_format: Option<BinaryFormat>,
+}
+
+#[derive(Args)]
+pub(super) struct BuildArgs {
+    /// brainfuck source, - for the standard input
+    #[arg(value_name = "source")]
+    pub(super) source: Option<String>,
+
+    /// output file, - for the standard output, or the other sources with
+    /// --out-dir
+    #[arg(value_name = "output | sources")]
+    pub(super) outputs: Vec<String>,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) build: BuildOptions,
+
+    #[command(flatten)]
+    pub(super) format: FormatArgs,
+
+    #[command(flatten)]
+    pub(super) lints: LintArgs,
+}
+
+impl BuildArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        self.compile.apply(&mut config);
+        config.codegen_checks = self.build.codegen_checks;
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct CheckArgs {
+    #[command(flatten)]
+    pub(super) program: ProgramArgs,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) lints: LintArgs,
+
+    /// rewrite the source in place, removing the useless sequences and the
+    /// dead loops reported
+    #[arg(long)]
+    pub(super) fix: bool,
+}
+
+impl CheckArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct FmtArgs {
+    /// brainfuck source, - for the standard input
+    #[arg(value_name = "source")]
+    pub(super) source: Option<String>,
+
+    /// output file, - for the standard output
+    #[arg(value_name = "output")]
+    pub(super) output: Option<String>,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) format: FormatArgs,
+}
+
+impl FmtArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = Config::default();
+        self.dialect.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct ReplArgs {
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+}
+
+impl ReplArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        self.compile.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct DebugArgs {
+    #[command(flatten)]
+    pub(super) program: ProgramArgs,
+
+    /// full-screen debugger, showing the source, the tape and the output,
+    /// driven by single keys
+    #[cfg(feature = "tui")]
+    #[arg(long)]
+    pub(super) tui: bool,
+
+    /// stop at these byte offsets of the source, or labels, separated by
+    /// commas, running up to the first one
+    #[arg(long = "break", value_name = "breakpoints")]
+    pub(super) breakpoints: Option<String>,
+
+    /// stop after each read and write of these cells, separated by commas
+    #[arg(long, value_name = "cells")]
+    pub(super) watch_cell: Option<String>,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) input: InputArgs,
+}
+
+impl DebugArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        config
+    }
+}
+
+#[cfg(feature = "server")]
+#[derive(Args)]
+pub(super) struct DapArgs {
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+}
+
+#[cfg(feature = "server")]
+impl DapArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        config
+    }
+}
+
+#[cfg(feature = "server")]
+#[derive(Args)]
+pub(super) struct LspArgs {
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) lints: LintArgs,
+
+    #[command(flatten)]
+    pub(super) format: FormatArgs,
+}
+
+#[cfg(feature = "server")]
+impl LspArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        config
+    }
+}
+
+#[cfg(feature = "server")]
+#[derive(Args)]
+pub(super) struct JupyterKernelArgs {
+    /// connection file given by Jupyter
+    #[arg(value_name = "connection file")]
+    pub(super) connection_file: Option<String>,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) limits: LimitArgs,
+
+    /// keep the tape and the pointer across the cells
+    #[arg(long)]
+    pub(super) persistent_tape: bool,
+
+    /// accept a connection file without a key, whose messages anyone reaching
+    /// the ports can forge
+    #[arg(long)]
+    pub(super) unsigned: bool,
+
+    /// print the kernel.json installing this kernel, and exit
+    #[arg(long)]
+    pub(super) kernelspec: bool,
+}
+
+#[cfg(feature = "server")]
+impl JupyterKernelArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        self.compile.apply(&mut config);
+        self.limits.apply(&mut config);
+        config
+    }
+}
+
+#[cfg(feature = "server")]
+#[derive(Args)]
+pub(super) struct ServeArgs {
+    #[command(flatten)]
+    pub(super) program: ProgramArgs,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    #[command(flatten)]
+    pub(super) limits: LimitArgs,
+
+    /// address of the server, 127.0.0.1:8080 by default
+    #[arg(
+        long,
+        value_name = "address",
+        default_value = "127.0.0.1:8080",
+        hide_default_value = true
+    )]
+    pub(super) listen: String,
+}
+
+#[cfg(feature = "server")]
+impl ServeArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        self.compile.apply(&mut config);
+        self.limits.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct PipeArgs {
+    /// sources of the programs, in the order of the pipe
+    #[arg(value_name = "sources", required = true)]
+    pub(super) sources: Vec<String>,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+}
+
+impl PipeArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        self.compile.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct ExamplesArgs {
+    /// list, run, show or export
+    #[arg(value_name = "action")]
+    pub(super) action: Option<String>,
+
+    /// example to run or show, or directory of the export
+    #[arg(value_name = "name | directory")]
+    pub(super) name: Option<String>,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+}
+
+impl ExamplesArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.compile.apply(&mut config);
+        config
+    }
+}
+
+#[derive(Args)]
+pub(super) struct WatchArgs {
+    /// brainfuck source
+    #[arg(value_name = "source")]
+    pub(super) source: String,
+
+    /// output file, the program being run when it is missing
+    #[arg(value_name = "output")]
+    pub(super) output: Option<String>,
+
+    #[command(flatten)]
+    pub(super) dialect: DialectArgs,
+
+    #[command(flatten)]
+    pub(super) compile: CompileArgs,
+
+    #[command(flatten)]
+    pub(super) tape: TapeArgs,
+
+    /// output language, guessed from the output file extension by default, as
+    /// for build
+    #[arg(short, long, value_name = "target")]
+    pub(super) target: Option<String>,
+}
+
+impl WatchArgs {
+    pub(super) fn config(&self) -> Config {
+        let mut config = self.tape.config();
+        self.dialect.apply(&mut config);
+        self.compile.apply(&mut config);
+        config
+    }
+}
+
+/// Format of the diagnostics, by --message-format
+#[derive(Clone, Copy, ValueEnum)]
+pub(super) enum MessageFormat {
+    Human,
+    Json,
+}
+
+impl MessageFormat {
+    pub(super) fn apply(self) {
+        JSON_MESSAGES.store(matches!(self, MessageFormat::Json), Ordering::Relaxed);
+    }
+}
+
+impl ValueEnum for Eof {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Eof::Unchanged, Eof::Zero, Eof::MinusOne]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            Eof::Unchanged => "unchanged",
+            Eof::Zero => "zero",
+            Eof::MinusOne => "minus-one",
+        }))
+    }
+}
+
+impl ValueEnum for Cell {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Cell::U8, Cell::U16, Cell::U32]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            Cell::U8 => "8",
+            Cell::U16 => "16",
+            Cell::U32 => "32",
+        }))
+    }
+}
+
+impl ValueEnum for Bounds {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Bounds::Unchecked, Bounds::Wrap]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            Bounds::Unchecked => "unchecked",
+            Bounds::Wrap => "wrap",
+        }))
+    }
+}
+
+impl ValueEnum for BinaryFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[BinaryFormat::Elf, BinaryFormat::Pe, BinaryFormat::MachO]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            BinaryFormat::Elf => "elf",
+            BinaryFormat::Pe => "pe",
+            BinaryFormat::MachO => "macho",
+        }))
+    }
+}
+
+/// Language of a --dialect
+fn parse_dialect(name: &str) -> Result<Language, String> {
+    match name {
+        "classic" => Ok(Language::Classic),
+        "pbrain" => Ok(Language::Pbrain),
+        "extended" => Ok(Language::Extended),
+        name => match DIALECTS.iter().find(|(dialect, _)| *dialect == name) {
+            Some((_, dialect)) => Ok(Language::Words(dialect)),
+            None => Err(String::from("unknown dialect")),
+        },
+    }
+}
+
+/// Duration of a --timeout, in seconds
+fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    seconds
+        .parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| String::from("expected a number of seconds"))
+}
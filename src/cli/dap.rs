@@ -0,0 +1,378 @@
+//! The dap command, a debug adapter for the editors speaking the Debug
+//! Adapter Protocol
+
+use std::str;
+use std::sync::mpsc::Receiver;
+
+use crate::json::Json;
+use crate::lexer::parse_source_in;
+use crate::vm::Input;
+use crate::Config;
+
+use super::{
+    compile_program, json_object, json_string, read_source, send_message, source_name,
+    spawn_message_reader, CliError, Debuggee, Failure, DEBUG_RUN_STEPS,
+};
+
+/// Text of an error of the command line, with its location
+fn located_message(error: &CliError) -> String {
+    match &error.location {
+        Some(location) => format!(
+            "{}:{}:{}: {}",
+            location.path, location.line, location.column, error.message
+        ),
+        None => error.message.clone(),
+    }
+}
+
+/// The debug adapter, between an editor and the program it debugs
+struct Dap {
+    config: Config,
+    seq: i64, // Sequence number of the last message sent
+    debuggee: Option<Debuggee>,
+    path: String,
+    commands: Vec<usize>, // Offsets of the commands of the source
+    stop_on_entry: bool,
+    sent: usize,        // Bytes of the output already sent
+    pending: Vec<Json>, // Requests received while the program ran
+}
+
+impl Dap {
+    /// Send a message to the editor
+    fn send(&mut self, members: Vec<(&str, Json)>) {
+        self.seq += 1;
+        let mut message = vec![("seq", Json::Number(self.seq))];
+        message.extend(members);
+        send_message(json_object(message));
+    }
+
+    fn respond(&mut self, request: &Json, body: Result<Json, String>) {
+        let command = request.get("command").cloned().unwrap_or(Json::Null);
+        let seq = request.get("seq").cloned().unwrap_or(Json::Null);
+        let mut members = vec![
+            ("type", json_string("response")),
+            ("request_seq", seq),
+            ("success", Json::Bool(body.is_ok())),
+            ("command", command),
+        ];
+        match body {
+            Ok(body) => members.push(("body", body)),
+            Err(message) => members.push(("message", Json::String(message))),
+        }
+        self.send(members);
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.send(vec![
+            ("type", json_string("event")),
+            ("event", json_string(event)),
+            ("body", body),
+        ]);
+    }
+
+    fn stopped(&mut self, reason: &str) {
+        self.send_output();
+        let body = json_object(vec![
+            ("reason", json_string(reason)),
+            ("threadId", Json::Number(1)),
+            ("allThreadsStopped", Json::Bool(true)),
+        ]);
+        self.event("stopped", body);
+    }
+
+    /// Send the bytes written by the program since the last time
+    fn send_output(&mut self) {
+        let output = match &self.debuggee {
+            Some(debuggee) => debuggee.output.contents(),
+            None => return,
+        };
+        if output.len() > self.sent {
+            let text = String::from_utf8_lossy(&output[self.sent..]).into_owned();
+            self.sent = output.len();
+            let body = json_object(vec![
+                ("category", json_string("stdout")),
+                ("output", Json::String(text)),
+            ]);
+            self.event("output", body);
+        }
+    }
+
+    /// Tell the editor the program ended, with the error stopping it if any
+    fn exited(&mut self, error: Option<String>) {
+        self.send_output();
+        if let Some(message) = &error {
+            let body = json_object(vec![
+                ("category", json_string("stderr")),
+                ("output", Json::String(format!("{}\n", message))),
+            ]);
+            self.event("output", body);
+        }
+        let code = match error {
+            Some(_) => Failure::Runtime as i64,
+            None => 0,
+        };
+        self.event(
+            "exited",
+            json_object(vec![("exitCode", Json::Number(code))]),
+        );
+        self.event("terminated", json_object(vec![]));
+    }
+
+    /// Run a step, or until a breakpoint or a pause
+    fn resume(&mut self, messages: &Receiver<Json>, single: bool) {
+        let steps = if single { 1 } else { DEBUG_RUN_STEPS };
+        loop {
+            let debuggee = match &mut self.debuggee {
+                Some(debuggee) => debuggee,
+                None => return,
+            };
+            for _ in 0..steps {
+                match debuggee.step() {
+                    Ok(true) => {}
+                    Ok(false) => return self.exited(None),
+                    Err(message) => return self.exited(Some(message)),
+                }
+                if debuggee.at_breakpoint() && !single {
+                    return self.stopped("breakpoint");
+                }
+            }
+            if single {
+                return self.stopped("step");
+            }
+            self.send_output();
+
+            // The other requests wait for the program to stop
+            while let Ok(message) = messages.try_recv() {
+                let command = message.get("command").and_then(Json::as_str);
+                match command {
+                    Some("pause") => {
+                        self.respond(&message, Ok(json_object(vec![])));
+                        return self.stopped("pause");
+                    }
+                    Some("disconnect" | "terminate") => {
+                        self.pending.push(message);
+                        return;
+                    }
+                    _ => self.pending.push(message),
+                }
+            }
+        }
+    }
+
+    /// Load the program of a launch request
+    fn launch(&mut self, arguments: &Json) -> Result<Json, String> {
+        let path = arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or("the launch request needs the path of the program")?;
+        let source = read_source(path).map_err(|error| located_message(&error))?;
+        let ast = compile_program(path, &source, self.config.language, 0)
+            .map_err(|error| located_message(&error))?;
+        let input = arguments.get("input").and_then(Json::as_str).unwrap_or("");
+        let input = Input::Bytes(input.as_bytes().to_vec());
+        self.stop_on_entry = arguments.get("stopOnEntry") == Some(&Json::Bool(true));
+        self.commands = parse_source_in(&source, self.config.language)
+            .iter()
+            .map(|(offset, _)| *offset)
+            .collect();
+        self.debuggee = Some(Debuggee::new(
+            String::from(path),
+            &source,
+            &ast,
+            self.config.clone(),
+            input,
+        ));
+        self.path = String::from(path);
+        Ok(json_object(vec![]))
+    }
+
+    /// Put the breakpoints on the first command of their line, from their
+    /// column when it is given
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        let path = arguments
+            .get("source")
+            .and_then(|source| source.get("path"));
+        let requested = match arguments.get("breakpoints") {
+            Some(Json::Array(breakpoints)) => breakpoints.as_slice(),
+            _ => &[],
+        };
+        let mut breakpoints = Vec::new();
+        let mut offsets = Vec::new();
+        for breakpoint in requested {
+            let line = breakpoint.get("line").and_then(Json::as_i64).unwrap_or(0);
+            let column = breakpoint.get("column").and_then(Json::as_i64).unwrap_or(1);
+            let location = self.debuggee.as_ref().and_then(|debuggee| {
+                let same_source = path.and_then(Json::as_str) == Some(self.path.as_str());
+                self.commands
+                    .iter()
+                    .map(|&offset| (offset, debuggee.lines.location(offset)))
+                    .filter(|_| same_source)
+                    .find(|&(_, (l, c))| l as i64 == line && c as i64 >= column)
+            });
+            let mut members = vec![("verified", Json::Bool(location.is_some()))];
+            if let Some((offset, (line, column))) = location {
+                offsets.push(offset);
+                members.push(("line", Json::Number(line as i64)));
+                members.push(("column", Json::Number(column as i64)));
+            }
+            breakpoints.push(json_object(members));
+        }
+        if let Some(debuggee) = &mut self.debuggee {
+            debuggee.breakpoints = offsets;
+        }
+        json_object(vec![("breakpoints", Json::Array(breakpoints))])
+    }
+
+    /// The single frame of the program, at its next command
+    fn stack_trace(&self) -> Json {
+        let mut frame = vec![("id", Json::Number(1)), ("name", json_string("main"))];
+        let location = self.debuggee.as_ref().and_then(|debuggee| {
+            let offset = debuggee.stepper.next_offset()?;
+            Some(debuggee.lines.location(offset))
+        });
+        let (line, column) = location.unwrap_or((0, 0));
+        frame.extend([
+            ("line", Json::Number(line as i64)),
+            ("column", Json::Number(column as i64)),
+            (
+                "source",
+                json_object(vec![
+                    ("name", json_string(source_name(&self.path))),
+                    ("path", json_string(&self.path)),
+                ]),
+            ),
+        ]);
+        json_object(vec![
+            ("stackFrames", Json::Array(vec![json_object(frame)])),
+            ("totalFrames", Json::Number(1)),
+        ])
+    }
+
+    /// The pointer, and the cells of the tape up to the highest one used
+    fn variables(&self) -> Json {
+        let variable = |name: String, value: String| {
+            json_object(vec![
+                ("name", Json::String(name)),
+                ("value", Json::String(value)),
+                ("variablesReference", Json::Number(0)),
+            ])
+        };
+        let mut variables = Vec::new();
+        if let Some(debuggee) = &self.debuggee {
+            let state = &debuggee.state;
+            variables.push(variable(String::from("pointer"), state.index.to_string()));
+            let end = (state.highest.max(state.index) + 1).min(state.memory.len());
+            for (index, value) in state.memory[..end].iter().enumerate() {
+                variables.push(variable(format!("[{}]", index), value.to_string()));
+            }
+        }
+        json_object(vec![("variables", Json::Array(variables))])
+    }
+
+    /// Answer a request, returning false once the editor disconnects
+    fn handle(&mut self, request: &Json, messages: &Receiver<Json>) -> bool {
+        let empty = json_object(vec![]);
+        let arguments = request.get("arguments").unwrap_or(&empty);
+        match request.get("command").and_then(Json::as_str).unwrap_or("") {
+            "initialize" => {
+                let capabilities =
+                    json_object(vec![("supportsConfigurationDoneRequest", Json::Bool(true))]);
+                self.respond(request, Ok(capabilities));
+            }
+            "launch" => {
+                let result = self.launch(arguments);
+                let launched = result.is_ok();
+                self.respond(request, result);
+                if launched {
+                    self.event("initialized", json_object(vec![]));
+                }
+            }
+            "setBreakpoints" => {
+                let body = self.set_breakpoints(arguments);
+                self.respond(request, Ok(body));
+            }
+            "setExceptionBreakpoints" | "setFunctionBreakpoints" => {
+                self.respond(request, Ok(empty.clone()))
+            }
+            "configurationDone" => {
+                self.respond(request, Ok(empty.clone()));
+                match self.stop_on_entry {
+                    true => self.stopped("entry"),
+                    false => self.resume(messages, false),
+                }
+            }
+            "threads" => {
+                let thread =
+                    json_object(vec![("id", Json::Number(1)), ("name", json_string("main"))]);
+                let body = json_object(vec![("threads", Json::Array(vec![thread]))]);
+                self.respond(request, Ok(body));
+            }
+            "stackTrace" => {
+                let body = self.stack_trace();
+                self.respond(request, Ok(body));
+            }
+            "scopes" => {
+                let scope = json_object(vec![
+                    ("name", json_string("Tape")),
+                    ("variablesReference", Json::Number(1)),
+                    ("expensive", Json::Bool(false)),
+                ]);
+                let body = json_object(vec![("scopes", Json::Array(vec![scope]))]);
+                self.respond(request, Ok(body));
+            }
+            "variables" => {
+                let body = self.variables();
+                self.respond(request, Ok(body));
+            }
+            "continue" => {
+                let body = json_object(vec![("allThreadsContinued", Json::Bool(true))]);
+                self.respond(request, Ok(body));
+                self.resume(messages, false);
+            }
+            // The program has no functions to step over or out of
+            "next" | "stepIn" | "stepOut" => {
+                self.respond(request, Ok(empty.clone()));
+                self.resume(messages, true);
+            }
+            "pause" => {
+                self.respond(request, Ok(empty.clone()));
+                self.stopped("pause");
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(empty.clone()));
+                return false;
+            }
+            command => self.respond(request, Err(format!("unsupported request {:?}", command))),
+        }
+        true
+    }
+}
+
+/// Serve the Debug Adapter Protocol on the standard input and output, until
+/// the editor disconnects
+pub fn command_dap(config: Config) {
+    let messages = spawn_message_reader();
+    let mut dap = Dap {
+        config,
+        seq: 0,
+        debuggee: None,
+        path: String::new(),
+        commands: Vec::new(),
+        stop_on_entry: false,
+        sent: 0,
+        pending: Vec::new(),
+    };
+    loop {
+        let message = match dap.pending.is_empty() {
+            false => dap.pending.remove(0),
+            true => match messages.recv() {
+                Ok(message) => message,
+                Err(_) => return,
+            },
+        };
+        let request = message.get("type").and_then(Json::as_str) == Some("request");
+        if request && !dap.handle(&message, &messages) {
+            return;
+        }
+    }
+}
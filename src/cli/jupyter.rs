@@ -0,0 +1,786 @@
+//! The jupyter-kernel command, a Jupyter kernel running the cells of the
+//! notebooks, over the ZMTP protocol of ZeroMQ
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::rc::Rc;
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::json::{parse_json, Json};
+use crate::lexer::{parse_source_in, ParseError, Token};
+use crate::vm::{run_ast, State};
+use crate::{Compiler, Config};
+
+use super::args::JupyterKernelArgs;
+use super::{compile_program, fail, json_object, json_string, runtime_error, usage_error, Failure};
+
+/// Write a frame of ZMTP 3.0, the protocol of ZeroMQ
+fn write_zmtp_frame(buffer: &mut Vec<u8>, body: &[u8], more: bool, command: bool) {
+    let flags = more as u8 | (command as u8) << 2;
+    match body.len() {
+        0..=255 => buffer.extend_from_slice(&[flags, body.len() as u8]),
+        length => {
+            buffer.push(flags | 2);
+            buffer.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    buffer.extend_from_slice(body);
+}
+
+/// Greet a peer of a ZeroMQ socket, with the NULL security mechanism, and
+/// exchange the types of the sockets
+fn zmtp_handshake(stream: &mut TcpStream, socket_type: &str) -> io::Result<()> {
+    let mut greeting = [0; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    stream.write_all(&greeting)?;
+    let mut peer = [0; 64];
+    stream.read_exact(&mut peer)?;
+    if peer[0] != 0xff || peer[9] & 1 == 0 || peer[10] < 3 || &peer[12..17] != b"NULL\0" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a ZeroMQ peer with the NULL mechanism",
+        ));
+    }
+
+    let mut ready = b"\x05READY\x0bSocket-Type".to_vec();
+    ready.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    ready.extend_from_slice(socket_type.as_bytes());
+    let mut buffer = Vec::new();
+    write_zmtp_frame(&mut buffer, &ready, false, true);
+    stream.write_all(&buffer)?;
+    // The READY of the peer comes before its messages, and is skipped with
+    // the other commands
+    Ok(())
+}
+
+/// Bytes of the largest message read from a ZeroMQ socket, well over the
+/// size of the cells of the notebooks
+const MAX_ZMTP_MESSAGE: u64 = 64 << 20;
+
+/// Read a message of a ZeroMQ socket, as its frames, skipping the commands
+fn read_zmtp_message(stream: &mut TcpStream) -> io::Result<Vec<Vec<u8>>> {
+    let mut frames = Vec::new();
+    let mut size = 0;
+    loop {
+        let mut flags = [0];
+        stream.read_exact(&mut flags)?;
+        let length = match flags[0] & 2 {
+            0 => {
+                let mut length = [0];
+                stream.read_exact(&mut length)?;
+                length[0] as u64
+            }
+            _ => {
+                let mut length = [0; 8];
+                stream.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            }
+        };
+        size += length;
+        if size > MAX_ZMTP_MESSAGE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message over the size limit",
+            ));
+        }
+        let mut body = Vec::new();
+        Read::by_ref(stream).take(length).read_to_end(&mut body)?;
+        if body.len() as u64 != length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if flags[0] & 4 != 0 {
+            continue;
+        }
+        frames.push(body);
+        if flags[0] & 1 == 0 {
+            return Ok(frames);
+        }
+    }
+}
+
+/// Write a message of a ZeroMQ socket, made of some frames
+fn write_zmtp_message(mut stream: &TcpStream, frames: &[Vec<u8>]) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        write_zmtp_frame(&mut buffer, frame, i + 1 < frames.len(), false);
+    }
+    stream.write_all(&buffer)
+}
+
+/// Accept the connections of a ZeroMQ socket of Jupyter, each handled on a
+/// thread of its own once greeted
+fn spawn_zmtp_listener(
+    listener: TcpListener,
+    socket_type: &'static str,
+    handle: impl Fn(TcpStream) + Clone + Send + 'static,
+) {
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handle = handle.clone();
+            thread::spawn(move || {
+                let mut stream = stream;
+                if zmtp_handshake(&mut stream, socket_type).is_ok() {
+                    handle(stream);
+                }
+            });
+        }
+    });
+}
+
+/// The delimiter of the identities of a Jupyter message
+const JUPYTER_DELIMITER: &[u8] = b"<IDS|MSG>";
+
+/// A message of the Jupyter protocol
+struct JupyterMessage {
+    identities: Vec<Vec<u8>>, // Routing frames, sent back with the replies
+    header: Json,
+    content: Json,
+}
+
+impl JupyterMessage {
+    fn msg_type(&self) -> &str {
+        self.header
+            .get("msg_type")
+            .and_then(Json::as_str)
+            .unwrap_or("")
+    }
+}
+
+/// The signing of the messages and the publishing socket, shared by the
+/// kernel and the input of the cells
+#[derive(Clone)]
+struct JupyterSession {
+    key: Arc<[u8]>, // Empty with --unsigned, when the messages aren't signed
+    session: Rc<str>,
+    sent: Rc<std::cell::Cell<u64>>, // Messages sent so far, numbering their ids
+    iopub: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+/// The HMAC-SHA256 of the parts of a Jupyter message
+fn jupyter_mac(key: &[u8], parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+/// Bytes of a signature, in hexadecimal
+fn hex_bytes(hex: &[u8]) -> Option<Vec<u8>> {
+    hex.chunks(2)
+        .map(|digits| u8::from_str_radix(str::from_utf8(digits).ok()?, 16).ok())
+        .collect()
+}
+
+/// Parse the frames of a Jupyter message, checking its signature unless the
+/// key is empty
+fn parse_jupyter_message(key: &[u8], frames: Vec<Vec<u8>>) -> Option<JupyterMessage> {
+    let delimiter = frames.iter().position(|frame| frame == JUPYTER_DELIMITER)?;
+    let mut frames = frames.into_iter();
+    let identities = frames.by_ref().take(delimiter).collect();
+    let mut parts = frames.skip(1);
+    let signature = parts.next()?;
+    let parts: Vec<_> = parts.take(4).collect();
+    if parts.len() < 4 {
+        return None;
+    }
+    let parts_ref: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+    // The signature is compared in constant time, telling nothing of the
+    // key to the senders of forged messages
+    if !key.is_empty()
+        && jupyter_mac(key, &parts_ref)
+            .verify_slice(&hex_bytes(&signature)?)
+            .is_err()
+    {
+        return None;
+    }
+    let json = |part: &[u8]| parse_json(str::from_utf8(part).ok()?).ok();
+    Some(JupyterMessage {
+        identities,
+        header: json(&parts[0])?,
+        content: json(&parts[3])?,
+    })
+}
+
+impl JupyterSession {
+    /// Parse the frames of a message, checking its signature
+    fn parse(&self, frames: Vec<Vec<u8>>) -> Option<JupyterMessage> {
+        parse_jupyter_message(&self.key, frames)
+    }
+
+    /// The frames of a message answering a parent one
+    fn message(&self, msg_type: &str, parent: &JupyterMessage, content: Json) -> Vec<Vec<u8>> {
+        self.sent.set(self.sent.get() + 1);
+        let header = json_object(vec![
+            (
+                "msg_id",
+                json_string(&format!("{}-{}", self.session, self.sent.get())),
+            ),
+            ("session", json_string(&self.session)),
+            ("username", json_string("kernel")),
+            ("date", json_string(&utc_timestamp())),
+            ("msg_type", json_string(msg_type)),
+            ("version", json_string("5.3")),
+        ]);
+        let parts = [
+            header.to_string(),
+            parent.header.to_string(),
+            String::from("{}"),
+            content.to_string(),
+        ];
+        let parts_ref: Vec<&[u8]> = parts.iter().map(String::as_bytes).collect();
+        let signature = match self.key.is_empty() {
+            true => String::new(),
+            false => jupyter_mac(&self.key, &parts_ref)
+                .finalize()
+                .into_bytes()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        };
+        let mut frames = parent.identities.clone();
+        frames.push(JUPYTER_DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.extend(parts.iter().map(|part| part.as_bytes().to_vec()));
+        frames
+    }
+
+    /// Publish a message on the IOPub channel, to all its subscribers
+    fn publish(&self, msg_type: &str, parent: &JupyterMessage, content: Json) {
+        let mut frames = self.message(msg_type, parent, content);
+        frames.drain(..parent.identities.len());
+        frames.insert(0, msg_type.as_bytes().to_vec());
+        let mut subscribers = self.iopub.lock().unwrap();
+        subscribers.retain(|stream| write_zmtp_message(stream, &frames).is_ok());
+    }
+
+    /// Publish some output of a cell, if any
+    fn publish_output(&self, parent: &JupyterMessage, output: &[u8]) {
+        if !output.is_empty() {
+            let content = json_object(vec![
+                ("name", json_string("stdout")),
+                ("text", json_string(&String::from_utf8_lossy(output))),
+            ]);
+            self.publish("stream", parent, content);
+        }
+    }
+}
+
+/// The current time, in the ISO 8601 format of the Jupyter headers
+fn utc_timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let (days, seconds) = (now.as_secs() / 86400, now.as_secs() % 86400);
+
+    // The civil date of a day from 1970-01-01, by eras of 400 years
+    let days = days as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        now.subsec_micros()
+    )
+}
+
+/// Bytes of the output of a cell published at once, when no line ends in
+/// them
+const CELL_OUTPUT_CHUNK: usize = 4096;
+
+/// The output of a cell, published at the end of each line, by chunks of
+/// `CELL_OUTPUT_CHUNK` bytes for the longer lines, before each input request
+/// and at its end
+#[derive(Clone)]
+struct CellOutput {
+    session: JupyterSession,
+    request: Rc<JupyterMessage>, // The execute request of the cell
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CellOutput {
+    /// Publish the output written so far
+    fn publish(&self) {
+        let output = std::mem::take(&mut *self.buffer.borrow_mut());
+        self.session.publish_output(&self.request, &output);
+    }
+}
+
+impl Write for CellOutput {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let mut output = self.buffer.borrow_mut();
+        output.extend_from_slice(buffer);
+        let end = match buffer.iter().rposition(|byte| *byte == b'\n') {
+            Some(newline) => output.len() - buffer.len() + newline + 1,
+            None if output.len() >= CELL_OUTPUT_CHUNK => match str::from_utf8(&output) {
+                // A character cut at the end of the chunk waits for its
+                // next bytes
+                Err(error) if error.error_len().is_none() => error.valid_up_to(),
+                _ => output.len(),
+            },
+            None => return Ok(buffer.len()),
+        };
+        let lines: Vec<u8> = output.drain(..end).collect();
+        drop(output);
+        self.session.publish_output(&self.request, &lines);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.publish();
+        Ok(())
+    }
+}
+
+/// The input of a cell, a line asked to the notebook whenever the program
+/// reads past the previous one
+struct CellInput {
+    output: CellOutput, // The output of the cell, and its execute request
+    stdin: Arc<Mutex<Option<TcpStream>>>,
+    replies: Rc<Receiver<Vec<Vec<u8>>>>,
+    interrupt: Arc<AtomicBool>, // Ends the input of an interrupted cell
+    line: VecDeque<u8>,
+}
+
+impl Read for CellInput {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.line.is_empty() {
+            self.output.publish();
+            let session = &self.output.session;
+            let frames = session.message(
+                "input_request",
+                &self.output.request,
+                json_object(vec![
+                    ("prompt", json_string("")),
+                    ("password", Json::Bool(false)),
+                ]),
+            );
+            match &*self.stdin.lock().unwrap() {
+                Some(stream) => write_zmtp_message(stream, &frames)?,
+                None => return Ok(0),
+            }
+            // The notebook sends the line without its newline
+            let frames = loop {
+                match self.replies.recv_timeout(Duration::from_millis(100)) {
+                    Ok(frames) => break frames,
+                    Err(mpsc::RecvTimeoutError::Timeout)
+                        if !self.interrupt.load(Ordering::Relaxed) => {}
+                    Err(_) => return Ok(0),
+                }
+            };
+            let reply = session
+                .parse(frames)
+                .filter(|reply| reply.msg_type() == "input_reply");
+            let value = match reply.as_ref().and_then(|reply| reply.content.get("value")) {
+                Some(Json::String(value)) => value.clone(),
+                _ => return Ok(0),
+            };
+            self.line.extend(value.bytes().chain(Some(b'\n')));
+        }
+        let count = buffer.len().min(self.line.len());
+        for (byte, read) in buffer.iter_mut().zip(self.line.drain(..count)) {
+            *byte = read;
+        }
+        Ok(count)
+    }
+}
+
+/// A Jupyter kernel running the cells of a notebook, on a tape kept across
+/// the cells or not
+struct JupyterKernel {
+    session: JupyterSession,
+    config: Config,
+    tape: Option<State>, // The tape kept across the cells, with --persistent-tape
+    persistent: bool,
+    execution_count: i64,
+    stdin: Arc<Mutex<Option<TcpStream>>>, // The last connection of the stdin channel
+    replies: Rc<Receiver<Vec<Vec<u8>>>>,  // Messages of the stdin channel
+    interrupt: Arc<AtomicBool>,           // Set by the interrupt requests, stopping the cell
+}
+
+impl JupyterKernel {
+    /// Handle a request of the shell or control channel, returning false on
+    /// a shutdown
+    fn handle(&mut self, stream: &TcpStream, request: JupyterMessage) -> bool {
+        let session = self.session.clone();
+        let status = |state| json_object(vec![("execution_state", json_string(state))]);
+        session.publish("status", &request, status("busy"));
+        let (reply, mut content) = match request.msg_type() {
+            "kernel_info_request" => (
+                "kernel_info_reply",
+                vec![
+                    ("protocol_version", json_string("5.3")),
+                    ("implementation", json_string("brainfuck")),
+                    (
+                        "implementation_version",
+                        json_string(env!("CARGO_PKG_VERSION")),
+                    ),
+                    (
+                        "language_info",
+                        json_object(vec![
+                            ("name", json_string("brainfuck")),
+                            ("version", json_string(env!("CARGO_PKG_VERSION"))),
+                            ("mimetype", json_string("text/x-brainfuck")),
+                            ("file_extension", json_string(".bf")),
+                        ]),
+                    ),
+                    ("banner", json_string("brainfuck")),
+                    ("help_links", Json::Array(Vec::new())),
+                ],
+            ),
+            "execute_request" => ("execute_reply", self.execute(&request)),
+            "is_complete_request" => {
+                let code = request.content.get("code").and_then(Json::as_str);
+                let status = match Compiler::new()
+                    .dialect(self.config.language)
+                    .compile(code.unwrap_or(""))
+                {
+                    Ok(_) => "complete",
+                    // A loop left open goes on in the next line
+                    Err(ParseError::Unmatched(offset))
+                        if parse_source_in(code.unwrap_or(""), self.config.language)
+                            .iter()
+                            .any(|(start, token)| {
+                                *start == offset
+                                    && matches!(token, Token::LoopBegin | Token::ProcedureBegin)
+                            }) =>
+                    {
+                        "incomplete"
+                    }
+                    Err(_) => "invalid",
+                };
+                (
+                    "is_complete_reply",
+                    vec![("status", json_string(status)), ("indent", json_string(""))],
+                )
+            }
+            "complete_request" => {
+                let cursor = request.content.get("cursor_pos").and_then(Json::as_i64);
+                let cursor = Json::Number(cursor.unwrap_or(0));
+                (
+                    "complete_reply",
+                    vec![
+                        ("matches", Json::Array(Vec::new())),
+                        ("cursor_start", cursor.clone()),
+                        ("cursor_end", cursor),
+                        ("metadata", json_object(Vec::new())),
+                    ],
+                )
+            }
+            "inspect_request" => (
+                "inspect_reply",
+                vec![
+                    ("found", Json::Bool(false)),
+                    ("data", json_object(Vec::new())),
+                    ("metadata", json_object(Vec::new())),
+                ],
+            ),
+            "history_request" => ("history_reply", vec![("history", Json::Array(Vec::new()))]),
+            "comm_info_request" => ("comm_info_reply", vec![("comms", json_object(Vec::new()))]),
+            "interrupt_request" => {
+                // The flag set by the reader of the control channel stopped
+                // the cell running then, if any
+                self.interrupt.store(false, Ordering::Relaxed);
+                ("interrupt_reply", Vec::new())
+            }
+            "shutdown_request" => {
+                let restart = request.content.get("restart").cloned();
+                (
+                    "shutdown_reply",
+                    vec![("restart", restart.unwrap_or(Json::Bool(false)))],
+                )
+            }
+            _ => {
+                session.publish("status", &request, status("idle"));
+                return true;
+            }
+        };
+        // The failed executions give their own status
+        if content.iter().all(|(name, _)| *name != "status") {
+            content.insert(0, ("status", json_string("ok")));
+        }
+        let frames = session.message(reply, &request, json_object(content));
+        // A client leaving is seen by the reader of its connection
+        let _ = write_zmtp_message(stream, &frames);
+        session.publish("status", &request, status("idle"));
+        request.msg_type() != "shutdown_request"
+    }
+
+    /// Run the code of an execute request, publishing its output, and give
+    /// the content of the reply
+    fn execute(&mut self, request: &JupyterMessage) -> Vec<(&'static str, Json)> {
+        let session = self.session.clone();
+        let content = &request.content;
+        let code = content.get("code").and_then(Json::as_str).unwrap_or("");
+        let flag = |name| matches!(content.get(name), Some(Json::Bool(true)));
+        if !flag("silent") {
+            self.execution_count += 1;
+        }
+        let count = Json::Number(self.execution_count);
+        session.publish(
+            "execute_input",
+            request,
+            json_object(vec![
+                ("code", json_string(code)),
+                ("execution_count", count.clone()),
+            ]),
+        );
+
+        let config = &self.config;
+        let mut state = self.tape.take().unwrap_or_else(|| State::new(config));
+        // An interrupt coming before the cell starts, while it is queued,
+        // stops it too
+        state.interrupt = Some(Arc::clone(&self.interrupt));
+        let output = CellOutput {
+            session: session.clone(),
+            request: Rc::new(JupyterMessage {
+                identities: request.identities.clone(),
+                header: request.header.clone(),
+                content: Json::Null,
+            }),
+            buffer: Rc::default(),
+        };
+        state.output = Box::new(output.clone());
+        state.input = match flag("allow_stdin") {
+            true => Box::new(CellInput {
+                output: output.clone(),
+                stdin: Arc::clone(&self.stdin),
+                replies: Rc::clone(&self.replies),
+                interrupt: Arc::clone(&self.interrupt),
+                line: VecDeque::new(),
+            }),
+            false => Box::new(io::empty()),
+        };
+        // The cells of a persistent kernel run on the tape of the previous
+        // ones, instead of the blank one level 3 assumes
+        let level = match self.persistent {
+            true => config.opt_level.min(2),
+            false => config.opt_level,
+        };
+        let result = compile_program("cell", code, config.language, level).and_then(|ast| {
+            run_ast(&ast, &mut state, config)
+                .and_then(|_| Ok(state.output.flush_bytes()?))
+                .map_err(|error| runtime_error(error, "cell", Some(code)))
+        });
+        output.publish();
+        // The interrupt is handled once the cell stops, for the next ones to
+        // run
+        self.interrupt.store(false, Ordering::Relaxed);
+        if self.persistent {
+            state.input = Box::new(io::empty());
+            state.output = Box::new(io::sink());
+            self.tape = Some(state);
+        }
+
+        match result {
+            Ok(()) => vec![
+                ("execution_count", count),
+                ("user_expressions", json_object(Vec::new())),
+                ("payload", Json::Array(Vec::new())),
+            ],
+            Err(error) => {
+                let message = match &error.location {
+                    Some(location) => {
+                        format!("{}:{}: {}", location.line, location.column, error.message)
+                    }
+                    None => error.message.clone(),
+                };
+                let error = vec![
+                    ("ename", json_string("Error")),
+                    ("evalue", json_string(&message)),
+                    ("traceback", Json::Array(vec![json_string(&message)])),
+                ];
+                session.publish("error", request, json_object(error.clone()));
+                let mut reply = vec![("status", json_string("error")), ("execution_count", count)];
+                reply.extend(error);
+                reply
+            }
+        }
+    }
+}
+
+/// The kernelspec of Jupyter starting this executable as a kernel
+fn jupyter_kernelspec() -> Json {
+    let executable = env::current_exe()
+        .ok()
+        .and_then(|path| path.to_str().map(String::from))
+        .unwrap_or_else(|| String::from("brainfuck"));
+    json_object(vec![
+        (
+            "argv",
+            Json::Array(vec![
+                json_string(&executable),
+                json_string("jupyter-kernel"),
+                json_string("{connection_file}"),
+            ]),
+        ),
+        ("display_name", json_string("Brainfuck")),
+        ("language", json_string("brainfuck")),
+    ])
+}
+
+pub fn command_jupyter_kernel(args: &JupyterKernelArgs, config: Config) {
+    if args.kernelspec {
+        println!("{}", jupyter_kernelspec());
+        return;
+    }
+    let path = match &args.connection_file {
+        Some(path) => path,
+        None => usage_error("missing connection file"),
+    };
+    let connection = fs::read_to_string(path)
+        .map_err(|error| format!("can't read {}: {}", path, error))
+        .and_then(|text| parse_json(&text).map_err(|error| format!("{}: {}", path, error)))
+        .unwrap_or_else(|message| fail(Failure::Io, &message));
+    let field = |name| connection.get(name).and_then(Json::as_str).unwrap_or("");
+    if field("transport") != "tcp" || !matches!(field("signature_scheme"), "hmac-sha256" | "") {
+        fail(
+            Failure::Usage,
+            &format!("{}: only tcp and hmac-sha256 are supported", path),
+        );
+    }
+    let key: Arc<[u8]> = Arc::from(field("key").as_bytes());
+    if key.is_empty() && !args.unsigned {
+        fail(
+            Failure::Usage,
+            &format!(
+                "{}: without a key, anyone reaching the ports could run code, \
+                 unless --unsigned is given",
+                path
+            ),
+        );
+    }
+    // Any address is the local one, for the other hosts not to run code
+    let ip = match field("ip") {
+        "" | "*" => "127.0.0.1",
+        ip => ip,
+    };
+    let listen = |name: &str| {
+        let port = connection.get(name).and_then(Json::as_i64).unwrap_or(0);
+        let port = u16::try_from(port).unwrap_or_else(|_| {
+            fail(
+                Failure::Usage,
+                &format!("{}: invalid {} {}", path, name, port),
+            )
+        });
+        TcpListener::bind((ip, port)).unwrap_or_else(|error| {
+            fail(
+                Failure::Io,
+                &format!("can't listen on {}:{}: {}", ip, port, error),
+            )
+        })
+    };
+
+    // The heartbeat echoes its messages
+    spawn_zmtp_listener(listen("hb_port"), "REP", |mut stream| {
+        while let Ok(frames) = read_zmtp_message(&mut stream) {
+            if write_zmtp_message(&stream, &frames).is_err() {
+                break;
+            }
+        }
+    });
+    let iopub = Arc::new(Mutex::new(Vec::new()));
+    let subscribers = Arc::clone(&iopub);
+    spawn_zmtp_listener(listen("iopub_port"), "PUB", move |mut stream| {
+        if let Ok(clone) = stream.try_clone() {
+            subscribers.lock().unwrap().push(clone);
+        }
+        // The subscriptions are all kept
+        while read_zmtp_message(&mut stream).is_ok() {}
+    });
+    let stdin = Arc::new(Mutex::new(None));
+    let (reply_sender, replies) = mpsc::channel();
+    let connected = Arc::clone(&stdin);
+    spawn_zmtp_listener(listen("stdin_port"), "ROUTER", move |mut stream| {
+        *connected.lock().unwrap() = stream.try_clone().ok();
+        while let Ok(frames) = read_zmtp_message(&mut stream) {
+            if reply_sender.send(frames).is_err() {
+                break;
+            }
+        }
+    });
+    // The cells run on the main thread, so the interrupts are seen by the
+    // reader of the control channel
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let (request_sender, requests) = mpsc::channel();
+    for port in ["shell_port", "control_port"] {
+        let request_sender = request_sender.clone();
+        let (key, interrupt) = (Arc::clone(&key), Arc::clone(&interrupt));
+        let control = port == "control_port";
+        spawn_zmtp_listener(listen(port), "ROUTER", move |mut stream| {
+            while let Ok(frames) = read_zmtp_message(&mut stream) {
+                if control
+                    && parse_jupyter_message(&key, frames.clone())
+                        .is_some_and(|request| request.msg_type() == "interrupt_request")
+                {
+                    interrupt.store(true, Ordering::Relaxed);
+                }
+                let clone = match stream.try_clone() {
+                    Ok(clone) => clone,
+                    Err(_) => break,
+                };
+                if request_sender.send((clone, frames)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let mut kernel = JupyterKernel {
+        session: JupyterSession {
+            key,
+            session: Rc::from(format!("brainfuck-{}", process::id())),
+            sent: Rc::default(),
+            iopub,
+        },
+        tape: None,
+        persistent: args.persistent_tape,
+        execution_count: 0,
+        stdin,
+        replies: Rc::new(replies),
+        interrupt,
+        config,
+    };
+    for (stream, frames) in requests.iter() {
+        if let Some(request) = kernel.session.parse(frames) {
+            if !kernel.handle(&stream, request) {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! The lsp command, a language server giving the diagnostics, hovers,
+//! highlights and formatting of the sources to the editors
+
+use std::collections::HashMap;
+use std::str;
+
+use crate::ast::{Extended, Node, NodeKind, Span};
+use crate::codegen::{Backend, PrettyBf};
+use crate::json::Json;
+use crate::lexer::{parse_source_in, shebang, Language, Token};
+use crate::lint::{lint, LintLevel};
+use crate::{Compiler, Config};
+
+use super::args::LspArgs;
+use super::{
+    compile_program, json_object, json_string, lint_levels, net_move, parse_error, send_message,
+    spawn_message_reader,
+};
+
+/// Position of a byte offset of a text, in lines and UTF-16 code units as
+/// in the Language Server Protocol
+fn lsp_position(text: &str, offset: usize) -> Json {
+    let line_start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+    let character: usize = text[line_start..offset].chars().map(char::len_utf16).sum();
+    json_object(vec![
+        (
+            "line",
+            Json::Number(text[..offset].matches('\n').count() as i64),
+        ),
+        ("character", Json::Number(character as i64)),
+    ])
+}
+
+/// Byte offset of a position of the Language Server Protocol in a text
+fn lsp_offset(text: &str, position: &Json) -> usize {
+    let number = |name| position.get(name).and_then(Json::as_i64).unwrap_or(0) as usize;
+    let (line, character) = (number("line"), number("character"));
+    let line_start = match line {
+        0 => 0,
+        line => text
+            .match_indices('\n')
+            .nth(line - 1)
+            .map_or(text.len(), |(newline, _)| newline + 1),
+    };
+    let mut units = 0;
+    for (offset, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + offset;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Range of a span of a text, for the Language Server Protocol
+fn lsp_range(text: &str, span: Span) -> Json {
+    json_object(vec![
+        ("start", lsp_position(text, span.start)),
+        ("end", lsp_position(text, span.end.min(text.len()))),
+    ])
+}
+
+/// Spans of the matching brackets of a source, the opening one first
+fn bracket_pairs(source: &str, language: Language) -> Vec<(Span, Span)> {
+    let mut pairs = Vec::new();
+    let mut open = Vec::new();
+    for (offset, token) in parse_source_in(source, language) {
+        let span = Span::new(offset, offset + language.token_text(token).len());
+        match token {
+            Token::LoopBegin | Token::ProcedureBegin => open.push(span),
+            Token::LoopEnd | Token::ProcedureEnd => {
+                if let Some(opening) = open.pop() {
+                    pairs.push((opening, span));
+                }
+            }
+            _ => {}
+        }
+    }
+    pairs.sort_by_key(|(opening, _)| opening.start);
+    pairs
+}
+
+/// What is known of the effect of a node of an optimized program, without
+/// running it
+fn describe_node(node: &Node) -> String {
+    let cells = |count: isize| match count.abs() {
+        1 => String::from("1 cell"),
+        count => format!("{} cells", count),
+    };
+    match &node.kind {
+        NodeKind::Incr(val) if *val < 0 => format!("subtracts {} from the cell", -val),
+        NodeKind::Incr(val) => format!("adds {} to the cell", val),
+        NodeKind::Move(val) if *val < 0 => format!("moves the pointer {} left", cells(*val)),
+        NodeKind::Move(val) => format!("moves the pointer {} right", cells(*val)),
+        NodeKind::Write => String::from("writes the cell"),
+        NodeKind::Read => String::from("reads a byte into the cell"),
+        NodeKind::Clear => String::from("sets the cell to 0"),
+        NodeKind::Mul(targets) => {
+            let targets: Vec<_> = targets
+                .iter()
+                .map(|(offset, factor)| format!("{} times to the cell at {:+}", factor, offset))
+                .collect();
+            format!("adds the cell {}, then sets it to 0", targets.join(", "))
+        }
+        NodeKind::Loop(body) => match net_move(body) {
+            Some(0) => String::from("loop coming back to its cell at each iteration"),
+            Some(moved) => format!("loop moving the pointer {} at each iteration", moved),
+            None => String::from("loop"),
+        },
+        NodeKind::Block(_) => String::new(),
+        NodeKind::Procedure(_) => String::from("defines the procedure of the value of the cell"),
+        NodeKind::Call => String::from("calls the procedure of the value of the cell"),
+        NodeKind::Extended(command) => String::from(match command {
+            Extended::Store => "copies the cell into the storage",
+            Extended::Load => "copies the storage into the cell",
+            Extended::ShiftRight => "shifts the cell one bit to the right",
+            Extended::ShiftLeft => "shifts the cell one bit to the left",
+            Extended::Not => "flips the bits of the cell",
+            Extended::Xor => "sets the cell to its exclusive or with the storage",
+            Extended::And => "sets the cell to its and with the storage",
+            Extended::Or => "sets the cell to its or with the storage",
+        }),
+    }
+}
+
+/// Innermost node of an optimized program at an offset of its source,
+/// blocks aside
+fn node_at(node: &Node, offset: usize) -> Option<&Node> {
+    if offset < node.span.start || offset >= node.span.end {
+        return None;
+    }
+    let inner = match &node.kind {
+        NodeKind::Loop(body) => node_at(body, offset),
+        NodeKind::Procedure(body) => node_at(body, offset),
+        NodeKind::Block(nodes) => nodes.iter().find_map(|node| node_at(node, offset)),
+        _ => None,
+    };
+    match &node.kind {
+        NodeKind::Block(_) => inner,
+        _ => inner.or(Some(node)),
+    }
+}
+
+/// The language server, and the documents opened in the editor
+struct Lsp {
+    config: Config,
+    pretty: PrettyBf, // Layout of the formatted sources
+    levels: HashMap<&'static str, LintLevel>,
+    documents: HashMap<String, String>, // Text of the documents, by URI
+}
+
+impl Lsp {
+    fn respond(&self, request: &Json, result: Result<Json, (i64, String)>) {
+        let id = request.get("id").cloned().unwrap_or(Json::Null);
+        let mut members = vec![("jsonrpc", json_string("2.0")), ("id", id)];
+        match result {
+            Ok(result) => members.push(("result", result)),
+            Err((code, message)) => members.push((
+                "error",
+                json_object(vec![
+                    ("code", Json::Number(code)),
+                    ("message", Json::String(message)),
+                ]),
+            )),
+        }
+        send_message(json_object(members));
+    }
+
+    fn notify(&self, method: &str, params: Json) {
+        send_message(json_object(vec![
+            ("jsonrpc", json_string("2.0")),
+            ("method", json_string(method)),
+            ("params", params),
+        ]));
+    }
+
+    /// Send the errors and the lints of a document
+    fn publish_diagnostics(&self, uri: &str) {
+        let mut diagnostics = Vec::new();
+        if let Some(text) = self.documents.get(uri) {
+            let diagnostic = |span, severity, code: Option<&str>, message: &str| {
+                let mut members = vec![
+                    ("range", lsp_range(text, span)),
+                    ("severity", Json::Number(severity)),
+                    ("source", json_string("brainfuck")),
+                    ("message", json_string(message)),
+                ];
+                if let Some(code) = code {
+                    members.push(("code", json_string(code)));
+                }
+                json_object(members)
+            };
+            match Compiler::new().config(self.config.clone()).compile(text) {
+                Ok(program) => {
+                    for lint in lint(&program.ast, &program.config).iter() {
+                        let severity = match self.levels[lint.name] {
+                            LintLevel::Allow => continue,
+                            LintLevel::Warn => 2,
+                            LintLevel::Deny => 1,
+                        };
+                        diagnostics.push(diagnostic(
+                            lint.span,
+                            severity,
+                            Some(lint.name),
+                            &lint.message,
+                        ));
+                    }
+                }
+                Err(error) => {
+                    let error = parse_error(error, uri, text, self.config.language);
+                    let span = error.location.as_ref().map_or(Span::default(), |l| l.span);
+                    diagnostics.push(diagnostic(span, 1, None, &error.message));
+                }
+            }
+        }
+        let params = json_object(vec![
+            ("uri", json_string(uri)),
+            ("diagnostics", Json::Array(diagnostics)),
+        ]);
+        self.notify("textDocument/publishDiagnostics", params);
+    }
+
+    /// The text of the document of a request, and the offset of its
+    /// position
+    fn document_at(&self, params: &Json) -> Option<(&str, usize)> {
+        let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+        let text = self.documents.get(uri)?;
+        let offset = lsp_offset(text, params.get("position")?);
+        Some((text, offset))
+    }
+
+    /// The brackets matching the one at the position, or just before it
+    fn highlight(&self, params: &Json) -> Json {
+        let (text, offset) = match self.document_at(params) {
+            Some(document) => document,
+            None => return Json::Null,
+        };
+        let touches = |span: Span| span.start <= offset && offset <= span.end;
+        let pairs = bracket_pairs(text, self.config.language);
+        let pair = pairs
+            .iter()
+            .find(|(opening, closing)| touches(*opening) || touches(*closing));
+        match pair {
+            Some((opening, closing)) => Json::Array(
+                [opening, closing]
+                    .iter()
+                    .map(|span| json_object(vec![("range", lsp_range(text, **span))]))
+                    .collect(),
+            ),
+            None => Json::Null,
+        }
+    }
+
+    /// The depth of the loops around the position, and what is known of the
+    /// command there
+    fn hover(&self, params: &Json) -> Json {
+        let (text, offset) = match self.document_at(params) {
+            Some(document) => document,
+            None => return Json::Null,
+        };
+        let depth = bracket_pairs(text, self.config.language)
+            .iter()
+            .filter(|(opening, closing)| opening.start < offset && offset < closing.start)
+            .count();
+        let mut lines = vec![format!("loop nesting depth: {}", depth)];
+        let program = Compiler::new().config(self.config.clone()).compile(text);
+        let node = program
+            .as_ref()
+            .ok()
+            .and_then(|program| node_at(&program.ast, offset));
+        if let Some(node) = node {
+            lines.insert(0, format!("`{}` {}", node, describe_node(node)));
+        }
+        let contents = json_object(vec![
+            ("kind", json_string("markdown")),
+            ("value", Json::String(lines.join("\n\n"))),
+        ]);
+        let mut members = vec![("contents", contents)];
+        if let Some(node) = node {
+            members.push(("range", lsp_range(text, node.span)));
+        }
+        json_object(members)
+    }
+
+    /// An edit replacing a document with its formatted program
+    fn format(&self, params: &Json) -> Json {
+        let uri = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str);
+        let text = match uri.and_then(|uri| self.documents.get(uri)) {
+            Some(text) => text,
+            None => return Json::Null,
+        };
+        // Sources which don't parse are left as they are
+        let ast = match compile_program("", text, self.config.language, 2) {
+            Ok(ast) => ast,
+            Err(_) => return Json::Null,
+        };
+        let mut formatted = shebang(text).as_bytes().to_vec();
+        if self
+            .pretty
+            .emit(&ast, &self.config, &mut formatted)
+            .is_err()
+        {
+            return Json::Null;
+        }
+        let edit = json_object(vec![
+            ("range", lsp_range(text, Span::new(0, text.len()))),
+            (
+                "newText",
+                Json::String(String::from_utf8_lossy(&formatted).into_owned()),
+            ),
+        ]);
+        Json::Array(vec![edit])
+    }
+
+    /// Handle a request or a notification, returning false on exit
+    fn handle(&mut self, message: &Json) -> bool {
+        let empty = json_object(vec![]);
+        let params = message.get("params").unwrap_or(&empty);
+        let document = params.get("textDocument").unwrap_or(&empty);
+        let uri = document.get("uri").and_then(Json::as_str).map(String::from);
+        match message.get("method").and_then(Json::as_str).unwrap_or("") {
+            "initialize" => {
+                let capabilities = json_object(vec![
+                    ("textDocumentSync", Json::Number(1)),
+                    ("hoverProvider", Json::Bool(true)),
+                    ("documentHighlightProvider", Json::Bool(true)),
+                    ("documentFormattingProvider", Json::Bool(true)),
+                ]);
+                let result = json_object(vec![
+                    ("capabilities", capabilities),
+                    (
+                        "serverInfo",
+                        json_object(vec![("name", json_string("brainfuck"))]),
+                    ),
+                ]);
+                self.respond(message, Ok(result));
+            }
+            "textDocument/didOpen" => {
+                if let (Some(uri), Some(text)) = (uri, document.get("text").and_then(Json::as_str))
+                {
+                    self.documents.insert(uri.clone(), String::from(text));
+                    self.publish_diagnostics(&uri);
+                }
+            }
+            // The whole text comes with each change
+            "textDocument/didChange" => {
+                let text = match params.get("contentChanges") {
+                    Some(Json::Array(changes)) => changes.last(),
+                    _ => None,
+                };
+                let text = text.and_then(|change| change.get("text")?.as_str());
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    self.documents.insert(uri.clone(), String::from(text));
+                    self.publish_diagnostics(&uri);
+                }
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = uri {
+                    self.documents.remove(&uri);
+                    self.publish_diagnostics(&uri);
+                }
+            }
+            "textDocument/hover" => self.respond(message, Ok(self.hover(params))),
+            "textDocument/documentHighlight" => self.respond(message, Ok(self.highlight(params))),
+            "textDocument/formatting" => self.respond(message, Ok(self.format(params))),
+            "shutdown" => self.respond(message, Ok(Json::Null)),
+            "exit" => return false,
+            // Requests have an id, unlike the notifications, which are ignored
+            method if message.get("id").is_some() => self.respond(
+                message,
+                Err((-32601, format!("unsupported method {:?}", method))),
+            ),
+            _ => {}
+        }
+        true
+    }
+}
+
+/// Serve the Language Server Protocol on the standard input and output,
+/// until the editor exits
+pub fn command_lsp(args: &LspArgs, config: Config) {
+    let messages = spawn_message_reader();
+    let mut lsp = Lsp {
+        config,
+        pretty: args.format.pretty(),
+        levels: lint_levels(&args.lints),
+        documents: HashMap::new(),
+    };
+    while let Ok(message) = messages.recv() {
+        if !lsp.handle(&message) {
+            return;
+        }
+    }
+}
@@ -0,0 +1,3030 @@
+//! The command line of the `brainfuck` executable, whose `main` only calls
+//! `cli::main`
+//!
+//! Each subcommand is a `command_*` function reading its options, parsed by
+//! clap into the structs of `args`. The servers and the debuggers have
+//! modules of their own.
+
+mod animation;
+mod args;
+#[cfg(feature = "server")]
+mod dap;
+#[cfg(feature = "server")]
+mod jupyter;
+#[cfg(feature = "server")]
+mod lsp;
+mod selftest;
+#[cfg(feature = "server")]
+mod serve;
+#[cfg(feature = "tui")]
+mod tui;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::io::Write;
+use std::iter;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
+use notify::{RecursiveMode, Watcher};
+
+use crate::ast::{build_ast, walk, Node, NodeKind, SourceMap, Span, Visitor};
+use crate::codegen::{
+    run_native, Backend, CodegenError, Executable, ObfuscatedBf, PrettyBf, Registry, Target,
+};
+use crate::json::{
+    node_from_json, parse_json, program_from_json, program_to_json, state_from_json, state_to_json,
+    Json,
+};
+use crate::lexer::{
+    parse_labels, parse_source_in, shebang, Language, ParseError, Token, MAX_NESTING,
+};
+use crate::lint::{fix_source, lint, LintLevel, LINTS};
+use crate::opt::optimize_program;
+use crate::symbolic::{explore, Fault, Limits};
+#[cfg(any(feature = "server", feature = "tui"))]
+use crate::vm::Stepper;
+use crate::vm::{
+    coverage, dump_memory, run_ast, run_stats, Access, Bounds, Input, Profile, RunStats,
+    RuntimeError, SharedBuffer, State, StepHook, PROMPT as PROMPT_DEFAULT,
+};
+use crate::{CompileError, Compiler, Config, Program};
+
+use animation::{Animation, ANIMATION_FRAMES};
+use args::{
+    BuildArgs, BuildOptions, CheckArgs, Cli, Commands, CompareArgs, DebugArgs, ExamplesArgs,
+    FmtArgs, InspectArgs, LintArgs, OutlineArgs, PipeArgs, ProgramArgs, ReplayArgs, RunArgs,
+    SymbolicArgs, TraceDiffArgs, WatchArgs,
+};
+#[cfg(feature = "server")]
+use dap::command_dap;
+#[cfg(feature = "server")]
+use jupyter::command_jupyter_kernel;
+#[cfg(feature = "server")]
+use lsp::command_lsp;
+use selftest::command_selftest;
+#[cfg(feature = "server")]
+use serve::command_serve;
+#[cfg(feature = "tui")]
+use tui::debug_tui;
+
+/// The terminal of the standard input in raw mode, where each key reaches
+/// the program as soon as it is pressed, without echo, until it is dropped
+///
+/// The settings are changed with `stty`, and restored on drop, which also
+/// happens on a panic.
+struct RawTerminal {
+    saved: String, // Settings of the terminal, as given by stty -g
+}
+
+impl RawTerminal {
+    fn enable() -> io::Result<Self> {
+        RawTerminal::with(&["-icanon", "-echo", "min", "1", "time", "0"])
+    }
+
+    fn with(settings: &[&str]) -> io::Result<Self> {
+        let stty = |args: &[&str]| -> io::Result<Vec<u8>> {
+            let output = Command::new("stty")
+                .args(args)
+                .stdin(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .output()?;
+            match output.status.success() {
+                true => Ok(output.stdout),
+                false => Err(io::Error::other(format!(
+                    "stty failed with {}",
+                    output.status
+                ))),
+            }
+        };
+        let saved = String::from_utf8_lossy(&stty(&["-g"])?).trim().to_string();
+        stty(settings)?;
+        Ok(RawTerminal { saved })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = Command::new("stty").arg(&self.saved).status();
+    }
+}
+
+/// Report a misuse of the command line and exit
+fn usage_error(message: &str) -> ! {
+    report_at(&ERROR, None, message, None);
+    match SUBCOMMAND.get() {
+        _ if JSON_MESSAGES.load(Ordering::Relaxed) => {}
+        Some(name) => eprintln!("see 'brainfuck {} --help'", name),
+        None => eprintln!("see 'brainfuck --help'"),
+    }
+    process::exit(Failure::Usage as i32);
+}
+
+/// Kind of the errors reported to the user, which gives the exit status
+#[derive(Clone, Copy)]
+enum Failure {
+    Usage = 2,   // Invalid command line
+    Parse = 3,   // Invalid program
+    Runtime = 4, // The program stopped on an error
+    Io = 5,      // A file can't be read or written
+    Limit = 6,   // The program reached a limit of its run
+}
+
+/// An error reported to the user
+struct CliError {
+    failure: Failure,
+    message: String,
+    location: Option<Box<Location>>, // Where the error is in the source
+}
+
+impl CliError {
+    fn new(failure: Failure, message: String) -> Self {
+        CliError {
+            failure,
+            message,
+            location: None,
+        }
+    }
+
+    /// Locate the error in a source
+    fn at(self, path: &str, source: &str, span: Span) -> Self {
+        CliError {
+            location: Some(Box::new(Location::new(path, source, span))),
+            ..self
+        }
+    }
+
+    /// Report the error on the standard error
+    fn report(&self) {
+        report_at(&ERROR, None, &self.message, self.location.as_deref());
+    }
+
+    /// Report the error, and exit with the status of its kind
+    fn exit(self) -> ! {
+        self.report();
+        process::exit(self.failure as i32);
+    }
+}
+
+/// How serious a diagnostic is, which gives its label and color
+struct Severity {
+    label: &'static str,
+    color: &'static str, // ANSI code of the color
+}
+
+const ERROR: Severity = Severity {
+    label: "error",
+    color: "31",
+};
+
+const WARNING: Severity = Severity {
+    label: "warning",
+    color: "33",
+};
+
+/// A line of a source, with the part a diagnostic is about
+struct Snippet {
+    line: usize,
+    text: String,
+    start: usize, // Characters of the line before the part
+    width: usize, // Characters of the part, on this line
+}
+
+impl Snippet {
+    /// The line of a source where a span starts, with the span up to the end
+    /// of the line
+    fn new(source: &str, span: Span) -> Self {
+        let line_start = source[..span.start]
+            .rfind('\n')
+            .map_or(0, |offset| offset + 1);
+        let line_end = source[span.start..]
+            .find('\n')
+            .map_or(source.len(), |offset| span.start + offset);
+        let end = span.end.clamp(span.start, line_end);
+        Snippet {
+            line: source[..span.start].matches('\n').count() + 1,
+            text: source[line_start..line_end]
+                .trim_end_matches('\r')
+                .to_string(),
+            start: source[line_start..span.start].chars().count(),
+            width: source[span.start..end].chars().count().max(1),
+        }
+    }
+}
+
+/// Where a diagnostic is in a source, with the line it is on
+struct Location {
+    path: String,
+    span: Span,
+    line: usize,
+    column: usize, // Bytes of the line before the span, plus one
+    snippet: Snippet,
+}
+
+impl Location {
+    fn new(path: &str, source: &str, span: Span) -> Self {
+        let (line, column) = SourceMap::new(path, source).location(span.start);
+        Location {
+            path: String::from(path),
+            span,
+            line,
+            column,
+            snippet: Snippet::new(source, span),
+        }
+    }
+}
+
+/// Whether the diagnostics are printed as JSON lines, by --message-format
+static JSON_MESSAGES: AtomicBool = AtomicBool::new(false);
+
+/// Whether the duration of each stage is reported, by --verbose
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Prompt of the lines read from a terminal by ",", set by --prompt
+static PROMPT: OnceLock<String> = OnceLock::new();
+
+/// Name of the subcommand running, for the hint of the usage errors
+static SUBCOMMAND: OnceLock<String> = OnceLock::new();
+
+/// Prompt of the lines read from a terminal by ","
+fn prompt() -> &'static str {
+    PROMPT.get().map_or(PROMPT_DEFAULT, String::as_str)
+}
+
+/// Whether the diagnostics are colored: on a terminal, unless NO_COLOR is set
+fn use_color() -> bool {
+    env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && io::stderr().is_terminal()
+}
+
+/// Print a diagnostic on the standard error, followed by the line of the
+/// source it is about with a caret under the offending part
+fn report(severity: &Severity, message: &str, snippet: Option<&Snippet>) {
+    report_coded(severity, None, message, snippet);
+}
+
+/// Print a diagnostic about a source, with the machine-readable name of its
+/// kind if any, in the format chosen by --message-format
+fn report_at(severity: &Severity, code: Option<&str>, message: &str, location: Option<&Location>) {
+    if JSON_MESSAGES.load(Ordering::Relaxed) {
+        let string = |text: &str| Json::String(String::from(text));
+        let number = |value: usize| Json::Number(value as i64);
+        let mut members = vec![
+            (String::from("severity"), string(severity.label)),
+            (String::from("code"), code.map_or(Json::Null, string)),
+            (String::from("message"), string(message)),
+        ];
+        if let Some(location) = location {
+            let span = vec![
+                (String::from("start"), number(location.span.start)),
+                (String::from("end"), number(location.span.end)),
+            ];
+            members.extend([
+                (String::from("path"), string(&location.path)),
+                (String::from("span"), Json::Object(span)),
+                (String::from("line"), number(location.line)),
+                (String::from("column"), number(location.column)),
+            ]);
+        }
+        eprintln!("{}", Json::Object(members));
+        return;
+    }
+
+    match location {
+        Some(location) => report_coded(
+            severity,
+            code,
+            &format!(
+                "{}:{}:{}: {}",
+                location.path, location.line, location.column, message
+            ),
+            Some(&location.snippet),
+        ),
+        None => report_coded(severity, code, message, None),
+    }
+}
+
+/// Print a diagnostic with the machine-readable name of its kind, as in
+/// "warning[dead-loop]: ..."
+fn report_coded(severity: &Severity, code: Option<&str>, message: &str, snippet: Option<&Snippet>) {
+    let color = use_color();
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("\x1b[1;{}m{}\x1b[0m", code, text)
+        } else {
+            String::from(text)
+        }
+    };
+    let label = match code {
+        Some(code) => format!("{}[{}]", severity.label, code),
+        None => String::from(severity.label),
+    };
+    eprintln!("{}: {}", paint(severity.color, &label), message);
+
+    if let Some(snippet) = snippet {
+        let number = snippet.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let bar = paint("34", "|");
+        // Tabs are kept, for the caret to line up
+        let indent: String = snippet
+            .text
+            .chars()
+            .take(snippet.start)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        eprintln!("{} {}", gutter, bar);
+        eprintln!("{} {} {}", paint("34", &number), bar, snippet.text);
+        eprintln!(
+            "{} {} {}{}",
+            gutter,
+            bar,
+            indent,
+            paint(severity.color, &"^".repeat(snippet.width))
+        );
+    }
+}
+
+/// Report an error to the user, and exit with the status of its kind
+fn fail(failure: Failure, message: &str) -> ! {
+    CliError::new(failure, String::from(message)).exit()
+}
+
+/// Error of the parser, located in its source
+fn parse_error(error: ParseError, name: &str, source: &str, language: Language) -> CliError {
+    let (message, span) = parse_error_location(error, source, language);
+    CliError::new(Failure::Parse, message).at(name, source, span)
+}
+
+/// Description of an error of the parser, and the span of its bracket
+fn parse_error_location(error: ParseError, source: &str, language: Language) -> (String, Span) {
+    let tokens = parse_source_in(source, language);
+    let (ParseError::Unmatched(offset) | ParseError::TooDeep(offset)) = error;
+    let bracket = bracket_text(&tokens, offset, language);
+    let message = match error {
+        ParseError::Unmatched(_) => format!("unmatched '{}'", bracket),
+        ParseError::TooDeep(_) => {
+            format!("'{}' nested deeper than {} levels", bracket, MAX_NESTING)
+        }
+    };
+    (message, Span::new(offset, offset + bracket.len()))
+}
+
+/// Build the AST of a source at some optimization level, locating the first
+/// unmatched bracket
+fn compile_program(
+    name: &str,
+    source: &str,
+    language: Language,
+    level: u8,
+) -> Result<Node, CliError> {
+    Compiler::new()
+        .dialect(language)
+        .opt_level(level)
+        .compile(source)
+        .map(|program| program.ast)
+        .map_err(|error| parse_error(error, name, source, language))
+}
+
+/// Text of the bracket at some offset, as written in the source
+fn bracket_text(tokens: &[(usize, Token)], offset: usize, language: Language) -> &'static str {
+    let token = tokens.iter().find(|(start, _)| *start == offset).unwrap().1;
+    language.token_text(token)
+}
+
+/// Error of the VM, located in the source when it is known
+fn runtime_error(error: RuntimeError, name: &str, source: Option<&str>) -> CliError {
+    let (span, message) = match runtime_location(&error) {
+        Some(location) => location,
+        None => return CliError::new(Failure::Io, error.to_string()),
+    };
+    let failure = match limit_option(&error) {
+        Some(_) => Failure::Limit,
+        None => Failure::Runtime,
+    };
+    match source {
+        Some(source) => CliError::new(failure, String::from(message)).at(name, source, span),
+        None => CliError::new(failure, error.to_string()),
+    }
+}
+
+/// Option setting the limit of the run reached by a failure of the VM, if any
+fn limit_option(error: &RuntimeError) -> Option<&'static str> {
+    match error {
+        RuntimeError::OutputLimit(_) => Some("--max-output"),
+        RuntimeError::StepLimit(_) => Some("--max-steps"),
+        RuntimeError::Timeout(_) => Some("--timeout"),
+        _ => None,
+    }
+}
+
+/// Span of the node of a failure of the VM, with its description
+fn runtime_location(error: &RuntimeError) -> Option<(Span, &'static str)> {
+    match error {
+        RuntimeError::OutOfTape(span) => Some((*span, "pointer out of the tape")),
+        RuntimeError::OutputLimit(span) => Some((*span, "output limit reached")),
+        RuntimeError::StepLimit(span) => Some((*span, "step limit reached")),
+        RuntimeError::Timeout(span) => Some((*span, "timed out")),
+        RuntimeError::UnknownProcedure(span) => Some((*span, "call of an undefined procedure")),
+        RuntimeError::Interrupted(span) => Some((*span, "interrupted")),
+        RuntimeError::TooDeep(span) => Some((*span, "loops and calls nested too deeply")),
+        RuntimeError::Io(_) => None,
+    }
+}
+
+/// Name of the project configuration, looked up from the directory of the
+/// source
+const PROJECT_CONFIG: &str = "bf.toml";
+
+/// Find the project configuration in the directory of a source, or in a
+/// parent one
+fn find_project_config(source: Option<&str>) -> Option<PathBuf> {
+    let directory = match source {
+        Some(path) if path != "-" => Path::new(path).parent().unwrap_or(Path::new("")),
+        _ => Path::new(""),
+    };
+    let directory = if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    };
+    fs::canonicalize(directory)
+        .ok()?
+        .ancestors()
+        .map(|directory| directory.join(PROJECT_CONFIG))
+        .find(|path| path.is_file())
+}
+
+/// Parse the `key = value` lines of a TOML document, whose strings, integers
+/// and booleans are read as JSON
+fn parse_toml(text: &str) -> Result<Vec<(String, Json)>, String> {
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+
+        // Comments start at a # outside of the strings
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut end = line.len();
+        for (offset, c) in line.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '#' if !in_string => {
+                    end = offset;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let line = line[..end].trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(error("tables aren't supported"));
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected key = value"))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(error(&format!("invalid key {:?}", key)));
+        }
+        let value = match parse_json(value.trim()) {
+            Ok(value @ Json::String(_))
+            | Ok(value @ Json::Number(_))
+            | Ok(value @ Json::Bool(_)) => value,
+            _ => return Err(error(&format!("invalid value for {}", key))),
+        };
+        entries.push((String::from(key), value));
+    }
+
+    Ok(entries)
+}
+
+/// Take the options missing from the command line from the project
+/// configuration, whose keys are the long names of the options, adding them
+/// to the arguments
+fn apply_project_config(command: &clap::Command, args: &mut Vec<String>) {
+    let matches = command
+        .clone()
+        .try_get_matches_from(args.iter())
+        .unwrap_or_else(|error| error.exit());
+    let (name, matches) = match matches.subcommand() {
+        Some(subcommand) => subcommand,
+        None => return,
+    };
+    let subcommand = command.find_subcommand(name).unwrap();
+    let source = subcommand
+        .get_positionals()
+        .next()
+        .and_then(|argument| matches.get_raw(argument.get_id().as_str()))
+        .and_then(|mut values| values.next())
+        .and_then(|value| value.to_str());
+    let path = match find_project_config(source) {
+        Some(path) => path,
+        None => return,
+    };
+    let fail_config = |failure: Failure, message: &str| -> ! {
+        fail(failure, &format!("{}: {}", path.display(), message))
+    };
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|error| fail_config(Failure::Io, &error.to_string()));
+    let entries = parse_toml(&text).unwrap_or_else(|error| fail_config(Failure::Usage, &error));
+
+    let long_names = || {
+        command
+            .get_subcommands()
+            .flat_map(|subcommand| subcommand.get_arguments())
+            .filter_map(|argument| argument.get_long())
+    };
+    // The options go before the arguments following --
+    let mut end = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    for (key, value) in entries {
+        if !long_names().any(|name| name == key) {
+            fail_config(Failure::Usage, &format!("unknown setting {:?}", key));
+        }
+
+        // Settings of the other subcommands are ignored
+        let argument = match subcommand
+            .get_arguments()
+            .find(|argument| argument.get_long() == Some(key.as_str()))
+        {
+            Some(argument) => argument,
+            None => continue,
+        };
+        if matches.value_source(argument.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let option = match (argument.get_action().takes_values(), value) {
+            (false, Json::Bool(true)) => format!("--{}", key),
+            (false, Json::Bool(false)) => continue,
+            (true, Json::String(text)) => format!("--{}={}", key, text),
+            (true, Json::Number(number)) => format!("--{}={}", key, number),
+            _ => fail_config(Failure::Usage, &format!("invalid value for {}", key)),
+        };
+        args.insert(end, option);
+        end += 1;
+    }
+}
+
+/// The targets, with the settings of the backends given to build
+fn registry(pretty: PrettyBf, options: &BuildOptions) -> Registry {
+    let mut registry = Registry::default();
+    registry.register("bf-pretty", &[], pretty);
+    if let Some(seed) = options.seed {
+        registry.register("bf-obfuscated", &[], ObfuscatedBf { seed });
+    }
+    if let Some(format) = options.binary_format {
+        registry.register("bin", &[], Executable { format });
+    }
+    registry
+}
+
+/// Read a source file, as text, or the standard input for -
+fn read_source(path: &str) -> Result<String, CliError> {
+    let data = if path == "-" {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        fs::read(path)
+    };
+    let data = data.map_err(|error| {
+        CliError::new(
+            Failure::Io,
+            format!("can't read {}: {}", source_name(path), error),
+        )
+    })?;
+    String::from_utf8(data).map_err(|error| {
+        let offset = error.utf8_error().valid_up_to();
+        CliError::new(
+            Failure::Parse,
+            format!("{}: invalid UTF-8 at byte {}", source_name(path), offset),
+        )
+    })
+}
+
+/// Name of a source file in the messages
+fn source_name(path: &str) -> &str {
+    if path == "-" {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+/// Name and text of the program of a subcommand, from its source or -c
+fn program_source(program: &ProgramArgs) -> (String, String) {
+    match (&program.code, &program.source) {
+        (Some(code), _) => (String::from("<code>"), code.clone()),
+        (None, path) => {
+            let path = path.as_deref().unwrap_or("-");
+            let source = read_source(path).unwrap_or_else(|error| error.exit());
+            (String::from(source_name(path)), source)
+        }
+    }
+}
+
+/// Load the program of a brainfuck source, or of an AST exported as .json
+fn load_program(
+    name: &str,
+    source: String,
+    config: &mut Config,
+    line_directives: bool,
+) -> Result<Node, CliError> {
+    if name.ends_with(".json") {
+        if line_directives {
+            return Err(CliError::new(
+                Failure::Usage,
+                String::from("line directives need a brainfuck source"),
+            ));
+        }
+        let start = Instant::now();
+        let ast = parse_json(&source)
+            .and_then(|json| node_from_json(&json))
+            .map_err(|error| CliError::new(Failure::Parse, format!("{}: {}", name, error)))?;
+        report_stage("load", start, node_count(&ast), "nodes");
+        return Ok(ast);
+    }
+
+    if line_directives {
+        config.line_directives = Some(SourceMap::new(name, &source));
+    }
+
+    // The stages of compile_source_at, timed on their own
+    let start = Instant::now();
+    let tokens = parse_source_in(&source, config.language);
+    report_stage("parse", start, tokens.len(), "tokens");
+    let start = Instant::now();
+    let mut ast =
+        build_ast(tokens).map_err(|error| parse_error(error, name, &source, config.language))?;
+    report_stage("build", start, node_count(&ast), "nodes");
+    if config.opt_level > 0 {
+        let start = Instant::now();
+        ast = optimize_program::<u32>(&ast, config);
+        report_stage("optimize", start, node_count(&ast), "nodes");
+    }
+    config.source = Some(source);
+    Ok(ast)
+}
+
+/// Load the program of a source on the standard input, as it is read
+///
+/// Its text isn't kept, so its errors give byte offsets instead of quoting
+/// it.
+fn stream_program(config: &mut Config) -> Result<Node, CliError> {
+    let start = Instant::now();
+    let program = Compiler::new()
+        .config(config.clone())
+        .compile_reader(io::stdin().lock())
+        .map_err(|error| match error {
+            CompileError::Io(error) if error.kind() != io::ErrorKind::InvalidData => {
+                CliError::new(Failure::Io, format!("can't read <stdin>: {}", error))
+            }
+            error => CliError::new(Failure::Parse, format!("<stdin>: {}", error)),
+        })?;
+    report_stage("load", start, node_count(&program.ast), "nodes");
+    Ok(program.ast)
+}
+
+/// Number of instructions of an AST, blocks aside
+fn node_count(ast: &Node) -> usize {
+    struct NodeCount(usize);
+    impl Visitor for NodeCount {
+        fn visit(&mut self, node: &Node) {
+            if !matches!(node.kind, NodeKind::Block(_)) {
+                self.0 += 1;
+            }
+            walk(self, node);
+        }
+    }
+
+    let mut count = NodeCount(0);
+    count.visit(ast);
+    count.0
+}
+
+/// Print the duration of a stage on stderr, with the size of its result, in
+/// verbose mode
+fn report_stage(stage: &str, start: Instant, size: usize, unit: &str) {
+    if VERBOSE.load(Ordering::Relaxed) {
+        eprintln!(
+            "{:<10}{:>10.3}ms  {} {}",
+            stage,
+            start.elapsed().as_secs_f64() * 1000.0,
+            size,
+            unit
+        );
+    }
+}
+
+/// Load the sources of a subcommand as a single program, each running after
+/// the previous one on the same tape
+fn link_programs(program: &ProgramArgs, sources: &[String], config: &mut Config) -> (String, Node) {
+    let paths: Vec<_> = match (&program.code, &program.source) {
+        (None, Some(source)) => iter::once(source).chain(sources).collect(),
+        _ => usage_error("--link needs sources, instead of -c"),
+    };
+    let programs: Vec<_> = paths
+        .iter()
+        .map(|path| {
+            let mut config = config.clone();
+            read_source(path)
+                .and_then(|source| load_program(source_name(path), source, &mut config, false))
+                .map(|ast| Program { ast, config })
+                .unwrap_or_else(|error| error.exit())
+        })
+        .collect();
+    let program = Program::sequence(&programs);
+    let names: Vec<_> = paths.iter().map(|path| source_name(path)).collect();
+    *config = program.config;
+    (names.join("+"), program.ast)
+}
+
+fn command_run(args: &RunArgs, config: Config) {
+    let mut config = config;
+    if let (Some(source), false) = (args.sources.first(), args.link) {
+        usage_error(&format!("unexpected argument {:?}", source));
+    }
+    // Each command is counted on its own
+    let coverage_path = args.coverage.as_deref();
+    if coverage_path.is_some() {
+        config.opt_level = 0;
+    }
+    let audit = args.audit_bounds;
+    let flamegraph_path = args.flamegraph.as_deref();
+    let streamed =
+        !args.run_native && coverage_path.is_none() && flamegraph_path.is_none() && !audit;
+    let (name, ast) = match (&args.program.code, args.program.source.as_deref()) {
+        _ if args.link => link_programs(&args.program, &args.sources, &mut config),
+        (None, None | Some("-")) if streamed => (
+            String::from(source_name("-")),
+            stream_program(&mut config).unwrap_or_else(|error| error.exit()),
+        ),
+        _ => {
+            let (name, source) = program_source(&args.program);
+            let ast = load_program(&name, source, &mut config, false)
+                .unwrap_or_else(|error| error.exit());
+            (name, ast)
+        }
+    };
+
+    let input = args.input.input();
+    let output = args.output_data.as_deref().map(|path| {
+        File::create(path)
+            .unwrap_or_else(|error| fail(Failure::Io, &format!("can't create {}: {}", path, error)))
+    });
+    let dump_range = args
+        .dump_range
+        .as_deref()
+        .map(|range| cell_range("--dump-range", range, config.tape_size));
+    let dump = args.dump_memory || dump_range.is_some();
+    // The terminal is restored before exiting, even on an error
+    let raw_terminal = match args.raw_input && matches!(input, Input::Stdin) {
+        true if io::stdin().is_terminal() => Some(RawTerminal::enable().unwrap_or_else(|error| {
+            fail(
+                Failure::Io,
+                &format!("can't set the terminal in raw mode: {}", error),
+            )
+        })),
+        _ => None,
+    };
+
+    // Build and run the program
+    if args.run_native {
+        let inspected = dump
+            || args.stats
+            || args.bench.is_some()
+            || args.watch_cell.is_some()
+            || coverage_path.is_some()
+            || args.trace.is_some()
+            || args.core.is_some()
+            || args.animate.is_some()
+            || flamegraph_path.is_some()
+            || audit;
+        let limited =
+            config.max_output.is_some() || config.max_steps.is_some() || config.timeout.is_some();
+        if inspected || limited {
+            usage_error("the run can't be inspected or limited with --run-native");
+        }
+        let target = args.target.as_deref().unwrap_or("c");
+        if !matches!(target, "c" | "rs") {
+            usage_error(&format!("unsupported native target {:?}", target));
+        }
+        let status = run_native(&ast, &config, target, &input, output);
+        drop(raw_terminal);
+        let status = status.unwrap_or_else(|error| {
+            fail(
+                Failure::Io,
+                &format!("can't run the native program: {}", error),
+            )
+        });
+        process::exit(status.code().unwrap_or(1));
+    }
+    if let Some(runs) = args.bench {
+        let mut bytes = Vec::new();
+        input
+            .reader(prompt())
+            .and_then(|mut reader| reader.read_to_end(&mut bytes))
+            .unwrap_or_else(|error| fail(Failure::Io, &format!("can't read the input: {}", error)));
+        bench_program(&name, &ast, &config, &bytes, runs.get(), args.bench_json)
+            .unwrap_or_else(|error| error.exit());
+        return;
+    }
+
+    let mut state = State::new(&config);
+    state.input = match raw_terminal {
+        // Each key is read as soon as it is pressed
+        Some(_) => Box::new(io::stdin()),
+        None => Box::new(input.reader(prompt()).unwrap_or_else(|error| {
+            fail(Failure::Io, &format!("can't read the input: {}", error))
+        })),
+    };
+    if let Some(output) = output {
+        state.output = Box::new(io::BufWriter::new(output));
+    }
+    if args.stats || coverage_path.is_some() || flamegraph_path.is_some() {
+        state.profile = Some(Profile::default());
+    }
+    if coverage_path.is_some() && config.source.is_none() {
+        usage_error("--coverage needs a brainfuck source");
+    }
+    if flamegraph_path.is_some() && config.source.is_none() {
+        usage_error("--flamegraph needs a brainfuck source");
+    }
+    let watched = watched_cells(args.watch_cell.as_deref(), &config);
+    if !watched.is_empty() {
+        let (name, source) = (name.clone(), config.source.clone());
+        state.access = Some(Box::new(move |access, index, value: &u32, span| {
+            if watched.contains(&index) {
+                let access = describe_access(access, index, *value, span, &name, source.as_deref());
+                eprintln!("watch: {}", access);
+            }
+        }));
+    }
+    let mut hooks: Vec<Box<StepHook>> = Vec::new();
+    let trace = args.trace.as_deref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|error| {
+            fail(Failure::Io, &format!("can't create {}: {}", path, error))
+        });
+        let trace = Rc::new(RefCell::new(io::BufWriter::new(file)));
+        let (writer, mut step) = (Rc::clone(&trace), 0);
+        hooks.push(Box::new(
+            move |node: &Node, memory: &[u32], index: usize| {
+                step += 1;
+                let span = node.span;
+                let entry = format!(
+                    "{{\"step\":{},\"offset\":{},\"end\":{},\"pointer\":{},\"cell\":{}}}",
+                    step, span.start, span.end, index, memory[index]
+                );
+                writeln!(writer.borrow_mut(), "{}", entry).unwrap_or_else(|error| {
+                    fail(Failure::Io, &format!("can't write the trace: {}", error))
+                });
+            },
+        ));
+        (path, trace)
+    });
+    let history = args.core.as_deref().map(|path| {
+        let history = Rc::new(RefCell::new(VecDeque::new()));
+        let recorder = Rc::clone(&history);
+        hooks.push(Box::new(move |node, memory, index| {
+            let mut history = recorder.borrow_mut();
+            if history.len() == CORE_HISTORY {
+                history.pop_front();
+            }
+            history.push_back((node.span, index, memory[index]));
+        }));
+        (path, history)
+    });
+    let animation = args.animate.as_deref().map(|path| {
+        let every = args.animate_every.unwrap_or(1);
+        let cells = args.animate_cells.map(NonZeroUsize::get);
+        let animation = Rc::new(RefCell::new(Animation::new(every, cells, config.tape_size)));
+        let recorder = Rc::clone(&animation);
+        hooks.push(Box::new(move |_, memory, index| {
+            recorder.borrow_mut().step(memory, index)
+        }));
+        (path, animation)
+    });
+    let bounds_audit = audit.then(|| {
+        if config.source.is_none() {
+            usage_error("--audit-bounds needs a brainfuck source");
+        }
+        let margin = args.audit_margin;
+        let audit = Rc::new(RefCell::new(BoundsAudit::default()));
+        let (recorder, wrap_config) = (Rc::clone(&audit), config.clone());
+        hooks.push(Box::new(move |node, _, index| {
+            recorder.borrow_mut().step(node, index, &wrap_config)
+        }));
+        let recorder = Rc::clone(&audit);
+        state.loop_enter = Some(Box::new(move |span, _, index| {
+            recorder.borrow_mut().enter(span, index)
+        }));
+        let recorder = Rc::clone(&audit);
+        state.loop_exit = Some(Box::new(move |_, _, index| {
+            recorder.borrow_mut().exit(index)
+        }));
+        (audit, margin)
+    });
+    if !hooks.is_empty() {
+        state.hook = Some(Box::new(move |node, memory, index| {
+            for hook in hooks.iter_mut() {
+                hook(node, memory, index);
+            }
+        }));
+    }
+    let start = Instant::now();
+    let result = run_ast(&ast, &mut state, &config).and_then(|_| Ok(state.output.flush_bytes()?));
+    let elapsed = start.elapsed();
+    drop(raw_terminal);
+    if let Some((path, animation)) = animation {
+        let mut animation = animation.borrow_mut();
+        animation.finish(&state.memory, state.index);
+        if animation.truncated {
+            let message = format!(
+                "the animation stops after {} frames, a larger --animate-every would cover the run",
+                ANIMATION_FRAMES
+            );
+            report(&WARNING, &message, None);
+        }
+        let data = match path.ends_with(".gif") {
+            true => animation.gif(),
+            false => animation.svg().into_bytes(),
+        };
+        fs::write(path, data)
+            .unwrap_or_else(|error| fail(Failure::Io, &format!("can't write {}: {}", path, error)));
+    }
+    if let Some((path, trace)) = trace {
+        trace
+            .borrow_mut()
+            .flush()
+            .unwrap_or_else(|error| fail(Failure::Io, &format!("can't write {}: {}", path, error)));
+    }
+
+    // The statistics also tell how far a failed run went
+    if let (Some((audit, margin)), Some(source)) = (bounds_audit, &config.source) {
+        audit
+            .borrow_mut()
+            .report(state.index, &SourceMap::new(&name, source), &config, margin);
+    }
+    if let (Some(path), Some(profile), Some(source)) =
+        (coverage_path, &state.profile, &config.source)
+    {
+        write_coverage(path, &name, source, &ast, profile)
+            .unwrap_or_else(|error| fail(Failure::Io, &format!("can't write {}: {}", path, error)));
+    }
+    if let (Some(path), Some(profile), Some(source)) =
+        (flamegraph_path, &state.profile, &config.source)
+    {
+        let folded = folded_stacks(&name, source, config.language, &ast, profile);
+        fs::write(path, folded)
+            .unwrap_or_else(|error| fail(Failure::Io, &format!("can't write {}: {}", path, error)));
+    }
+    if let Some(profile) = state.profile.as_ref().filter(|_| args.stats) {
+        let mut stats = RunStats::default();
+        run_stats(&ast, profile, &mut stats);
+        eprintln!("instructions executed {:>14}", stats.instructions);
+        eprintln!("loop iterations {:>20}", stats.iterations);
+        eprintln!("highest cell {:>23}", state.highest);
+        eprintln!("bytes read {:>25}", stats.reads);
+        eprintln!("bytes written {:>22}", stats.writes);
+        let limit = result.as_ref().err().and_then(limit_option);
+        eprintln!("limits hit {:>25}", limit.unwrap_or("none"));
+        eprintln!("run time {:>25.3}ms", elapsed.as_secs_f64() * 1000.0);
+    }
+    if let Err(error) = result {
+        if let Some((path, history)) = history {
+            let history: Vec<_> = history.borrow().iter().copied().collect();
+            let program = Program {
+                ast: ast.clone(),
+                config: config.clone(),
+            };
+            write_core(path, &name, &error, &program, &state, &history).unwrap_or_else(|error| {
+                fail(Failure::Io, &format!("can't write {}: {}", path, error))
+            });
+        }
+        runtime_error(error, &name, config.source.as_deref()).exit();
+    }
+    report_stage("run", start, state.highest + 1, "cells");
+
+    if dump {
+        let cells = dump_range.unwrap_or(0..state.highest + 1);
+        dump_memory(&state, &config, cells, &mut io::stderr()).unwrap();
+    }
+}
+
+fn command_compare(args: &CompareArgs, config: Config) {
+    let (name, source) = program_source(&args.program);
+    if name.ends_with(".json") {
+        usage_error("an AST has no optimization levels");
+    }
+    let mut input = Vec::new();
+    args.input
+        .input()
+        .reader(prompt())
+        .and_then(|mut reader| reader.read_to_end(&mut input))
+        .unwrap_or_else(|error| fail(Failure::Io, &format!("can't read the input: {}", error)));
+
+    // Each level runs twice: for the time, then for the counts
+    println!("{:<8}{:>16}{:>14}  result", "level", "instructions", "time");
+    let mut runs = Vec::new();
+    for level in 0..=3 {
+        let mut config = Config {
+            opt_level: level,
+            ..config.clone()
+        };
+        let ast = load_program(&name, source.clone(), &mut config, false)
+            .unwrap_or_else(|error| error.exit());
+        let run = |profile: Option<Profile>| {
+            let output = SharedBuffer::default();
+            let mut state = State::new(&config);
+            state.input = Box::new(io::Cursor::new(input.clone()));
+            state.output = Box::new(output.clone());
+            state.profile = profile;
+            let start = Instant::now();
+            // The nodes, and so the locations of the errors, vary by level
+            let result = run_ast(&ast, &mut state, &config).map_err(|error| {
+                runtime_location(&error)
+                    .map_or_else(|| error.to_string(), |(_, message)| String::from(message))
+            });
+            (output.contents(), result, start.elapsed(), state.profile)
+        };
+        let (output, result, elapsed, _) = run(None);
+        let (_, _, _, profile) = run(Some(Profile::default()));
+        let mut stats = RunStats::default();
+        run_stats(&ast, &profile.unwrap(), &mut stats);
+
+        println!(
+            "-O{:<6}{:>16}{:>12.3}ms  {}",
+            level,
+            stats.instructions,
+            elapsed.as_secs_f64() * 1000.0,
+            result.as_ref().err().map_or("ok", |error| error.as_str())
+        );
+        runs.push((level, output, result));
+    }
+
+    let (_, output, result) = &runs[0];
+    for (level, other_output, other_result) in runs[1..].iter() {
+        if other_output != output || other_result != result {
+            fail(
+                Failure::Runtime,
+                &format!(
+                    "{}: the run at -O{} differs from the one at -O0",
+                    name, level
+                ),
+            );
+        }
+    }
+}
+
+/// The cells reached by the pointer in a run, for run --audit-bounds
+#[derive(Default)]
+struct BoundsAudit {
+    overall: Option<(usize, usize)>, // Lowest and highest cells reached
+    loops: BTreeMap<Span, (usize, usize)>,
+    running: Vec<(Span, usize, usize)>, // The loops running, innermost last
+}
+
+impl BoundsAudit {
+    /// Add a cell to the range of the innermost loop running, the outer
+    /// ones getting it when it ends
+    fn reach(&mut self, cell: usize) {
+        let (low, high) = self.overall.get_or_insert((cell, cell));
+        *low = (*low).min(cell);
+        *high = (*high).max(cell);
+        if let Some((_, low, high)) = self.running.last_mut() {
+            *low = (*low).min(cell);
+            *high = (*high).max(cell);
+        }
+    }
+
+    /// Record a node, the rewritten loops counting as loops on their own
+    fn step(&mut self, node: &Node, index: usize, config: &Config) {
+        match &node.kind {
+            NodeKind::Clear => {
+                self.enter(node.span, index);
+                self.exit(index);
+            }
+            NodeKind::Mul(targets) => {
+                self.enter(node.span, index);
+                for (offset, _) in targets.iter() {
+                    let cell = match config.bounds {
+                        Bounds::Wrap => config.wrap(index, *offset),
+                        Bounds::Unchecked => index.saturating_add_signed(*offset),
+                    };
+                    self.reach(cell);
+                }
+                self.exit(index);
+            }
+            _ => self.reach(index),
+        }
+    }
+
+    fn enter(&mut self, span: Span, index: usize) {
+        self.running.push((span, index, index));
+    }
+
+    fn exit(&mut self, index: usize) {
+        self.reach(index);
+        if let Some((span, low, high)) = self.running.pop() {
+            let (total_low, total_high) = self.loops.entry(span).or_insert((low, high));
+            *total_low = (*total_low).min(low);
+            *total_high = (*total_high).max(high);
+            self.reach(low);
+            self.reach(high);
+        }
+    }
+
+    /// Print the ranges of the loops and of the run, ending on cell `index`,
+    /// warning when the pointer comes within `margin` cells of the end of the
+    /// tape
+    fn report(&mut self, index: usize, lines: &SourceMap, config: &Config, margin: usize) {
+        // The loops of a failed run end where it stops
+        self.reach(index);
+        while !self.running.is_empty() {
+            self.exit(index);
+        }
+        for (span, (low, high)) in self.loops.iter() {
+            let (start_line, start_column) = lines.location(span.start);
+            let (end_line, end_column) = lines.location(span.end - 1);
+            eprintln!(
+                "loop {}:{}-{}:{}  cells {} to {}",
+                start_line, start_column, end_line, end_column, low, high
+            );
+        }
+        let (low, high) = self.overall.unwrap_or((0, 0));
+        eprintln!(
+            "pointer from cell {} to cell {}, of {}",
+            low, high, config.tape_size
+        );
+        let left = config.tape_size - 1 - high.min(config.tape_size - 1);
+        if config.bounds == Bounds::Unchecked && left < margin {
+            let message = match left {
+                0 => format!("the pointer reaches cell {}, the last of the tape", high),
+                left => format!(
+                    "the pointer reaches cell {}, {} cells from the end of the tape",
+                    high, left
+                ),
+            };
+            report(&WARNING, &message, None);
+        }
+    }
+}
+
+/// The cells of a range given to an option as <start>..<end>, on a tape
+fn cell_range(option: &str, range: &str, tape_size: usize) -> Range<usize> {
+    let bounds = range
+        .split_once("..")
+        .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)));
+    match bounds {
+        Some((start, end)) if start <= end && end <= tape_size => start..end,
+        _ => usage_error(&format!("invalid value {:?} for {}", range, option)),
+    }
+}
+
+/// Instructions kept by run --core, up to the failing one
+const CORE_HISTORY: usize = 64;
+
+/// Write the core of a failed run: the program and its error, the state of
+/// the VM, and the last instructions run, with the pointer and its cell
+/// before each
+fn write_core(
+    path: &str,
+    name: &str,
+    error: &RuntimeError,
+    program: &Program,
+    state: &State,
+    history: &[(Span, usize, u32)],
+) -> io::Result<()> {
+    let span_json = |span: Span| {
+        json_object(vec![
+            ("start", Json::Number(span.start as i64)),
+            ("end", Json::Number(span.end as i64)),
+        ])
+    };
+    let (span, message) = match runtime_location(error) {
+        Some((span, message)) => (span_json(span), String::from(message)),
+        None => (Json::Null, error.to_string()),
+    };
+    let history = history.iter().map(|(span, pointer, cell)| {
+        json_object(vec![
+            ("span", span_json(*span)),
+            ("pointer", Json::Number(*pointer as i64)),
+            ("cell", Json::Number(*cell as i64)),
+        ])
+    });
+    let core = json_object(vec![
+        ("name", json_string(name)),
+        ("error", json_string(&message)),
+        ("span", span),
+        ("program", program_to_json(program)),
+        ("state", state_to_json(state)),
+        ("history", Json::Array(history.collect())),
+    ]);
+    fs::write(path, format!("{}\n", core))
+}
+
+/// A core written by run --core
+struct Core {
+    name: String,
+    error: String,
+    span: Option<Span>,
+    program: Program,
+    state: State,
+    history: Vec<(Span, i64, i64)>,
+}
+
+/// Read a core written by run --core
+fn read_core(path: &str) -> Result<Core, CliError> {
+    let text = read_source(path)?;
+    let invalid = |message: String| {
+        CliError::new(
+            Failure::Parse,
+            format!("{}: invalid core: {}", source_name(path), message),
+        )
+    };
+    let core = parse_json(&text).map_err(invalid)?;
+    let member = |name: &str| {
+        core.get(name)
+            .ok_or_else(|| invalid(format!("missing {}", name)))
+    };
+    let span = |json: &Json| {
+        let offset = |name| json.get(name)?.as_i64().filter(|offset| *offset >= 0);
+        Some(Span::new(
+            offset("start")? as usize,
+            offset("end")? as usize,
+        ))
+    };
+    let text = |name: &str| {
+        let text = member(name)?.as_str();
+        text.map(String::from)
+            .ok_or_else(|| invalid(format!("expected a string for {}", name)))
+    };
+
+    let program = program_from_json(member("program")?).map_err(invalid)?;
+    let mut state = State::new(&program.config);
+    state_from_json(member("state")?, &mut state).map_err(invalid)?;
+    let history = match member("history")? {
+        Json::Array(entries) => entries
+            .iter()
+            .map(|entry| {
+                let field = |name| entry.get(name)?.as_i64();
+                Some((span(entry.get("span")?)?, field("pointer")?, field("cell")?))
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid(String::from("invalid instruction in the history")))?,
+        _ => return Err(invalid(String::from("expected an array for history"))),
+    };
+    Ok(Core {
+        name: text("name")?,
+        error: text("error")?,
+        span: span(member("span")?),
+        program,
+        state,
+        history,
+    })
+}
+
+fn command_inspect(args: &InspectArgs) {
+    let core = read_core(&args.core).unwrap_or_else(|error| error.exit());
+    let config = &core.program.config;
+    let source = config.source.as_deref();
+
+    // Offsets are located in the source when the core has it
+    let lines = source.map(|source| SourceMap::new(&core.name, source));
+    let locate = |span: Span| match (&lines, source) {
+        (Some(lines), Some(source)) => {
+            let (line, column) = lines.location(span.start);
+            let code: String = source[span.start..span.end].chars().take(20).collect();
+            format!("{}:{} {:?}", line, column, code)
+        }
+        _ => format!("byte {}", span.start),
+    };
+    match (core.span, &lines) {
+        (Some(span), Some(lines)) => {
+            let (line, column) = lines.location(span.start);
+            println!("{}:{}:{}: {}", core.name, line, column, core.error);
+        }
+        _ => println!("{}: {}", core.name, core.error),
+    }
+    println!(
+        "highest cell {}, {} bytes written",
+        core.state.highest, core.state.written
+    );
+
+    let shown = match args.history {
+        true => core.history.len(),
+        false => core.history.len().min(8),
+    };
+    println!("last instructions:");
+    for (span, pointer, cell) in core.history[core.history.len() - shown..].iter() {
+        println!("  {}  pointer {}, cell {}", locate(*span), pointer, cell);
+    }
+
+    let tape_size = core.state.memory.len();
+    let cells = match &args.range {
+        Some(range) => cell_range("--range", range, tape_size),
+        None => core.state.index.saturating_sub(16)..(core.state.index + 16).min(tape_size),
+    };
+    dump_memory(&core.state, config, cells, &mut io::stdout()).unwrap();
+}
+
+/// Moves of the pointer by a node, known when its inner loops come back to
+/// their cell
+fn net_move(node: &Node) -> Option<isize> {
+    match &node.kind {
+        NodeKind::Move(val) => Some(*val),
+        NodeKind::Block(nodes) => nodes.iter().map(net_move).sum(),
+        // A loop coming back to its cell doesn't move the pointer
+        NodeKind::Loop(body) if net_move(body) == Some(0) => Some(0),
+        NodeKind::Loop(_) | NodeKind::Procedure(_) | NodeKind::Call => None,
+        _ => Some(0),
+    }
+}
+
+/// The loops and procedures of a program, as lines indented by their depth
+struct Outline<'a> {
+    lines: SourceMap,
+    profile: Option<&'a Profile>,
+    position: Option<isize>, // Cell of the pointer, from the start, when it is known
+    depth: usize,
+    outline: Vec<String>,
+}
+
+impl Visitor for Outline<'_> {
+    fn visit(&mut self, node: &Node) {
+        let body = match &node.kind {
+            NodeKind::Move(val) => {
+                self.position = self.position.map(|position| position + val);
+                return;
+            }
+            // Procedures may move the pointer anywhere
+            NodeKind::Call => {
+                self.position = None;
+                return;
+            }
+            NodeKind::Loop(body) => body.as_ref(),
+            NodeKind::Procedure(body) => body.as_ref(),
+            _ => return walk(self, node),
+        };
+
+        let (start_line, start_column) = self.lines.location(node.span.start);
+        let (end_line, end_column) = self.lines.location(node.span.end - 1);
+        let start = self.position;
+        let cell = match start {
+            Some(position) => format!("cell {}", position),
+            None => String::from("unknown cell"),
+        };
+        let moves = match net_move(body) {
+            Some(moves) => format!("pointer {:+} per iteration", moves),
+            None => String::from("unknown moves"),
+        };
+        let mut line = format!(
+            "{:indent$}{} {}:{}-{}:{}  depth {}, {}, {}",
+            "",
+            match node.kind {
+                NodeKind::Loop(_) => "loop",
+                _ => "procedure",
+            },
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            self.depth + 1,
+            cell,
+            moves,
+            indent = 2 * self.depth
+        );
+        if let Some(profile) = self.profile {
+            let runs = profile.count(node.span);
+            let iterations = first_count(body, profile);
+            let plural = |count: u64, noun: &str| match count {
+                1 => format!("1 {}", noun),
+                count => format!("{} {}s", count, noun),
+            };
+            line.push_str(&format!(
+                ", {}, {}",
+                plural(runs, "run"),
+                plural(iterations, "iteration")
+            ));
+        }
+        self.outline.push(line);
+
+        // The body of a procedure runs where it is called
+        if matches!(node.kind, NodeKind::Procedure(_)) {
+            self.position = None;
+        }
+        self.depth += 1;
+        walk(self, node);
+        self.depth -= 1;
+        self.position = match (&node.kind, net_move(body)) {
+            (NodeKind::Procedure(_), _) | (_, Some(0)) => start,
+            _ => None,
+        };
+    }
+}
+
+/// Executions of the first instruction of a body, its iterations
+fn first_count(body: &Node, profile: &Profile) -> u64 {
+    match &body.kind {
+        NodeKind::Block(nodes) => nodes.first().map_or(0, |node| first_count(node, profile)),
+        _ => profile.count(body.span),
+    }
+}
+
+fn command_outline(args: &OutlineArgs, config: Config) {
+    let (name, source) = program_source(&args.program);
+
+    // Each loop of the source is kept, without the optimizations
+    let ast =
+        compile_program(&name, &source, config.language, 0).unwrap_or_else(|error| error.exit());
+    let profile = args.profile.then(|| {
+        let mut state = State::new(&config);
+        let input = args.input.input();
+        state.input = Box::new(input.reader(prompt()).unwrap_or_else(|error| {
+            fail(Failure::Io, &format!("can't read the input: {}", error))
+        }));
+        state.output = Box::new(io::sink());
+        state.profile = Some(Profile::default());
+        run_ast(&ast, &mut state, &config)
+            .unwrap_or_else(|error| runtime_error(error, &name, Some(&source)).exit());
+        state.profile.unwrap()
+    });
+
+    let mut outline = Outline {
+        lines: SourceMap::new(&name, &source),
+        profile: profile.as_ref(),
+        position: Some(0),
+        depth: 0,
+        outline: Vec::new(),
+    };
+    outline.visit(&ast);
+    if outline.outline.is_empty() {
+        println!("no loops");
+    }
+    for line in outline.outline.iter() {
+        println!("{}", line);
+    }
+}
+
+/// An input found by symbolic, as a quoted string
+fn quote_input(input: &[u8]) -> String {
+    format!("\"{}\"", input.escape_ascii())
+}
+
+fn command_symbolic(args: &SymbolicArgs, config: Config) {
+    let (name, source) = program_source(&args.program);
+    let ast = compile_program(&name, &source, config.language, config.opt_level)
+        .unwrap_or_else(|error| error.exit());
+
+    let mut limits = Limits::default();
+    limits.max_input = args.max_input.unwrap_or(limits.max_input);
+    limits.max_paths = args.max_paths.unwrap_or(limits.max_paths);
+    limits.max_steps = args.max_steps.unwrap_or(limits.max_steps);
+    if let Some(range) = &args.byte_range {
+        let bounds = range
+            .split_once("..")
+            .and_then(|(first, last)| Some((first.parse().ok()?, last.parse().ok()?)));
+        limits.bytes = match bounds {
+            Some((first, last)) if first <= last => first..=last,
+            _ => usage_error(&format!("invalid value {:?} for --byte-range", range)),
+        };
+    }
+
+    let analysis = explore(&ast, &config, &limits);
+    let lines = SourceMap::new(&name, &source);
+    for (fault, span, input) in analysis.faults.iter() {
+        let (line, column) = lines.location(span.start);
+        println!(
+            "{} at {}:{} with input {}",
+            match fault {
+                Fault::Underflow => "underflow",
+                Fault::Overflow => "overflow",
+            },
+            line,
+            column,
+            quote_input(input)
+        );
+    }
+    for (span, input) in analysis.loops.iter() {
+        let (start_line, start_column) = lines.location(span.start);
+        let (end_line, end_column) = lines.location(span.end - 1);
+        let entered = match input {
+            Some(input) => format!("entered with input {}", quote_input(input)),
+            None => String::from("never entered"),
+        };
+        println!(
+            "loop {}:{}-{}:{} {}",
+            start_line, start_column, end_line, end_column, entered
+        );
+    }
+    println!(
+        "{} path{} explored{}",
+        analysis.paths,
+        if analysis.paths == 1 { "" } else { "s" },
+        if analysis.complete {
+            ""
+        } else {
+            ", not all the runs within the limits"
+        }
+    );
+    if !analysis.faults.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// An instruction of a trace written by run --trace, with the pointer and
+/// its cell before it
+struct TraceEntry {
+    step: i64,
+    span: Span,
+    pointer: i64,
+    cell: i64,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "step {}, offset {}, pointer {}, cell {}",
+            self.step, self.span.start, self.pointer, self.cell
+        )
+    }
+}
+
+/// The entries of a trace, read as they come
+fn read_trace(path: &str) -> Result<impl Iterator<Item = Result<TraceEntry, CliError>>, CliError> {
+    let file = File::open(path)
+        .map_err(|error| CliError::new(Failure::Io, format!("can't read {}: {}", path, error)))?;
+    let path = String::from(path);
+    let lines = io::BufReader::new(file).lines().enumerate();
+    Ok(lines.map(move |(number, line)| {
+        let line = line.map_err(|error| {
+            CliError::new(Failure::Io, format!("can't read {}: {}", path, error))
+        })?;
+        let json = parse_json(&line).ok();
+        let field = |name| json.as_ref()?.get(name)?.as_i64();
+        let entry = (|| {
+            let (start, end) = (field("offset")?, field("end")?);
+            if start < 0 || end < start {
+                return None;
+            }
+            Some(TraceEntry {
+                step: field("step")?,
+                span: Span::new(start as usize, end as usize),
+                pointer: field("pointer")?,
+                cell: field("cell")?,
+            })
+        })();
+        entry.ok_or_else(|| {
+            let message = format!("{}:{}: invalid trace entry", path, number + 1);
+            CliError::new(Failure::Parse, message)
+        })
+    }))
+}
+
+fn command_trace_diff(args: &TraceDiffArgs) {
+    let paths = [&args.traces[0], &args.traces[1]];
+    let mut traces = paths.map(|path| read_trace(path).unwrap_or_else(|error| error.exit()));
+    let mut next = |trace: usize| {
+        traces[trace]
+            .next()
+            .transpose()
+            .unwrap_or_else(|error| error.exit())
+    };
+    let ignore_offsets = args.ignore_offsets;
+
+    // The entries of one trace inside a node of the other one merging
+    // several instructions, as "[-]", are skipped
+    let mut merged = Span::default();
+    let inside = |entry: &TraceEntry, merged: Span| {
+        entry.span.start > merged.start && entry.span.start < merged.end
+    };
+    let (mut a, mut b) = (next(0), next(1));
+    let divergence = loop {
+        match (&a, &b) {
+            (None, None) => break None,
+            (Some(x), Some(y)) if ignore_offsets || x.span.start == y.span.start => {
+                if x.pointer != y.pointer || x.cell != y.cell {
+                    break Some(format!("{}: {}\n{}: {}", paths[0], x, paths[1], y));
+                }
+                merged = x.span.merge(y.span);
+                (a, b) = (next(0), next(1));
+            }
+            (Some(x), _) if inside(x, merged) && !b.as_ref().is_some_and(|y| inside(y, merged)) => {
+                a = next(0)
+            }
+            (_, Some(y)) if inside(y, merged) && !a.as_ref().is_some_and(|x| inside(x, merged)) => {
+                b = next(1)
+            }
+            (Some(x), Some(y)) => break Some(format!("{}: {}\n{}: {}", paths[0], x, paths[1], y)),
+            (Some(x), None) => break Some(format!("{}: {}\n{}: ended", paths[0], x, paths[1])),
+            (None, Some(y)) => break Some(format!("{}: ended\n{}: {}", paths[0], paths[1], y)),
+        }
+    };
+    match divergence {
+        Some(divergence) => {
+            println!("the traces diverge\n{}", divergence);
+            process::exit(1);
+        }
+        None => println!("the traces match"),
+    }
+}
+
+/// The bytes read by a traced run: the cells after its reads
+fn trace_input(ast: &Node, trace: &[TraceEntry]) -> Vec<u8> {
+    struct Reads(Vec<usize>);
+    impl Visitor for Reads {
+        fn visit(&mut self, node: &Node) {
+            if matches!(node.kind, NodeKind::Read) {
+                self.0.push(node.span.start);
+            }
+            walk(self, node);
+        }
+    }
+    let mut reads = Reads(Vec::new());
+    reads.visit(ast);
+
+    // A cell out of the bytes is the end of the input
+    trace
+        .windows(2)
+        .filter(|entries| reads.0.contains(&entries[0].span.start))
+        .map_while(|entries| match entries[1].cell {
+            cell @ 0..=255 => Some(cell as u8),
+            _ => None,
+        })
+        .collect()
+}
+
+fn command_replay(args: &ReplayArgs, config: Config) {
+    let to_step = args.to_step;
+    if to_step.is_some()
+        && args.program.code.is_none()
+        && args.program.source.as_deref().unwrap_or("-") == "-"
+    {
+        usage_error("the debugger commands are read on the standard input");
+    }
+    let (name, source) = program_source(&args.program);
+    let ast = compile_program(&name, &source, config.language, config.opt_level)
+        .unwrap_or_else(|error| error.exit());
+    let trace: Option<Vec<TraceEntry>> = args.trace.as_deref().map(|path| {
+        read_trace(path)
+            .and_then(|entries| entries.collect())
+            .unwrap_or_else(|error| error.exit())
+    });
+
+    let mut state = State::new(&config);
+    let input = args.input.input();
+    match &trace {
+        Some(_) if !matches!(input, Input::Stdin) => {
+            usage_error("the input of a trace can't be given with --input or --input-str")
+        }
+        Some(trace) => state.input = Box::new(io::Cursor::new(trace_input(&ast, trace))),
+        None if matches!(input, Input::Stdin) && to_step.is_some() => {}
+        None => {
+            state.input = Box::new(input.reader(prompt()).unwrap_or_else(|error| {
+                fail(Failure::Io, &format!("can't read the input: {}", error))
+            }))
+        }
+    }
+
+    let mut stops = Stops {
+        labels: parse_labels(&source, config.language),
+        breakpoints: Vec::new(),
+        watch: Rc::new(RefCell::new(Watch {
+            cells: Vec::new(),
+            hit: false,
+        })),
+        stepping: false,
+    };
+    let trace = Rc::new(trace);
+    let steps = Rc::new(RefCell::new(0));
+    let (counter, expected_trace) = (Rc::clone(&steps), Rc::clone(&trace));
+    let hook_source = source.clone();
+    state.hook = Some(Box::new(
+        move |node: &Node, memory: &[u32], index: usize| {
+            let mut step = counter.borrow_mut();
+            *step += 1;
+            let replayed = TraceEntry {
+                step: *step,
+                span: node.span,
+                pointer: index as i64,
+                cell: memory[index] as i64,
+            };
+            if let Some(trace) = expected_trace.as_ref() {
+                let expected = trace.get(*step as usize - 1);
+                let diverges = expected.is_none_or(|entry| {
+                    (entry.span.start, entry.pointer, entry.cell)
+                        != (node.span.start, replayed.pointer, replayed.cell)
+                });
+                if diverges {
+                    io::stdout().flush().unwrap();
+                    let expected = expected.map_or(String::from("ended"), ToString::to_string);
+                    eprintln!(
+                        "the replay diverges from the trace\ntrace: {}\nreplay: {}",
+                        expected, replayed
+                    );
+                    process::exit(1);
+                }
+            }
+            if to_step == Some(*step) {
+                eprintln!("step {}", step);
+                stops.stepping = true;
+            }
+            debug_prompt(node, memory, index, &hook_source, &mut stops);
+        },
+    ));
+    let result = run_ast(&ast, &mut state, &config).and_then(|_| Ok(state.output.flush_bytes()?));
+    if let Err(error) = result {
+        runtime_error(error, &name, Some(&source)).exit();
+    }
+    let steps = *steps.borrow();
+    if let Some(entry) = trace
+        .as_ref()
+        .as_ref()
+        .and_then(|trace| trace.get(steps as usize))
+    {
+        eprintln!(
+            "the replay diverges from the trace\ntrace: {}\nreplay: ended",
+            entry
+        );
+        process::exit(1);
+    }
+    if let Some(step) = to_step.filter(|step| *step > steps) {
+        let message = format!("the run ends after {} steps, before step {}", steps, step);
+        report(&WARNING, &message, None);
+    }
+}
+
+/// Run a program repeatedly on the same input, after a warmup run, and print
+/// the statistics of the run times
+fn bench_program(
+    name: &str,
+    ast: &Node,
+    config: &Config,
+    input: &[u8],
+    runs: usize,
+    json: bool,
+) -> Result<(), CliError> {
+    let run = |profile: Option<Profile>| {
+        let mut state = State::new(config);
+        state.input = Box::new(io::Cursor::new(input.to_vec()));
+        state.output = Box::new(io::sink());
+        state.profile = profile;
+        let start = Instant::now();
+        run_ast(ast, &mut state, config)
+            .map_err(|error| runtime_error(error, name, config.source.as_deref()))?;
+        Ok((start.elapsed(), state.profile))
+    };
+
+    // The warmup run counts the instructions
+    let (_, profile) = run(Some(Profile::default()))?;
+    let mut stats = RunStats::default();
+    run_stats(ast, &profile.unwrap(), &mut stats);
+    let mut times = (0..runs)
+        .map(|_| run(None).map(|(elapsed, _)| elapsed.as_secs_f64()))
+        .collect::<Result<Vec<_>, CliError>>()?;
+    times.sort_by(f64::total_cmp);
+    let min = times[0];
+    let median = (times[(runs - 1) / 2] + times[runs / 2]) / 2.0;
+    let mean = times.iter().sum::<f64>() / runs as f64;
+    let stddev = (times.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / runs as f64).sqrt();
+    let rate = stats.instructions as f64 / median;
+
+    if json {
+        let number = |value: f64| Json::Number(value as i64);
+        let nanoseconds = |seconds: f64| number(seconds * 1e9);
+        let members = vec![
+            (String::from("runs"), number(runs as f64)),
+            (
+                String::from("instructions"),
+                number(stats.instructions as f64),
+            ),
+            (String::from("min_ns"), nanoseconds(min)),
+            (String::from("median_ns"), nanoseconds(median)),
+            (String::from("stddev_ns"), nanoseconds(stddev)),
+            (String::from("instructions_per_second"), number(rate)),
+        ];
+        println!("{}", Json::Object(members));
+    } else {
+        println!("runs {:>30}", runs);
+        println!("min {:>29.3}ms", min * 1000.0);
+        println!("median {:>26.3}ms", median * 1000.0);
+        println!("stddev {:>26.3}ms", stddev * 1000.0);
+        println!("instructions {:>22}", stats.instructions);
+        println!("instructions per second {:>11.0}", rate);
+    }
+    Ok(())
+}
+
+/// The instructions executed in each chain of nested loops, as the lines of
+/// the folded stacks of flamegraph, in the order of the source
+struct FoldedStacks<'a> {
+    profile: &'a Profile,
+    lines: SourceMap,
+    labels: Vec<(String, usize)>,
+    stack: Vec<String>, // Frames of the loops around the node visited
+    current: usize,     // Index of the line of the stack in `folded`
+    folded: Vec<(String, u64)>,
+}
+
+impl Visitor for FoldedStacks<'_> {
+    fn visit(&mut self, node: &Node) {
+        if matches!(node.kind, NodeKind::Block(_)) {
+            return walk(self, node);
+        }
+        self.folded[self.current].1 += self.profile.count(node.span);
+        let kind = match node.kind {
+            NodeKind::Loop(_) => "loop",
+            NodeKind::Procedure(_) => "procedure",
+            _ => return,
+        };
+
+        // A loop is named by the label of its "[", or by its location
+        let frame = match self
+            .labels
+            .iter()
+            .find(|(_, offset)| *offset == node.span.start)
+        {
+            Some((label, _)) => label.clone(),
+            None => {
+                let (line, column) = self.lines.location(node.span.start);
+                format!("{} {}:{}", kind, line, column)
+            }
+        };
+        self.stack.push(frame);
+        let parent = self.current;
+        self.current = self.folded.len();
+        self.folded.push((self.stack.join(";"), 0));
+        walk(self, node);
+        self.current = parent;
+        self.stack.pop();
+    }
+}
+
+/// The folded stacks of a run of a source, each stack being a chain of
+/// nested loops under the name of the source, with the instructions executed
+/// in its innermost loop
+fn folded_stacks(
+    name: &str,
+    source: &str,
+    language: Language,
+    ast: &Node,
+    profile: &Profile,
+) -> String {
+    // The frames are separated by semicolons
+    let root = name.replace(';', ":");
+    let mut stacks = FoldedStacks {
+        profile,
+        lines: SourceMap::new(name, source),
+        labels: parse_labels(source, language),
+        stack: vec![root.clone()],
+        current: 0,
+        folded: vec![(root, 0)],
+    };
+    stacks.visit(ast);
+    stacks
+        .folded
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(stack, count)| format!("{} {}\n", stack, count))
+        .collect()
+}
+
+/// Write the coverage of a run of a source, as JSON or as the source with
+/// the executions of each line, marking its commands which never ran
+fn write_coverage(
+    path: &str,
+    name: &str,
+    source: &str,
+    ast: &Node,
+    profile: &Profile,
+) -> io::Result<()> {
+    let counts = coverage(ast, profile);
+    let executed = counts.iter().filter(|(_, count)| *count > 0).count();
+    let lines = SourceMap::new(name, source);
+    if path.ends_with(".json") {
+        let commands = counts.iter().map(|(span, count)| {
+            let (line, column) = lines.location(span.start);
+            json_object(vec![
+                ("offset", Json::Number(span.start as i64)),
+                ("line", Json::Number(line as i64)),
+                ("column", Json::Number(column as i64)),
+                ("command", json_string(&source[span.start..span.end])),
+                ("count", Json::Number(*count as i64)),
+            ])
+        });
+        let report = json_object(vec![
+            ("source", json_string(name)),
+            ("commands", Json::Number(counts.len() as i64)),
+            ("executed", Json::Number(executed as i64)),
+            ("counts", Json::Array(commands.collect())),
+        ]);
+        return fs::write(path, format!("{}\n", report));
+    }
+
+    // Each line shows the executions of its hottest command, ##### when
+    // none ran, and is followed by marks under the commands which never ran
+    let mut report = String::new();
+    let total = counts.len();
+    let mut counts = counts.iter().peekable();
+    let mut start = 0;
+    for line in source.split_inclusive('\n') {
+        let end = start + line.len();
+        let text = line.trim_end_matches(['\n', '\r']);
+        let mut on_line = Vec::new();
+        while let Some((span, count)) = counts.next_if(|(span, _)| span.start < end) {
+            on_line.push((*span, *count));
+        }
+        let hottest = on_line.iter().map(|(_, count)| *count).max();
+        let column = match hottest {
+            None => String::from("-"),
+            Some(0) => String::from("#####"),
+            Some(count) => count.to_string(),
+        };
+        report.push_str(&format!("{:>9} | {}\n", column, text));
+        if hottest.is_some_and(|count| count > 0) && on_line.iter().any(|(_, count)| *count == 0) {
+            let marks: String = text
+                .char_indices()
+                .map(|(offset, c)| {
+                    let never = |(span, count): &(Span, u64)| {
+                        *count == 0 && (span.start..span.end).contains(&(start + offset))
+                    };
+                    match c {
+                        '\t' => '\t',
+                        _ if on_line.iter().any(never) => '^',
+                        _ => ' ',
+                    }
+                })
+                .collect();
+            report.push_str(&format!("{:>9} | {}\n", "", marks.trim_end()));
+        }
+        start = end;
+    }
+    report.push_str(&format!("{} of {} commands executed\n", executed, total));
+    fs::write(path, report)
+}
+
+/// Compile a source with a target, into the generated code
+fn build_program(
+    config: &Config,
+    lints: &LintArgs,
+    options: &BuildOptions,
+    path: &str,
+    target: &Target,
+) -> Result<Vec<u8>, CliError> {
+    let mut config = config.clone();
+    let name = source_name(path);
+    let ast = load_program(
+        name,
+        read_source(path)?,
+        &mut config,
+        options.line_directives,
+    )?;
+    match (config.opt_level, &config.source) {
+        // The start run at compile time by level 3 doesn't follow the source
+        (3, Some(source)) => {
+            let linted = compile_program(name, source, config.language, 2)?;
+            report_lints(lints, name, &linted, &config)?;
+        }
+        _ => report_lints(lints, name, &ast, &config)?,
+    }
+
+    // Run the program first, to count the executions, its output being
+    // dropped for the one of the build
+    if options.profile {
+        let mut state = State::new(&config);
+        state.output = Box::new(io::sink());
+        state.profile = Some(Profile::default());
+        run_ast(&ast, &mut state, &config)
+            .and_then(|_| Ok(state.output.flush_bytes()?))
+            .map_err(|error| runtime_error(error, name, config.source.as_deref()))?;
+        config.profile = state.profile;
+    }
+
+    let start = Instant::now();
+    let mut code = Vec::new();
+    target
+        .backend
+        .emit(&ast, &config, &mut code)
+        .map_err(|error| match error {
+            CodegenError::Io(error) => CliError::new(Failure::Io, error.to_string()),
+            error => CliError::new(Failure::Usage, error.to_string()),
+        })?;
+    report_stage("codegen", start, code.len(), "bytes");
+    Ok(code)
+}
+
+/// Compile a source into a file, which is executable for the binary targets
+fn build_file(
+    config: &Config,
+    lints: &LintArgs,
+    options: &BuildOptions,
+    path: &str,
+    output: &Path,
+    target: &Target,
+) -> Result<(), CliError> {
+    let code = build_program(config, lints, options, path, target)?;
+    let write_error = |error: io::Error| {
+        CliError::new(
+            Failure::Io,
+            format!("can't write {}: {}", output.display(), error),
+        )
+    };
+    let file = File::create(output)
+        .and_then(|mut file| file.write_all(&code).map(|_| file))
+        .map_err(write_error)?;
+
+    // Executables must be runnable right away
+    #[cfg(unix)]
+    if matches!(target.name.as_str(), "bin" | "elf" | "macho") {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o755))
+            .map_err(write_error)?;
+    }
+
+    Ok(())
+}
+
+/// Build each source, or the .bf sources of each directory, into a directory,
+/// exiting with the status of the first failure once all are built
+fn build_batch(args: &BuildArgs, directory: &str, target: &Target) {
+    let mut failure = None;
+    let mut report = |error: CliError| {
+        error.report();
+        failure = failure.or(Some(error.failure));
+    };
+
+    let mut sources = Vec::new();
+    for argument in args.source.iter().chain(args.outputs.iter()) {
+        let path = Path::new(argument);
+        if argument == "-" {
+            usage_error("the standard input can't be built with --out-dir");
+        } else if path.is_dir() {
+            match fs::read_dir(path) {
+                Ok(entries) => {
+                    let mut paths: Vec<PathBuf> = entries
+                        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                        .filter(|path| path.extension().is_some_and(|ext| ext == "bf"))
+                        .collect();
+                    paths.sort();
+                    sources.extend(paths);
+                }
+                Err(error) => report(CliError::new(
+                    Failure::Io,
+                    format!("can't read {}: {}", argument, error),
+                )),
+            }
+        } else {
+            sources.push(path.to_path_buf());
+        }
+    }
+    if let Err(error) = fs::create_dir_all(directory) {
+        fail(
+            Failure::Io,
+            &format!("can't create {}: {}", directory, error),
+        );
+    }
+
+    let config = args.config();
+    for source in sources.iter() {
+        let mut output = Path::new(directory).join(source.file_stem().unwrap_or_default());
+        if let Some(extension) = target.extensions.first() {
+            output.set_extension(extension);
+        }
+        let path = source.to_string_lossy();
+        if let Err(error) = build_file(&config, &args.lints, &args.build, &path, &output, target) {
+            report(error);
+        }
+    }
+    if let Some(failure) = failure {
+        process::exit(failure as i32);
+    }
+}
+
+fn command_build(args: &BuildArgs) {
+    let registry = registry(args.format.pretty(), &args.build);
+    let find_target = |name: &str| {
+        registry
+            .get(name)
+            .unwrap_or_else(|| usage_error(&format!("unsupported target {:?}", name)))
+    };
+    let target = if args.build.obfuscate {
+        Some("bf-obfuscated")
+    } else {
+        args.build.target.as_deref()
+    };
+    let target = target.map(find_target);
+
+    if let Some(directory) = &args.build.out_dir {
+        if args.source.is_none() {
+            usage_error("no source to build in --out-dir");
+        }
+        build_batch(
+            args,
+            directory,
+            target.unwrap_or_else(|| find_target("bin")),
+        );
+        return;
+    }
+    if let Some(argument) = args.outputs.get(1) {
+        usage_error(&format!(
+            "unexpected argument {:?}, several sources are built with --out-dir",
+            argument
+        ));
+    }
+
+    let config = args.config();
+    let path = args.source.as_deref().unwrap_or("-");
+    match (args.outputs.first().map(String::as_str), target) {
+        // Output on stdout
+        (None, Some(target)) | (Some("-"), Some(target)) => {
+            let code = build_program(&config, &args.lints, &args.build, path, target)
+                .unwrap_or_else(|error| error.exit());
+            let stdout = io::stdout();
+            let mut lock = stdout.lock();
+            if let Err(error) = lock.write_all(&code).and_then(|_| lock.flush()) {
+                fail(Failure::Io, &format!("can't write the output: {}", error));
+            }
+        }
+        (None, None) | (Some("-"), None) => usage_error("a target is required to output on stdout"),
+        // Output in a file, in the language of its extension by default
+        (Some(output), target) => {
+            let output = Path::new(output);
+            let target = match (target, output.extension()) {
+                (Some(target), _) => target,
+                (None, Some(extension)) => {
+                    let extension = extension.to_string_lossy();
+                    registry.for_extension(&extension).unwrap_or_else(|| {
+                        usage_error(&format!("no target for the extension {:?}", extension))
+                    })
+                }
+                (None, None) => find_target("bin"),
+            };
+            build_file(&config, &args.lints, &args.build, path, output, target)
+                .unwrap_or_else(|error| error.exit());
+        }
+    }
+}
+
+fn command_check(args: &CheckArgs, config: Config) {
+    // The start run at compile time by level 3 doesn't follow the source
+    let mut config = Config {
+        opt_level: config.opt_level.min(2),
+        ..config
+    };
+    let (name, source) = program_source(&args.program);
+    let fix_path = match (args.fix, &args.program.source) {
+        (false, _) => None,
+        (true, Some(path)) if args.program.code.is_none() && path != "-" => Some(path),
+        (true, _) => usage_error("--fix needs a source file"),
+    };
+    let mut ast =
+        load_program(&name, source, &mut config, false).unwrap_or_else(|error| error.exit());
+
+    if let (Some(path), Some(source)) = (fix_path, &config.source) {
+        let levels = lint_levels(&args.lints);
+        let lints: Vec<_> = lint(&ast, &config)
+            .into_iter()
+            .filter(|lint| levels[lint.name] != LintLevel::Allow)
+            .collect();
+        let (fixed, count) = fix_source(source, config.language, &lints);
+        if count > 0 {
+            if let Err(error) = fs::write(path, &fixed) {
+                fail(Failure::Io, &format!("can't write {}: {}", path, error));
+            }
+            eprintln!(
+                "{}: fixed {} lint{}",
+                name,
+                count,
+                if count > 1 { "s" } else { "" }
+            );
+            ast =
+                load_program(&name, fixed, &mut config, false).unwrap_or_else(|error| error.exit());
+        }
+    }
+    report_lints(&args.lints, &name, &ast, &config).unwrap_or_else(|error| error.exit());
+}
+
+/// Level of each lint, from the defaults and the lint options
+fn lint_levels(lints: &LintArgs) -> HashMap<&'static str, LintLevel> {
+    let mut levels: HashMap<_, _> = LINTS.iter().cloned().collect();
+    if lints.deny_warnings {
+        for level in levels.values_mut() {
+            if *level == LintLevel::Warn {
+                *level = LintLevel::Deny;
+            }
+        }
+    }
+    for (names, level) in [
+        (&lints.allow, LintLevel::Allow),
+        (&lints.warn, LintLevel::Warn),
+        (&lints.deny, LintLevel::Deny),
+    ] {
+        for name in names.iter().flat_map(|names| names.split(',')) {
+            match levels.get_mut(name.trim()) {
+                Some(entry) => *entry = level,
+                None => usage_error(&format!("unknown lint {:?}", name)),
+            }
+        }
+    }
+    levels
+}
+
+/// Report the lints of a program with their level, failing when some are
+/// denied
+fn report_lints(lints: &LintArgs, name: &str, ast: &Node, config: &Config) -> Result<(), CliError> {
+    let levels = lint_levels(lints);
+    let mut denied = 0;
+    for lint in lint(ast, config).iter() {
+        let severity = match levels[lint.name] {
+            LintLevel::Allow => continue,
+            LintLevel::Warn => &WARNING,
+            LintLevel::Deny => {
+                denied += 1;
+                &ERROR
+            }
+        };
+        match &config.source {
+            Some(source) => {
+                let location = Location::new(name, source, lint.span);
+                report_at(severity, Some(lint.name), &lint.message, Some(&location));
+            }
+            None => {
+                let message = format!("{}: {}", name, lint.message);
+                report_at(severity, Some(lint.name), &message, None);
+            }
+        }
+    }
+    match denied {
+        0 => Ok(()),
+        1 => Err(CliError::new(
+            Failure::Parse,
+            format!("{}: 1 denied lint", name),
+        )),
+        _ => Err(CliError::new(
+            Failure::Parse,
+            format!("{}: {} denied lints", name, denied),
+        )),
+    }
+}
+
+fn command_fmt(args: &FmtArgs, config: Config) {
+    let pretty = args.format.pretty();
+    let path = args.source.as_deref().unwrap_or("-");
+    let source = read_source(path).unwrap_or_else(|error| error.exit());
+    let ast = compile_program(source_name(path), &source, config.language, 2)
+        .unwrap_or_else(|error| error.exit());
+    let result = match args.output.as_deref() {
+        None | Some("-") => {
+            let stdout = io::stdout();
+            let mut lock = stdout.lock();
+            lock.write_all(shebang(&source).as_bytes())
+                .map_err(CodegenError::from)
+                .and_then(|_| pretty.emit(&ast, &config, &mut lock))
+        }
+        Some(path) => File::create(path)
+            .map_err(CodegenError::from)
+            .and_then(|mut file| {
+                file.write_all(shebang(&source).as_bytes())?;
+                pretty.emit(&ast, &config, &mut file)
+            }),
+    };
+    if let Err(error) = result {
+        fail(Failure::Io, &format!("can't write the output: {}", error));
+    }
+}
+
+fn command_repl(config: Config) {
+    let mut state = State::new(&config);
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) => {
+                println!();
+                return;
+            }
+            Ok(_) => {}
+            Err(error) => {
+                report(&ERROR, &error.to_string(), None);
+                continue;
+            }
+        }
+
+        if let Some(command) = line.trim().strip_prefix(':') {
+            repl_command(command, &mut state, &config);
+            continue;
+        }
+
+        // Errors are reported without leaving the REPL, and keep the tape,
+        // which the lines run on instead of a blank one, as level 3 assumes
+        let compiler = Compiler::new().config(config.clone());
+        let ast = match compiler.opt_level(config.opt_level.min(2)).compile(&line) {
+            Ok(program) => program.ast,
+            Err(error) => {
+                let (message, span) = parse_error_location(error, &line, config.language);
+                report(
+                    &ERROR,
+                    &format!("{} at column {}", message, span.start + 1),
+                    Some(&Snippet::new(&line, span)),
+                );
+                continue;
+            }
+        };
+        match run_ast(&ast, &mut state, &config) {
+            Err(error) => match runtime_location(&error) {
+                Some((span, message)) => report(
+                    &ERROR,
+                    &format!("{} at column {}", message, span.start + 1),
+                    Some(&Snippet::new(&line, span)),
+                ),
+                None => report(&ERROR, &error.to_string(), None),
+            },
+            Ok(()) => println!(),
+        }
+    }
+}
+
+/// Meta-commands of the REPL, given after a colon
+fn repl_command(command: &str, state: &mut State, config: &Config) {
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some("tape"), None) => {
+            let cells = 0..state.highest.max(state.index) + 1;
+            dump_memory(state, config, cells, &mut io::stdout()).unwrap();
+        }
+        (Some("ptr"), None) => println!("{}", state.index),
+        (Some("reset"), None) => state.reset(),
+        (Some("load"), Some(path)) => {
+            // The program runs on the current tape
+            let result = read_source(path).and_then(|source| {
+                let level = config.opt_level.min(2);
+                let ast = compile_program(path, &source, config.language, level)?;
+                run_ast(&ast, state, config)
+                    .map_err(|error| runtime_error(error, path, Some(&source)))
+            });
+            match result {
+                Ok(()) => println!(),
+                Err(error) => error.report(),
+            }
+        }
+        _ => eprintln!("commands: :tape, :ptr, :reset, :load <file>"),
+    }
+}
+
+/// The cells given to --watch-cell
+fn watched_cells(list: Option<&str>, config: &Config) -> Vec<usize> {
+    let list = match list {
+        Some(list) => list,
+        None => return Vec::new(),
+    };
+    list.split(',')
+        .map(|cell| match cell.trim().parse() {
+            Ok(cell) if cell < config.tape_size => cell,
+            _ => usage_error(&format!("invalid value {:?} for --watch-cell", list)),
+        })
+        .collect()
+}
+
+/// An access of a watched cell, with the command doing it, located in the
+/// source when it is known
+fn describe_access(
+    access: Access,
+    index: usize,
+    value: u32,
+    span: Span,
+    name: &str,
+    source: Option<&str>,
+) -> String {
+    let verb = match access {
+        Access::Read => "read",
+        Access::Write => "written",
+    };
+    let command = match source {
+        Some(source) => {
+            let (line, column) = SourceMap::new(name, source).location(span.start);
+            let code: String = source[span.start..span.end].chars().take(20).collect();
+            format!("by {:?} at {}:{}:{}", code, name, line, column)
+        }
+        None => format!("at byte {} of the source", span.start),
+    };
+    format!("cell {} {} {}, value {}", index, verb, command, value)
+}
+
+/// The cells watched by the line debugger, shared with the access hook of
+/// the VM
+struct Watch {
+    cells: Vec<usize>,
+    hit: bool, // A watched cell was accessed since the last node
+}
+
+/// Where the line debugger stops
+struct Stops {
+    labels: Vec<(String, usize)>,
+    breakpoints: Vec<usize>, // Offsets of the commands to stop at
+    watch: Rc<RefCell<Watch>>,
+    stepping: bool, // Stop at each node
+}
+
+/// Offset of the command of a breakpoint, given as a byte offset of the
+/// source or as the name of one of its labels
+fn breakpoint(text: &str, labels: &[(String, usize)]) -> Result<usize, String> {
+    if let Ok(offset) = text.parse() {
+        return Ok(offset);
+    }
+    match labels.iter().find(|(name, _)| name == text) {
+        Some((_, offset)) => Ok(*offset),
+        None => Err(format!("no label {:?} in the source", text)),
+    }
+}
+
+fn debug_prompt(node: &Node, memory: &[u32], index: usize, source: &str, stops: &mut Stops) {
+    let hit = std::mem::take(&mut stops.watch.borrow_mut().hit);
+    if !stops.stepping && !hit && !stops.breakpoints.contains(&node.span.start) {
+        return;
+    }
+
+    io::stdout().flush().unwrap();
+    let (line, column) = SourceMap::new("", source).location(node.span.start);
+    let code: String = source[node.span.start..node.span.end]
+        .chars()
+        .take(20)
+        .collect();
+    eprintln!(
+        "{}:{} {:?}  pointer {}, cell {}",
+        line, column, code, index, memory[index]
+    );
+    loop {
+        eprint!("(debug) ");
+        let mut command = String::new();
+        match io::stdin().read_line(&mut command) {
+            Ok(0) => process::exit(0),
+            Ok(_) => {}
+            Err(error) => {
+                report(&ERROR, &error.to_string(), None);
+                continue;
+            }
+        }
+        let mut words = command.split_whitespace();
+        match words.next() {
+            None | Some("s") | Some("step") => {
+                stops.stepping = true;
+                return;
+            }
+            Some("c") | Some("continue") => {
+                stops.stepping = false;
+                return;
+            }
+            Some("b") | Some("break") => match words.next().map(|b| breakpoint(b, &stops.labels)) {
+                Some(Ok(offset)) => stops.breakpoints.push(offset),
+                Some(Err(message)) => eprintln!("{}", message),
+                None => eprintln!("usage: break <byte offset in the source, or label>"),
+            },
+            Some("w") | Some("watch") => match words.next().map(str::parse) {
+                Some(Ok(cell)) if cell < memory.len() => stops.watch.borrow_mut().cells.push(cell),
+                _ => eprintln!("usage: watch <index of a cell>"),
+            },
+            Some("t") | Some("tape") => {
+                let start = index.saturating_sub(8);
+                let end = (index + 9).min(memory.len());
+                for (i, cell) in memory[start..end].iter().enumerate() {
+                    let marker = if start + i == index { '*' } else { ' ' };
+                    eprint!("{}{} ", marker, cell);
+                }
+                eprintln!();
+            }
+            Some("q") | Some("quit") => process::exit(0),
+            Some(_) => {
+                eprintln!(
+                    "commands: step (or nothing), continue, break <offset or label>, watch <cell>, tape, quit"
+                )
+            }
+        }
+    }
+}
+
+/// Steps run by the debuggers between two looks at their input, when the
+/// program runs
+#[cfg(any(feature = "server", feature = "tui"))]
+const DEBUG_RUN_STEPS: usize = 10_000;
+
+/// A program run a step at a time by the debuggers, on a fixed input, its
+/// output kept
+#[cfg(any(feature = "server", feature = "tui"))]
+struct Debuggee {
+    name: String,
+    lines: SourceMap,
+    config: Config,
+    stepper: Stepper,
+    state: State,
+    output: SharedBuffer,
+    breakpoints: Vec<usize>, // Offsets of the commands to stop at
+    steps: u64,
+    ended: bool, // The program finished, or failed
+}
+
+#[cfg(any(feature = "server", feature = "tui"))]
+impl Debuggee {
+    /// A program at its start, reading nothing on the standard input, which
+    /// the debugger reads
+    fn new(name: String, source: &str, ast: &Node, config: Config, input: Input) -> Self {
+        let input = match input {
+            Input::Stdin => Input::Bytes(Vec::new()),
+            input => input,
+        };
+        let mut state = State::new(&config);
+        state.input = Box::new(input.reader(prompt()).unwrap_or_else(|error| {
+            fail(Failure::Io, &format!("can't read the input: {}", error))
+        }));
+        let output = SharedBuffer::default();
+        state.output = Box::new(output.clone());
+        let stepper = Stepper::new(ast);
+        Debuggee {
+            lines: SourceMap::new(&name, source),
+            name,
+            config,
+            ended: stepper.finished(),
+            stepper,
+            state,
+            output,
+            breakpoints: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    /// Run the next step, returning whether the program goes on, or the
+    /// error stopping it, located in the source
+    fn step(&mut self) -> Result<bool, String> {
+        if self.ended {
+            return Ok(false);
+        }
+        self.steps += 1;
+        let result = self.stepper.step(&mut self.state, &self.config);
+        if !matches!(result, Ok(true)) {
+            self.ended = true;
+            let _ = self.state.output.flush_bytes();
+        }
+        result.map_err(|error| match runtime_location(&error) {
+            Some((span, message)) => {
+                let (line, column) = self.lines.location(span.start);
+                format!("{}:{}:{}: {}", self.name, line, column, message)
+            }
+            None => error.to_string(),
+        })
+    }
+
+    /// Whether the next command has a breakpoint
+    fn at_breakpoint(&self) -> bool {
+        let offset = self.stepper.next_offset();
+        offset.is_some_and(|offset| self.breakpoints.contains(&offset))
+    }
+}
+
+fn command_debug(args: &DebugArgs, config: Config) {
+    if args.program.code.is_none() && args.program.source.as_deref().unwrap_or("-") == "-" {
+        usage_error("the debugger commands are read on the standard input");
+    }
+    let (name, source) = program_source(&args.program);
+
+    // Step through each command, without the optimizations merging them
+    let ast =
+        compile_program(&name, &source, config.language, 0).unwrap_or_else(|error| error.exit());
+    let input = args.input.input();
+    let labels = parse_labels(&source, config.language);
+    let breakpoints: Vec<usize> = match &args.breakpoints {
+        Some(list) => list
+            .split(',')
+            .map(|text| breakpoint(text.trim(), &labels))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|message| usage_error(&message)),
+        None => Vec::new(),
+    };
+    let watched = watched_cells(args.watch_cell.as_deref(), &config);
+    #[cfg(feature = "tui")]
+    if args.tui {
+        if !watched.is_empty() {
+            usage_error("the cells can't be watched with --tui");
+        }
+        return debug_tui(name, source, &ast, config, input, breakpoints);
+    }
+    let mut state = State::new(&config);
+    if !matches!(input, Input::Stdin) {
+        state.input = Box::new(input.reader(prompt()).unwrap_or_else(|error| {
+            fail(Failure::Io, &format!("can't read the input: {}", error))
+        }));
+    }
+    // With breakpoints, the program runs up to the first one
+    let mut stops = Stops {
+        labels,
+        stepping: breakpoints.is_empty(),
+        breakpoints,
+        watch: Rc::new(RefCell::new(Watch {
+            cells: watched,
+            hit: false,
+        })),
+    };
+    let watch = Rc::clone(&stops.watch);
+    let (watch_name, watch_source) = (name.clone(), source.clone());
+    state.access = Some(Box::new(move |access, index, value: &u32, span| {
+        let mut watch = watch.borrow_mut();
+        if watch.cells.contains(&index) {
+            let access = describe_access(
+                access,
+                index,
+                *value,
+                span,
+                &watch_name,
+                Some(&watch_source),
+            );
+            eprintln!("watch: {}", access);
+            watch.hit = true;
+        }
+    }));
+    let hook_source = source.clone();
+    state.hook = Some(Box::new(
+        move |node: &Node, memory: &[u32], index: usize| {
+            debug_prompt(node, memory, index, &hook_source, &mut stops)
+        },
+    ));
+    let result = run_ast(&ast, &mut state, &config).and_then(|_| Ok(state.output.flush_bytes()?));
+    if let Err(error) = result {
+        runtime_error(error, &name, Some(&source)).exit();
+    }
+}
+
+/// A JSON object of some members
+fn json_object(members: Vec<(&str, Json)>) -> Json {
+    let members = members.into_iter();
+    Json::Object(
+        members
+            .map(|(name, value)| (String::from(name), value))
+            .collect(),
+    )
+}
+
+/// A JSON string
+fn json_string(text: &str) -> Json {
+    Json::String(String::from(text))
+}
+
+/// Read the messages of the Debug Adapter and Language Server Protocols on
+/// the standard input, each after a Content-Length header, on a thread
+#[cfg(feature = "server")]
+fn spawn_message_reader() -> mpsc::Receiver<Json> {
+    let (messages, received) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        loop {
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                if stdin.read_line(&mut header).unwrap_or(0) == 0 {
+                    return;
+                }
+                match header.trim_end().split_once(':') {
+                    Some((name, value)) if name.eq_ignore_ascii_case("content-length") => {
+                        length = value.trim().parse().unwrap_or(0)
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            let mut body = vec![0; length];
+            if stdin.read_exact(&mut body).is_err() {
+                return;
+            }
+            // The messages which aren't JSON are skipped
+            let message = str::from_utf8(&body)
+                .ok()
+                .and_then(|text| parse_json(text).ok());
+            if let Some(message) = message {
+                if messages.send(message).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    received
+}
+
+/// Write a message of the Debug Adapter or Language Server Protocol on the
+/// standard output, after its Content-Length header
+#[cfg(feature = "server")]
+fn send_message(message: Json) {
+    let text = message.to_string();
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "Content-Length: {}\r\n\r\n{}", text.len(), text)
+        .and_then(|_| stdout.flush());
+}
+
+/// Run a program in the interpreter, on the standard input and output
+fn run_program(name: &str, source: String, config: Config) -> Result<(), CliError> {
+    let mut config = config;
+    let ast = load_program(name, source, &mut config, false)?;
+    let mut state = State::new(&config);
+    run_ast(&ast, &mut state, &config)
+        .and_then(|_| Ok(state.output.flush_bytes()?))
+        .map_err(|error| runtime_error(error, name, config.source.as_deref()))
+}
+
+/// Run a source in the interpreter, on the standard input and output
+fn run_file(path: &str, config: Config) -> Result<(), CliError> {
+    run_program(path, read_source(path)?, config)
+}
+
+/// Run programs concurrently, each reading the output of the previous one
+fn command_pipe(args: &PipeArgs, config: Config) {
+    let programs: Vec<_> = args
+        .sources
+        .iter()
+        .map(|path| {
+            let mut config = config.clone();
+            read_source(path)
+                .and_then(|source| load_program(path, source, &mut config, false))
+                .map(|ast| (path.clone(), ast, config))
+                .unwrap_or_else(|error| error.exit())
+        })
+        .collect();
+
+    // Each program runs on its own thread, until the end of its input or
+    // until the next one stops reading
+    let count = programs.len();
+    let mut input: Box<dyn Read + Send> = Box::new(io::stdin());
+    let mut stages = Vec::new();
+    for (i, (path, ast, config)) in programs.into_iter().enumerate() {
+        let stage_input = std::mem::replace(&mut input, Box::new(io::empty()));
+        let output: Box<dyn Write + Send> = if i + 1 < count {
+            let (reader, writer) = io::pipe().unwrap_or_else(|error| {
+                fail(Failure::Io, &format!("can't create a pipe: {}", error))
+            });
+            input = Box::new(reader);
+            Box::new(writer)
+        } else {
+            Box::new(io::stdout())
+        };
+        stages.push(thread::spawn(move || {
+            let mut state = State::new(&config);
+            state.input = Box::new(stage_input);
+            state.output = Box::new(output);
+            match run_ast(&ast, &mut state, &config).and_then(|_| Ok(state.output.flush_bytes()?)) {
+                Err(RuntimeError::Io(error)) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                result => {
+                    result.map_err(|error| runtime_error(error, &path, config.source.as_deref()))
+                }
+            }
+        }));
+    }
+
+    for stage in stages {
+        stage.join().unwrap().unwrap_or_else(|error| error.exit());
+    }
+}
+
+/// Programs shipped with the compiler, by name
+const EXAMPLES: &[(&str, &str, &str)] = &[
+    (
+        "hello",
+        "print Hello World!",
+        include_str!("../../data/hello_world.bf"),
+    ),
+    (
+        "cat",
+        "copy the input to the output",
+        include_str!("../../data/cat.bf"),
+    ),
+    (
+        "rot13",
+        "ROT13 of the input",
+        include_str!("../../data/rot13.bf"),
+    ),
+    (
+        "mandelbrot",
+        "draw the Mandelbrot set",
+        include_str!("../../data/mandelbrot.bf"),
+    ),
+    (
+        "sierpinski",
+        "draw a Sierpinski triangle",
+        include_str!("../../data/sierpinski.bf"),
+    ),
+];
+
+fn command_examples(args: &ExamplesArgs, config: Config) {
+    let find_example = |name: Option<&str>| match name {
+        Some(name) => EXAMPLES
+            .iter()
+            .find(|(example, _, _)| *example == name)
+            .unwrap_or_else(|| usage_error(&format!("no example named {:?}", name))),
+        None => usage_error("missing the name of the example"),
+    };
+
+    match args.action.as_deref() {
+        None | Some("list") => {
+            for (name, about, _) in EXAMPLES.iter() {
+                println!("    {:<24}{}", name, about);
+            }
+        }
+        Some("run") => {
+            let (name, _, source) = find_example(args.name.as_deref());
+            run_program(name, String::from(*source), config).unwrap_or_else(|error| error.exit());
+        }
+        Some("show") => print!("{}", find_example(args.name.as_deref()).2),
+        Some("export") => {
+            let directory = Path::new(args.name.as_deref().unwrap_or("."));
+            for (name, _, source) in EXAMPLES.iter() {
+                let path = directory.join(format!("{}.bf", name));
+                let result = fs::create_dir_all(directory).and_then(|_| fs::write(&path, source));
+                if let Err(error) = result {
+                    fail(
+                        Failure::Io,
+                        &format!("can't write {}: {}", path.display(), error),
+                    );
+                }
+                println!("{}", path.display());
+            }
+        }
+        Some(action) => usage_error(&format!("unknown action {:?}", action)),
+    }
+}
+
+fn command_watch(args: &WatchArgs) {
+    let (path, output) = (args.source.as_str(), args.output.as_deref());
+    let config = args.config();
+    let registry = Registry::default();
+    let target = match (&args.target, output) {
+        (Some(name), _) => Some(
+            registry
+                .get(name)
+                .unwrap_or_else(|| usage_error(&format!("unsupported target {:?}", name))),
+        ),
+        (None, Some(output)) => {
+            let extension = Path::new(output).extension().unwrap_or_default();
+            let extension = extension.to_string_lossy();
+            Some(match extension.as_ref() {
+                "" => registry.get("bin").unwrap(),
+                extension => registry.for_extension(extension).unwrap_or_else(|| {
+                    usage_error(&format!("no target for the extension {:?}", extension))
+                }),
+            })
+        }
+        (None, None) => None,
+    };
+
+    // The directory of the source is watched, for the editors replacing the
+    // file on each save
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .unwrap_or_else(|error| fail(Failure::Io, &format!("can't watch {}: {}", path, error)));
+    let file = Path::new(path);
+    let dir = match file.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    if let Err(error) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+        fail(Failure::Io, &format!("can't watch {}: {}", path, error));
+    }
+    let name = file.file_name();
+    loop {
+        eprintln!("--- {}", path);
+        let (lints, options) = (LintArgs::default(), BuildOptions::default());
+        let result = match (output, target) {
+            (Some(output), Some(target)) => {
+                build_file(&config, &lints, &options, path, Path::new(output), target)
+            }
+            (None, Some(target)) => build_program(&config, &lints, &options, path, target)
+                .and_then(|code| {
+                    let mut stdout = io::stdout();
+                    stdout
+                        .write_all(&code)
+                        .and_then(|_| stdout.flush())
+                        .map_err(|error| {
+                            CliError::new(Failure::Io, format!("can't write the output: {}", error))
+                        })
+                }),
+            _ => run_file(path, config.clone()),
+        };
+        match result {
+            Ok(()) => eprintln!("--- done"),
+            Err(error) => error.report(),
+        }
+
+        // Wait for a change of the source, then for the end of the events of
+        // its save
+        let changed = |event: &notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                !event.kind.is_access() && event.paths.iter().any(|path| path.file_name() == name)
+            }
+            Err(_) => false,
+        };
+        loop {
+            match events.recv() {
+                Ok(event) if changed(&event) => break,
+                Ok(_) => {}
+                Err(_) => fail(Failure::Io, &format!("can't watch {}", path)),
+            }
+        }
+        while events.recv_timeout(Duration::from_millis(100)).is_ok() {}
+    }
+}
+
+/// Whether the second argument given without a command is the output of the
+/// former `brainfuck <source> <output>`, rather than a source run by --link
+/// or an option of run
+fn is_legacy_output(arg: &str) -> bool {
+    !arg.starts_with('-') && !arg.ends_with(".bf")
+}
+
+/// Run the command line on the arguments of the process, exiting with the
+/// code of its failure, if any
+pub fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    let command = Cli::command();
+    match args.get(1).map(String::as_str) {
+        None => {
+            command.clone().print_help().unwrap();
+
+            return;
+        }
+        Some("-h") | Some("--help") => {}
+        // The former command line took a source and an output to build
+        Some(name)
+            if args.len() == 3 && Path::new(name).is_file() && is_legacy_output(&args[2]) =>
+        {
+            let message = format!(
+                "'brainfuck <source> <output>' is deprecated, use 'brainfuck build {} {}'",
+                name, args[2]
+            );
+            report(&WARNING, &message, None);
+            args.insert(1, String::from("build"))
+        }
+        // A source, maybe after the options of a "#!" line, is run
+        Some(name) if name.starts_with('-') || Path::new(name).is_file() => {
+            args.insert(1, String::from("run"))
+        }
+        Some(_) => {}
+    }
+    apply_project_config(&command, &mut args);
+    let matches = command.get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    if let Some(name) = matches.subcommand_name() {
+        let _ = SUBCOMMAND.set(String::from(name));
+    }
+    if let Some(format) = cli.message_format {
+        format.apply();
+    }
+
+    match &cli.command {
+        Commands::Run(args) => command_run(args, args.config()),
+        Commands::Compare(args) => command_compare(args, args.config()),
+        Commands::Outline(args) => command_outline(args, args.config()),
+        Commands::Symbolic(args) => command_symbolic(args, args.config()),
+        Commands::Inspect(args) => command_inspect(args),
+        Commands::Replay(args) => command_replay(args, args.config()),
+        Commands::TraceDiff(args) => command_trace_diff(args),
+        Commands::Build(args) => command_build(args),
+        Commands::Check(args) => command_check(args, args.config()),
+        Commands::Fmt(args) => command_fmt(args, args.config()),
+        Commands::Repl(args) => command_repl(args.config()),
+        Commands::Debug(args) => command_debug(args, args.config()),
+        #[cfg(feature = "server")]
+        Commands::Dap(args) => command_dap(args.config()),
+        #[cfg(feature = "server")]
+        Commands::Lsp(args) => command_lsp(args, args.config()),
+        #[cfg(feature = "server")]
+        Commands::JupyterKernel(args) => command_jupyter_kernel(args, args.config()),
+        #[cfg(feature = "server")]
+        Commands::Serve(args) => command_serve(args, args.config()),
+        Commands::Pipe(args) => command_pipe(args, args.config()),
+        Commands::Examples(args) => command_examples(args, args.config()),
+        Commands::Selftest => command_selftest(),
+        Commands::Watch(args) => command_watch(args),
+    }
+}
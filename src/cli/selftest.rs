@@ -0,0 +1,252 @@
+//! The conformance corpus of selftest, run by the interpreter and by each
+//! target running on this system
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::process::Stdio;
+
+use crate::codegen::{runner, runs_here, CodegenError, Registry};
+use crate::vm::{Cell, Eof, Interpreter, SharedBuffer};
+use crate::{Compiler, Config};
+
+use super::{fail, Failure};
+
+/// A program of the conformance corpus of selftest, with its expected output
+struct SelfTest {
+    name: &'static str,
+    source: &'static str,
+    input: &'static [u8],
+    output: &'static [u8],
+    eof: Eof,
+    cell: Cell,
+}
+
+impl SelfTest {
+    const fn new(name: &'static str, source: &'static str, output: &'static [u8]) -> Self {
+        SelfTest {
+            name,
+            source,
+            input: b"",
+            output,
+            eof: Eof::Unchanged,
+            cell: Cell::U8,
+        }
+    }
+}
+
+/// Print ! when the first cell holds 256, which it doesn't with 8-bit cells
+const CELL_256: &str = concat!(
+    "++++++++[>++++++++<-]>[<++++>-]<",
+    "[>+++++++++++++++++++++++++++++++++.<[-]]",
+);
+
+const SELFTESTS: &[SelfTest] = &[
+    SelfTest::new(
+        "hello",
+        include_str!("../../data/hello_world.bf"),
+        b"Hello World!\n",
+    ),
+    SelfTest {
+        input: b"Hello, World!",
+        ..SelfTest::new(
+            "rot13",
+            include_str!("../../data/rot13.bf"),
+            b"Uryyb, Jbeyq!",
+        )
+    },
+    SelfTest {
+        input: b"cat\n",
+        eof: Eof::Zero,
+        ..SelfTest::new("cat", ",[.,]", b"cat\n")
+    },
+    SelfTest::new("eof-unchanged", "+++,.", &[3]),
+    SelfTest {
+        eof: Eof::Zero,
+        ..SelfTest::new("eof-zero", "+++,.", &[0])
+    },
+    SelfTest {
+        eof: Eof::MinusOne,
+        ..SelfTest::new("eof-minus-one", "+++,.", &[255])
+    },
+    SelfTest::new("wrap-down", "-.", &[255]),
+    SelfTest::new("wrap-up", "-[+>+<]>.", &[1]),
+    SelfTest::new("narrow-cells", CELL_256, b""),
+    SelfTest {
+        cell: Cell::U16,
+        ..SelfTest::new("wide-cells", CELL_256, b"!")
+    },
+    SelfTest::new(
+        "multiply",
+        ">+++++[<++++++++++>>+++++++++++++<-]<.>>.",
+        b"2A",
+    ),
+    SelfTest::new("clear", "+++++[-].+++[[-]>+<].>.", &[0, 0, 1]),
+    SelfTest::new(
+        "deep-nesting",
+        concat!(
+            "+[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[-",
+            "]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]]",
+            "+++++++++++++++++++++++++++++++++.",
+        ),
+        b"!",
+    ),
+];
+
+/// Run a program of the corpus in the interpreter, into its output
+fn selftest_interpret(test: &SelfTest, config: &Config) -> Result<Vec<u8>, String> {
+    let program = Compiler::new()
+        .config(config.clone())
+        .compile(test.source)
+        .map_err(|error| error.to_string())?;
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .config(config.clone())
+        .input(test.input)
+        .output(output.clone())
+        .build();
+    match program.run(&mut interpreter) {
+        Ok(()) => Ok(output.contents()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Build a program of the corpus for a target and run it, into its output,
+/// or None when the target doesn't support the cells or the end of input of
+/// the program
+fn selftest_target(
+    test: &SelfTest,
+    config: &Config,
+    target: &str,
+    dir: &Path,
+) -> io::Result<Option<Result<Vec<u8>, String>>> {
+    let program = match Compiler::new().config(config.clone()).compile(test.source) {
+        Ok(program) => program,
+        Err(error) => return Ok(Some(Err(error.to_string()))),
+    };
+    let mut runner = match runner(&program.ast, &program.config, target, dir) {
+        Ok(runner) => runner,
+        Err(CodegenError::Unsupported(_)) => return Ok(None),
+        Err(error) => return Ok(Some(Err(error.to_string()))),
+    };
+    for command in runner.build.iter_mut() {
+        let status = command
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        if !status.success() {
+            return Ok(Some(Err(String::from("the program doesn't build"))));
+        }
+    }
+
+    let mut child = runner
+        .run
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    match child.stdin.take().unwrap().write_all(test.input) {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(error),
+        _ => {}
+    }
+    let run = child.wait_with_output()?;
+    Ok(Some(match run.status.success() {
+        true => Ok(run.stdout),
+        false => Err(format!("the program failed with {}", run.status)),
+    }))
+}
+
+/// Run the corpus with a backend, into the description of its failures and
+/// the count of the programs it doesn't support; an error means that the
+/// backend can't run at all, as without a compiler
+fn selftest_backend(backend: &str, dir: &Path) -> io::Result<(Vec<String>, usize)> {
+    let mut failures = Vec::new();
+    let mut unsupported = 0;
+    for test in SELFTESTS.iter() {
+        let config = Config {
+            eof: test.eof,
+            cell: test.cell,
+            opt_level: if backend == "interpreter -O0" { 0 } else { 2 },
+            ..Config::default()
+        };
+        let result = match backend {
+            "interpreter -O0" | "interpreter -O2" => selftest_interpret(test, &config),
+            target => match selftest_target(test, &config, target, dir)? {
+                Some(result) => result,
+                None => {
+                    unsupported += 1;
+                    continue;
+                }
+            },
+        };
+        match result {
+            Ok(output) if output == test.output => {}
+            Ok(output) => failures.push(format!(
+                "{}: expected {:?}, got {:?}",
+                test.name,
+                String::from_utf8_lossy(test.output),
+                String::from_utf8_lossy(&output)
+            )),
+            Err(error) => failures.push(format!("{}: {}", test.name, error)),
+        }
+    }
+    Ok((failures, unsupported))
+}
+
+pub(super) fn command_selftest() {
+    let dir = env::temp_dir().join(format!("brainfuck-selftest-{}", process::id()));
+    if let Err(error) = fs::create_dir_all(&dir) {
+        fail(
+            Failure::Io,
+            &format!("can't create {}: {}", dir.display(), error),
+        );
+    }
+
+    // The interpreter, then each target running on this system
+    let registry = Registry::default();
+    let targets = registry
+        .targets()
+        .map(|target| target.name.as_str())
+        .filter(|target| runs_here(target));
+    let mut failed = 0;
+    for backend in ["interpreter -O0", "interpreter -O2"]
+        .iter()
+        .copied()
+        .chain(targets)
+    {
+        let (failures, unsupported) = match selftest_backend(backend, &dir) {
+            Ok(results) => results,
+            Err(error) => {
+                println!("{:<16} skipped, can't run: {}", backend, error);
+                continue;
+            }
+        };
+        let passed = SELFTESTS.len() - unsupported - failures.len();
+        let unsupported = match unsupported {
+            0 => String::new(),
+            count => format!(", {} unsupported", count),
+        };
+        if failures.is_empty() {
+            println!("{:<16} ok, {} passed{}", backend, passed, unsupported);
+            continue;
+        }
+        println!(
+            "{:<16} FAILED, {} passed, {} failed{}",
+            backend,
+            passed,
+            failures.len(),
+            unsupported
+        );
+        for failure in failures.iter() {
+            println!("    {}", failure);
+        }
+        failed += failures.len();
+    }
+    let _ = fs::remove_dir_all(&dir);
+
+    if failed > 0 {
+        fail(Failure::Runtime, &format!("{} tests failed", failed));
+    }
+}
@@ -0,0 +1,371 @@
+//! The serve command, running a program for each client of a WebSocket
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Write;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use sha1::{Digest, Sha1};
+
+use crate::ast::Node;
+use crate::vm::{run_ast, RuntimeError, State};
+use crate::Config;
+
+use super::args::ServeArgs;
+use super::{fail, load_program, program_source, runtime_error, Failure};
+
+/// Opcodes of the frames of the WebSockets
+const WEBSOCKET_TEXT: u8 = 1;
+const WEBSOCKET_BINARY: u8 = 2;
+const WEBSOCKET_CLOSE: u8 = 8;
+const WEBSOCKET_PING: u8 = 9;
+const WEBSOCKET_PONG: u8 = 10;
+
+/// Status of the close frames answering the frames of a client breaking the
+/// protocol, or too big
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+/// Bytes of the largest frame read from a client, well over the lines typed
+/// for the input of a program
+const MAX_WEBSOCKET_FRAME: u64 = 1 << 20;
+
+/// Bytes of each line of a handshake at most, and headers in a handshake
+const MAX_HANDSHAKE_LINE: u64 = 8192;
+const MAX_HANDSHAKE_HEADERS: usize = 100;
+
+/// Messages of a client waiting for the program to read them, past which
+/// the frames of the client wait too
+const MAX_PENDING_MESSAGES: usize = 64;
+
+/// Steps of the program run for each client, unless --max-steps is given
+const SERVE_MAX_STEPS: u64 = 1_000_000_000;
+
+/// Answer an HTTP request with an error, closing the connection
+fn refuse_request(
+    mut stream: &TcpStream,
+    status: &str,
+    headers: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n{}Content-Type: text/plain\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        headers,
+        body.len(),
+        body
+    )
+}
+
+/// Answer the HTTP request opening a WebSocket, returning false when it is
+/// another request, which is refused
+fn websocket_handshake(stream: &mut TcpStream) -> io::Result<bool> {
+    let mut reader = io::BufReader::new(&*stream);
+    let mut key = None;
+    let mut upgrade = false;
+    let mut line = String::new();
+    // The lines, and the headers, are read up to a limit, for the clients not
+    // to fill the memory
+    let mut read_line = |line: &mut String| {
+        line.clear();
+        Read::by_ref(&mut reader)
+            .take(MAX_HANDSHAKE_LINE)
+            .read_line(line)
+    };
+    if read_line(&mut line)? == 0 {
+        return Ok(false);
+    }
+    let get = line.starts_with("GET ");
+    let mut too_large = !line.ends_with('\n');
+    let mut headers = 0;
+    while !too_large {
+        if read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        headers += 1;
+        too_large = !line.ends_with('\n') || headers > MAX_HANDSHAKE_HEADERS;
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+                "sec-websocket-key" => key = Some(String::from(value)),
+                _ => {}
+            }
+        }
+    }
+    if too_large {
+        let body = "the request is too large\n";
+        refuse_request(stream, "431 Request Header Fields Too Large", "", body)?;
+        return Ok(false);
+    }
+    // The client waits for the answer before sending frames, so nothing is
+    // left in the buffer of the reader
+    let key = match (get && upgrade, key) {
+        (true, Some(key)) => key,
+        _ => {
+            let body = "a WebSocket running the program is served here\n";
+            let headers = "Upgrade: websocket\r\n";
+            refuse_request(stream, "426 Upgrade Required", headers, body)?;
+            return Ok(false);
+        }
+    };
+    let accept = BASE64.encode(Sha1::digest(
+        format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key).as_bytes(),
+    ));
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+         Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )?;
+    Ok(true)
+}
+
+/// Write a frame of a WebSocket, from the server and so unmasked
+fn write_websocket_frame(mut stream: &TcpStream, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        0..=125 => frame.push(payload.len() as u8),
+        length @ 126..=65535 => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+/// Read a frame of a WebSocket, as its opcode and its unmasked payload, or
+/// as the status of the close answering a frame breaking the protocol
+///
+/// The fragments of a message are given as they come, which is enough for
+/// the bytes of the input. The frames of the clients are masked, and hold
+/// up to `MAX_WEBSOCKET_FRAME` bytes.
+fn read_websocket_frame(stream: &mut TcpStream) -> io::Result<Result<(u8, Vec<u8>), u16>> {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head)?;
+    let length = match head[1] & 0x7f {
+        126 => {
+            let mut length = [0; 2];
+            stream.read_exact(&mut length)?;
+            u16::from_be_bytes(length) as u64
+        }
+        127 => {
+            let mut length = [0; 8];
+            stream.read_exact(&mut length)?;
+            u64::from_be_bytes(length)
+        }
+        length => length as u64,
+    };
+    if head[1] & 0x80 == 0 {
+        return Ok(Err(CLOSE_PROTOCOL_ERROR));
+    }
+    if length > MAX_WEBSOCKET_FRAME {
+        return Ok(Err(CLOSE_TOO_BIG));
+    }
+    let mut mask = [0; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = Vec::new();
+    Read::by_ref(stream)
+        .take(length)
+        .read_to_end(&mut payload)?;
+    if payload.len() as u64 != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok(Ok((head[0] & 0x0f, payload)))
+}
+
+/// The output of a program served on a WebSocket, sent a line at a time,
+/// and on the flushes of the VM before each read
+struct WebSocketOutput {
+    stream: Arc<TcpStream>,
+    buffer: Vec<u8>,
+}
+
+impl Write for WebSocketOutput {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(bytes);
+        if bytes.contains(&b'\n') {
+            self.flush()?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let buffer = std::mem::take(&mut self.buffer);
+        match buffer.is_empty() {
+            true => Ok(()),
+            false => write_websocket_frame(&self.stream, WEBSOCKET_BINARY, &buffer),
+        }
+    }
+}
+
+/// The input of a program served on a WebSocket, the bytes of the messages
+/// of the client
+struct WebSocketInput {
+    messages: Receiver<Vec<u8>>,
+    pending: VecDeque<u8>,
+}
+
+impl Read for WebSocketInput {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            // The input ends when the client closes the connection
+            match self.messages.recv() {
+                Ok(message) => self.pending.extend(message),
+                Err(_) => return Ok(0),
+            }
+        }
+        let count = buffer.len().min(self.pending.len());
+        for (byte, read) in buffer.iter_mut().zip(self.pending.drain(..count)) {
+            *byte = read;
+        }
+        Ok(count)
+    }
+}
+
+/// Read the frames of a client, giving its messages to the program, until
+/// it closes the connection, or breaks the protocol
+fn read_client_frames(
+    stream: &TcpStream,
+    messages: SyncSender<Vec<u8>>,
+    closing: &AtomicBool,
+) -> io::Result<()> {
+    let mut reading = stream.try_clone()?;
+    loop {
+        let status = match read_websocket_frame(&mut reading)? {
+            Ok((WEBSOCKET_TEXT | WEBSOCKET_BINARY | 0, payload)) => {
+                // The program may have ended, and stopped reading
+                let _ = messages.send(payload);
+                continue;
+            }
+            Ok((WEBSOCKET_PING, payload)) => {
+                write_websocket_frame(stream, WEBSOCKET_PONG, &payload)?;
+                continue;
+            }
+            Ok((WEBSOCKET_CLOSE, payload)) => payload,
+            Ok(_) => continue,
+            Err(status) => status.to_be_bytes().to_vec(),
+        };
+        if !closing.swap(true, Ordering::Relaxed) {
+            write_websocket_frame(stream, WEBSOCKET_CLOSE, &status)?;
+        }
+        return stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Run a program for a client of a WebSocket, until its end or until the
+/// client leaves
+fn serve_websocket(mut stream: TcpStream, ast: &Node, config: &Config, name: &str, source: &str) {
+    match websocket_handshake(&mut stream) {
+        Ok(true) => {}
+        _ => return,
+    }
+    let stream = Arc::new(stream);
+
+    // The frames of the client are read on their own thread, the program
+    // being interrupted at its next iteration or call once the client leaves
+    let (sender, messages) = mpsc::sync_channel(MAX_PENDING_MESSAGES);
+    let closing = Arc::new(AtomicBool::new(false)); // A close frame was sent
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let stream = Arc::clone(&stream);
+        let closing = Arc::clone(&closing);
+        let interrupt = Arc::clone(&interrupt);
+        thread::spawn(move || {
+            let result = read_client_frames(&stream, sender, &closing);
+            interrupt.store(true, Ordering::Relaxed);
+            result
+        })
+    };
+
+    let mut state = State::new(config);
+    state.interrupt = Some(interrupt);
+    state.input = Box::new(WebSocketInput {
+        messages,
+        pending: VecDeque::new(),
+    });
+    state.output = Box::new(WebSocketOutput {
+        stream: Arc::clone(&stream),
+        buffer: Vec::new(),
+    });
+    let result = run_ast(ast, &mut state, config).and_then(|_| Ok(state.output.flush_bytes()?));
+    // The reader may wait for the program to take the messages
+    drop(state);
+
+    // The errors of the program are told to the client before closing, unless
+    // it left
+    let status: u16 = match result {
+        Ok(()) => 1000,
+        Err(RuntimeError::Io(_) | RuntimeError::Interrupted(_)) => return,
+        Err(error) => {
+            let error = runtime_error(error, name, Some(source));
+            let message = match &error.location {
+                Some(location) => format!(
+                    "{}:{}:{}: {}",
+                    name, location.line, location.column, error.message
+                ),
+                None => error.message.clone(),
+            };
+            let _ = write_websocket_frame(&stream, WEBSOCKET_TEXT, message.as_bytes());
+            1011
+        }
+    };
+    if !closing.swap(true, Ordering::Relaxed)
+        && write_websocket_frame(&stream, WEBSOCKET_CLOSE, &status.to_be_bytes()).is_ok()
+    {
+        // The client answers the close before the connection ends
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        let _ = reader.join();
+    }
+}
+
+/// Serve a program on WebSockets, running it for each client on its own
+/// tape
+pub fn command_serve(args: &ServeArgs, config: Config) {
+    let address = args.listen.as_str();
+    let (name, source) = program_source(&args.program);
+    let mut config = config;
+    let ast = load_program(&name, source.clone(), &mut config, false)
+        .unwrap_or_else(|error| error.exit());
+    config.max_steps.get_or_insert(SERVE_MAX_STEPS);
+    let listener = TcpListener::bind(address).unwrap_or_else(|error| {
+        fail(
+            Failure::Io,
+            &format!("can't listen on {}: {}", address, error),
+        )
+    });
+    eprintln!("serving {} on ws://{}", name, address);
+
+    let program = Arc::new((ast, config, name, source));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let program = Arc::clone(&program);
+        thread::spawn(move || {
+            let (ast, config, name, source) = &*program;
+            serve_websocket(stream, ast, config, name, source);
+        });
+    }
+}
@@ -0,0 +1,352 @@
+//! The full-screen debugger of debug --tui, drawn on the terminal with
+//! ratatui
+
+use std::io;
+use std::io::Write;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::ast::Node;
+use crate::lexer::parse_source_in;
+use crate::vm::Input;
+use crate::Config;
+
+use super::{fail, Debuggee, Failure, DEBUG_RUN_STEPS};
+
+/// A key pressed in the full-screen debugger
+enum Key {
+    Char(char),
+    Up,
+    Down,
+    Right,
+    Left,
+    Quit, // Ctrl-C or Ctrl-D, the terminal sending no signal
+}
+
+/// The next key, waiting for it unless `wait` is false
+fn next_key(wait: bool) -> io::Result<Option<Key>> {
+    loop {
+        if !wait && !event::poll(Duration::ZERO)? {
+            return Ok(None);
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        return Ok(Some(match key.code {
+            KeyCode::Char('c' | 'd') if key.modifiers.contains(KeyModifiers::CONTROL) => Key::Quit,
+            KeyCode::Char(c) => Key::Char(c),
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Right => Key::Right,
+            KeyCode::Left => Key::Left,
+            _ => Key::Char('\0'),
+        }));
+    }
+}
+
+/// Lines of the output shown by the full-screen debugger
+const TUI_OUTPUT_LINES: u16 = 3;
+
+/// The full-screen debugger, and the program it steps through
+struct Tui {
+    debuggee: Debuggee,
+    source: String,
+    commands: Vec<usize>, // Offsets of the commands of the source
+    cursor: usize,        // Index in `commands` of the command under the cursor
+    status: String,       // Why the program stopped
+}
+
+impl Tui {
+    /// Run the next step, returning whether the program goes on
+    fn step(&mut self) -> bool {
+        if self.debuggee.ended {
+            return false;
+        }
+        match self.debuggee.step() {
+            Ok(true) => true,
+            Ok(false) => {
+                self.status = String::from("finished");
+                false
+            }
+            Err(message) => {
+                self.status = message;
+                false
+            }
+        }
+    }
+
+    /// Put the cursor on the next command
+    fn follow(&mut self) {
+        if let Some(offset) = self.debuggee.stepper.next_offset() {
+            self.cursor = match self.commands.binary_search(&offset) {
+                Ok(index) | Err(index) => index.min(self.commands.len().saturating_sub(1)),
+            };
+        }
+    }
+
+    /// Run until a breakpoint, the end of the program, or a key
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        self.status = String::from("running");
+        loop {
+            for _ in 0..DEBUG_RUN_STEPS {
+                if !self.step() {
+                    return Ok(());
+                }
+                if self.debuggee.at_breakpoint() {
+                    self.status = String::from("breakpoint");
+                    return Ok(());
+                }
+            }
+            self.follow();
+            terminal.draw(|frame| self.draw(frame))?;
+            if next_key(false)?.is_some() {
+                self.status = String::from("paused");
+                return Ok(());
+            }
+        }
+    }
+
+    /// Move the cursor to the first command of another line
+    fn move_line(&mut self, down: bool) {
+        let line = |offset: usize| self.debuggee.lines.location(offset).0;
+        let current = match self.commands.get(self.cursor) {
+            Some(&offset) => line(offset),
+            None => return,
+        };
+        let target = match down {
+            true => self
+                .commands
+                .iter()
+                .map(|&offset| line(offset))
+                .find(|&l| l > current),
+            false => self
+                .commands
+                .iter()
+                .map(|&offset| line(offset))
+                .rfind(|&l| l < current),
+        };
+        if let Some(target) = target {
+            self.cursor = self
+                .commands
+                .iter()
+                .position(|&offset| line(offset) == target)
+                .unwrap();
+        }
+    }
+
+    /// Draw the whole screen: the source around the cursor, the tape around
+    /// the pointer, the end of the output and the keys
+    fn draw(&self, frame: &mut Frame) {
+        let bar = Style::new().add_modifier(Modifier::REVERSED);
+        let [title, source, tape, output, keys] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(3),
+            Constraint::Length(1 + TUI_OUTPUT_LINES),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        frame.render_widget(
+            Paragraph::new(format!(
+                " brainfuck debug {}  step {}  {}",
+                self.debuggee.name, self.debuggee.steps, self.status
+            ))
+            .style(bar),
+            title,
+        );
+        frame.render_widget(self.source_view(source), source);
+        frame.render_widget(self.tape_view(tape), tape);
+
+        // The end of the output, its control characters shown as dots
+        let contents = self.debuggee.output.contents();
+        let text = String::from_utf8_lossy(&contents);
+        let lines: Vec<_> = text.split('\n').collect();
+        let lines: Vec<Line> = lines[lines.len().saturating_sub(TUI_OUTPUT_LINES as usize)..]
+            .iter()
+            .map(|line| {
+                let line = line.chars().map(|c| if c.is_control() { '.' } else { c });
+                Line::from(line.collect::<String>())
+            })
+            .collect();
+        let block = Block::new()
+            .borders(Borders::TOP)
+            .title(format!(" output, {} bytes ", contents.len()));
+        frame.render_widget(Paragraph::new(lines).block(block), output);
+
+        frame.render_widget(
+            Paragraph::new(" s step  r run  b breakpoint  arrows move the cursor  q quit")
+                .style(bar),
+            keys,
+        );
+    }
+
+    /// The source, scrolled to the cursor
+    fn source_view(&self, area: Rect) -> Paragraph<'_> {
+        let current = self
+            .debuggee
+            .stepper
+            .next_offset()
+            .filter(|_| !self.debuggee.ended);
+        let cursor = self.commands.get(self.cursor).copied();
+        let (cursor_line, cursor_column) =
+            cursor.map_or((1, 1), |offset| self.debuggee.lines.location(offset));
+        let rows = usize::from(area.height).max(1);
+        let width = usize::from(area.width).saturating_sub(5).max(1);
+        let shift = (cursor_column + 10).saturating_sub(width);
+        let first = cursor_line.saturating_sub(rows / 2).max(1);
+
+        let mut lines = Vec::new();
+        let mut line_start = 0;
+        for (number, text) in self
+            .source
+            .split('\n')
+            .enumerate()
+            .map(|(i, text)| (i + 1, text))
+        {
+            let start = line_start;
+            line_start += text.len() + 1;
+            if number < first {
+                continue;
+            }
+            if number >= first + rows {
+                break;
+            }
+            let mut spans = vec![Span::raw(format!("{:>4} ", number))];
+            for (offset, c) in text
+                .trim_end_matches('\r')
+                .char_indices()
+                .skip(shift)
+                .take(width)
+            {
+                let offset = start + offset;
+                let c = if c.is_control() { ' ' } else { c };
+                let mut style = Style::new();
+                if current == Some(offset) {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                if self.debuggee.breakpoints.contains(&offset) {
+                    style = style.bg(Color::Red);
+                }
+                if cursor == Some(offset) {
+                    style = style.add_modifier(Modifier::UNDERLINED);
+                }
+                spans.push(Span::styled(c.to_string(), style));
+            }
+            lines.push(Line::from(spans));
+        }
+        Paragraph::new(lines)
+    }
+
+    /// The tape, centered on the pointer
+    fn tape_view(&self, area: Rect) -> Paragraph<'_> {
+        let memory = &self.debuggee.state.memory;
+        let index = self.debuggee.state.index;
+        let digits = |value: usize| value.to_string().len();
+        let cell_width =
+            digits(self.debuggee.config.cell.max() as usize).max(digits(memory.len())) + 1;
+        let count = (usize::from(area.width) / cell_width).clamp(1, memory.len().max(1));
+        let end = (index.saturating_sub(count / 2) + count).min(memory.len());
+        let mut indices = String::new();
+        let mut values = Vec::new();
+        let start = end.saturating_sub(count);
+        for (cell, value) in memory[start..end].iter().enumerate() {
+            let cell = start + cell;
+            indices.push_str(&format!("{:>cell_width$}", cell));
+            let value = format!("{:>cell_width$}", value);
+            values.push(match cell == index {
+                true => Span::styled(value, Style::new().add_modifier(Modifier::REVERSED)),
+                false => Span::raw(value),
+            });
+        }
+        let block = Block::new()
+            .borders(Borders::TOP)
+            .title(format!(" tape, pointer at {} ", index));
+        Paragraph::new(vec![Line::from(indices), Line::from(values)]).block(block)
+    }
+}
+
+/// Step through a program in the full-screen debugger, then print its
+/// output
+pub fn debug_tui(
+    name: String,
+    source: String,
+    ast: &Node,
+    config: Config,
+    input: Input,
+    breakpoints: Vec<usize>,
+) {
+    let commands = parse_source_in(&source, config.language)
+        .iter()
+        .map(|(offset, _)| *offset)
+        .collect();
+    let mut tui = Tui {
+        debuggee: Debuggee::new(name, &source, ast, config, input),
+        source,
+        commands,
+        cursor: 0,
+        status: String::from("stopped"),
+    };
+    tui.debuggee.breakpoints = breakpoints;
+    if tui.debuggee.ended {
+        tui.status = String::from("finished");
+    }
+    tui.follow();
+
+    // The alternate screen in raw mode, restored on a panic too, keeps the
+    // shell as it was
+    let mut terminal = ratatui::try_init().unwrap_or_else(|error| {
+        fail(
+            Failure::Io,
+            &format!("can't set the terminal in raw mode: {}", error),
+        )
+    });
+    let result: io::Result<()> = (|| loop {
+        terminal.draw(|frame| tui.draw(frame))?;
+        match next_key(true)? {
+            Some(Key::Char('s' | ' ')) => {
+                if tui.step() {
+                    tui.status = String::from("stopped");
+                }
+                tui.follow();
+            }
+            Some(Key::Char('r')) => {
+                tui.run(&mut terminal)?;
+                tui.follow();
+            }
+            Some(Key::Char('b')) => {
+                if let Some(&offset) = tui.commands.get(tui.cursor) {
+                    match tui.debuggee.breakpoints.iter().position(|&b| b == offset) {
+                        Some(position) => drop(tui.debuggee.breakpoints.remove(position)),
+                        None => tui.debuggee.breakpoints.push(offset),
+                    }
+                }
+            }
+            Some(Key::Left) => tui.cursor = tui.cursor.saturating_sub(1),
+            Some(Key::Right) => {
+                tui.cursor = (tui.cursor + 1).min(tui.commands.len().saturating_sub(1))
+            }
+            Some(Key::Up) => tui.move_line(false),
+            Some(Key::Down) => tui.move_line(true),
+            Some(Key::Char('q') | Key::Quit) | None => return Ok(()),
+            Some(Key::Char(_)) => {}
+        }
+    })();
+    ratatui::restore();
+    if let Err(error) = result {
+        fail(Failure::Io, &format!("can't draw the debugger: {}", error));
+    }
+
+    // The output stays on the terminal once the screen is restored
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(&tui.debuggee.output.contents())
+        .and_then(|_| stdout.flush())
+        .unwrap_or_else(|error| fail(Failure::Io, &format!("can't write the output: {}", error)));
+}
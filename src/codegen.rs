@@ -0,0 +1,4405 @@
+//! Backends compiling the programs to other languages
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::process::{Command, ExitStatus};
+use std::time::Instant;
+
+use thiserror::Error;
+
+use crate::ast::{build_ast, tape_usage, walk, Node, NodeKind, Span, Visitor};
+use crate::json::node_to_json;
+use crate::lexer::{parse_source_in, Dialect, Language, Token, DIALECTS};
+use crate::vm::{Bounds, Cell, Eof, Input, Profile};
+use crate::Config;
+
+/// File format of the executables written by the bin target
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryFormat {
+    Elf,   // Linux
+    Pe,    // Windows
+    MachO, // macOS
+}
+
+impl BinaryFormat {
+    /// Format of the executables of the operating system we run on
+    pub fn host() -> Self {
+        if cfg!(windows) {
+            BinaryFormat::Pe
+        } else if cfg!(target_os = "macos") {
+            BinaryFormat::MachO
+        } else {
+            BinaryFormat::Elf
+        }
+    }
+}
+
+/// An error of a backend
+#[derive(Debug, Error)]
+pub enum CodegenError {
+    #[error("{0}")]
+    Unsupported(String), // The target can't express the program or its configuration
+    #[error("{0}")]
+    Compiler(String), // An external compiler run by the target failed
+    #[error("{0}")]
+    Io(#[from] io::Error), // Error writing the generated code
+}
+
+/// Write a run of increments or moves, as `val` times `positive` or `negative`
+fn write_bf_run(
+    val: isize,
+    positive: &[u8],
+    negative: &[u8],
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    for _ in 0..val.abs() {
+        if val < 0 {
+            write.write_all(negative)?;
+        } else {
+            write.write_all(positive)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_bf(ast: &Node, _config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    Ok(write!(write, "{}", ast)?)
+}
+
+/// Flush the pending line of formatted brainfuck
+fn write_bf_line(line: &mut String, depth: usize, write: &mut dyn Write) -> io::Result<()> {
+    if !line.is_empty() {
+        write.write_all(format!("{}{}\n", "    ".repeat(depth), line).as_bytes())?;
+        line.clear();
+    }
+
+    Ok(())
+}
+
+fn write_bf_pretty_ast(
+    ast: &Node,
+    style: &PrettyBf,
+    depth: usize,
+    line: &mut String,
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    match &ast.kind {
+        // Loops and procedures get their own lines, with their body indented
+        NodeKind::Loop(_) | NodeKind::Procedure(_) => {
+            let (begin, node, end) = match &ast.kind {
+                NodeKind::Loop(node) => ("[", node.as_ref(), "]"),
+                NodeKind::Procedure(node) => ("(", node.as_ref(), ")"),
+                _ => unreachable!("matched above"),
+            };
+            write_bf_line(line, depth, write)?;
+            write.write_all(format!("{}{}\n", "    ".repeat(depth), begin).as_bytes())?;
+            let mut body = String::new();
+            write_bf_pretty_ast(node, style, depth + 1, &mut body, write)?;
+            write_bf_line(&mut body, depth + 1, write)?;
+            write.write_all(format!("{}{}\n", "    ".repeat(depth), end).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_bf_pretty_ast(node, style, depth, line, write)?;
+            }
+        }
+        // Other nodes, including the small loops rewritten by the optimizer,
+        // are flowed on lines
+        _ => {
+            let mut word = ast.to_string();
+            let separator = if style.annotate_runs { " " } else { "" };
+            if let (true, NodeKind::Incr(val) | NodeKind::Move(val)) =
+                (style.annotate_runs, &ast.kind)
+            {
+                if val.abs() > 1 {
+                    word = format!("{} x{}", word, val.abs());
+                }
+            }
+            if !line.is_empty()
+                && 4 * depth + line.len() + separator.len() + word.len() > style.line_width
+            {
+                write_bf_line(line, depth, write)?;
+            }
+            if !line.is_empty() {
+                line.push_str(separator);
+            }
+            line.push_str(&word);
+        }
+    }
+
+    Ok(())
+}
+
+/// The bf-pretty target: formatted brainfuck, with its loops indented
+pub struct PrettyBf {
+    pub line_width: usize,   // Width of the lines
+    pub annotate_runs: bool, // Follow the runs with their length
+}
+
+impl Default for PrettyBf {
+    fn default() -> Self {
+        PrettyBf {
+            line_width: 80,
+            annotate_runs: false,
+        }
+    }
+}
+
+impl Backend for PrettyBf {
+    fn emit(
+        &self,
+        ast: &Node,
+        _config: &Config,
+        write: &mut dyn Write,
+    ) -> Result<(), CodegenError> {
+        let mut line = String::new();
+        write_bf_pretty_ast(ast, self, 0, &mut line, write)?;
+        Ok(write_bf_line(&mut line, 0, write)?)
+    }
+}
+
+/// Deterministic pseudo-random numbers, with the SplitMix64 generator
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// True once in `n` times
+    fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+}
+
+/// Maybe add a pair of commands cancelling each other
+fn obfuscate_noise(random: &mut Random, code: &mut Vec<u8>) {
+    match random.below(4) {
+        0 => code.extend_from_slice(b"+-"),
+        1 => code.extend_from_slice(b"-+"),
+        _ => {}
+    }
+}
+
+/// Append a run of increments, overshooting and coming back
+fn obfuscate_incr(val: isize, random: &mut Random, code: &mut Vec<u8>) {
+    let extra = random.below(4) as isize;
+    let (forward, backward) = if val < 0 { (b'-', b'+') } else { (b'+', b'-') };
+    for _ in 0..val.abs() + extra {
+        code.push(forward);
+    }
+    obfuscate_noise(random, code);
+    for _ in 0..extra {
+        code.push(backward);
+    }
+}
+
+/// Append a loop which is never entered, as the current cell is known to be 0
+fn obfuscate_dead_loop(random: &mut Random, code: &mut Vec<u8>) {
+    code.push(b'[');
+    for _ in 0..1 + random.below(12) {
+        code.push(b"+-<>.,"[random.below(6) as usize]);
+    }
+    code.push(b']');
+}
+
+fn obfuscate_ast(
+    ast: &Node,
+    config: &Config,
+    random: &mut Random,
+    code: &mut Vec<u8>,
+) -> io::Result<()> {
+    match &ast.kind {
+        NodeKind::Incr(val) => obfuscate_incr(*val, random, code),
+        NodeKind::Move(val) => write_bf_run(*val, b">", b"<", code)?,
+        NodeKind::Write => code.push(b'.'),
+        NodeKind::Read => code.push(b','),
+        NodeKind::Clear => {
+            // Counting up is only quick enough with 8-bit cells
+            if config.cell == Cell::U8 && random.one_in(2) {
+                code.extend_from_slice(b"[+");
+            } else {
+                code.extend_from_slice(b"[-");
+            }
+            obfuscate_noise(random, code);
+            code.push(b']');
+        }
+        NodeKind::Mul(targets) => {
+            // Visit the targets in any order, decrementing the counter first or
+            // last
+            let mut targets = targets.clone();
+            for i in (1..targets.len()).rev() {
+                targets.swap(i, random.below(i as u64 + 1) as usize);
+            }
+            let decrement_first = random.one_in(2);
+            code.push(b'[');
+            if decrement_first {
+                code.push(b'-');
+            }
+            let mut position = 0;
+            for (offset, factor) in targets.iter() {
+                write_bf_run(offset - position, b">", b"<", code)?;
+                obfuscate_incr(*factor, random, code);
+                position = *offset;
+            }
+            write_bf_run(-position, b">", b"<", code)?;
+            if !decrement_first {
+                code.push(b'-');
+            }
+            code.push(b']');
+        }
+        NodeKind::Loop(node) => {
+            code.push(b'[');
+            obfuscate_ast(node, config, random, code)?;
+            code.push(b']');
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                obfuscate_noise(random, code);
+                obfuscate_ast(node, config, random, code)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    // Loops only exit on a cell at 0
+    if matches!(
+        ast.kind,
+        NodeKind::Clear | NodeKind::Mul(_) | NodeKind::Loop(_)
+    ) && random.one_in(3)
+    {
+        obfuscate_dead_loop(random, code);
+    }
+
+    Ok(())
+}
+
+/// The bf-obfuscated target: brainfuck behaving the same as the program
+///
+/// The obfuscation is deterministic for a given seed: cancelling pairs of
+/// commands and dead loops are inserted, runs overshoot and come back, the
+/// loops moving values are reordered and the lines have random lengths.
+#[derive(Default)]
+pub struct ObfuscatedBf {
+    pub seed: u64,
+}
+
+impl Backend for ObfuscatedBf {
+    fn emit(&self, ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+        write_bf_obfuscated(ast, config, self.seed, write)
+    }
+}
+
+fn write_bf_obfuscated(
+    ast: &Node,
+    config: &Config,
+    seed: u64,
+    write: &mut dyn Write,
+) -> Result<(), CodegenError> {
+    require_classic(ast, "bf-obfuscated")?;
+    let mut random = Random(seed);
+    let mut code = Vec::new();
+    obfuscate_dead_loop(&mut random, &mut code);
+    obfuscate_ast(ast, config, &mut random, &mut code)?;
+
+    let mut rest = code.as_slice();
+    while !rest.is_empty() {
+        let width = rest.len().min(40 + random.below(40) as usize);
+        write.write_all(&rest[..width])?;
+        write.write_all(b"\n")?;
+        rest = &rest[width..];
+    }
+
+    Ok(())
+}
+
+/// Write the program in a dialect
+fn write_dialect(ast: &Node, dialect: &Dialect, write: &mut dyn Write) -> Result<(), CodegenError> {
+    let commands = ast.to_string();
+    for (i, command) in commands.bytes().enumerate() {
+        if i > 0 {
+            write.write_all(if i % dialect.per_line == 0 {
+                b"\n"
+            } else {
+                b" "
+            })?;
+        }
+        let word = b"><+-.,[]".iter().position(|c| *c == command).unwrap();
+        write.write_all(dialect.words[word].as_bytes())?;
+    }
+    write.write_all(b"\n")?;
+
+    Ok(())
+}
+
+/// Fail when a backend is asked for the procedures of pbrain or the commands
+/// of Extended Brainfuck, which it can't express
+fn require_classic(ast: &Node, target: &str) -> Result<(), CodegenError> {
+    let feature = if ast_any(ast, &|node| {
+        matches!(node.kind, NodeKind::Procedure(_) | NodeKind::Call)
+    }) {
+        "the procedures of pbrain"
+    } else if ast_any(ast, &|node| matches!(node.kind, NodeKind::Extended(_))) {
+        "the commands of Extended Brainfuck"
+    } else {
+        return Ok(());
+    };
+
+    Err(CodegenError::Unsupported(format!(
+        "the {} target can't express {}",
+        target, feature
+    )))
+}
+
+/// Fail when a backend is asked for cells or a tape it doesn't implement
+fn require_plain_tape(config: &Config, target: &str) -> Result<(), CodegenError> {
+    if config.cell != Cell::U8 || config.bounds != Bounds::Unchecked {
+        return Err(CodegenError::Unsupported(format!(
+            "the {} target only supports 8-bit cells on an unchecked tape",
+            target
+        )));
+    }
+
+    Ok(())
+}
+
+/// Fail when a backend is asked for an end of input it can't tell apart
+/// from a byte read, when not leaving the cell unchanged
+fn require_unchanged_eof(config: &Config, target: &str) -> Result<(), CodegenError> {
+    if config.eof != Eof::Unchanged {
+        return Err(CodegenError::Unsupported(format!(
+            "the {} target only leaves the cell unchanged on end of input",
+            target
+        )));
+    }
+
+    Ok(())
+}
+
+/// Byte stored in the cell on end of input, if any
+fn eof_byte(eof: Eof) -> Option<u8> {
+    match eof {
+        Eof::Unchanged => None,
+        Eof::Zero => Some(0),
+        Eof::MinusOne => Some(u8::MAX),
+    }
+}
+
+/// Describe what a read does to the cell on end of input, for the comments
+/// of the generated code
+fn eof_behavior(eof: Eof) -> &'static str {
+    match eof {
+        Eof::Unchanged => "leaving the cell unchanged",
+        Eof::Zero => "setting the cell to 0",
+        Eof::MinusOne => "setting the cell to -1",
+    }
+}
+
+/// Format the index of a cell relative to the current one, for the backends
+/// writing source code with a `memory` array and an `index` variable
+fn cell_index(offset: isize, config: &Config) -> String {
+    match offset {
+        0 => String::from("index"),
+        o if config.bounds == Bounds::Wrap => format!(
+            "(index + {}) % {}",
+            o.rem_euclid(config.tape_size as isize),
+            config.tape_size
+        ),
+        o if o < 0 => format!("index - {}", -o),
+        o => format!("index + {}", o),
+    }
+}
+
+/// Format the C expression of a cell relative to the current one
+fn c_cell(offset: isize, config: &Config) -> String {
+    format!("memory[{}]", cell_index(offset, config))
+}
+
+/// Value stored in the cell on end of input, if any
+fn eof_value(config: &Config) -> Option<u32> {
+    match config.eof {
+        Eof::Unchanged => None,
+        Eof::Zero => Some(0),
+        Eof::MinusOne => Some(config.cell.max()),
+    }
+}
+
+/// C type of a cell
+fn c_cell_type(cell: Cell) -> &'static str {
+    match cell {
+        Cell::U8 => "uint8_t",
+        Cell::U16 => "uint16_t",
+        Cell::U32 => "uint32_t",
+    }
+}
+
+/// Write a #line directive pointing to the source of a node, if enabled
+fn write_c_line_directive(span: Span, config: &Config, write: &mut dyn Write) -> io::Result<()> {
+    if let Some(source_map) = &config.line_directives {
+        let (line, _) = source_map.location(span.start);
+        let path = source_map.path.replace('\\', "\\\\").replace('"', "\\\"");
+        write.write_all(
+            format!(
+                "#line {} \"{}\" // bytes {}..{}\n",
+                line, path, span.start, span.end
+            )
+            .as_bytes(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write a check that the cell at some offset of the current one is on the
+/// tape, reporting the node at `span` otherwise, if enabled
+fn write_c_check(
+    offset: isize,
+    span: Span,
+    config: &Config,
+    indent: &str,
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    if !config.codegen_checks || config.bounds == Bounds::Wrap {
+        return Ok(());
+    }
+
+    // The index is unsigned, so leaving the tape from the left wraps above it
+    let index = match offset {
+        0 => String::from("index"),
+        o if o < 0 => format!("index - {}", -o),
+        o => format!("index + {}", o),
+    };
+    write.write_all(
+        format!(
+            "{}if ({} >= {}) out_of_tape({});\n",
+            indent, index, config.tape_size, span.start
+        )
+        .as_bytes(),
+    )
+}
+
+/// Write the function stopping the program when `write_c_check` fails
+fn write_c_out_of_tape(config: &Config, write: &mut dyn Write) -> io::Result<()> {
+    if config.codegen_checks {
+        write.write_all(b"static void out_of_tape(size_t offset) {\n")?;
+        // The output so far is kept, as when the interpreter stops
+        write.write_all(b"    fflush(stdout);\n")?;
+        write.write_all(
+            b"    fprintf(stderr, \"pointer out of the tape at byte %zu of the source\\n\", offset);\n",
+        )?;
+        write.write_all(b"    abort();\n")?;
+        write.write_all(b"}\n")?;
+        write.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Where the generated C reads and writes its bytes
+#[derive(Clone, Copy, PartialEq)]
+enum CIo {
+    Stdio,   // The standard streams
+    Buffers, // The buffers given to bf_run
+    Uart,    // The serial port of an AVR
+}
+
+fn write_c_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    depth: usize,
+    io: CIo,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    match &ast.kind {
+        NodeKind::Loop(node) => {
+            write_c_line_directive(ast.span, config, write)?;
+            write.write_all(format!("{}while (memory[index] != 0) {{\n", indent).as_bytes())?;
+            write_c_ast(node, write, config, depth + 1, io)?;
+            write.write_all(format!("{}}}\n", indent).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            let mut i = 0;
+            while i < nodes.len() {
+                // Turn runs of cleared cells into a single memset, unless
+                // they may wrap around the end of the tape
+                let mut count = 0;
+                while let Some(NodeKind::Clear) = nodes.get(i + 2 * count).map(|node| &node.kind) {
+                    count += 1;
+                    if !matches!(
+                        nodes.get(i + 2 * count - 1).map(|node| &node.kind),
+                        Some(NodeKind::Move(1))
+                    ) {
+                        break;
+                    }
+                }
+                if count > 1 && config.bounds == Bounds::Unchecked {
+                    write_c_memset(&nodes[i..i + 2 * count - 1], config, &indent, write)?;
+                    i += 2 * count - 1;
+                    continue;
+                }
+
+                write_c_ast(&nodes[i], write, config, depth, io)?;
+                i += 1;
+            }
+        }
+        // The commands are written by their own function, keeping the frames
+        // of the recursion on the nesting small
+        _ => write_c_command(ast, write, config, &indent, depth, io)?,
+    }
+
+    Ok(())
+}
+
+fn write_c_memset(
+    nodes: &[Node],
+    config: &Config,
+    indent: &str,
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    let count = nodes.len().div_ceil(2);
+    let span = nodes[0].span.merge(nodes[nodes.len() - 1].span);
+    let last_move = nodes[nodes.len() - 2].span;
+    write_c_check(count as isize - 1, last_move, config, indent, write)?;
+    write_c_line_directive(span, config, write)?;
+    write.write_all(
+        format!(
+            "{}memset(memory + index, 0, {} * sizeof(*memory));\n",
+            indent, count
+        )
+        .as_bytes(),
+    )?;
+    write_c_line_directive(span, config, write)?;
+    write.write_all(format!("{}index += {};\n", indent, count - 1).as_bytes())?;
+    Ok(())
+}
+
+fn write_c_command(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    indent: &str,
+    depth: usize,
+    io: CIo,
+) -> io::Result<()> {
+    let cell_type = c_cell_type(config.cell);
+    if !matches!(ast.kind, NodeKind::Mul(_)) {
+        write_c_line_directive(ast.span, config, write)?;
+    }
+
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(
+                format!(
+                    "{}memory[index] = ({})(memory[index] {} {});\n",
+                    indent,
+                    cell_type,
+                    if *val < 0 { '-' } else { '+' },
+                    val.abs()
+                )
+                .as_bytes(),
+            )?;
+        }
+
+        NodeKind::Move(val) => {
+            let line = match config.bounds {
+                Bounds::Unchecked => format!("index += {};", val),
+                Bounds::Wrap => format!(
+                    "index = (index + {}) % {};",
+                    val.rem_euclid(config.tape_size as isize),
+                    config.tape_size
+                ),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+            write_c_check(0, ast.span, config, indent, write)?;
+        }
+        NodeKind::Write => {
+            // Libraries write into the caller's buffer, and fail when it is full
+            let line = match io {
+                CIo::Stdio => "putchar(memory[index]);",
+                CIo::Buffers => {
+                    "if (out_len < cap) out[out_len++] = memory[index]; else goto full;"
+                }
+                CIo::Uart => "uart_putchar(memory[index]);",
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Read => {
+            let getchar = match io {
+                CIo::Stdio => "getchar()",
+                CIo::Buffers => "(in_pos < len ? input[in_pos++] : EOF)",
+                CIo::Uart => "uart_getchar()",
+            };
+            let line = match config.eof {
+                Eof::Unchanged => format!("if ((c = {}) != EOF) memory[index] = c;", getchar),
+                Eof::Zero => format!("memory[index] = (c = {}) == EOF ? 0 : c;", getchar),
+                Eof::MinusOne => format!("memory[index] = {};", getchar),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Clear => {
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Mul(targets) => {
+            // The loop doesn't touch its targets on a cell at 0, so the checks
+            // only apply to the other values
+            let checked = config.codegen_checks && config.bounds == Bounds::Unchecked;
+            let outer_indent = indent;
+            let indent = if checked {
+                write_c_line_directive(ast.span, config, write)?;
+                write.write_all(format!("{}if (memory[index] != 0) {{\n", indent).as_bytes())?;
+                "    ".repeat(depth + 1)
+            } else {
+                indent.to_string()
+            };
+            for (offset, _) in targets.iter() {
+                write_c_check(*offset, ast.span, config, &indent, write)?;
+            }
+            for (offset, factor) in targets.iter() {
+                write_c_line_directive(ast.span, config, write)?;
+                write.write_all(
+                    format!(
+                        "{0}{1} = ({2})({1} + memory[index] * {3});\n",
+                        indent,
+                        c_cell(*offset, config),
+                        cell_type,
+                        factor
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write_c_line_directive(ast.span, config, write)?;
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+            if checked {
+                write.write_all(format!("{}}}\n", outer_indent).as_bytes())?;
+            }
+        }
+        NodeKind::Loop(_) | NodeKind::Block(_) => unreachable!("written by write_c_ast"),
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_c(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "c")?;
+    write.write_all(b"#include <stdint.h>\n")?;
+    write.write_all(b"#include <stdio.h>\n")?;
+    write.write_all(b"#include <stdlib.h>\n")?;
+    write.write_all(b"#include <string.h>\n")?;
+    write.write_all(b"\n")?;
+    write_c_out_of_tape(config, write)?;
+    write.write_all(
+        format!(
+            "static {} memory[{}];\n",
+            c_cell_type(config.cell),
+            config.tape_size
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"\n")?;
+    write.write_all(b"int main(int argc, char ** argv) {\n")?;
+    write.write_all(b"    size_t index = 0;\n")?;
+    write.write_all(b"    int c;\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    // bf source code\n")?;
+    write_c_ast(ast, write, config, 1, CIo::Stdio)?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    return EXIT_SUCCESS;\n")?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// Cells of the tape of an AVR when the program's usage is unknown, leaving
+/// room for the stack in the 2 KiB of an ATmega328P
+const AVR_TAPE_SIZE: usize = 1024;
+const AVR_SRAM_SIZE: usize = 2048;
+
+/// Write the program as C for an ATmega328P (Arduino Uno), using the UART
+///
+/// The tape is sized by the usage of the program when it is known, to fit
+/// the SRAM, and the configured tape size is an upper bound.
+fn write_avr(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "avr")?;
+    if config.codegen_checks {
+        return Err(CodegenError::Unsupported(String::from(
+            "the avr target has no stderr to report --codegen-checks on",
+        )));
+    }
+    let tape_size = tape_usage(ast)
+        .unwrap_or(AVR_TAPE_SIZE)
+        .min(config.tape_size);
+    let cell_size = match config.cell {
+        Cell::U8 => 1,
+        Cell::U16 => 2,
+        Cell::U32 => 4,
+    };
+    if tape_size * cell_size > AVR_SRAM_SIZE / 2 {
+        return Err(CodegenError::Unsupported(format!(
+            "a tape of {} bytes doesn't fit in the SRAM of the avr target",
+            tape_size * cell_size
+        )));
+    }
+    let config = Config {
+        tape_size,
+        ..config.clone()
+    };
+
+    write.write_all(b"#ifndef F_CPU\n")?;
+    write.write_all(b"#define F_CPU 16000000UL\n")?;
+    write.write_all(b"#endif\n")?;
+    write.write_all(b"#define BAUD 9600\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"#include <avr/io.h>\n")?;
+    write.write_all(b"#include <stdint.h>\n")?;
+    write.write_all(b"#include <stdio.h>\n")?;
+    write.write_all(b"#include <string.h>\n")?;
+    write.write_all(b"#include <util/setbaud.h>\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"static void uart_putchar(uint8_t c) {\n")?;
+    write.write_all(b"    loop_until_bit_is_set(UCSR0A, UDRE0);\n")?;
+    write.write_all(b"    UDR0 = c;\n")?;
+    write.write_all(b"}\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"static int uart_getchar(void) {\n")?;
+    write.write_all(b"    loop_until_bit_is_set(UCSR0A, RXC0);\n")?;
+    write.write_all(b"    return UDR0;\n")?;
+    write.write_all(b"}\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(
+        format!(
+            "static {} memory[{}];\n",
+            c_cell_type(config.cell),
+            config.tape_size
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"\n")?;
+    write.write_all(b"int main(void) {\n")?;
+    write.write_all(b"    size_t index = 0;\n")?;
+    write.write_all(b"    int c;\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    UBRR0H = UBRRH_VALUE;\n")?;
+    write.write_all(b"    UBRR0L = UBRRL_VALUE;\n")?;
+    write.write_all(b"#if USE_2X\n")?;
+    write.write_all(b"    UCSR0A |= _BV(U2X0);\n")?;
+    write.write_all(b"#else\n")?;
+    write.write_all(b"    UCSR0A &= ~_BV(U2X0);\n")?;
+    write.write_all(b"#endif\n")?;
+    write.write_all(b"    UCSR0C = _BV(UCSZ01) | _BV(UCSZ00);\n")?;
+    write.write_all(b"    UCSR0B = _BV(RXEN0) | _BV(TXEN0);\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    // bf source code\n")?;
+    write_c_ast(ast, write, &config, 1, CIo::Uart)?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    // Wait for the last byte to be sent, then halt\n")?;
+    write.write_all(b"    loop_until_bit_is_set(UCSR0A, UDRE0);\n")?;
+    write.write_all(b"    for (;;) {\n")?;
+    write.write_all(b"    }\n")?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// Write the program as a C function running it on an input buffer
+///
+/// `bf_run` returns the number of bytes written to `out`, -1 when they don't
+/// fit in `cap` bytes, or -2 when the tape can't be allocated.
+fn write_c_library(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "c-lib")?;
+    write.write_all(b"#include <stddef.h>\n")?;
+    write.write_all(b"#include <stdint.h>\n")?;
+    write.write_all(b"#include <stdio.h>\n")?;
+    write.write_all(b"#include <stdlib.h>\n")?;
+    write.write_all(b"#include <string.h>\n")?;
+    write.write_all(b"\n")?;
+    write_c_out_of_tape(config, write)?;
+    write.write_all(
+        b"int bf_run(const uint8_t * input, size_t len, uint8_t * out, size_t cap) {\n",
+    )?;
+    write.write_all(
+        format!(
+            "    {0} * memory = calloc({1}, sizeof({0}));\n",
+            c_cell_type(config.cell),
+            config.tape_size
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"    size_t index = 0;\n")?;
+    write.write_all(b"    size_t in_pos = 0;\n")?;
+    write.write_all(b"    size_t out_len = 0;\n")?;
+    write.write_all(b"    int result = -1;\n")?;
+    write.write_all(b"    int c;\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    if (memory == NULL) return -2;\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    // bf source code\n")?;
+    write_c_ast(ast, write, config, 1, CIo::Buffers)?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    result = (int)out_len;\n")?;
+    if ast_any(ast, &|node| matches!(node.kind, NodeKind::Write)) {
+        write.write_all(b"full:\n")?;
+    }
+    write.write_all(b"    free(memory);\n")?;
+    write.write_all(b"    return result;\n")?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// Build the program as a shared library exporting `bf_run`
+///
+/// The C library source is compiled with `$CC` (`cc` by default).
+fn write_shared_library(
+    ast: &Node,
+    config: &Config,
+    write: &mut dyn Write,
+) -> Result<(), CodegenError> {
+    let dir = env::temp_dir().join(format!("brainfuck-{}", process::id()));
+    fs::create_dir_all(&dir)?;
+    let source_path = dir.join("library.c");
+    let library_path = dir.join("library.so");
+    write_c_library(ast, config, &mut File::create(&source_path)?)?;
+
+    let status = Command::new(env::var_os("CC").unwrap_or_else(|| "cc".into()))
+        .args(["-O2", "-shared", "-fPIC", "-o"])
+        .arg(&library_path)
+        .arg(&source_path)
+        .status()?;
+    if !status.success() {
+        fs::remove_dir_all(&dir)?;
+        return Err(CodegenError::Compiler(format!(
+            "the C compiler failed with {}",
+            status
+        )));
+    }
+
+    write.write_all(&fs::read(&library_path)?)?;
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+}
+
+/// Deepest nesting of the loops of an AST
+fn loop_depth(ast: &Node) -> usize {
+    match &ast.kind {
+        NodeKind::Loop(body) => 1 + loop_depth(body),
+        NodeKind::Procedure(body) => loop_depth(body),
+        NodeKind::Block(nodes) => nodes.iter().map(loop_depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Check whether any node of an AST matches a predicate
+fn ast_any(ast: &Node, predicate: &dyn Fn(&Node) -> bool) -> bool {
+    struct Any<'a> {
+        predicate: &'a dyn Fn(&Node) -> bool,
+        found: bool,
+    }
+    impl Visitor for Any<'_> {
+        fn visit(&mut self, node: &Node) {
+            if !self.found {
+                self.found = (self.predicate)(node);
+                walk(self, node);
+            }
+        }
+    }
+
+    let mut any = Any {
+        predicate,
+        found: false,
+    };
+    any.visit(ast);
+    any.found
+}
+
+/// Format the Rust expression of a cell relative to the current one
+fn rust_cell(offset: isize, config: &Config) -> String {
+    match offset {
+        0 => String::from("memory[index]"),
+        o if config.bounds == Bounds::Wrap => format!(
+            "memory[(index + {}) % {}]",
+            o.rem_euclid(config.tape_size as isize),
+            config.tape_size
+        ),
+        o if o < 0 => format!("memory[index - {}]", -o),
+        o => format!("memory[index + {}]", o),
+    }
+}
+
+/// Rust type of a cell
+fn rust_cell_type(cell: Cell) -> &'static str {
+    match cell {
+        Cell::U8 => "u8",
+        Cell::U16 => "u16",
+        Cell::U32 => "u32",
+    }
+}
+
+/// Where the generated Rust reads and writes its bytes
+#[derive(Clone, Copy, PartialEq)]
+enum RustIo {
+    Std,       // The standard streams
+    Callbacks, // The `put` and `get` functions given to `run`
+}
+
+fn write_rust_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    depth: usize,
+    io: RustIo,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(
+                format!(
+                    "{}memory[index] = memory[index].{}({});\n",
+                    indent,
+                    if *val < 0 {
+                        "wrapping_sub"
+                    } else {
+                        "wrapping_add"
+                    },
+                    val.unsigned_abs() as u64 & config.cell.max() as u64
+                )
+                .as_bytes(),
+            )?;
+        }
+
+        NodeKind::Move(val) => {
+            let line = match config.bounds {
+                Bounds::Unchecked => format!(
+                    "index {} {};",
+                    if *val < 0 { "-=" } else { "+=" },
+                    val.abs()
+                ),
+                Bounds::Wrap => format!(
+                    "index = (index + {}) % {};",
+                    val.rem_euclid(config.tape_size as isize),
+                    config.tape_size
+                ),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Write => {
+            let cell = match config.cell {
+                Cell::U8 => "memory[index]",
+                _ => "memory[index] as u8",
+            };
+            let line = match io {
+                RustIo::Std => format!("output.write_all(&[{}])?;", cell),
+                RustIo::Callbacks => format!("put({});", cell),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        // The callbacks have no end of input to handle
+        NodeKind::Read if io == RustIo::Callbacks => {
+            let byte = match config.cell {
+                Cell::U8 => String::from("get()"),
+                cell => format!("{}::from(get())", rust_cell_type(cell)),
+            };
+            write.write_all(format!("{}memory[index] = {};\n", indent, byte).as_bytes())?;
+        }
+        NodeKind::Read => {
+            let cell_type = rust_cell_type(config.cell);
+            let byte = match config.cell {
+                Cell::U8 => String::from("byte?"),
+                _ => format!("{}::from(byte?)", cell_type),
+            };
+            let eof = match config.eof {
+                Eof::Unchanged => String::new(),
+                Eof::Zero => String::from(" else { memory[index] = 0; }"),
+                Eof::MinusOne => format!(" else {{ memory[index] = {}::MAX; }}", cell_type),
+            };
+            write.write_all(
+                format!(
+                    "{}if let Some(byte) = input.next() {{ memory[index] = {}; }}{}\n",
+                    indent, byte, eof
+                )
+                .as_bytes(),
+            )?;
+        }
+        NodeKind::Clear => {
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                write.write_all(
+                    format!(
+                        "{0}{1} = {1}.{2}(memory[index].wrapping_mul({3}));\n",
+                        indent,
+                        rust_cell(*offset, config),
+                        if *factor < 0 {
+                            "wrapping_sub"
+                        } else {
+                            "wrapping_add"
+                        },
+                        factor.unsigned_abs() as u64 & config.cell.max() as u64
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Loop(node) => {
+            write.write_all(format!("{}while memory[index] != 0 {{\n", indent).as_bytes())?;
+            write_rust_ast(node, write, config, depth + 1, io)?;
+            write.write_all(format!("{}}}\n", indent).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_rust_ast(node, write, config, depth, io)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_rust(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "rs")?;
+    // Only declare what the program uses, so that the output is warning-free
+    let reads = ast_any(ast, &|node| matches!(node.kind, NodeKind::Read));
+    let writes = ast_any(ast, &|node| matches!(node.kind, NodeKind::Write));
+    let moves = ast_any(ast, &|node| matches!(node.kind, NodeKind::Move(_)));
+    let modifies = ast_any(ast, &|node| {
+        matches!(
+            node.kind,
+            NodeKind::Incr(_) | NodeKind::Read | NodeKind::Clear | NodeKind::Mul(_)
+        )
+    });
+    let accesses = ast_any(ast, &|node| {
+        !matches!(node.kind, NodeKind::Move(_) | NodeKind::Block(_))
+    });
+
+    match (reads, writes) {
+        (true, true) => write.write_all(b"use std::io::{self, Read, Write};\n"),
+        (true, false) => write.write_all(b"use std::io::{self, Read};\n"),
+        (false, true) => write.write_all(b"use std::io::{self, Write};\n"),
+        (false, false) => write.write_all(b"use std::io;\n"),
+    }?;
+    write.write_all(b"\n")?;
+    // The tape and the pointer are often left with values that are never read
+    write.write_all(b"#[allow(unused_assignments)]\n")?;
+    write.write_all(b"fn main() -> io::Result<()> {\n")?;
+    if accesses {
+        write.write_all(
+            format!(
+                "    let {}memory = vec![0{}; {}];\n",
+                if modifies { "mut " } else { "" },
+                rust_cell_type(config.cell),
+                config.tape_size
+            )
+            .as_bytes(),
+        )?;
+        write.write_all(
+            format!(
+                "    let {}index: usize = 0;\n",
+                if moves { "mut " } else { "" }
+            )
+            .as_bytes(),
+        )?;
+    }
+    if reads {
+        write.write_all(b"    let mut input = io::stdin().lock().bytes();\n")?;
+    }
+    if writes {
+        write.write_all(b"    let mut output = io::BufWriter::new(io::stdout().lock());\n")?;
+    }
+    write.write_all(b"\n")?;
+    write.write_all(b"    // bf source code\n")?;
+    // Without any cell access, the program has no observable behavior
+    if accesses {
+        write_rust_ast(ast, write, config, 1, RustIo::Std)?;
+    }
+    write.write_all(b"\n")?;
+    if writes {
+        write.write_all(b"    output.flush()\n")?;
+    } else {
+        write.write_all(b"    Ok(())\n")?;
+    }
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// Write the program as a `#![no_std]` Rust crate, for firmwares
+///
+/// The crate exports `run`, taking the functions writing and reading a byte;
+/// the tape lives on the stack, so `--tape-size` should fit it.
+fn write_rust_nostd(
+    ast: &Node,
+    config: &Config,
+    write: &mut dyn Write,
+) -> Result<(), CodegenError> {
+    require_classic(ast, "rs-nostd")?;
+    // Only declare what the program uses, so that the output is warning-free
+    let reads = ast_any(ast, &|node| matches!(node.kind, NodeKind::Read));
+    let writes = ast_any(ast, &|node| matches!(node.kind, NodeKind::Write));
+    let moves = ast_any(ast, &|node| matches!(node.kind, NodeKind::Move(_)));
+    let modifies = ast_any(ast, &|node| {
+        matches!(
+            node.kind,
+            NodeKind::Incr(_) | NodeKind::Read | NodeKind::Clear | NodeKind::Mul(_)
+        )
+    });
+    let accesses = ast_any(ast, &|node| {
+        !matches!(node.kind, NodeKind::Move(_) | NodeKind::Block(_))
+    });
+
+    write.write_all(b"#![no_std]\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"/// Run the program, writing with `put` and reading with `get`\n")?;
+    // The tape and the pointer are often left with values that are never read
+    write.write_all(b"#[allow(unused_assignments)]\n")?;
+    let put = if writes && accesses {
+        "mut put"
+    } else {
+        "_put"
+    };
+    let get = if reads && accesses { "mut get" } else { "_get" };
+    write.write_all(
+        format!(
+            "pub fn run({}: impl FnMut(u8), {}: impl FnMut() -> u8) {{\n",
+            put, get
+        )
+        .as_bytes(),
+    )?;
+    if accesses {
+        write.write_all(
+            format!(
+                "    let {}memory = [0{}; {}];\n",
+                if modifies { "mut " } else { "" },
+                rust_cell_type(config.cell),
+                config.tape_size
+            )
+            .as_bytes(),
+        )?;
+        write.write_all(
+            format!(
+                "    let {}index: usize = 0;\n",
+                if moves { "mut " } else { "" }
+            )
+            .as_bytes(),
+        )?;
+    }
+    write.write_all(b"\n")?;
+    write.write_all(b"    // bf source code\n")?;
+    // Without any cell access, the program has no observable behavior
+    if accesses {
+        write_rust_ast(ast, write, config, 1, RustIo::Callbacks)?;
+    }
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// LLVM type of a cell
+#[cfg(feature = "llvm")]
+fn llvm_cell_type(cell: Cell) -> &'static str {
+    match cell {
+        Cell::U8 => "i8",
+        Cell::U16 => "i16",
+        Cell::U32 => "i32",
+    }
+}
+
+/// Format a value as an LLVM constant of the type of a cell, which LLVM
+/// reads as signed
+#[cfg(feature = "llvm")]
+fn llvm_constant(val: isize, cell: Cell) -> String {
+    match cell {
+        Cell::U8 => (val as i8).to_string(),
+        Cell::U16 => (val as i16).to_string(),
+        Cell::U32 => (val as i32).to_string(),
+    }
+}
+
+/// Write the computation of `%index{id}`, the index of the cell at an offset
+/// of `index`, wrapped on a circular tape
+#[cfg(feature = "llvm")]
+fn write_llvm_index(
+    index: &str,
+    offset: isize,
+    id: usize,
+    config: &Config,
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    let line = match config.bounds {
+        Bounds::Unchecked => format!("  %index{} = add i64 {}, {}\n", id, index, offset),
+        Bounds::Wrap => format!(
+            "  %sum{id} = add i64 {index}, {offset}\n  %index{id} = urem i64 %sum{id}, {size}\n",
+            id = id,
+            index = index,
+            offset = offset.rem_euclid(config.tape_size as isize),
+            size = config.tape_size
+        ),
+    };
+    write.write_all(line.as_bytes())
+}
+
+#[cfg(feature = "llvm")]
+fn write_llvm_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    id: &mut usize,
+) -> io::Result<()> {
+    let ty = llvm_cell_type(config.cell);
+    match &ast.kind {
+        NodeKind::Loop(node) => {
+            *id += 1;
+            let loop_id = *id;
+            write
+                .write_all(
+                    format!(
+                        "  br label %loop{id}.cond\n\nloop{id}.cond:\n  %index{id} = load i64, ptr %index\n  %ptr{id} = getelementptr inbounds {ty}, ptr @memory, i64 %index{id}\n  %cell{id} = load {ty}, ptr %ptr{id}\n  %test{id} = icmp ne {ty} %cell{id}, 0\n  br i1 %test{id}, label %loop{id}.body, label %loop{id}.end\n\nloop{id}.body:\n",
+                        id = loop_id,
+                        ty = ty
+                    )
+                    .as_bytes(),
+                )
+                ?;
+            write_llvm_ast(node, write, config, id)?;
+            write.write_all(
+                format!("  br label %loop{id}.cond\n\nloop{id}.end:\n", id = loop_id).as_bytes(),
+            )?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_llvm_ast(node, write, config, id)?;
+            }
+        }
+        // The commands are written by their own function, keeping the frames
+        // of the recursion on the nesting small
+        _ => write_llvm_command(ast, write, config, id)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "llvm")]
+fn write_llvm_command(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    id: &mut usize,
+) -> io::Result<()> {
+    let ty = llvm_cell_type(config.cell);
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            *id += 1;
+            write
+                .write_all(
+                    format!(
+                        "  %index{id} = load i64, ptr %index\n  %ptr{id} = getelementptr inbounds {ty}, ptr @memory, i64 %index{id}\n  %cell{id} = load {ty}, ptr %ptr{id}\n  %new{id} = add {ty} %cell{id}, {val}\n  store {ty} %new{id}, ptr %ptr{id}\n",
+                        id = id,
+                        ty = ty,
+                        val = llvm_constant(*val, config.cell)
+                    )
+                    .as_bytes(),
+                )
+                ?;
+        }
+        NodeKind::Move(val) => {
+            *id += 1;
+            write.write_all(format!("  %old{} = load i64, ptr %index\n", id).as_bytes())?;
+            write_llvm_index(&format!("%old{}", id), *val, *id, config, write)?;
+            write.write_all(format!("  store i64 %index{}, ptr %index\n", id).as_bytes())?;
+        }
+        NodeKind::Write => {
+            *id += 1;
+            // putchar takes an int, and writes its low byte
+            let char = match config.cell {
+                Cell::U32 => String::new(),
+                _ => format!(
+                    "  %char{id} = zext {ty} %cell{id} to i32\n",
+                    id = id,
+                    ty = ty
+                ),
+            };
+            let arg = match config.cell {
+                Cell::U32 => format!("%cell{}", id),
+                _ => format!("%char{}", id),
+            };
+            write
+                .write_all(
+                    format!(
+                        "  %index{id} = load i64, ptr %index\n  %ptr{id} = getelementptr inbounds {ty}, ptr @memory, i64 %index{id}\n  %cell{id} = load {ty}, ptr %ptr{id}\n{char}  call i32 @putchar(i32 {arg})\n",
+                        id = id,
+                        ty = ty,
+                        char = char,
+                        arg = arg
+                    )
+                    .as_bytes(),
+                )
+                ?;
+        }
+        NodeKind::Read => {
+            *id += 1;
+            let eof = match eof_value(config) {
+                Some(value) => llvm_constant(value as isize, config.cell),
+                None => format!("%cell{}", id),
+            };
+            let (byte, value) = match config.cell {
+                Cell::U32 => (String::new(), format!("%char{}", id)),
+                _ => (
+                    format!(
+                        "  %byte{id} = trunc i32 %char{id} to {ty}\n",
+                        id = id,
+                        ty = ty
+                    ),
+                    format!("%byte{}", id),
+                ),
+            };
+            write
+                .write_all(
+                    format!(
+                        "  %index{id} = load i64, ptr %index\n  %ptr{id} = getelementptr inbounds {ty}, ptr @memory, i64 %index{id}\n  %cell{id} = load {ty}, ptr %ptr{id}\n  %char{id} = call i32 @getchar()\n  %eof{id} = icmp slt i32 %char{id}, 0\n{byte}  %new{id} = select i1 %eof{id}, {ty} {eof}, {ty} {value}\n  store {ty} %new{id}, ptr %ptr{id}\n",
+                        id = id,
+                        ty = ty,
+                        eof = eof,
+                        byte = byte,
+                        value = value
+                    )
+                    .as_bytes(),
+                )
+                ?;
+        }
+        NodeKind::Clear => {
+            *id += 1;
+            write
+                .write_all(
+                    format!(
+                        "  %index{id} = load i64, ptr %index\n  %ptr{id} = getelementptr inbounds {ty}, ptr @memory, i64 %index{id}\n  store {ty} 0, ptr %ptr{id}\n",
+                        id = id,
+                        ty = ty
+                    )
+                    .as_bytes(),
+                )
+                ?;
+        }
+        NodeKind::Mul(targets) => {
+            *id += 1;
+            let mul_id = *id;
+            write
+                .write_all(
+                    format!(
+                        "  %index{id} = load i64, ptr %index\n  %ptr{id} = getelementptr inbounds {ty}, ptr @memory, i64 %index{id}\n  %cell{id} = load {ty}, ptr %ptr{id}\n",
+                        id = mul_id,
+                        ty = ty
+                    )
+                    .as_bytes(),
+                )
+                ?;
+            for (offset, factor) in targets.iter() {
+                *id += 1;
+                write_llvm_index(&format!("%index{}", mul_id), *offset, *id, config, write)?;
+                write
+                    .write_all(
+                        format!(
+                            "  %ptr{id} = getelementptr inbounds {ty}, ptr @memory, i64 %index{id}\n  %cell{id} = load {ty}, ptr %ptr{id}\n  %product{id} = mul {ty} %cell{mul_id}, {factor}\n  %new{id} = add {ty} %cell{id}, %product{id}\n  store {ty} %new{id}, ptr %ptr{id}\n",
+                            id = id,
+                            ty = ty,
+                            mul_id = mul_id,
+                            factor = llvm_constant(*factor, config.cell)
+                        )
+                        .as_bytes(),
+                    )
+                    ?;
+            }
+            write.write_all(format!("  store {} 0, ptr %ptr{}\n", ty, mul_id).as_bytes())?;
+        }
+        NodeKind::Loop(_) | NodeKind::Block(_) => unreachable!("written by write_llvm_ast"),
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "llvm")]
+fn write_llvm(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "ll")?;
+    write.write_all(
+        format!(
+            "@memory = internal global [{} x {}] zeroinitializer\n",
+            config.tape_size,
+            llvm_cell_type(config.cell)
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"\n")?;
+    write.write_all(b"declare i32 @putchar(i32)\n")?;
+    write.write_all(b"declare i32 @getchar()\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"define i32 @main() {\n")?;
+    write.write_all(b"entry:\n")?;
+    write.write_all(b"  %index = alloca i64\n")?;
+    write.write_all(b"  store i64 0, ptr %index\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"  ; bf source code\n")?;
+    write_llvm_ast(ast, write, config, &mut 0)?;
+    write.write_all(b"  ret i32 0\n")?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+#[cfg(feature = "wasm-emit")]
+fn write_wat_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth + 2);
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write
+                .write_all(
+                    format!(
+                        "{0}(i32.store8 (local.get $index) (i32.add (i32.load8_u (local.get $index)) (i32.const {1})))\n",
+                        indent, val
+                    )
+                    .as_bytes(),
+                )
+                ?;
+        }
+        NodeKind::Move(val) => {
+            write.write_all(
+                format!(
+                    "{0}(local.set $index (i32.add (local.get $index) (i32.const {1})))\n",
+                    indent, val
+                )
+                .as_bytes(),
+            )?;
+        }
+        NodeKind::Write => {
+            write.write_all(
+                format!(
+                    "{}(call $putchar (i32.load8_u (local.get $index)))\n",
+                    indent
+                )
+                .as_bytes(),
+            )?;
+        }
+        NodeKind::Read => {
+            let eof = match eof_byte(config.eof) {
+                Some(byte) => format!(
+                    "\n{}  (else (i32.store8 (local.get $index) (i32.const {})))",
+                    indent, byte
+                ),
+                None => String::new(),
+            };
+            write
+                .write_all(
+                    format!(
+                        "{0}(if (i32.ge_s (local.tee $char (call $getchar)) (i32.const 0))\n{0}  (then (i32.store8 (local.get $index) (local.get $char))){1})\n",
+                        indent, eof
+                    )
+                    .as_bytes(),
+                )
+                ?;
+        }
+        NodeKind::Clear => {
+            write.write_all(
+                format!("{}(i32.store8 (local.get $index) (i32.const 0))\n", indent).as_bytes(),
+            )?;
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                write
+                    .write_all(
+                        format!(
+                            "{0}(i32.store8 (i32.add (local.get $index) (i32.const {1}))\n{0}  (i32.add (i32.load8_u (i32.add (local.get $index) (i32.const {1})))\n{0}    (i32.mul (i32.load8_u (local.get $index)) (i32.const {2}))))\n",
+                            indent, offset, factor
+                        )
+                        .as_bytes(),
+                    )
+                    ?;
+            }
+            write.write_all(
+                format!("{}(i32.store8 (local.get $index) (i32.const 0))\n", indent).as_bytes(),
+            )?;
+        }
+        NodeKind::Loop(node) => {
+            write
+                .write_all(
+                    format!(
+                        "{0}(block $end{1}\n{0}  (loop $begin{1}\n{0}    (br_if $end{1} (i32.eqz (i32.load8_u (local.get $index))))\n",
+                        indent, depth
+                    )
+                    .as_bytes(),
+                )
+                ?;
+            write_wat_ast(node, write, config, depth + 2)?;
+            write.write_all(format!("{0}    (br $begin{1})))\n", indent, depth).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_wat_ast(node, write, config, depth)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "wasm-emit")]
+fn write_wat(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "wat")?;
+    require_classic(ast, "wat")?;
+    write.write_all(b"(module\n")?;
+    write.write_all(b"  (import \"env\" \"putchar\" (func $putchar (param i32)))\n")?;
+    write.write_all(b"  (import \"env\" \"getchar\" (func $getchar (result i32)))\n")?;
+    write.write_all(
+        format!(
+            "  (memory (export \"memory\") {})\n",
+            config.tape_size.div_ceil(WASM_PAGE_SIZE)
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"\n")?;
+    write.write_all(b"  (func (export \"main\")\n")?;
+    write.write_all(b"    (local $index i32)\n")?;
+    write.write_all(b"    (local $char i32)\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    ;; bf source code\n")?;
+    write_wat_ast(ast, write, config, 0)?;
+    write.write_all(b"  )\n")?;
+    write.write_all(b")\n")?;
+
+    Ok(())
+}
+
+/// Encode an unsigned integer in the LEB128 format
+#[cfg(feature = "wasm-emit")]
+fn write_uleb128(mut val: usize, code: &mut Vec<u8>) {
+    loop {
+        let byte = (val & 0x7f) as u8;
+        val >>= 7;
+        if val == 0 {
+            code.push(byte);
+            return;
+        }
+        code.push(byte | 0x80);
+    }
+}
+
+/// Encode a signed integer in the LEB128 format
+#[cfg(feature = "wasm-emit")]
+fn write_sleb128(mut val: isize, code: &mut Vec<u8>) {
+    loop {
+        let byte = (val & 0x7f) as u8;
+        val >>= 7;
+        if (val == 0 && byte & 0x40 == 0) || (val == -1 && byte & 0x40 != 0) {
+            code.push(byte);
+            return;
+        }
+        code.push(byte | 0x80);
+    }
+}
+
+/// Encode a WebAssembly section
+#[cfg(feature = "wasm-emit")]
+fn write_wasm_section(id: u8, content: &[u8], write: &mut dyn Write) -> io::Result<()> {
+    let mut header = vec![id];
+    write_uleb128(content.len(), &mut header);
+    write.write_all(&header)?;
+    write.write_all(content)?;
+
+    Ok(())
+}
+
+// Function and local indices of the generated module
+#[cfg(feature = "wasm-emit")]
+const WASM_PAGE_SIZE: usize = 0x1_0000;
+#[cfg(feature = "wasm-emit")]
+const WASM_PUTCHAR: u8 = 0;
+#[cfg(feature = "wasm-emit")]
+const WASM_GETCHAR: u8 = 1;
+#[cfg(feature = "wasm-emit")]
+const WASM_INDEX: u8 = 0;
+#[cfg(feature = "wasm-emit")]
+const WASM_CHAR: u8 = 1;
+
+#[cfg(feature = "wasm-emit")]
+fn write_wasm_ast(ast: &Node, code: &mut Vec<u8>, config: &Config) {
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            // local.get $index, local.get $index, i32.load8_u, i32.const val
+            code.extend_from_slice(&[0x20, WASM_INDEX, 0x20, WASM_INDEX, 0x2d, 0, 0, 0x41]);
+            write_sleb128(*val, code);
+            // i32.add, i32.store8
+            code.extend_from_slice(&[0x6a, 0x3a, 0, 0]);
+        }
+        NodeKind::Move(val) => {
+            // local.get $index, i32.const val
+            code.extend_from_slice(&[0x20, WASM_INDEX, 0x41]);
+            write_sleb128(*val, code);
+            // i32.add, local.set $index
+            code.extend_from_slice(&[0x6a, 0x21, WASM_INDEX]);
+        }
+        NodeKind::Write => {
+            // local.get $index, i32.load8_u, call $putchar
+            code.extend_from_slice(&[0x20, WASM_INDEX, 0x2d, 0, 0, 0x10, WASM_PUTCHAR]);
+        }
+        NodeKind::Read => {
+            // call $getchar, local.tee $char, i32.const 0, i32.ge_s, if
+            code.extend_from_slice(&[
+                0x10,
+                WASM_GETCHAR,
+                0x22,
+                WASM_CHAR,
+                0x41,
+                0,
+                0x4e,
+                0x04,
+                0x40,
+            ]);
+            // local.get $index, local.get $char, i32.store8
+            code.extend_from_slice(&[0x20, WASM_INDEX, 0x20, WASM_CHAR, 0x3a, 0, 0]);
+            if let Some(byte) = eof_byte(config.eof) {
+                // else, local.get $index, i32.const byte, i32.store8
+                code.extend_from_slice(&[0x05, 0x20, WASM_INDEX, 0x41]);
+                write_sleb128(byte as i8 as isize, code);
+                code.extend_from_slice(&[0x3a, 0, 0]);
+            }
+            // end
+            code.push(0x0b);
+        }
+        NodeKind::Clear => {
+            // local.get $index, i32.const 0, i32.store8
+            code.extend_from_slice(&[0x20, WASM_INDEX, 0x41, 0, 0x3a, 0, 0]);
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                // local.get $index, i32.const offset, i32.add
+                code.extend_from_slice(&[0x20, WASM_INDEX, 0x41]);
+                write_sleb128(*offset, code);
+                code.push(0x6a);
+                // local.get $index, i32.const offset, i32.add, i32.load8_u
+                code.extend_from_slice(&[0x20, WASM_INDEX, 0x41]);
+                write_sleb128(*offset, code);
+                code.extend_from_slice(&[0x6a, 0x2d, 0, 0]);
+                // local.get $index, i32.load8_u, i32.const factor, i32.mul
+                code.extend_from_slice(&[0x20, WASM_INDEX, 0x2d, 0, 0, 0x41]);
+                write_sleb128(*factor, code);
+                code.push(0x6c);
+                // i32.add, i32.store8
+                code.extend_from_slice(&[0x6a, 0x3a, 0, 0]);
+            }
+            // local.get $index, i32.const 0, i32.store8
+            code.extend_from_slice(&[0x20, WASM_INDEX, 0x41, 0, 0x3a, 0, 0]);
+        }
+        NodeKind::Loop(node) => {
+            // block, loop, local.get $index, i32.load8_u, i32.eqz, br_if 1
+            code.extend_from_slice(&[
+                0x02, 0x40, 0x03, 0x40, 0x20, WASM_INDEX, 0x2d, 0, 0, 0x45, 0x0d, 1,
+            ]);
+            write_wasm_ast(node, code, config);
+            // br 0, end, end
+            code.extend_from_slice(&[0x0c, 0, 0x0b, 0x0b]);
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_wasm_ast(node, code, config);
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+}
+
+#[cfg(feature = "wasm-emit")]
+fn write_wasm(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "wasm")?;
+    require_classic(ast, "wasm")?;
+    // Magic number and version
+    write.write_all(b"\0asm\x01\0\0\0")?;
+
+    // Types: (i32) -> (), () -> (i32), () -> ()
+    write_wasm_section(
+        1,
+        &[3, 0x60, 1, 0x7f, 0, 0x60, 0, 1, 0x7f, 0x60, 0, 0],
+        write,
+    )?;
+
+    // Imports: env.putchar and env.getchar
+    let mut imports = vec![2];
+    for (name, type_index) in [("putchar", 0), ("getchar", 1)].iter() {
+        imports.extend_from_slice(&[3, b'e', b'n', b'v', name.len() as u8]);
+        imports.extend_from_slice(name.as_bytes());
+        imports.extend_from_slice(&[0x00, *type_index]);
+    }
+    write_wasm_section(2, &imports, write)?;
+
+    // Functions: main
+    write_wasm_section(3, &[1, 2], write)?;
+
+    // Memory: one page, large enough for the tape
+    let mut memory = vec![1, 0];
+    write_uleb128(config.tape_size.div_ceil(WASM_PAGE_SIZE), &mut memory);
+    write_wasm_section(5, &memory, write)?;
+
+    // Exports: memory and main
+    let mut exports = vec![2];
+    exports.extend_from_slice(&[6, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0]);
+    exports.extend_from_slice(&[4, b'm', b'a', b'i', b'n', 0x00, 2]);
+    write_wasm_section(7, &exports, write)?;
+
+    // Code: the body of main, with $index and $char locals
+    let mut body = vec![1, 2, 0x7f];
+    write_wasm_ast(ast, &mut body, config);
+    body.push(0x0b);
+    let mut code = vec![1];
+    write_uleb128(body.len(), &mut code);
+    code.extend_from_slice(&body);
+    write_wasm_section(10, &code, write)?;
+
+    Ok(())
+}
+
+fn write_x86_64_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    id: &mut usize,
+) -> io::Result<()> {
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write
+                .write_all(format!("    add byte ptr [rbx], {}\n", *val as u8 as i8).as_bytes())?;
+        }
+        NodeKind::Move(val) => {
+            write.write_all(format!("    add rbx, {}\n", val).as_bytes())?;
+        }
+        NodeKind::Write => {
+            write.write_all(b"    mov eax, 1\n")?;
+            write.write_all(b"    mov edi, 1\n")?;
+            write.write_all(b"    mov rsi, rbx\n")?;
+            write.write_all(b"    mov edx, 1\n")?;
+            write.write_all(b"    syscall\n")?;
+        }
+        NodeKind::Read => {
+            write.write_all(b"    xor eax, eax\n")?;
+            write.write_all(b"    xor edi, edi\n")?;
+            write.write_all(b"    mov rsi, rbx\n")?;
+            write.write_all(b"    mov edx, 1\n")?;
+            write.write_all(b"    syscall\n")?;
+            // Nothing is read on end of input
+            if let Some(byte) = eof_byte(config.eof) {
+                *id += 1;
+                write.write_all(
+                    format!(
+                        "    test eax, eax\n    jg .Lread{0}_end\n    mov byte ptr [rbx], {1}\n.Lread{0}_end:\n",
+                        id, byte
+                    )
+                    .as_bytes(),
+                )?;
+            }
+        }
+        NodeKind::Clear => {
+            write.write_all(b"    mov byte ptr [rbx], 0\n")?;
+        }
+        NodeKind::Mul(targets) => {
+            write.write_all(b"    movzx eax, byte ptr [rbx]\n")?;
+            for (offset, factor) in targets.iter() {
+                write.write_all(
+                    format!(
+                        "    imul ecx, eax, {}\n    add byte ptr [rbx + {}], cl\n",
+                        factor, offset
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write.write_all(b"    mov byte ptr [rbx], 0\n")?;
+        }
+        NodeKind::Loop(node) => {
+            *id += 1;
+            let loop_id = *id;
+            write.write_all(
+                format!(
+                    ".Lloop{0}_begin:\n    cmp byte ptr [rbx], 0\n    jz .Lloop{0}_end\n",
+                    loop_id
+                )
+                .as_bytes(),
+            )?;
+            write_x86_64_ast(node, write, config, id)?;
+            write.write_all(
+                format!("    jmp .Lloop{0}_begin\n.Lloop{0}_end:\n", loop_id).as_bytes(),
+            )?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_x86_64_ast(node, write, config, id)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_x86_64(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "asm-x86_64")?;
+    require_classic(ast, "asm-x86_64")?;
+    write.write_all(b"    .intel_syntax noprefix\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    .bss\n")?;
+    write.write_all(b"memory:\n")?;
+    write.write_all(format!("    .zero {}\n", config.tape_size).as_bytes())?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    .text\n")?;
+    write.write_all(b"    .globl _start\n")?;
+    write.write_all(b"_start:\n")?;
+    write.write_all(b"    lea rbx, [rip + memory]\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    # bf source code\n")?;
+    write_x86_64_ast(ast, write, config, &mut 0)?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    mov eax, 60\n")?;
+    write.write_all(b"    xor edi, edi\n")?;
+    write.write_all(b"    syscall\n")?;
+
+    Ok(())
+}
+
+fn write_riscv64_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    id: &mut usize,
+) -> io::Result<()> {
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(b"    lbu t0, 0(s1)\n")?;
+            write.write_all(format!("    addi t0, t0, {}\n", *val as u8 as i8).as_bytes())?;
+            write.write_all(b"    sb t0, 0(s1)\n")?;
+        }
+        NodeKind::Move(val) => {
+            if (-2048..2048).contains(val) {
+                write.write_all(format!("    addi s1, s1, {}\n", val).as_bytes())?;
+            } else {
+                write.write_all(format!("    li t0, {}\n    add s1, s1, t0\n", val).as_bytes())?;
+            }
+        }
+        NodeKind::Write => {
+            write.write_all(b"    li a7, 64\n")?;
+            write.write_all(b"    li a0, 1\n")?;
+            write.write_all(b"    mv a1, s1\n")?;
+            write.write_all(b"    li a2, 1\n")?;
+            write.write_all(b"    ecall\n")?;
+        }
+        NodeKind::Read => {
+            write.write_all(b"    li a7, 63\n")?;
+            write.write_all(b"    li a0, 0\n")?;
+            write.write_all(b"    mv a1, s1\n")?;
+            write.write_all(b"    li a2, 1\n")?;
+            write.write_all(b"    ecall\n")?;
+            // Nothing is read on end of input
+            if let Some(byte) = eof_byte(config.eof) {
+                *id += 1;
+                write.write_all(
+                    format!(
+                        "    bgtz a0, .Lread{0}_end\n    li t0, {1}\n    sb t0, 0(s1)\n.Lread{0}_end:\n",
+                        id, byte
+                    )
+                    .as_bytes(),
+                )?;
+            }
+        }
+        NodeKind::Clear => {
+            write.write_all(b"    sb zero, 0(s1)\n")?;
+        }
+        NodeKind::Mul(targets) => {
+            write.write_all(b"    lbu t1, 0(s1)\n")?;
+            for (offset, factor) in targets.iter() {
+                write
+                    .write_all(
+                        format!(
+                            "    li t2, {}\n    mul t2, t1, t2\n    li t3, {}\n    add t3, s1, t3\n    lbu t0, 0(t3)\n    add t0, t0, t2\n    sb t0, 0(t3)\n",
+                            factor, offset
+                        )
+                        .as_bytes(),
+                    )
+                    ?;
+            }
+            write.write_all(b"    sb zero, 0(s1)\n")?;
+        }
+        NodeKind::Loop(node) => {
+            // Conditional branches only reach +/-4KiB, so jump over an
+            // unconditional one to exit the loop
+            *id += 1;
+            let loop_id = *id;
+            write
+                .write_all(
+                    format!(
+                        ".Lloop{0}_begin:\n    lbu t0, 0(s1)\n    bnez t0, .Lloop{0}_body\n    j .Lloop{0}_end\n.Lloop{0}_body:\n",
+                        loop_id
+                    )
+                    .as_bytes(),
+                )
+                ?;
+            write_riscv64_ast(node, write, config, id)?;
+            write.write_all(
+                format!("    j .Lloop{0}_begin\n.Lloop{0}_end:\n", loop_id).as_bytes(),
+            )?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_riscv64_ast(node, write, config, id)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_riscv64(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "asm-riscv64")?;
+    require_classic(ast, "asm-riscv64")?;
+    write.write_all(b"    .bss\n")?;
+    write.write_all(b"memory:\n")?;
+    write.write_all(format!("    .zero {}\n", config.tape_size).as_bytes())?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    .text\n")?;
+    write.write_all(b"    .globl _start\n")?;
+    write.write_all(b"_start:\n")?;
+    write.write_all(b"    la s1, memory\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    # bf source code\n")?;
+    write_riscv64_ast(ast, write, config, &mut 0)?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    li a7, 93\n")?;
+    write.write_all(b"    li a0, 0\n")?;
+    write.write_all(b"    ecall\n")?;
+
+    Ok(())
+}
+
+fn write_6502_ast(ast: &Node, write: &mut dyn Write, id: &mut usize) -> io::Result<()> {
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(b"    lda (ptr),y\n")?;
+            write.write_all(b"    clc\n")?;
+            write.write_all(format!("    adc #${:02x}\n", *val as u8).as_bytes())?;
+            write.write_all(b"    sta (ptr),y\n")?;
+        }
+        // The pointer is 16 bits wide, in the zero page
+        NodeKind::Move(1) => {
+            write.write_all(b"    inc ptr\n")?;
+            write.write_all(b"    bne :+\n")?;
+            write.write_all(b"    inc ptr+1\n")?;
+            write.write_all(b":\n")?;
+        }
+        NodeKind::Move(-1) => {
+            write.write_all(b"    lda ptr\n")?;
+            write.write_all(b"    bne :+\n")?;
+            write.write_all(b"    dec ptr+1\n")?;
+            write.write_all(b":   dec ptr\n")?;
+        }
+        NodeKind::Move(val) => {
+            let val = *val as u16;
+            write.write_all(b"    clc\n")?;
+            write.write_all(b"    lda ptr\n")?;
+            write.write_all(format!("    adc #${:02x}\n", val & 0xff).as_bytes())?;
+            write.write_all(b"    sta ptr\n")?;
+            write.write_all(b"    lda ptr+1\n")?;
+            write.write_all(format!("    adc #${:02x}\n", val >> 8).as_bytes())?;
+            write.write_all(b"    sta ptr+1\n")?;
+        }
+        NodeKind::Write => {
+            write.write_all(b"    lda (ptr),y\n")?;
+            write.write_all(b"    jsr CHROUT\n")?;
+            write.write_all(b"    ldy #0\n")?;
+        }
+        NodeKind::Read => {
+            write.write_all(b"    jsr CHRIN\n")?;
+            write.write_all(b"    ldy #0\n")?;
+            write.write_all(b"    sta (ptr),y\n")?;
+        }
+        NodeKind::Clear => {
+            write.write_all(b"    lda #0\n")?;
+            write.write_all(b"    sta (ptr),y\n")?;
+        }
+        // Without a multiplication instruction, the loop is kept as is
+        NodeKind::Mul(targets) => {
+            let mut body = vec![Node::new(NodeKind::Incr(-1), ast.span)];
+            let mut position = 0;
+            for (offset, factor) in targets.iter() {
+                body.push(Node::new(NodeKind::Move(offset - position), ast.span));
+                body.push(Node::new(NodeKind::Incr(*factor), ast.span));
+                position = *offset;
+            }
+            body.push(Node::new(NodeKind::Move(-position), ast.span));
+            let body = Node::new(NodeKind::Block(body), ast.span);
+            write_6502_ast(
+                &Node::new(NodeKind::Loop(Box::new(body)), ast.span),
+                write,
+                id,
+            )?;
+        }
+        // Branches only reach 127 bytes away, so jmp does the long jumps
+        NodeKind::Loop(node) => {
+            *id += 1;
+            let loop_id = *id;
+            write.write_all(format!("loop{}_begin:\n", loop_id).as_bytes())?;
+            write.write_all(b"    lda (ptr),y\n")?;
+            write.write_all(format!("    bne loop{}_body\n", loop_id).as_bytes())?;
+            write.write_all(format!("    jmp loop{}_end\n", loop_id).as_bytes())?;
+            write.write_all(format!("loop{}_body:\n", loop_id).as_bytes())?;
+            write_6502_ast(node, write, id)?;
+            write.write_all(format!("    jmp loop{}_begin\n", loop_id).as_bytes())?;
+            write.write_all(format!("loop{}_end:\n", loop_id).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_6502_ast(node, write, id)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+/// Cells of the tape of a 6502 when the program's usage is unknown
+const R6502_TAPE_SIZE: usize = 1024;
+
+/// Write the program as 6502 assembly, in the ca65 syntax
+///
+/// `start` runs the program and returns with `rts`. Bytes are written with
+/// `CHROUT` and read with `CHRIN`, which default to the C64 KERNAL routines
+/// and can be set with `ca65 -D CHROUT=...`. The tape is sized like for the
+/// avr target.
+fn write_6502(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "6502")?;
+    require_unchanged_eof(config, "6502")?;
+    require_classic(ast, "6502")?;
+    let tape_size = tape_usage(ast)
+        .unwrap_or(R6502_TAPE_SIZE)
+        .min(config.tape_size);
+    let pages = tape_size.div_ceil(256);
+    if pages > 0x80 {
+        return Err(CodegenError::Unsupported(format!(
+            "a tape of {} bytes doesn't fit in the memory of the 6502 target",
+            tape_size
+        )));
+    }
+
+    write.write_all(b".ifndef CHROUT\n")?;
+    write.write_all(b"    CHROUT = $ffd2\n")?;
+    write.write_all(b".endif\n")?;
+    write.write_all(b".ifndef CHRIN\n")?;
+    write.write_all(b"    CHRIN = $ffcf\n")?;
+    write.write_all(b".endif\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b".export start\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b".segment \"ZEROPAGE\"\n")?;
+    write.write_all(b"ptr: .res 2\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b".segment \"BSS\"\n")?;
+    write.write_all(format!("tape: .res {}\n", pages * 256).as_bytes())?;
+    write.write_all(b"\n")?;
+    write.write_all(b".segment \"CODE\"\n")?;
+    write.write_all(b"start:\n")?;
+    write.write_all(b"    ; Clear the tape, one page at a time\n")?;
+    write.write_all(b"    lda #<tape\n")?;
+    write.write_all(b"    sta ptr\n")?;
+    write.write_all(b"    lda #>tape\n")?;
+    write.write_all(b"    sta ptr+1\n")?;
+    write.write_all(b"    lda #0\n")?;
+    write.write_all(b"    tay\n")?;
+    write.write_all(format!("    ldx #{}\n", pages).as_bytes())?;
+    write.write_all(b":   sta (ptr),y\n")?;
+    write.write_all(b"    iny\n")?;
+    write.write_all(b"    bne :-\n")?;
+    write.write_all(b"    inc ptr+1\n")?;
+    write.write_all(b"    dex\n")?;
+    write.write_all(b"    bne :-\n")?;
+    write.write_all(b"    lda #>tape\n")?;
+    write.write_all(b"    sta ptr+1\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    ; bf source code, with Y kept at 0\n")?;
+    write_6502_ast(ast, write, &mut 0)?;
+    write.write_all(b"\n")?;
+    write.write_all(b"    rts\n")?;
+
+    Ok(())
+}
+
+// Virtual addresses of the generated executable
+#[cfg(feature = "native-emit")]
+const ELF_CODE_ADDRESS: u64 = 0x40_0000;
+#[cfg(feature = "native-emit")]
+const ELF_MEMORY_ADDRESS: u64 = 0x60_0000;
+
+/// x86_64 machine code writing and reading the cell at rbx on a system
+#[cfg(feature = "native-emit")]
+struct NativeIo {
+    write: &'static [u8],
+    read: &'static [u8], // Leaves the cell unchanged on end of input
+}
+
+/// mov eax, 1 (write); mov edi, 1; mov rsi, rbx; mov edx, 1; syscall, and
+/// xor eax, eax (read); xor edi, edi; mov rsi, rbx; mov edx, 1; syscall
+#[cfg(feature = "native-emit")]
+const LINUX_IO: NativeIo = NativeIo {
+    write: &[
+        0xb8, 1, 0, 0, 0, 0xbf, 1, 0, 0, 0, 0x48, 0x89, 0xde, 0xba, 1, 0, 0, 0, 0x0f, 0x05,
+    ],
+    read: &[
+        0x31, 0xc0, 0x31, 0xff, 0x48, 0x89, 0xde, 0xba, 1, 0, 0, 0, 0x0f, 0x05,
+    ],
+};
+
+/// The same with the BSD system calls, in the 0x2000000 class
+#[cfg(feature = "native-emit")]
+const MACOS_IO: NativeIo = NativeIo {
+    write: &[
+        0xb8, 4, 0, 0, 2, 0xbf, 1, 0, 0, 0, 0x48, 0x89, 0xde, 0xba, 1, 0, 0, 0, 0x0f, 0x05,
+    ],
+    read: &[
+        0xb8, 3, 0, 0, 2, 0x31, 0xff, 0x48, 0x89, 0xde, 0xba, 1, 0, 0, 0, 0x0f, 0x05,
+    ],
+};
+
+/// WriteFile(r14, rbx, 1, rsp + 40, NULL) and ReadFile(r15, rbx, 1, rsp + 40,
+/// NULL), with WriteFile in r12, ReadFile in r13 and the standard handles in
+/// r14 and r15: mov rcx, r14 (or r15); mov rdx, rbx; mov r8d, 1;
+/// lea r9, [rsp + 40]; mov qword [rsp + 32], 0; call r12 (or r13)
+#[cfg(feature = "native-emit")]
+const WINDOWS_IO: NativeIo = NativeIo {
+    write: &[
+        0x4c, 0x89, 0xf1, 0x48, 0x89, 0xda, 0x41, 0xb8, 1, 0, 0, 0, 0x4c, 0x8d, 0x4c, 0x24, 0x28,
+        0x48, 0xc7, 0x44, 0x24, 0x20, 0, 0, 0, 0, 0x41, 0xff, 0xd4,
+    ],
+    read: &[
+        0x4c, 0x89, 0xf9, 0x48, 0x89, 0xda, 0x41, 0xb8, 1, 0, 0, 0, 0x4c, 0x8d, 0x4c, 0x24, 0x28,
+        0x48, 0xc7, 0x44, 0x24, 0x20, 0, 0, 0, 0, 0x41, 0xff, 0xd5,
+    ],
+};
+
+/// Write the x86_64 machine code of a program, with the tape at rbx
+#[cfg(feature = "native-emit")]
+fn write_native_ast(ast: &Node, io: &NativeIo, code: &mut Vec<u8>) {
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            // add byte [rbx], val
+            code.extend_from_slice(&[0x80, 0x03, *val as u8]);
+        }
+        NodeKind::Move(val) => {
+            // add rbx, val
+            code.extend_from_slice(&[0x48, 0x81, 0xc3]);
+            code.extend_from_slice(&(*val as i32).to_le_bytes());
+        }
+        NodeKind::Write => {
+            code.extend_from_slice(io.write);
+        }
+        NodeKind::Read => {
+            code.extend_from_slice(io.read);
+        }
+        NodeKind::Clear => {
+            // mov byte [rbx], 0
+            code.extend_from_slice(&[0xc6, 0x03, 0x00]);
+        }
+        NodeKind::Mul(targets) => {
+            // movzx eax, byte [rbx]
+            code.extend_from_slice(&[0x0f, 0xb6, 0x03]);
+            for (offset, factor) in targets.iter() {
+                // imul ecx, eax, factor; add byte [rbx + offset], cl
+                code.extend_from_slice(&[0x69, 0xc8]);
+                code.extend_from_slice(&(*factor as i32).to_le_bytes());
+                code.extend_from_slice(&[0x00, 0x8b]);
+                code.extend_from_slice(&(*offset as i32).to_le_bytes());
+            }
+            // mov byte [rbx], 0
+            code.extend_from_slice(&[0xc6, 0x03, 0x00]);
+        }
+        NodeKind::Loop(node) => {
+            // cmp byte [rbx], 0; jz end
+            let begin = code.len();
+            code.extend_from_slice(&[0x80, 0x3b, 0x00, 0x0f, 0x84, 0, 0, 0, 0]);
+            let jz = code.len();
+            write_native_ast(node, io, code);
+
+            // jmp begin
+            code.push(0xe9);
+            let end = code.len() + 4;
+            code.extend_from_slice(&(begin as i32 - end as i32).to_le_bytes());
+            code[jz - 4..jz].copy_from_slice(&(end as i32 - jz as i32).to_le_bytes());
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_native_ast(node, io, code);
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+}
+
+/// Write a program header of an ELF64 file
+#[cfg(feature = "native-emit")]
+fn write_elf_program_header(
+    flags: u32,
+    offset: u64,
+    address: u64,
+    file_size: u64,
+    memory_size: u64,
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    write.write_all(&1u32.to_le_bytes())?; // PT_LOAD
+    write.write_all(&flags.to_le_bytes())?;
+    write.write_all(&offset.to_le_bytes())?;
+    write.write_all(&address.to_le_bytes())?; // Virtual address
+    write.write_all(&address.to_le_bytes())?; // Physical address
+    write.write_all(&file_size.to_le_bytes())?;
+    write.write_all(&memory_size.to_le_bytes())?;
+    write.write_all(&0x1000u64.to_le_bytes())?; // Alignment
+
+    Ok(())
+}
+
+#[cfg(feature = "native-emit")]
+fn write_elf(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "elf")?;
+    require_unchanged_eof(config, "elf")?;
+    require_classic(ast, "elf")?;
+    // Machine code: mov rbx, memory; bf source code; exit(0)
+    let mut code = vec![0x48, 0xbb];
+    code.extend_from_slice(&ELF_MEMORY_ADDRESS.to_le_bytes());
+    write_native_ast(ast, &LINUX_IO, &mut code);
+    code.extend_from_slice(&[0xb8, 60, 0, 0, 0, 0x31, 0xff, 0x0f, 0x05]);
+
+    // The code follows the ELF header and the two program headers
+    let headers_size = 64 + 2 * 56;
+    let file_size = (headers_size + code.len()) as u64;
+
+    // ELF header
+    write.write_all(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0")?;
+    write.write_all(&2u16.to_le_bytes())?; // ET_EXEC
+    write.write_all(&0x3eu16.to_le_bytes())?; // EM_X86_64
+    write.write_all(&1u32.to_le_bytes())?; // EV_CURRENT
+    write.write_all(&(ELF_CODE_ADDRESS + headers_size as u64).to_le_bytes())?; // Entry point
+    write.write_all(&64u64.to_le_bytes())?; // Program headers offset
+    write.write_all(&0u64.to_le_bytes())?; // Section headers offset
+    write.write_all(&0u32.to_le_bytes())?; // Flags
+    write.write_all(&64u16.to_le_bytes())?; // ELF header size
+    write.write_all(&56u16.to_le_bytes())?; // Program header size
+    write.write_all(&2u16.to_le_bytes())?; // Program headers count
+    write.write_all(&64u16.to_le_bytes())?; // Section header size
+    write.write_all(&0u16.to_le_bytes())?; // Section headers count
+    write.write_all(&0u16.to_le_bytes())?; // Section names index
+
+    // Program headers: the whole file as code (R+X), the tape as bss (R+W)
+    write_elf_program_header(5, 0, ELF_CODE_ADDRESS, file_size, file_size, write)?;
+    write_elf_program_header(6, 0, ELF_MEMORY_ADDRESS, 0, config.tape_size as u64, write)?;
+
+    write.write_all(&code)?;
+
+    Ok(())
+}
+
+/// Round a size up to a multiple of an alignment
+#[cfg(feature = "native-emit")]
+fn align_up(size: usize, alignment: usize) -> usize {
+    size.div_ceil(alignment) * alignment
+}
+
+/// Push an instruction addressing `target` relative to rip, for code loaded
+/// at `base`
+#[cfg(feature = "native-emit")]
+fn push_rip_relative(code: &mut Vec<u8>, base: usize, opcode: &[u8], target: usize) {
+    code.extend_from_slice(opcode);
+    let next = base + code.len() + 4;
+    code.extend_from_slice(&(target as i32 - next as i32).to_le_bytes());
+}
+
+#[cfg(feature = "native-emit")]
+const PE_IMAGE_BASE: u64 = 0x1_4000_0000;
+#[cfg(feature = "native-emit")]
+const PE_SECTION_ALIGNMENT: usize = 0x1000;
+#[cfg(feature = "native-emit")]
+const PE_FILE_ALIGNMENT: usize = 0x200;
+
+/// Write a section header of a PE file
+#[cfg(feature = "native-emit")]
+fn write_pe_section_header(
+    name: &[u8; 8],
+    virtual_size: usize,
+    address: usize,
+    raw_size: usize,
+    raw_offset: usize,
+    characteristics: u32,
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    write.write_all(name)?;
+    write.write_all(&(virtual_size as u32).to_le_bytes())?;
+    write.write_all(&(address as u32).to_le_bytes())?;
+    write.write_all(&(raw_size as u32).to_le_bytes())?;
+    write.write_all(&(raw_offset as u32).to_le_bytes())?;
+    write.write_all(&[0; 12])?; // No relocations nor line numbers
+
+    write.write_all(&characteristics.to_le_bytes())
+}
+
+/// Write the program as a Windows x86_64 executable, calling kernel32.dll
+#[cfg(feature = "native-emit")]
+fn write_pe(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "pe")?;
+    require_unchanged_eof(config, "pe")?;
+    require_classic(ast, "pe")?;
+    // The tape is the first section, so that its address is known to the code
+    let tape_address = PE_SECTION_ALIGNMENT;
+    let text_address = tape_address + align_up(config.tape_size, PE_SECTION_ALIGNMENT);
+
+    // Imports: the directory, the lookup and address tables, then the names
+    let functions = ["GetStdHandle", "WriteFile", "ReadFile", "ExitProcess"];
+    let table_size = 8 * (functions.len() + 1);
+    let lookup_address = text_address + 2 * 20;
+    let address_address = lookup_address + table_size;
+    let names_address = address_address + table_size;
+    let mut names = Vec::new();
+    let mut thunks = Vec::new();
+    for function in functions.iter() {
+        thunks.push((names_address + names.len()) as u64);
+        names.extend_from_slice(&0u16.to_le_bytes()); // Hint
+        names.extend_from_slice(function.as_bytes());
+        names.resize(names.len() + 2 - names.len() % 2, 0);
+    }
+    let dll_address = names_address + names.len();
+    names.extend_from_slice(b"KERNEL32.dll\0");
+
+    let mut text = Vec::new();
+    text.extend_from_slice(&(lookup_address as u32).to_le_bytes());
+    text.extend_from_slice(&[0; 8]); // Time stamp and forwarder chain
+    text.extend_from_slice(&(dll_address as u32).to_le_bytes());
+    text.extend_from_slice(&(address_address as u32).to_le_bytes());
+    text.extend_from_slice(&[0; 20]);
+    for _ in 0..2 {
+        for thunk in thunks.iter() {
+            text.extend_from_slice(&thunk.to_le_bytes());
+        }
+        text.extend_from_slice(&0u64.to_le_bytes());
+    }
+    text.extend_from_slice(&names);
+    text.resize(align_up(text.len(), 16), 0);
+
+    // Machine code: sub rsp, 56 (shadow space, a fifth argument and the
+    // count of bytes read or written); r14 = GetStdHandle(-11);
+    // r15 = GetStdHandle(-10); r12 = WriteFile; r13 = ReadFile;
+    // lea rbx, tape; bf source code; ExitProcess(0)
+    let entry_point = text_address + text.len();
+    let import = |index: usize| address_address + 8 * index;
+    text.extend_from_slice(&[0x48, 0x83, 0xec, 0x38]);
+    text.extend_from_slice(&[0xb9, 0xf5, 0xff, 0xff, 0xff]);
+    push_rip_relative(&mut text, text_address, &[0xff, 0x15], import(0));
+    text.extend_from_slice(&[0x49, 0x89, 0xc6]);
+    text.extend_from_slice(&[0xb9, 0xf6, 0xff, 0xff, 0xff]);
+    push_rip_relative(&mut text, text_address, &[0xff, 0x15], import(0));
+    text.extend_from_slice(&[0x49, 0x89, 0xc7]);
+    push_rip_relative(&mut text, text_address, &[0x4c, 0x8b, 0x25], import(1));
+    push_rip_relative(&mut text, text_address, &[0x4c, 0x8b, 0x2d], import(2));
+    push_rip_relative(&mut text, text_address, &[0x48, 0x8d, 0x1d], tape_address);
+    write_native_ast(ast, &WINDOWS_IO, &mut text);
+    text.extend_from_slice(&[0x31, 0xc9]);
+    push_rip_relative(&mut text, text_address, &[0xff, 0x15], import(3));
+
+    let text_raw_size = align_up(text.len(), PE_FILE_ALIGNMENT);
+    let image_size = text_address + align_up(text.len(), PE_SECTION_ALIGNMENT);
+
+    // DOS header, only pointing to the PE header
+    let mut dos_header = [0; 64];
+    dos_header[..2].copy_from_slice(b"MZ");
+    dos_header[0x3c] = 64;
+    write.write_all(&dos_header)?;
+
+    // COFF header
+    write.write_all(b"PE\0\0")?;
+    write.write_all(&0x8664u16.to_le_bytes())?; // IMAGE_FILE_MACHINE_AMD64
+    write.write_all(&2u16.to_le_bytes())?; // Sections count
+    write.write_all(&[0; 12])?; // Time stamp and symbols
+    write.write_all(&240u16.to_le_bytes())?; // Optional header size
+    write.write_all(&0x23u16.to_le_bytes())?; // Executable, large addresses, no relocations
+
+    // Optional header
+    write.write_all(&0x20bu16.to_le_bytes())?; // PE32+
+    write.write_all(&[0; 2])?; // Linker version
+    write.write_all(&(text_raw_size as u32).to_le_bytes())?; // Code size
+    write.write_all(&0u32.to_le_bytes())?; // Initialized data size
+    write.write_all(&(align_up(config.tape_size, PE_FILE_ALIGNMENT) as u32).to_le_bytes())?;
+    write.write_all(&(entry_point as u32).to_le_bytes())?;
+    write.write_all(&(text_address as u32).to_le_bytes())?; // Code base
+    write.write_all(&PE_IMAGE_BASE.to_le_bytes())?;
+    write.write_all(&(PE_SECTION_ALIGNMENT as u32).to_le_bytes())?;
+    write.write_all(&(PE_FILE_ALIGNMENT as u32).to_le_bytes())?;
+    write.write_all(&6u16.to_le_bytes())?; // Operating system version
+    write.write_all(&0u16.to_le_bytes())?;
+    write.write_all(&[0; 4])?; // Image version
+    write.write_all(&6u16.to_le_bytes())?; // Subsystem version
+    write.write_all(&0u16.to_le_bytes())?;
+    write.write_all(&[0; 4])?; // Win32 version
+    write.write_all(&(image_size as u32).to_le_bytes())?;
+    write.write_all(&(PE_FILE_ALIGNMENT as u32).to_le_bytes())?; // Headers size
+    write.write_all(&[0; 4])?; // Checksum
+    write.write_all(&3u16.to_le_bytes())?; // IMAGE_SUBSYSTEM_WINDOWS_CUI
+    write.write_all(&0u16.to_le_bytes())?; // DLL characteristics
+    write.write_all(&0x10_0000u64.to_le_bytes())?; // Stack reserve
+    write.write_all(&0x1000u64.to_le_bytes())?; // Stack commit
+    write.write_all(&0x10_0000u64.to_le_bytes())?; // Heap reserve
+    write.write_all(&0x1000u64.to_le_bytes())?; // Heap commit
+    write.write_all(&[0; 4])?; // Loader flags
+    write.write_all(&16u32.to_le_bytes())?; // Data directories count
+    for directory in 0..16 {
+        let (address, size) = match directory {
+            1 => (text_address, 2 * 20),         // Import directory
+            12 => (address_address, table_size), // Import address table
+            _ => (0, 0),
+        };
+        write.write_all(&(address as u32).to_le_bytes())?;
+        write.write_all(&(size as u32).to_le_bytes())?;
+    }
+
+    // Section headers: the tape as bss (R+W), the imports and code (R+W+X)
+    write_pe_section_header(
+        b".bss\0\0\0\0",
+        config.tape_size,
+        tape_address,
+        0,
+        0,
+        0xc000_0080,
+        write,
+    )?;
+    write_pe_section_header(
+        b".text\0\0\0",
+        text.len(),
+        text_address,
+        text_raw_size,
+        PE_FILE_ALIGNMENT,
+        0xe000_0060,
+        write,
+    )?;
+
+    let headers_size = 64 + 4 + 20 + 240 + 2 * 40;
+    write.write_all(&vec![0; PE_FILE_ALIGNMENT - headers_size])?;
+    text.resize(text_raw_size, 0);
+    write.write_all(&text)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "native-emit")]
+const MACHO_CODE_ADDRESS: u64 = 0x1_0000_0000;
+#[cfg(feature = "native-emit")]
+const MACHO_MEMORY_ADDRESS: u64 = 0x2_0000_0000;
+#[cfg(feature = "native-emit")]
+const MACHO_PAGE_SIZE: usize = 0x1000;
+
+/// Write a segment load command of a Mach-O file, without sections
+#[cfg(feature = "native-emit")]
+fn write_macho_segment(
+    name: &[u8],
+    address: u64,
+    memory_size: u64,
+    file_size: u64,
+    protection: u32,
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    let mut segment_name = [0; 16];
+    segment_name[..name.len()].copy_from_slice(name);
+    write.write_all(&0x19u32.to_le_bytes())?; // LC_SEGMENT_64
+    write.write_all(&72u32.to_le_bytes())?; // Command size
+    write.write_all(&segment_name)?;
+    write.write_all(&address.to_le_bytes())?;
+    write.write_all(&memory_size.to_le_bytes())?;
+    write.write_all(&0u64.to_le_bytes())?; // File offset
+    write.write_all(&file_size.to_le_bytes())?;
+    write.write_all(&protection.to_le_bytes())?; // Maximum protection
+    write.write_all(&protection.to_le_bytes())?; // Initial protection
+    write.write_all(&[0; 8])?; // Sections count and flags
+
+    Ok(())
+}
+
+/// Write the program as a static macOS x86_64 executable
+///
+/// macOS doesn't promise a stable system call interface, so this relies on
+/// the kernel still accepting executables without dyld.
+#[cfg(feature = "native-emit")]
+fn write_macho(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "macho")?;
+    require_unchanged_eof(config, "macho")?;
+    require_classic(ast, "macho")?;
+    // Machine code: mov rbx, memory; bf source code; exit(0)
+    let mut code = vec![0x48, 0xbb];
+    code.extend_from_slice(&MACHO_MEMORY_ADDRESS.to_le_bytes());
+    write_native_ast(ast, &MACOS_IO, &mut code);
+    code.extend_from_slice(&[0xb8, 1, 0, 0, 2, 0x31, 0xff, 0x0f, 0x05]);
+
+    // The code follows the Mach-O header, the three segments and the thread
+    let commands_size = 3 * 72 + 184;
+    let headers_size = 32 + commands_size;
+    let file_size = (headers_size + code.len()) as u64;
+
+    // Mach-O header
+    write.write_all(&0xfeed_facfu32.to_le_bytes())?; // MH_MAGIC_64
+    write.write_all(&0x0100_0007u32.to_le_bytes())?; // CPU_TYPE_X86_64
+    write.write_all(&3u32.to_le_bytes())?; // CPU_SUBTYPE_X86_64_ALL
+    write.write_all(&2u32.to_le_bytes())?; // MH_EXECUTE
+    write.write_all(&4u32.to_le_bytes())?; // Commands count
+    write.write_all(&(commands_size as u32).to_le_bytes())?;
+    write.write_all(&1u32.to_le_bytes())?; // MH_NOUNDEFS
+    write.write_all(&0u32.to_le_bytes())?;
+
+    // Segments: the unmapped first 4 GiB, the whole file as code (R+X), the
+    // tape (R+W)
+    write_macho_segment(b"__PAGEZERO", 0, MACHO_CODE_ADDRESS, 0, 0, write)?;
+    write_macho_segment(
+        b"__TEXT",
+        MACHO_CODE_ADDRESS,
+        align_up(file_size as usize, MACHO_PAGE_SIZE) as u64,
+        file_size,
+        5,
+        write,
+    )?;
+    write_macho_segment(
+        b"__DATA",
+        MACHO_MEMORY_ADDRESS,
+        align_up(config.tape_size, MACHO_PAGE_SIZE) as u64,
+        0,
+        3,
+        write,
+    )?;
+
+    // Initial thread, starting at the code
+    write.write_all(&5u32.to_le_bytes())?; // LC_UNIXTHREAD
+    write.write_all(&184u32.to_le_bytes())?; // Command size
+    write.write_all(&4u32.to_le_bytes())?; // x86_THREAD_STATE64
+    write.write_all(&42u32.to_le_bytes())?; // State size, in 32-bit words
+    for register in 0..21 {
+        let value = match register {
+            16 => MACHO_CODE_ADDRESS + headers_size as u64, // rip
+            _ => 0,
+        };
+        write.write_all(&value.to_le_bytes())?;
+    }
+
+    write.write_all(&code)?;
+
+    Ok(())
+}
+
+/// The bin target: an executable in some format, the one of the host by
+/// default
+#[cfg(feature = "native-emit")]
+pub struct Executable {
+    pub format: BinaryFormat,
+}
+
+#[cfg(feature = "native-emit")]
+impl Default for Executable {
+    fn default() -> Self {
+        Executable {
+            format: BinaryFormat::host(),
+        }
+    }
+}
+
+#[cfg(feature = "native-emit")]
+impl Backend for Executable {
+    fn emit(&self, ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+        match self.format {
+            BinaryFormat::Elf => write_elf(ast, config, write),
+            BinaryFormat::Pe => write_pe(ast, config, write),
+            BinaryFormat::MachO => write_macho(ast, config, write),
+        }
+    }
+}
+
+/// JavaScript typed array of the cells, which wraps the values stored in it
+fn js_cell_array(cell: Cell) -> &'static str {
+    match cell {
+        Cell::U8 => "Uint8Array",
+        Cell::U16 => "Uint16Array",
+        Cell::U32 => "Uint32Array",
+    }
+}
+
+fn write_js_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(format!("{}memory[index] += {};\n", indent, val).as_bytes())?;
+        }
+        NodeKind::Move(val) => {
+            let line = match config.bounds {
+                Bounds::Unchecked => format!("index += {};", val),
+                Bounds::Wrap => format!("index = {};", cell_index(*val, config)),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Write => {
+            write.write_all(
+                format!(
+                    "{}process.stdout.write(Buffer.of(memory[index]));\n",
+                    indent
+                )
+                .as_bytes(),
+            )?;
+        }
+        NodeKind::Read => {
+            write.write_all(format!("{}read();\n", indent).as_bytes())?;
+        }
+        NodeKind::Clear => {
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                // The products of 32-bit cells lose their low bits as doubles
+                let product = match config.cell {
+                    Cell::U32 => format!("Math.imul(memory[index], {})", factor),
+                    _ => format!("memory[index] * {}", factor),
+                };
+                write.write_all(
+                    format!(
+                        "{}memory[{}] += {};\n",
+                        indent,
+                        cell_index(*offset, config),
+                        product
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Loop(node) => {
+            write.write_all(format!("{}while (memory[index] !== 0) {{\n", indent).as_bytes())?;
+            write_js_ast(node, write, config, depth + 1)?;
+            write.write_all(format!("{}}}\n", indent).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_js_ast(node, write, config, depth)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_js(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "js")?;
+    write.write_all(b"\"use strict\";\n")?;
+    write.write_all(b"const fs = require(\"fs\");\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(
+        format!(
+            "const memory = new {}({});\n",
+            js_cell_array(config.cell),
+            config.tape_size
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"let index = 0;\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(
+        format!(
+            "// Read a byte from stdin, {} on end of input\n",
+            eof_behavior(config.eof)
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"function read() {\n")?;
+    write.write_all(b"    const buffer = Buffer.alloc(1);\n")?;
+    write.write_all(b"    try {\n")?;
+    write.write_all(b"        if (fs.readSync(0, buffer, 0, 1, null) === 1) {\n")?;
+    write.write_all(b"            memory[index] = buffer[0];\n")?;
+    if eof_value(config).is_some() {
+        write.write_all(b"            return;\n")?;
+    }
+    write.write_all(b"        }\n")?;
+    write.write_all(b"    } catch (e) {\n")?;
+    write.write_all(b"        if (e.code !== \"EOF\") throw e;\n")?;
+    write.write_all(b"    }\n")?;
+    if let Some(value) = eof_value(config) {
+        write.write_all(format!("    memory[index] = {};\n", value).as_bytes())?;
+    }
+    write.write_all(b"}\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"// bf source code\n")?;
+    write_js_ast(ast, write, config, 0)?;
+
+    Ok(())
+}
+
+fn write_python_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    let modulus = config.cell.max() as u64 + 1;
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(
+                format!(
+                    "{}memory[index] = (memory[index] + {}) % {}\n",
+                    indent, val, modulus
+                )
+                .as_bytes(),
+            )?;
+        }
+        NodeKind::Move(val) => {
+            let line = match config.bounds {
+                Bounds::Unchecked => format!("index += {}", val),
+                Bounds::Wrap => format!("index = {}", cell_index(*val, config)),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Write => {
+            let byte = match config.cell {
+                Cell::U8 => "memory[index]",
+                _ => "memory[index] & 255",
+            };
+            write.write_all(
+                format!("{}sys.stdout.buffer.write(bytes(({},)))\n", indent, byte).as_bytes(),
+            )?;
+        }
+        NodeKind::Read => {
+            write.write_all(format!("{}read()\n", indent).as_bytes())?;
+        }
+        NodeKind::Clear => {
+            write.write_all(format!("{}memory[index] = 0\n", indent).as_bytes())?;
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                write.write_all(
+                    format!(
+                        "{0}memory[{1}] = (memory[{1}] + memory[index] * {2}) % {3}\n",
+                        indent,
+                        cell_index(*offset, config),
+                        factor,
+                        modulus
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write.write_all(format!("{}memory[index] = 0\n", indent).as_bytes())?;
+        }
+        NodeKind::Loop(node) => {
+            write.write_all(format!("{}while memory[index] != 0:\n", indent).as_bytes())?;
+            if let NodeKind::Block(nodes) = &node.kind {
+                if nodes.is_empty() {
+                    write.write_all(format!("{}    pass\n", indent).as_bytes())?;
+                }
+            }
+            write_python_ast(node, write, config, depth + 1)?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_python_ast(node, write, config, depth)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_python(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "py")?;
+    // Python refuses more nested blocks
+    if loop_depth(ast) > 20 {
+        return Err(CodegenError::Unsupported(String::from(
+            "the py target nests up to 20 loops",
+        )));
+    }
+    write.write_all(b"#!/usr/bin/env python3\n")?;
+    write.write_all(b"import sys\n")?;
+    write.write_all(b"\n")?;
+    let memory = match config.cell {
+        Cell::U8 => format!("memory = bytearray({})\n", config.tape_size),
+        _ => format!("memory = [0] * {}\n", config.tape_size),
+    };
+    write.write_all(memory.as_bytes())?;
+    write.write_all(b"index = 0\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"def read():\n")?;
+    write.write_all(
+        format!(
+            "    \"\"\"Read a byte from stdin, {} on end of input\"\"\"\n",
+            eof_behavior(config.eof)
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"    sys.stdout.buffer.flush()\n")?;
+    write.write_all(b"    byte = sys.stdin.buffer.read(1)\n")?;
+    match eof_value(config) {
+        None => {
+            write.write_all(b"    if byte:\n")?;
+            write.write_all(b"        memory[index] = byte[0]\n")?;
+        }
+        Some(value) => write.write_all(
+            format!("    memory[index] = byte[0] if byte else {}\n", value).as_bytes(),
+        )?,
+    }
+    write.write_all(b"\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"# bf source code\n")?;
+    write_python_ast(ast, write, config, 0)?;
+
+    Ok(())
+}
+
+/// Go type of a cell
+fn go_cell_type(cell: Cell) -> &'static str {
+    match cell {
+        Cell::U8 => "byte",
+        Cell::U16 => "uint16",
+        Cell::U32 => "uint32",
+    }
+}
+
+fn write_go_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "\t".repeat(depth);
+    // Go refuses constants overflowing the type of the cells
+    let max = config.cell.max();
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(
+                format!("{}memory[index] += {}\n", indent, *val as u32 & max).as_bytes(),
+            )?;
+        }
+        NodeKind::Move(val) => {
+            let line = match config.bounds {
+                Bounds::Unchecked => format!("index += {}", val),
+                Bounds::Wrap => format!("index = {}", cell_index(*val, config)),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Write => {
+            let byte = match config.cell {
+                Cell::U8 => "memory[index]",
+                _ => "byte(memory[index])",
+            };
+            write.write_all(format!("{}out.WriteByte({})\n", indent, byte).as_bytes())?;
+        }
+        NodeKind::Read => {
+            write.write_all(format!("{}read()\n", indent).as_bytes())?;
+        }
+        NodeKind::Clear => {
+            write.write_all(format!("{}memory[index] = 0\n", indent).as_bytes())?;
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                write.write_all(
+                    format!(
+                        "{}memory[{}] += memory[index] * {}\n",
+                        indent,
+                        cell_index(*offset, config),
+                        *factor as u32 & max
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write.write_all(format!("{}memory[index] = 0\n", indent).as_bytes())?;
+        }
+        NodeKind::Loop(node) => {
+            write.write_all(format!("{}for memory[index] != 0 {{\n", indent).as_bytes())?;
+            write_go_ast(node, write, config, depth + 1)?;
+            write.write_all(format!("{}}}\n", indent).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_go_ast(node, write, config, depth)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_go(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "go")?;
+    let cell_type = go_cell_type(config.cell);
+    write.write_all(b"package main\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"import (\n")?;
+    write.write_all(b"\t\"bufio\"\n")?;
+    write.write_all(b"\t\"os\"\n")?;
+    write.write_all(b")\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"var (\n")?;
+    write.write_all(format!("\tmemory [{}]{}\n", config.tape_size, cell_type).as_bytes())?;
+    write.write_all(b"\tindex  int\n")?;
+    write.write_all(b"\tin     = bufio.NewReader(os.Stdin)\n")?;
+    write.write_all(b"\tout    = bufio.NewWriter(os.Stdout)\n")?;
+    write.write_all(b")\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(
+        format!(
+            "// read reads a byte from stdin, {} on end of input\n",
+            eof_behavior(config.eof)
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"func read() {\n")?;
+    write.write_all(b"\tout.Flush()\n")?;
+    write.write_all(b"\tif c, err := in.ReadByte(); err == nil {\n")?;
+    match config.cell {
+        Cell::U8 => write.write_all(b"\t\tmemory[index] = c\n")?,
+        _ => write.write_all(format!("\t\tmemory[index] = {}(c)\n", cell_type).as_bytes())?,
+    }
+    if let Some(value) = eof_value(config) {
+        write.write_all(b"\t} else {\n")?;
+        write.write_all(format!("\t\tmemory[index] = {}\n", value).as_bytes())?;
+    }
+    write.write_all(b"\t}\n")?;
+    write.write_all(b"}\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"func main() {\n")?;
+    write.write_all(b"\tdefer out.Flush()\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"\t// bf source code\n")?;
+    write_go_ast(ast, write, config, 1)?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+fn write_lua_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    let modulus = config.cell.max() as u64 + 1;
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(
+                format!(
+                    "{}memory[index] = (memory[index] + {}) % {}\n",
+                    indent, val, modulus
+                )
+                .as_bytes(),
+            )?;
+        }
+        NodeKind::Move(val) => {
+            let line = match config.bounds {
+                Bounds::Unchecked => format!("index = index + {}", val),
+                Bounds::Wrap => format!("index = {}", cell_index(*val, config)),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Write => {
+            let byte = match config.cell {
+                Cell::U8 => "memory[index]",
+                _ => "memory[index] % 256",
+            };
+            write.write_all(format!("{}io.write(string.char({}))\n", indent, byte).as_bytes())?;
+        }
+        NodeKind::Read => {
+            write.write_all(format!("{}read()\n", indent).as_bytes())?;
+        }
+        NodeKind::Clear => {
+            write.write_all(format!("{}memory[index] = 0\n", indent).as_bytes())?;
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                write.write_all(
+                    format!(
+                        "{0}memory[{1}] = (memory[{1}] + memory[index] * {2}) % {3}\n",
+                        indent,
+                        cell_index(*offset, config),
+                        factor,
+                        modulus
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write.write_all(format!("{}memory[index] = 0\n", indent).as_bytes())?;
+        }
+        NodeKind::Loop(node) => {
+            write.write_all(format!("{}while memory[index] ~= 0 do\n", indent).as_bytes())?;
+            write_lua_ast(node, write, config, depth + 1)?;
+            write.write_all(format!("{}end\n", indent).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_lua_ast(node, write, config, depth)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_lua(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "lua")?;
+    write.write_all(b"local memory = {}\n")?;
+    write.write_all(format!("for i = 0, {} do\n", config.tape_size - 1).as_bytes())?;
+    write.write_all(b"    memory[i] = 0\n")?;
+    write.write_all(b"end\n")?;
+    write.write_all(b"local index = 0\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(
+        format!(
+            "-- Read a byte from stdin, {} on end of input\n",
+            eof_behavior(config.eof)
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"local function read()\n")?;
+    write.write_all(b"    io.stdout:flush()\n")?;
+    write.write_all(b"    local c = io.read(1)\n")?;
+    write.write_all(b"    if c then\n")?;
+    write.write_all(b"        memory[index] = c:byte()\n")?;
+    if let Some(value) = eof_value(config) {
+        write.write_all(b"    else\n")?;
+        write.write_all(format!("        memory[index] = {}\n", value).as_bytes())?;
+    }
+    write.write_all(b"    end\n")?;
+    write.write_all(b"end\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"-- bf source code\n")?;
+    write_lua_ast(ast, write, config, 0)?;
+
+    Ok(())
+}
+
+/// Java type of a cell, char being its only unsigned type
+fn java_cell_type(cell: Cell) -> &'static str {
+    match cell {
+        Cell::U8 => "byte",
+        Cell::U16 => "char",
+        Cell::U32 => "int",
+    }
+}
+
+fn write_java_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(format!("{}memory[index] += {};\n", indent, val).as_bytes())?;
+        }
+        NodeKind::Move(val) => {
+            let line = match config.bounds {
+                Bounds::Unchecked => format!("index += {};", val),
+                Bounds::Wrap => format!("index = {};", cell_index(*val, config)),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Write => {
+            write.write_all(format!("{}System.out.write(memory[index]);\n", indent).as_bytes())?;
+        }
+        NodeKind::Read => {
+            write.write_all(format!("{}System.out.flush();\n", indent).as_bytes())?;
+            let cell_type = java_cell_type(config.cell);
+            let line = match config.eof {
+                Eof::Unchanged => format!(
+                    "if ((c = System.in.read()) != -1) memory[index] = ({}) c;",
+                    cell_type
+                ),
+                Eof::Zero => format!(
+                    "memory[index] = ({}) ((c = System.in.read()) == -1 ? 0 : c);",
+                    cell_type
+                ),
+                Eof::MinusOne => format!("memory[index] = ({}) System.in.read();", cell_type),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Clear => {
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                write.write_all(
+                    format!(
+                        "{}memory[{}] += memory[index] * {};\n",
+                        indent,
+                        cell_index(*offset, config),
+                        factor
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Loop(node) => {
+            write.write_all(format!("{}while (memory[index] != 0) {{\n", indent).as_bytes())?;
+            write_java_ast(node, write, config, depth + 1)?;
+            write.write_all(format!("{}}}\n", indent).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_java_ast(node, write, config, depth)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_java(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "java")?;
+    write.write_all(b"import java.io.IOException;\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"class Main {\n")?;
+    write.write_all(b"    public static void main(String[] args) throws IOException {\n")?;
+    write.write_all(
+        format!(
+            "        {0}[] memory = new {0}[{1}];\n",
+            java_cell_type(config.cell),
+            config.tape_size
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"        int index = 0;\n")?;
+    write.write_all(b"        int c;\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"        // bf source code\n")?;
+    write_java_ast(ast, write, config, 2)?;
+    write.write_all(b"        System.out.flush();\n")?;
+    write.write_all(b"    }\n")?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// C# type of a cell
+fn csharp_cell_type(cell: Cell) -> &'static str {
+    match cell {
+        Cell::U8 => "byte",
+        Cell::U16 => "ushort",
+        Cell::U32 => "uint",
+    }
+}
+
+fn write_csharp_ast(
+    ast: &Node,
+    config: &Config,
+    write: &mut dyn Write,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    match &ast.kind {
+        NodeKind::Loop(node) => {
+            write.write_all(format!("{}while (memory[index] != 0)\n", indent).as_bytes())?;
+            write.write_all(format!("{}{{\n", indent).as_bytes())?;
+            write_csharp_ast(node, config, write, depth + 1)?;
+            write.write_all(format!("{}}}\n", indent).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_csharp_ast(node, config, write, depth)?;
+            }
+        }
+        // The commands are written by their own function, keeping the frames
+        // of the recursion on the nesting small
+        _ => write_csharp_command(ast, config, write, depth)?,
+    }
+
+    Ok(())
+}
+
+fn write_csharp_command(
+    ast: &Node,
+    config: &Config,
+    write: &mut dyn Write,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    let cell_type = csharp_cell_type(config.cell);
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(
+                format!(
+                    "{}memory[index] = ({})(memory[index] {} {});\n",
+                    indent,
+                    cell_type,
+                    if *val < 0 { '-' } else { '+' },
+                    val.abs()
+                )
+                .as_bytes(),
+            )?;
+        }
+        NodeKind::Move(val) => {
+            let line = match config.bounds {
+                Bounds::Unchecked => format!("index += {};", val),
+                Bounds::Wrap => format!("index = {};", cell_index(*val, config)),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Write => {
+            let byte = match config.cell {
+                Cell::U8 => "memory[index]",
+                _ => "(byte)memory[index]",
+            };
+            write.write_all(format!("{}output.WriteByte({});\n", indent, byte).as_bytes())?;
+        }
+        NodeKind::Read => {
+            write.write_all(format!("{}output.Flush();\n", indent).as_bytes())?;
+            let line = match config.eof {
+                Eof::Unchanged => format!(
+                    "if ((c = input.ReadByte()) != -1) memory[index] = ({})c;",
+                    cell_type
+                ),
+                Eof::Zero => format!(
+                    "memory[index] = ({})((c = input.ReadByte()) == -1 ? 0 : c);",
+                    cell_type
+                ),
+                Eof::MinusOne => format!("memory[index] = ({})input.ReadByte();", cell_type),
+            };
+            write.write_all(format!("{}{}\n", indent, line).as_bytes())?;
+        }
+        NodeKind::Clear => {
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                write.write_all(
+                    format!(
+                        "{0}memory[{1}] = ({2})(memory[{1}] + memory[index] * {3});\n",
+                        indent,
+                        cell_index(*offset, config),
+                        cell_type,
+                        factor
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            write.write_all(format!("{}memory[index] = 0;\n", indent).as_bytes())?;
+        }
+        NodeKind::Loop(_) | NodeKind::Block(_) => unreachable!("written by write_csharp_ast"),
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_csharp(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_classic(ast, "cs")?;
+    let reads = ast_any(ast, &|node| matches!(node.kind, NodeKind::Read));
+    write.write_all(b"using System;\n")?;
+    write.write_all(b"using System.IO;\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"class Program\n")?;
+    write.write_all(b"{\n")?;
+    write.write_all(b"    static void Main()\n")?;
+    write.write_all(b"    {\n")?;
+    write.write_all(
+        format!(
+            "        var memory = new {}[{}];\n",
+            csharp_cell_type(config.cell),
+            config.tape_size
+        )
+        .as_bytes(),
+    )?;
+    write.write_all(b"        var index = 0;\n")?;
+    if reads {
+        write.write_all(b"        int c;\n")?;
+        write.write_all(b"        var input = Console.OpenStandardInput();\n")?;
+    }
+    write.write_all(b"        var output = new BufferedStream(Console.OpenStandardOutput());\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"        // bf source code\n")?;
+    write_csharp_ast(ast, config, write, 2)?;
+    write.write_all(b"        output.Flush();\n")?;
+    write.write_all(b"    }\n")?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+// Constant pool of the generated class
+const JVM_CONSTANTS: &[&[u8]] = &[
+    b"\x01\x00\x04Main",                   // #1 Utf8
+    b"\x07\x00\x01",                       // #2 Class Main
+    b"\x01\x00\x10java/lang/Object",       // #3 Utf8
+    b"\x07\x00\x03",                       // #4 Class java/lang/Object
+    b"\x01\x00\x04main",                   // #5 Utf8
+    b"\x01\x00\x16([Ljava/lang/String;)V", // #6 Utf8
+    b"\x01\x00\x04Code",                   // #7 Utf8
+    b"\x01\x00\x10java/lang/System",       // #8 Utf8
+    b"\x07\x00\x08",                       // #9 Class java/lang/System
+    b"\x01\x00\x03out",                    // #10 Utf8
+    b"\x01\x00\x15Ljava/io/PrintStream;",  // #11 Utf8
+    b"\x0c\x00\x0a\x00\x0b",               // #12 NameAndType out
+    b"\x09\x00\x09\x00\x0c",               // #13 Fieldref System.out
+    b"\x01\x00\x02in",                     // #14 Utf8
+    b"\x01\x00\x15Ljava/io/InputStream;",  // #15 Utf8
+    b"\x0c\x00\x0e\x00\x0f",               // #16 NameAndType in
+    b"\x09\x00\x09\x00\x10",               // #17 Fieldref System.in
+    b"\x01\x00\x13java/io/PrintStream",    // #18 Utf8
+    b"\x07\x00\x12",                       // #19 Class java/io/PrintStream
+    b"\x01\x00\x05write",                  // #20 Utf8
+    b"\x01\x00\x04(I)V",                   // #21 Utf8
+    b"\x0c\x00\x14\x00\x15",               // #22 NameAndType write
+    b"\x0a\x00\x13\x00\x16",               // #23 Methodref PrintStream.write
+    b"\x01\x00\x05flush",                  // #24 Utf8
+    b"\x01\x00\x03()V",                    // #25 Utf8
+    b"\x0c\x00\x18\x00\x19",               // #26 NameAndType flush
+    b"\x0a\x00\x13\x00\x1a",               // #27 Methodref PrintStream.flush
+    b"\x01\x00\x13java/io/InputStream",    // #28 Utf8
+    b"\x07\x00\x1c",                       // #29 Class java/io/InputStream
+    b"\x01\x00\x04read",                   // #30 Utf8
+    b"\x01\x00\x03()I",                    // #31 Utf8
+    b"\x0c\x00\x1e\x00\x1f",               // #32 NameAndType read
+    b"\x0a\x00\x1d\x00\x20",               // #33 Methodref InputStream.read
+];
+
+fn write_jvm_ast(ast: &Node, code: &mut Vec<u8>, config: &Config) {
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            // aload_1, iload_2, aload_1, iload_2, baload, bipush val,
+            // iadd, i2b, bastore
+            code.extend_from_slice(&[0x2b, 0x1c, 0x2b, 0x1c, 0x33, 0x10, *val as u8]);
+            code.extend_from_slice(&[0x60, 0x91, 0x54]);
+        }
+        NodeKind::Move(val) => {
+            // wide iinc 2, val
+            let mut val = *val;
+            while val != 0 {
+                let step = val.clamp(i16::MIN as isize, i16::MAX as isize);
+                code.extend_from_slice(&[0xc4, 0x84, 0x00, 0x02]);
+                code.extend_from_slice(&(step as i16).to_be_bytes());
+                val -= step;
+            }
+        }
+        NodeKind::Write => {
+            // getstatic System.out, aload_1, iload_2, baload,
+            // invokevirtual PrintStream.write
+            code.extend_from_slice(&[0xb2, 0x00, 0x0d, 0x2b, 0x1c, 0x33, 0xb6, 0x00, 0x17]);
+        }
+        NodeKind::Read => {
+            // getstatic System.out, invokevirtual PrintStream.flush,
+            // getstatic System.in, invokevirtual InputStream.read, istore_3
+            code.extend_from_slice(&[0xb2, 0x00, 0x0d, 0xb6, 0x00, 0x1b]);
+            code.extend_from_slice(&[0xb2, 0x00, 0x11, 0xb6, 0x00, 0x21, 0x3e]);
+            match config.eof {
+                // iload_3, iflt +8, aload_1, iload_2, iload_3, i2b, bastore
+                Eof::Unchanged => {
+                    code.extend_from_slice(&[0x1d, 0x9b, 0x00, 0x08, 0x2b, 0x1c, 0x1d, 0x91, 0x54])
+                }
+                // aload_1, iload_2, iload_3, iload_3, bipush 31, ishr,
+                // iconst_m1, ixor, iand, i2b, bastore: c & ~(c >> 31)
+                Eof::Zero => code.extend_from_slice(&[
+                    0x2b, 0x1c, 0x1d, 0x1d, 0x10, 0x1f, 0x7a, 0x02, 0x82, 0x7e, 0x91, 0x54,
+                ]),
+                // aload_1, iload_2, iload_3, i2b, bastore
+                Eof::MinusOne => code.extend_from_slice(&[0x2b, 0x1c, 0x1d, 0x91, 0x54]),
+            }
+        }
+        NodeKind::Clear => {
+            // aload_1, iload_2, iconst_0, bastore
+            code.extend_from_slice(&[0x2b, 0x1c, 0x03, 0x54]);
+        }
+        NodeKind::Mul(targets) => {
+            for (offset, factor) in targets.iter() {
+                // aload_1, iload_2, sipush offset, iadd, dup2, baload
+                code.extend_from_slice(&[0x2b, 0x1c, 0x11]);
+                code.extend_from_slice(&(*offset as i16).to_be_bytes());
+                code.extend_from_slice(&[0x60, 0x5c, 0x33]);
+                // aload_1, iload_2, baload, bipush factor, imul, iadd, i2b,
+                // bastore
+                code.extend_from_slice(&[0x2b, 0x1c, 0x33, 0x10, *factor as u8]);
+                code.extend_from_slice(&[0x68, 0x60, 0x91, 0x54]);
+            }
+            // aload_1, iload_2, iconst_0, bastore
+            code.extend_from_slice(&[0x2b, 0x1c, 0x03, 0x54]);
+        }
+        NodeKind::Loop(node) => {
+            // aload_1, iload_2, baload, ifeq end
+            let begin = code.len();
+            code.extend_from_slice(&[0x2b, 0x1c, 0x33, 0x99, 0, 0]);
+            let ifeq = code.len() - 3;
+            write_jvm_ast(node, code, config);
+
+            // goto begin
+            let goto = code.len();
+            code.push(0xa7);
+            code.extend_from_slice(&((begin as isize - goto as isize) as i16).to_be_bytes());
+            let end = code.len();
+            code[ifeq + 1..ifeq + 3]
+                .copy_from_slice(&((end as isize - ifeq as isize) as i16).to_be_bytes());
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_jvm_ast(node, code, config);
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+}
+
+fn write_jvm(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "class")?;
+    require_classic(ast, "class")?;
+    if config.tape_size > i16::MAX as usize {
+        return Err(CodegenError::Unsupported(String::from(
+            "the class target supports tapes of up to 32767 cells",
+        )));
+    }
+    // sipush tape_size, newarray byte, astore_1, iconst_0, istore_2
+    let mut code = vec![0x11];
+    code.extend_from_slice(&(config.tape_size as u16).to_be_bytes());
+    code.extend_from_slice(&[0xbc, 0x08, 0x4c, 0x03, 0x3d]);
+    write_jvm_ast(ast, &mut code, config);
+    // getstatic System.out, invokevirtual PrintStream.flush, return
+    code.extend_from_slice(&[0xb2, 0x00, 0x0d, 0xb6, 0x00, 0x1b, 0xb1]);
+    if code.len() > u16::MAX as usize {
+        return Err(CodegenError::Unsupported(String::from(
+            "program too large for a JVM method",
+        )));
+    }
+
+    // Header: magic number and version 49.0, which doesn't require stack
+    // map frames
+    write.write_all(b"\xca\xfe\xba\xbe\x00\x00\x00\x31")?;
+
+    // Constant pool
+    write.write_all(&(JVM_CONSTANTS.len() as u16 + 1).to_be_bytes())?;
+    for constant in JVM_CONSTANTS.iter() {
+        write.write_all(constant)?;
+    }
+
+    // public class Main extends Object, without interfaces nor fields
+    write.write_all(b"\x00\x21\x00\x02\x00\x04\x00\x00\x00\x00")?;
+
+    // public static void main(String[]), with a Code attribute using 5 stack
+    // slots and 4 locals (args, memory, index, c)
+    write.write_all(b"\x00\x01\x00\x09\x00\x05\x00\x06\x00\x01\x00\x07")?;
+    write.write_all(&(code.len() as u32 + 12).to_be_bytes())?;
+    write.write_all(b"\x00\x05\x00\x04")?;
+    write.write_all(&(code.len() as u32).to_be_bytes())?;
+    write.write_all(&code)?;
+    write.write_all(b"\x00\x00\x00\x00")?;
+
+    // No class attributes
+    write.write_all(b"\x00\x00")?;
+
+    Ok(())
+}
+
+fn write_qbe_ast(
+    ast: &Node,
+    write: &mut dyn Write,
+    config: &Config,
+    id: &mut usize,
+) -> io::Result<()> {
+    match &ast.kind {
+        NodeKind::Incr(val) => {
+            write.write_all(
+                format!(
+                    "\t%cell =w loadub %index\n\t%cell =w add %cell, {}\n\tstoreb %cell, %index\n",
+                    val
+                )
+                .as_bytes(),
+            )?;
+        }
+        NodeKind::Move(val) => {
+            write.write_all(format!("\t%index =l add %index, {}\n", val).as_bytes())?;
+        }
+        NodeKind::Write => {
+            write.write_all(b"\t%cell =w loadub %index\n\tcall $putchar(w %cell)\n")?;
+        }
+        NodeKind::Read => {
+            *id += 1;
+            let code = match eof_byte(config.eof) {
+                Some(byte) => format!(
+                    "\t%char =w call $getchar()\n\t%eof =w csltw %char, 0\n\tjnz %eof, @read{0}.eof, @read{0}.store\n@read{0}.eof\n\t%char =w copy {1}\n@read{0}.store\n\tstoreb %char, %index\n",
+                    id, byte
+                ),
+                None => format!(
+                    "\t%char =w call $getchar()\n\t%eof =w csltw %char, 0\n\tjnz %eof, @read{0}.end, @read{0}.store\n@read{0}.store\n\tstoreb %char, %index\n@read{0}.end\n",
+                    id
+                ),
+            };
+            write.write_all(code.as_bytes())?;
+        }
+        NodeKind::Clear => {
+            write.write_all(b"\tstoreb 0, %index\n")?;
+        }
+        NodeKind::Mul(targets) => {
+            write.write_all(b"\t%cell =w loadub %index\n")?;
+            for (offset, factor) in targets.iter() {
+                write
+                    .write_all(
+                        format!(
+                            "\t%target =l add %index, {}\n\t%value =w loadub %target\n\t%product =w mul %cell, {}\n\t%value =w add %value, %product\n\tstoreb %value, %target\n",
+                            offset, factor
+                        )
+                        .as_bytes(),
+                    )
+                    ?;
+            }
+            write.write_all(b"\tstoreb 0, %index\n")?;
+        }
+        NodeKind::Loop(node) => {
+            *id += 1;
+            let loop_id = *id;
+            write
+                .write_all(
+                    format!(
+                        "@loop{0}.cond\n\t%cell =w loadub %index\n\tjnz %cell, @loop{0}.body, @loop{0}.end\n@loop{0}.body\n",
+                        loop_id
+                    )
+                    .as_bytes(),
+                )
+                ?;
+            write_qbe_ast(node, write, config, id)?;
+            write.write_all(format!("\tjmp @loop{0}.cond\n@loop{0}.end\n", loop_id).as_bytes())?;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_qbe_ast(node, write, config, id)?;
+            }
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            unreachable!("checked by require_classic")
+        }
+    }
+
+    Ok(())
+}
+
+fn write_qbe(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    require_plain_tape(config, "qbe")?;
+    require_classic(ast, "qbe")?;
+    write.write_all(format!("data $memory = align 1 {{ z {} }}\n", config.tape_size).as_bytes())?;
+    write.write_all(b"\n")?;
+    write.write_all(b"export function w $main() {\n")?;
+    write.write_all(b"@start\n")?;
+    write.write_all(b"\t%index =l copy $memory\n")?;
+    write.write_all(b"\n")?;
+    write.write_all(b"\t# bf source code\n")?;
+    write_qbe_ast(ast, write, config, &mut 0)?;
+    write.write_all(b"\tret 0\n")?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// Write the AST in the given target language
+/// Escape a string for a DOT label
+fn dot_escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Short description of the instruction of a node
+fn node_label(kind: &NodeKind) -> String {
+    match kind {
+        NodeKind::Incr(val) => format!("{} {}", if *val < 0 { "sub" } else { "add" }, val.abs()),
+        NodeKind::Move(val) => format!("move {}", val),
+        NodeKind::Write => String::from("write"),
+        NodeKind::Read => String::from("read"),
+        NodeKind::Clear => String::from("clear"),
+        NodeKind::Mul(targets) => {
+            let targets: Vec<String> = targets
+                .iter()
+                .map(|(offset, factor)| format!("[{}] += {}", offset, factor))
+                .collect();
+            format!("mul {}", targets.join(", "))
+        }
+        NodeKind::Loop(_) => String::from("loop"),
+        NodeKind::Block(_) => String::from("block"),
+        NodeKind::Procedure(_) => String::from("procedure"),
+        NodeKind::Call => String::from("call"),
+        NodeKind::Extended(command) => String::from(command.name()),
+    }
+}
+
+/// Write the nodes of an AST as a DOT graph, returning the id of its root
+fn write_dot_ast(
+    ast: &Node,
+    config: &Config,
+    write: &mut dyn Write,
+    id: &mut usize,
+) -> io::Result<usize> {
+    let node_id = *id;
+    *id += 1;
+
+    let label = node_label(&ast.kind);
+    let mut attributes = vec![format!(
+        "label=\"{}\\n{}..{}\"",
+        dot_escape(&label),
+        ast.span.start,
+        ast.span.end
+    )];
+    match ast.kind {
+        // Loops rewritten by the optimizer
+        NodeKind::Clear | NodeKind::Mul(_) => attributes.push(String::from("peripheries=2")),
+        NodeKind::Loop(_) | NodeKind::Procedure(_) => {
+            attributes.push(String::from("shape=ellipse"))
+        }
+        NodeKind::Block(_) => attributes.push(String::from("shape=point")),
+        _ => {}
+    }
+    // Shade by the share of executions, on a log scale
+    if let Some(profile) = &config.profile {
+        if !matches!(ast.kind, NodeKind::Block(_)) && profile.max() > 0 {
+            let count = profile.count(ast.span);
+            let heat = ((count + 1) as f64).ln() / ((profile.max() + 1) as f64).ln();
+            attributes.push(format!(
+                "style=filled, fillcolor=\"0.000 {:.3} 1.000\", tooltip=\"{} executions\"",
+                heat, count
+            ));
+        }
+    }
+    write.write_all(format!("    n{} [{}];\n", node_id, attributes.join(", ")).as_bytes())?;
+
+    let children = match &ast.kind {
+        NodeKind::Loop(node) => std::slice::from_ref(node.as_ref()),
+        NodeKind::Procedure(node) => std::slice::from_ref(node.as_ref()),
+        NodeKind::Block(nodes) => nodes.as_slice(),
+        _ => &[],
+    };
+    for child in children.iter() {
+        let child_id = write_dot_ast(child, config, write, id)?;
+        write.write_all(format!("    n{} -> n{};\n", node_id, child_id).as_bytes())?;
+    }
+
+    Ok(node_id)
+}
+
+/// Write the structure of the program as a Graphviz graph
+///
+/// Nodes are shaded by their number of executions when a profile is
+/// available.
+fn write_dot(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    write.write_all(b"digraph program {\n")?;
+    write.write_all(b"    node [shape=box, fontname=\"monospace\"];\n")?;
+    write.write_all(b"\n")?;
+    write_dot_ast(ast, config, write, &mut 0)?;
+    write.write_all(b"}\n")?;
+
+    Ok(())
+}
+
+/// Write the optimized AST as JSON
+///
+/// The layout is the one serde derives for `Node`, and can be read back as
+/// an input source.
+pub fn write_ast_json(
+    ast: &Node,
+    _config: &Config,
+    write: &mut dyn Write,
+) -> Result<(), CodegenError> {
+    writeln!(write, "{}", node_to_json(ast))?;
+
+    Ok(())
+}
+
+/// Write the tokens of the source, one per line after their byte offset
+fn write_tokens(_ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    let source = config.source.as_deref().ok_or_else(|| {
+        CodegenError::Unsupported(String::from("the tokens target needs a brainfuck source"))
+    })?;
+    for (offset, token) in parse_source_in(source, config.language) {
+        let name = match token {
+            Token::Incr => "incr",
+            Token::Decr => "decr",
+            Token::MoveLeft => "move left",
+            Token::MoveRight => "move right",
+            Token::Write => "write",
+            Token::Read => "read",
+            Token::LoopBegin => "loop begin",
+            Token::LoopEnd => "loop end",
+            Token::ProcedureBegin => "procedure begin",
+            Token::ProcedureEnd => "procedure end",
+            Token::Call => "call",
+            Token::Extended(command) => command.name(),
+        };
+        let text = config.language.token_text(token);
+        write.write_all(format!("{:>6} {} {}\n", offset, text, name).as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Write an AST as an indented tree, with the source span of each node
+fn write_tree_ast(ast: &Node, depth: usize, write: &mut dyn Write) -> io::Result<()> {
+    write.write_all(
+        format!(
+            "{:indent$}{} {}..{}\n",
+            "",
+            node_label(&ast.kind),
+            ast.span.start,
+            ast.span.end,
+            indent = depth * 2
+        )
+        .as_bytes(),
+    )?;
+    match &ast.kind {
+        NodeKind::Loop(node) => write_tree_ast(node, depth + 1, write),
+        NodeKind::Procedure(node) => write_tree_ast(node, depth + 1, write),
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                write_tree_ast(node, depth + 1, write)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Write the AST of the source, as parsed, before any optimization
+fn write_tree(_ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    let source = config.source.as_deref().ok_or_else(|| {
+        CodegenError::Unsupported(String::from("the ast target needs a brainfuck source"))
+    })?;
+    let ast = build_ast(parse_source_in(source, config.language))
+        .map_err(|error| CodegenError::Unsupported(format!("the ast target got an {}", error)))?;
+    Ok(write_tree_ast(&ast, 0, write)?)
+}
+
+/// Write the optimized AST given to the other targets
+fn write_ir(ast: &Node, _config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    Ok(write_tree_ast(ast, 0, write)?)
+}
+
+/// Record the execution count of each source byte of an AST
+struct ProfileOffsets<'a> {
+    profile: &'a Profile,
+    counts: &'a mut [Option<u64>], // Count of each byte of the source
+}
+
+impl Visitor for ProfileOffsets<'_> {
+    fn visit(&mut self, node: &Node) {
+        let count = Some(self.profile.count(node.span));
+        match &node.kind {
+            NodeKind::Loop(_) | NodeKind::Procedure(_) => {
+                self.counts[node.span.start] = count;
+                self.counts[node.span.end - 1] = count;
+            }
+            NodeKind::Block(_) => {}
+            _ => self.counts[node.span.start..node.span.end].fill(count),
+        }
+        walk(self, node);
+    }
+}
+
+/// Write the source as a standalone, highlighted HTML page
+///
+/// Hovering a bracket highlights its match, and commands show their number
+/// of executions when a profile is available.
+fn write_html(ast: &Node, config: &Config, write: &mut dyn Write) -> Result<(), CodegenError> {
+    let source = config
+        .source
+        .as_deref()
+        .filter(|_| config.language == Language::Classic)
+        .ok_or_else(|| {
+            CodegenError::Unsupported(String::from(
+                "the html target needs a classic brainfuck source",
+            ))
+        })?;
+
+    // Pair the brackets
+    let mut matches = vec![None; source.len()];
+    let mut stack = Vec::new();
+    for (offset, c) in source.char_indices() {
+        match c {
+            '[' => stack.push(offset),
+            ']' => {
+                if let Some(begin) = stack.pop() {
+                    matches[begin] = Some(offset);
+                    matches[offset] = Some(begin);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut counts = vec![None; source.len()];
+    if let Some(profile) = &config.profile {
+        ProfileOffsets {
+            profile,
+            counts: &mut counts,
+        }
+        .visit(ast);
+    }
+
+    let title = config
+        .line_directives
+        .as_ref()
+        .map_or("brainfuck", |source_map| source_map.path.as_str());
+    write.write_all(b"<!DOCTYPE html>\n")?;
+    write.write_all(b"<html>\n")?;
+    write.write_all(b"<head>\n")?;
+    write.write_all(b"<meta charset=\"utf-8\">\n")?;
+    write.write_all(format!("<title>{}</title>\n", html_escape(title)).as_bytes())?;
+    write.write_all(b"<style>\n")?;
+    write.write_all(b"pre { font-family: monospace; line-height: 1.4; }\n")?;
+    write.write_all(b".comment { color: #999; }\n")?;
+    write.write_all(b".incr { color: #080; }\n")?;
+    write.write_all(b".decr { color: #c00; }\n")?;
+    write.write_all(b".move { color: #00c; }\n")?;
+    write.write_all(b".io { color: #c60; font-weight: bold; }\n")?;
+    write.write_all(b".loop { color: #808; font-weight: bold; }\n")?;
+    write.write_all(b".unmatched { background: #fcc; }\n")?;
+    write.write_all(b".match { background: #ff0; }\n")?;
+    write.write_all(b"</style>\n")?;
+    write.write_all(b"</head>\n")?;
+    write.write_all(b"<body>\n")?;
+    write.write_all(b"<pre>")?;
+    let mut comment = String::new();
+    for (offset, c) in source.char_indices() {
+        let class = match c {
+            '+' => "incr",
+            '-' => "decr",
+            '<' | '>' => "move",
+            '.' | ',' => "io",
+            '[' | ']' => "loop",
+            _ => {
+                comment.push(c);
+                continue;
+            }
+        };
+        write_html_comment(&mut comment, write)?;
+        let mut attributes = format!("id=\"c{}\" class=\"{}", offset, class);
+        if let '[' | ']' = c {
+            match matches[offset] {
+                Some(other) => attributes += &format!("\" data-match=\"c{}", other),
+                None => attributes += " unmatched",
+            }
+        }
+        attributes += "\"";
+        if let Some(count) = counts[offset] {
+            attributes += &format!(" title=\"{} executions\"", count);
+        }
+        write.write_all(
+            format!(
+                "<span {}>{}</span>",
+                attributes,
+                html_escape(&c.to_string())
+            )
+            .as_bytes(),
+        )?;
+    }
+    write_html_comment(&mut comment, write)?;
+    write.write_all(b"</pre>\n")?;
+    write.write_all(b"<script>\n")?;
+    write.write_all(b"for (const bracket of document.querySelectorAll(\"[data-match]\")) {\n")?;
+    write.write_all(b"    const other = document.getElementById(bracket.dataset.match);\n")?;
+    write.write_all(
+        b"    for (const [event, toggle] of [[\"mouseenter\", true], [\"mouseleave\", false]]) {\n",
+    )?;
+    write.write_all(b"        bracket.addEventListener(event, () => {\n")?;
+    write.write_all(b"            bracket.classList.toggle(\"match\", toggle);\n")?;
+    write.write_all(b"            other.classList.toggle(\"match\", toggle);\n")?;
+    write.write_all(b"        });\n")?;
+    write.write_all(b"    }\n")?;
+    write.write_all(b"}\n")?;
+    write.write_all(b"</script>\n")?;
+    write.write_all(b"</body>\n")?;
+    write.write_all(b"</html>\n")?;
+
+    Ok(())
+}
+
+/// Flush the pending comment of the HTML output
+fn write_html_comment(comment: &mut String, write: &mut dyn Write) -> io::Result<()> {
+    if !comment.is_empty() {
+        write.write_all(
+            format!("<span class=\"comment\">{}</span>", html_escape(comment)).as_bytes(),
+        )?;
+        comment.clear();
+    }
+
+    Ok(())
+}
+
+/// Escape the HTML special characters of a string
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A code generator for some output language
+pub trait Backend {
+    /// Emit the program described by the AST
+    fn emit(&self, ast: &Node, config: &Config, out: &mut dyn Write) -> Result<(), CodegenError>;
+}
+
+impl<F> Backend for F
+where
+    F: Fn(&Node, &Config, &mut dyn Write) -> Result<(), CodegenError>,
+{
+    fn emit(&self, ast: &Node, config: &Config, out: &mut dyn Write) -> Result<(), CodegenError> {
+        self(ast, config, out)
+    }
+}
+
+/// A named backend, with the file extensions it is guessed from
+pub struct Target {
+    pub name: String,
+    pub extensions: Vec<String>,
+    pub backend: Box<dyn Backend>,
+}
+
+/// The set of available targets
+pub struct Registry {
+    targets: Vec<Target>,
+}
+
+impl Registry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Registry {
+            targets: Vec::new(),
+        }
+    }
+
+    /// Add a target, replacing any previous target of the same name
+    pub fn register(&mut self, name: &str, extensions: &[&str], backend: impl Backend + 'static) {
+        self.targets.retain(|target| target.name != name);
+        self.targets.push(Target {
+            name: name.to_string(),
+            extensions: extensions
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+            backend: Box::new(backend),
+        });
+    }
+
+    /// Find a target by name
+    pub fn get(&self, name: &str) -> Option<&Target> {
+        self.targets.iter().find(|target| target.name == name)
+    }
+
+    /// Find the target producing files with the given extension
+    pub fn for_extension(&self, extension: &str) -> Option<&Target> {
+        self.targets
+            .iter()
+            .find(|target| target.extensions.iter().any(|ext| ext == extension))
+    }
+
+    /// The registered targets, in registration order
+    pub fn targets(&self) -> impl Iterator<Item = &Target> {
+        self.targets.iter()
+    }
+}
+
+impl Default for Registry {
+    /// A registry with all the builtin targets, of the enabled features
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register("bf", &["bf"], write_bf);
+        registry.register("bf-pretty", &[], PrettyBf::default());
+        registry.register("bf-obfuscated", &[], ObfuscatedBf::default());
+        for (name, dialect) in DIALECTS.iter() {
+            registry.register(
+                name,
+                &[name],
+                move |ast: &Node, _: &Config, write: &mut dyn Write| {
+                    require_classic(ast, name)?;
+                    write_dialect(ast, dialect, write)
+                },
+            );
+        }
+        registry.register("c", &["c"], write_c);
+        registry.register("c-lib", &[], write_c_library);
+        registry.register("so", &["so"], write_shared_library);
+        registry.register("avr", &[], write_avr);
+        registry.register("rs", &["rs"], write_rust);
+        registry.register("rs-nostd", &[], write_rust_nostd);
+        #[cfg(feature = "llvm")]
+        registry.register("ll", &["ll"], write_llvm);
+        #[cfg(feature = "wasm-emit")]
+        registry.register("wat", &["wat"], write_wat);
+        #[cfg(feature = "wasm-emit")]
+        registry.register("wasm", &["wasm"], write_wasm);
+        registry.register("asm-x86_64", &[], write_x86_64);
+        registry.register("asm-riscv64", &[], write_riscv64);
+        registry.register("6502", &[], write_6502);
+        #[cfg(feature = "native-emit")]
+        {
+            registry.register("elf", &["elf"], write_elf);
+            registry.register("pe", &["exe"], write_pe);
+            registry.register("macho", &[], write_macho);
+            registry.register("bin", &[], Executable::default());
+        }
+        registry.register("js", &["js"], write_js);
+        registry.register("py", &["py"], write_python);
+        registry.register("go", &["go"], write_go);
+        registry.register("lua", &["lua"], write_lua);
+        registry.register("java", &["java"], write_java);
+        registry.register("cs", &["cs"], write_csharp);
+        registry.register("class", &["class"], write_jvm);
+        registry.register("qbe", &["qbe"], write_qbe);
+        registry.register("dot", &["dot", "gv"], write_dot);
+        registry.register("ast.json", &["json"], write_ast_json);
+        registry.register("tokens", &[], write_tokens);
+        registry.register("ast", &[], write_tree);
+        registry.register("ir", &[], write_ir);
+        registry.register("html", &["html"], write_html);
+        registry
+    }
+}
+
+/// Write the program of a native target in a directory, returning the
+/// command compiling it into the executable `program` of the directory
+///
+/// The C target is built with `$CC` (`cc` by default) and the Rust one with
+/// `$RUSTC` (`rustc` by default).
+pub fn native_compiler(
+    ast: &Node,
+    config: &Config,
+    target: &str,
+    dir: &Path,
+) -> Result<Command, CodegenError> {
+    let source_path = dir.join(format!("program.{}", target));
+    let mut source = File::create(&source_path)?;
+    let mut compiler = match target {
+        "c" => {
+            write_c(ast, config, &mut source)?;
+            let mut command = Command::new(env::var_os("CC").unwrap_or_else(|| "cc".into()));
+            command.args(["-O2", "-o"]);
+            command
+        }
+        "rs" => {
+            write_rust(ast, config, &mut source)?;
+            let mut command = Command::new(env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()));
+            command.args(["--edition", "2021", "-C", "opt-level=3", "-o"]);
+            command
+        }
+        _ => {
+            return Err(CodegenError::Unsupported(format!(
+                "the {} target can't be compiled natively",
+                target
+            )))
+        }
+    };
+    compiler.arg(dir.join("program")).arg(&source_path);
+    Ok(compiler)
+}
+
+/// Host of the programs of the wasm target on node, giving them the standard
+/// streams
+const WASM_NODE_HOST: &str = r#""use strict";
+const fs = require("fs");
+
+const input = Buffer.alloc(1);
+const output = [];
+function flush() {
+    fs.writeSync(1, Buffer.from(output));
+    output.length = 0;
+}
+function getchar() {
+    flush();
+    try {
+        return fs.readSync(0, input, 0, 1, null) === 1 ? input[0] : -1;
+    } catch (e) {
+        if (e.code !== "EOF") throw e;
+        return -1;
+    }
+}
+
+const program = new WebAssembly.Module(fs.readFileSync(process.argv[2]));
+const env = { putchar: (c) => output.push(c), getchar };
+new WebAssembly.Instance(program, { env }).exports.main();
+flush();
+"#;
+
+/// The commands building the program of a target and running it on the
+/// standard streams, for selftest and the tests of the backends
+#[doc(hidden)]
+pub struct Runner {
+    pub build: Vec<Command>, // Run in order, each of them succeeding
+    pub run: Command,
+}
+
+/// Whether `runner` can run the programs of a target on this system, given
+/// the tools of the target
+#[doc(hidden)]
+pub fn runs_here(target: &str) -> bool {
+    match target {
+        "c" | "rs" | "qbe" | "js" | "py" | "go" | "lua" | "java" | "class" | "cs" => true,
+        "ll" => cfg!(feature = "llvm"),
+        "wasm" => cfg!(feature = "wasm-emit"),
+        "asm-x86_64" => cfg!(all(target_os = "linux", target_arch = "x86_64")),
+        "asm-riscv64" => cfg!(all(target_os = "linux", target_arch = "riscv64")),
+        "elf" => cfg!(all(
+            feature = "native-emit",
+            target_os = "linux",
+            target_arch = "x86_64"
+        )),
+        "macho" => cfg!(all(
+            feature = "native-emit",
+            target_os = "macos",
+            target_arch = "x86_64"
+        )),
+        "pe" => cfg!(all(
+            feature = "native-emit",
+            windows,
+            target_arch = "x86_64"
+        )),
+        _ => false,
+    }
+}
+
+/// The C compiler, `$CC` or `cc`, which also assembles and links
+fn c_compiler() -> Command {
+    Command::new(env::var_os("CC").unwrap_or_else(|| "cc".into()))
+}
+
+/// The LLVM compiler, asked for the opaque pointers of the ll target in
+/// LLVM 14, where they aren't the default
+fn llvm_compiler() -> Command {
+    let mut command = Command::new("llc");
+    let version = Command::new("llc").arg("--version").output();
+    if version
+        .is_ok_and(|version| String::from_utf8_lossy(&version.stdout).contains("LLVM version 14."))
+    {
+        command.arg("-opaque-pointers");
+    }
+    command.arg("-relocation-model=pic");
+    command
+}
+
+/// Write the program of a target in a directory, returning the commands
+/// building it and running it
+///
+/// The tools are the usual ones of each language, and may be missing: `$CC`
+/// for C and the assembled targets, `$RUSTC` for Rust, `llc`, `qbe`,
+/// `node` for JavaScript and WebAssembly, `python3`, `go`, `lua`, `javac` and
+/// `java`, and `mcs` and `mono` for C#. The targets for which `runs_here` is
+/// false are unsupported.
+#[doc(hidden)]
+pub fn runner(
+    ast: &Node,
+    config: &Config,
+    target: &str,
+    dir: &Path,
+) -> Result<Runner, CodegenError> {
+    if !runs_here(target) {
+        return Err(CodegenError::Unsupported(format!(
+            "the {} target can't run on this system",
+            target
+        )));
+    }
+    let program = dir.join("program");
+    if let "c" | "rs" = target {
+        return Ok(Runner {
+            build: vec![native_compiler(ast, config, target, dir)?],
+            run: Command::new(program),
+        });
+    }
+
+    // The executables are run as they are, and the class is named after
+    // the one it holds
+    let source_path = match target {
+        "elf" | "macho" => program.clone(),
+        "pe" => dir.join("program.exe"),
+        "class" => dir.join("Main.class"),
+        "asm-x86_64" | "asm-riscv64" => dir.join("program.s"),
+        _ => dir.join(format!("program.{}", target)),
+    };
+    let registry = Registry::default();
+    let backend = &registry.get(target).unwrap().backend;
+    let mut source = File::create(&source_path)?;
+    backend.emit(ast, config, &mut source)?;
+    #[cfg(unix)]
+    if let "elf" | "macho" = target {
+        use std::os::unix::fs::PermissionsExt;
+        source.set_permissions(fs::Permissions::from_mode(0o755))?;
+    }
+
+    let mut build = Vec::new();
+    let mut run = Command::new(&program);
+    match target {
+        "ll" | "qbe" => {
+            let assembly = dir.join("program.s");
+            let mut compile = match target {
+                "ll" => llvm_compiler(),
+                _ => Command::new("qbe"),
+            };
+            compile.arg("-o").arg(&assembly).arg(&source_path);
+            let mut link = c_compiler();
+            link.arg("-o").arg(&program).arg(&assembly);
+            build.extend([compile, link]);
+        }
+        "asm-x86_64" | "asm-riscv64" => {
+            let mut assemble = c_compiler();
+            assemble.args(["-nostdlib", "-static", "-o"]);
+            assemble.arg(&program).arg(&source_path);
+            build.push(assemble);
+        }
+        "go" => {
+            let mut compile = Command::new("go");
+            compile
+                .args(["build", "-o"])
+                .arg(&program)
+                .arg(&source_path);
+            build.push(compile);
+        }
+        "java" => {
+            let mut compile = Command::new("javac");
+            compile.arg("-d").arg(dir).arg(&source_path);
+            build.push(compile);
+            run = Command::new("java");
+            run.arg("-cp").arg(dir).arg("Main");
+        }
+        "class" => {
+            run = Command::new("java");
+            run.arg("-cp").arg(dir).arg("Main");
+        }
+        "cs" => {
+            let assembly = dir.join("program.exe");
+            let mut out = std::ffi::OsString::from("-out:");
+            out.push(&assembly);
+            let mut compile = Command::new("mcs");
+            compile.arg(out).arg(&source_path);
+            build.push(compile);
+            run = Command::new("mono");
+            run.arg(assembly);
+        }
+        "wasm" => {
+            let host = dir.join("host.js");
+            fs::write(&host, WASM_NODE_HOST)?;
+            run = Command::new("node");
+            run.arg(host).arg(&source_path);
+        }
+        "js" | "py" | "lua" => {
+            run = Command::new(match target {
+                "js" => "node",
+                "py" => "python3",
+                _ => "lua",
+            });
+            run.arg(&source_path);
+        }
+        _ => run = Command::new(&source_path),
+    }
+
+    Ok(Runner { build, run })
+}
+
+/// Compile the program with the system compiler and run it, the program
+/// inheriting the standard streams
+pub fn run_native(
+    ast: &Node,
+    config: &Config,
+    target: &str,
+    input: &Input,
+    output: Option<File>, // Written by the program, instead of the standard output
+) -> Result<ExitStatus, CodegenError> {
+    let dir = env::temp_dir().join(format!("brainfuck-{}", process::id()));
+    fs::create_dir_all(&dir)?;
+    let binary_path = dir.join("program");
+
+    let start = Instant::now();
+    let status = native_compiler(ast, config, target, &dir)?.status()?;
+    let compile_time = start.elapsed();
+    if !status.success() {
+        fs::remove_dir_all(&dir)?;
+        return Ok(status);
+    }
+
+    let mut command = Command::new(&binary_path);
+    let bytes = input.feed(&mut command)?;
+    if let Some(output) = output {
+        command.stdout(output);
+    }
+    let start = Instant::now();
+    let mut child = command.spawn()?;
+    if let Some(bytes) = bytes {
+        // Closing the pipe ends the input, and programs may stop reading
+        // before that
+        match child.stdin.take().unwrap().write_all(bytes) {
+            Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(error.into()),
+            _ => {}
+        }
+    }
+    let status = child.wait()?;
+    let run_time = start.elapsed();
+    fs::remove_dir_all(&dir)?;
+
+    eprintln!(
+        "compiled in {:.3}s, ran in {:.3}s",
+        compile_time.as_secs_f64(),
+        run_time.as_secs_f64()
+    );
+
+    Ok(status)
+}
@@ -0,0 +1,331 @@
+//! An asynchronous engine for the VM, for the servers running many programs
+//! at once
+//!
+//! "," and "." wait on asynchronous streams, and the engine yields to the
+//! executor every few steps, so that a program looping forever doesn't hold
+//! a thread. The futures are `Send` when the streams are.
+//!
+//! With the `futures-io` feature, the `AsyncRead` and `AsyncWrite` streams
+//! of the executors are streams of the engine, as they are.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::future::{poll_fn, Future};
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::ast::{Node, NodeKind};
+use crate::cell::CellValue;
+use crate::prelude::*;
+use crate::vm::{ByteRead, ByteWrite, Interpreter, IoError, Profile, RuntimeError, State, Stepper};
+use crate::Config;
+
+/// Steps run between two yields to the executor, by default
+pub const YIELD_STEPS: usize = 10_000;
+
+/// Asynchronous stream read by ","
+pub trait AsyncByteRead {
+    /// Poll for the next byte, or None at the end of the input
+    fn poll_read_byte(&mut self, cx: &mut Context) -> Poll<Result<Option<u8>, IoError>>;
+}
+
+/// Asynchronous stream written by "."
+pub trait AsyncByteWrite {
+    /// Poll for writing a byte, maybe buffered
+    fn poll_write_byte(&mut self, cx: &mut Context, byte: u8) -> Poll<Result<(), IoError>>;
+
+    /// Poll for writing out the buffered bytes
+    fn poll_flush_bytes(&mut self, cx: &mut Context) -> Poll<Result<(), IoError>>;
+}
+
+/// A synchronous stream, always ready, as an asynchronous one
+pub struct Ready<T>(pub T);
+
+impl<R: ByteRead> AsyncByteRead for Ready<R> {
+    fn poll_read_byte(&mut self, _cx: &mut Context) -> Poll<Result<Option<u8>, IoError>> {
+        Poll::Ready(self.0.read_byte())
+    }
+}
+
+impl<W: ByteWrite> AsyncByteWrite for Ready<W> {
+    fn poll_write_byte(&mut self, _cx: &mut Context, byte: u8) -> Poll<Result<(), IoError>> {
+        Poll::Ready(self.0.write_byte(byte))
+    }
+
+    fn poll_flush_bytes(&mut self, _cx: &mut Context) -> Poll<Result<(), IoError>> {
+        Poll::Ready(self.0.flush_bytes())
+    }
+}
+
+/// A stream of an executor, read a byte at a time, which a buffered reader
+/// keeps cheap
+#[cfg(feature = "futures-io")]
+impl<T: futures_io::AsyncRead + Unpin> AsyncByteRead for T {
+    fn poll_read_byte(&mut self, cx: &mut Context) -> Poll<Result<Option<u8>, IoError>> {
+        let mut byte = [0];
+        loop {
+            return match Pin::new(&mut *self).poll_read(cx, &mut byte) {
+                Poll::Ready(Ok(0)) => Poll::Ready(Ok(None)),
+                Poll::Ready(Ok(_)) => Poll::Ready(Ok(Some(byte[0]))),
+                Poll::Ready(Err(error)) if error.kind() == std::io::ErrorKind::Interrupted => {
+                    continue
+                }
+                Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+                Poll::Pending => Poll::Pending,
+            };
+        }
+    }
+}
+
+/// A stream of an executor, written a byte at a time, which a buffered
+/// writer keeps cheap
+#[cfg(feature = "futures-io")]
+impl<T: futures_io::AsyncWrite + Unpin> AsyncByteWrite for T {
+    fn poll_write_byte(&mut self, cx: &mut Context, byte: u8) -> Poll<Result<(), IoError>> {
+        loop {
+            return match Pin::new(&mut *self).poll_write(cx, &[byte]) {
+                Poll::Ready(Ok(0)) => Poll::Ready(Err(std::io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(_)) => Poll::Ready(Ok(())),
+                Poll::Ready(Err(error)) if error.kind() == std::io::ErrorKind::Interrupted => {
+                    continue
+                }
+                Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+                Poll::Pending => Poll::Pending,
+            };
+        }
+    }
+
+    fn poll_flush_bytes(&mut self, cx: &mut Context) -> Poll<Result<(), IoError>> {
+        Pin::new(self).poll_flush(cx)
+    }
+}
+
+/// The byte read by the next ",", already awaited
+struct Awaited(Option<u8>);
+
+impl ByteRead for Awaited {
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError> {
+        Ok(self.0.take())
+    }
+}
+
+/// Output of the steps without ".", which is written by the engine
+struct Nowhere;
+
+impl ByteWrite for Nowhere {
+    fn write_byte(&mut self, _byte: u8) -> Result<(), IoError> {
+        Ok(())
+    }
+
+    fn flush_bytes(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+/// A future pending once, giving the executor a turn
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if mem::replace(&mut self.0, true) {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// The parts of a state kept between the steps, without its streams and
+/// hooks, which aren't `Send`
+struct Tape<C> {
+    memory: Vec<C>,
+    index: usize,
+    highest: usize,
+    written: u64,
+    profile: Option<Profile>,
+    procedures: BTreeMap<u32, Arc<Node>>,
+    storage: C,
+}
+
+impl<C: CellValue> Tape<C> {
+    // A state on the tape, for a few steps
+    fn take(&mut self, input: Option<u8>) -> State<C> {
+        State {
+            memory: mem::take(&mut self.memory),
+            index: self.index,
+            highest: self.highest,
+            written: self.written,
+            steps: 0,
+            #[cfg(feature = "std")]
+            deadline: None,
+            profile: self.profile.take(),
+            hook: None,
+            loop_enter: None,
+            loop_exit: None,
+            access: None,
+            input: Box::new(Awaited(input)),
+            output: Box::new(Nowhere),
+            procedures: mem::take(&mut self.procedures),
+            storage: self.storage.clone(),
+            interrupt: None,
+        }
+    }
+
+    // Keep the tape of a state, after its steps
+    fn keep(&mut self, state: State<C>) {
+        self.memory = state.memory;
+        self.index = state.index;
+        self.highest = state.highest;
+        self.written = state.written;
+        self.profile = state.profile;
+        self.procedures = state.procedures;
+        self.storage = state.storage;
+    }
+
+    // Run steps up to the next "," or ".", which wait on the streams, or to a
+    // number of them, returning whether the program goes on, and the steps
+    fn run_until_io(
+        &mut self,
+        stepper: &mut Stepper,
+        config: &Config,
+        limit: usize,
+    ) -> Result<(bool, usize), RuntimeError> {
+        let mut state = self.take(None);
+        let mut steps = 0;
+        let result = loop {
+            if steps == limit || io_command(stepper).is_some() {
+                break Ok(true);
+            }
+            match stepper.step(&mut state, config) {
+                Ok(true) => steps += 1,
+                result => break result,
+            }
+        };
+        self.keep(state);
+        Ok((result?, steps))
+    }
+
+    // Run the next step, reading an awaited byte
+    fn run_step(
+        &mut self,
+        stepper: &mut Stepper,
+        config: &Config,
+        input: Option<u8>,
+    ) -> Result<bool, RuntimeError> {
+        let mut state = self.take(input);
+        let result = stepper.step(&mut state, config);
+        self.keep(state);
+        result
+    }
+}
+
+// The "," or "." run by the next step, if any
+fn io_command(stepper: &Stepper) -> Option<NodeKind> {
+    match stepper.next_command()?.kind {
+        NodeKind::Read => Some(NodeKind::Read),
+        NodeKind::Write => Some(NodeKind::Write),
+        _ => None,
+    }
+}
+
+/// A brainfuck VM on asynchronous streams
+pub struct AsyncInterpreter<R, W, C = u32> {
+    config: Config,
+    tape: Tape<C>,
+    input: R,
+    output: W,
+    yield_steps: usize,
+}
+
+impl<R: AsyncByteRead, W: AsyncByteWrite, C: CellValue> AsyncInterpreter<R, W, C> {
+    /// An engine on the settings and the tape of an interpreter, reading and
+    /// writing asynchronous streams
+    ///
+    /// The streams and the hooks of the interpreter are left behind.
+    pub fn new(mut interpreter: Interpreter<C>, input: R, output: W) -> Self {
+        let state = interpreter.state_mut();
+        AsyncInterpreter {
+            tape: Tape {
+                memory: mem::take(&mut state.memory),
+                index: state.index,
+                highest: state.highest,
+                written: state.written,
+                profile: state.profile.take(),
+                procedures: mem::take(&mut state.procedures),
+                storage: state.storage.clone(),
+            },
+            config: interpreter.config().clone(),
+            input,
+            output,
+            yield_steps: YIELD_STEPS,
+        }
+    }
+
+    /// Steps run between two yields to the executor
+    pub fn yield_every(mut self, steps: usize) -> Self {
+        self.yield_steps = steps.max(1);
+        self
+    }
+
+    /// Run a program, on the tape left by the previous ones
+    pub async fn run(&mut self, ast: &Node) -> Result<(), RuntimeError> {
+        let mut stepper = Stepper::new(ast);
+        let mut running = !stepper.finished();
+        while running {
+            let (going, steps) =
+                self.tape
+                    .run_until_io(&mut stepper, &self.config, self.yield_steps)?;
+            if !going {
+                break;
+            }
+            if steps == self.yield_steps {
+                YieldNow(false).await;
+                continue;
+            }
+
+            let command = io_command(&stepper);
+            let input = match command {
+                Some(NodeKind::Read) => {
+                    poll_fn(|cx| self.output.poll_flush_bytes(cx)).await?;
+                    poll_fn(|cx| self.input.poll_read_byte(cx)).await?
+                }
+                _ => None,
+            };
+            running = self.tape.run_step(&mut stepper, &self.config, input)?;
+            if let Some(NodeKind::Write) = command {
+                let byte = self.tape.memory[self.tape.index].to_byte();
+                poll_fn(|cx| self.output.poll_write_byte(cx, byte)).await?;
+            }
+        }
+        Ok(poll_fn(|cx| self.output.poll_flush_bytes(cx)).await?)
+    }
+
+    /// Cells of the tape
+    pub fn memory(&self) -> &[C] {
+        &self.tape.memory
+    }
+
+    /// Index of the pointer
+    pub fn index(&self) -> usize {
+        self.tape.index
+    }
+
+    /// Stream read by ","
+    pub fn input(&self) -> &R {
+        &self.input
+    }
+
+    /// Stream written by "."
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    /// Take back the streams
+    pub fn into_streams(self) -> (R, W) {
+        (self.input, self.output)
+    }
+}
@@ -0,0 +1,800 @@
+//! JSON documents, and the syntax trees, programs and tapes exported as JSON
+//!
+//! The layouts are the ones serde derives for the types, which the crate
+//! writes by hand as it has no dependencies. With the `serde` feature,
+//! programs and tapes also implement `Serialize` and `Deserialize`, in the
+//! same layouts.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::ast::{Extended, Node, NodeKind, Span};
+use crate::lexer::{Language, DIALECTS, MAX_NESTING};
+use crate::vm::{Bounds, Cell, Eof, State};
+use crate::{Config, Program};
+
+/// A JSON value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64), // Only integers are supported
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// A member of an object
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// The text of a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of a number
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Deepest nesting of the arrays and objects of a JSON document, which leaves
+/// room for the syntax trees of the most nested sources, and fits in the 2 MiB
+/// stack of the threads spawned by std
+pub const MAX_DEPTH: usize = 1536;
+
+/// Recursive descent parser of JSON documents
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize, // Arrays and objects being parsed
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("invalid JSON at byte {}: {}", self.pos, message)
+    }
+
+    // Kept out of parse_value, whose frames pile up on the nesting
+    #[inline(never)]
+    fn too_deep(&self) -> String {
+        self.error(&format!("nested deeper than {} levels", MAX_DEPTH))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {:?}", literal)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(b'[' | b'{') if self.depth == MAX_DEPTH => Err(self.too_deep()),
+            Some(b'[') => {
+                self.depth += 1;
+                let array = self.parse_array();
+                self.depth -= 1;
+                array
+            }
+            Some(b'{') => {
+                self.depth += 1;
+                let object = self.parse_object();
+                self.depth -= 1;
+                object
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            members.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        if self.bytes.get(self.pos) == Some(&b'-') {
+            self.pos += 1;
+        }
+        while let Some(b'0'..=b'9') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+        if let Some(b'.' | b'e' | b'E') = self.bytes.get(self.pos) {
+            return Err(self.error("only integers are supported"));
+        }
+        str::from_utf8(&self.bytes[start..self.pos])
+            .unwrap()
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .and_then(|digits| str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut string = Vec::new();
+        loop {
+            match self.bytes.get(self.pos) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return String::from_utf8(string).map_err(|_| self.error("invalid UTF-8"));
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.bytes.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let mut code = self.parse_hex4()?;
+                            // Surrogate pairs encode the characters outside the BMP
+                            if (0xd800..0xdc00).contains(&code) {
+                                self.expect("\\u")?;
+                                let low = self.parse_hex4()?;
+                                code =
+                                    0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00));
+                            }
+                            self.pos -= 1;
+                            char::from_u32(code)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.pos += 1;
+                    string.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(byte) => {
+                    string.push(*byte);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Compact JSON text of a value
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write_string = |f: &mut fmt::Formatter, string: &str| {
+            f.write_str("\"")?;
+            for c in string.chars() {
+                match c {
+                    '"' => f.write_str("\\\"")?,
+                    '\\' => f.write_str("\\\\")?,
+                    '\n' => f.write_str("\\n")?,
+                    c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            f.write_str("\"")
+        };
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(members) => {
+                f.write_str("{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Parse a JSON document
+pub fn parse_json(text: &str) -> Result<Json, String> {
+    let mut parser = JsonParser {
+        bytes: text.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.bytes.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Get a member of a JSON object
+fn json_member<'a>(json: &'a Json, name: &str) -> Result<&'a Json, String> {
+    match json {
+        Json::Object(members) => members
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("missing {:?} in {:?}", name, json)),
+        _ => Err(format!("expected an object, got {:?}", json)),
+    }
+}
+
+/// Get an integer out of a JSON value
+fn json_integer(json: &Json) -> Result<i64, String> {
+    match json {
+        Json::Number(number) => Ok(*number),
+        _ => Err(format!("expected an integer, got {:?}", json)),
+    }
+}
+
+/// Rebuild an AST from its JSON representation, its loops and procedures
+/// nesting up to `lexer::MAX_NESTING` as in the sources
+pub fn node_from_json(json: &Json) -> Result<Node, String> {
+    node_from_json_at(json, 0)
+}
+
+fn node_from_json_at(json: &Json, depth: usize) -> Result<Node, String> {
+    let span = json_member(json, "span")?;
+    let span = Span::new(
+        json_integer(json_member(span, "start")?)? as usize,
+        json_integer(json_member(span, "end")?)? as usize,
+    );
+    let kind = match json_member(json, "kind")? {
+        Json::String(kind) => match kind.as_str() {
+            "Write" => NodeKind::Write,
+            "Read" => NodeKind::Read,
+            "Clear" => NodeKind::Clear,
+            "Call" => NodeKind::Call,
+            _ => return Err(format!("unknown node kind {:?}", kind)),
+        },
+        Json::Object(members) if members.len() == 1 => {
+            let (kind, value) = &members[0];
+            match (kind.as_str(), value) {
+                ("Incr", value) => NodeKind::Incr(json_integer(value)? as isize),
+                ("Move", value) => NodeKind::Move(json_integer(value)? as isize),
+                ("Mul", Json::Array(targets)) => NodeKind::Mul(
+                    targets
+                        .iter()
+                        .map(|target| match target {
+                            Json::Array(pair) if pair.len() == 2 => Ok((
+                                json_integer(&pair[0])? as isize,
+                                json_integer(&pair[1])? as isize,
+                            )),
+                            _ => Err(format!(
+                                "expected an [offset, factor] pair, got {:?}",
+                                target
+                            )),
+                        })
+                        .collect::<Result<_, _>>()?,
+                ),
+                ("Loop" | "Procedure", _) if depth == MAX_NESTING => {
+                    return Err(format!("nodes nested deeper than {} levels", MAX_NESTING))
+                }
+                ("Loop", body) => NodeKind::Loop(Box::new(node_from_json_at(body, depth + 1)?)),
+                ("Procedure", body) => {
+                    NodeKind::Procedure(Arc::new(node_from_json_at(body, depth + 1)?))
+                }
+                ("Extended", Json::String(name)) => NodeKind::Extended(
+                    Extended::ALL
+                        .iter()
+                        .copied()
+                        .find(|command| format!("{:?}", command) == *name)
+                        .ok_or_else(|| format!("unknown command {:?}", name))?,
+                ),
+                ("Block", Json::Array(nodes)) => {
+                    let nodes = nodes.iter().map(|node| node_from_json_at(node, depth));
+                    NodeKind::Block(nodes.collect::<Result<_, _>>()?)
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid node kind {:?}",
+                        json_member(json, "kind")?
+                    ))
+                }
+            }
+        }
+        kind => return Err(format!("invalid node kind {:?}", kind)),
+    };
+
+    Ok(Node::new(kind, span))
+}
+
+/// JSON representation of an AST
+pub fn node_to_json(ast: &Node) -> Json {
+    let variant = |name: &str, value| Json::Object(vec![(String::from(name), value)]);
+    let kind = match &ast.kind {
+        NodeKind::Incr(val) => variant("Incr", Json::Number(*val as i64)),
+        NodeKind::Move(val) => variant("Move", Json::Number(*val as i64)),
+        NodeKind::Write => Json::String(String::from("Write")),
+        NodeKind::Read => Json::String(String::from("Read")),
+        NodeKind::Clear => Json::String(String::from("Clear")),
+        NodeKind::Call => Json::String(String::from("Call")),
+        NodeKind::Mul(targets) => variant(
+            "Mul",
+            Json::Array(
+                targets
+                    .iter()
+                    .map(|(offset, factor)| {
+                        Json::Array(vec![
+                            Json::Number(*offset as i64),
+                            Json::Number(*factor as i64),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        NodeKind::Loop(body) => variant("Loop", node_to_json(body)),
+        NodeKind::Procedure(body) => variant("Procedure", node_to_json(body)),
+        NodeKind::Extended(command) => variant("Extended", Json::String(format!("{:?}", command))),
+        NodeKind::Block(nodes) => variant(
+            "Block",
+            Json::Array(nodes.iter().map(node_to_json).collect()),
+        ),
+    };
+    Json::Object(vec![
+        (String::from("kind"), kind),
+        (
+            String::from("span"),
+            Json::Object(vec![
+                (String::from("start"), Json::Number(ast.span.start as i64)),
+                (String::from("end"), Json::Number(ast.span.end as i64)),
+            ]),
+        ),
+    ])
+}
+
+/// Get a string out of a JSON value
+fn json_string(json: &Json) -> Result<&str, String> {
+    match json {
+        Json::String(string) => Ok(string),
+        _ => Err(format!("expected a string, got {:?}", json)),
+    }
+}
+
+/// JSON representation of the settings of a program, those of the command
+/// line's output and profiling keeping their default
+fn config_to_json(config: &Config) -> Json {
+    let language =
+        match config.language {
+            Language::Classic => Json::String(String::from("Classic")),
+            Language::Pbrain => Json::String(String::from("Pbrain")),
+            Language::Extended => Json::String(String::from("Extended")),
+            // Only the dialects of DIALECTS can be restored, by their name
+            Language::Words(dialect) => DIALECTS
+                .iter()
+                .find(|(_, known)| *known == dialect)
+                .map_or(Json::Null, |(name, _)| {
+                    Json::Object(vec![(
+                        String::from("Words"),
+                        Json::String(String::from(*name)),
+                    )])
+                }),
+        };
+    let number = |value: Option<u64>| value.map_or(Json::Null, |value| Json::Number(value as i64));
+    Json::Object(vec![
+        (
+            String::from("eof"),
+            Json::String(format!("{:?}", config.eof)),
+        ),
+        (
+            String::from("tape_size"),
+            Json::Number(config.tape_size as i64),
+        ),
+        (
+            String::from("cell"),
+            Json::String(format!("{:?}", config.cell)),
+        ),
+        (
+            String::from("bounds"),
+            Json::String(format!("{:?}", config.bounds)),
+        ),
+        (
+            String::from("opt_level"),
+            Json::Number(config.opt_level as i64),
+        ),
+        (String::from("max_output"), number(config.max_output)),
+        (String::from("max_steps"), number(config.max_steps)),
+        (
+            String::from("timeout_ms"),
+            number(config.timeout.map(|timeout| timeout.as_millis() as u64)),
+        ),
+        (String::from("language"), language),
+        (
+            String::from("source"),
+            config.source.clone().map_or(Json::Null, Json::String),
+        ),
+    ])
+}
+
+/// Rebuild the settings of a program from their JSON representation
+fn config_from_json(json: &Json) -> Result<Config, String> {
+    let eof = match json_string(json_member(json, "eof")?)? {
+        "Unchanged" => Eof::Unchanged,
+        "Zero" => Eof::Zero,
+        "MinusOne" => Eof::MinusOne,
+        eof => return Err(format!("unknown eof {:?}", eof)),
+    };
+    let cell = match json_string(json_member(json, "cell")?)? {
+        "U8" => Cell::U8,
+        "U16" => Cell::U16,
+        "U32" => Cell::U32,
+        cell => return Err(format!("unknown cell {:?}", cell)),
+    };
+    let bounds = match json_string(json_member(json, "bounds")?)? {
+        "Unchecked" => Bounds::Unchecked,
+        "Wrap" => Bounds::Wrap,
+        bounds => return Err(format!("unknown bounds {:?}", bounds)),
+    };
+    let max_output = match json_member(json, "max_output")? {
+        Json::Null => None,
+        max_output => Some(json_integer(max_output)? as u64),
+    };
+    let max_steps = match json_member(json, "max_steps")? {
+        Json::Null => None,
+        max_steps => Some(json_integer(max_steps)? as u64),
+    };
+    let timeout = match json_member(json, "timeout_ms")? {
+        Json::Null => None,
+        timeout => Some(Duration::from_millis(json_integer(timeout)? as u64)),
+    };
+    let language = match json_member(json, "language")? {
+        Json::String(language) if language == "Classic" => Language::Classic,
+        Json::String(language) if language == "Pbrain" => Language::Pbrain,
+        Json::String(language) if language == "Extended" => Language::Extended,
+        language => {
+            let name = json_string(json_member(language, "Words")?)?;
+            let (_, dialect) = DIALECTS
+                .iter()
+                .find(|(dialect, _)| *dialect == name)
+                .ok_or_else(|| format!("unknown dialect {:?}", name))?;
+            Language::Words(dialect)
+        }
+    };
+    let source = match json_member(json, "source")? {
+        Json::Null => None,
+        source => Some(String::from(json_string(source)?)),
+    };
+    Ok(Config {
+        eof,
+        tape_size: json_integer(json_member(json, "tape_size")?)? as usize,
+        cell,
+        bounds,
+        opt_level: json_integer(json_member(json, "opt_level")?)? as u8,
+        max_output,
+        max_steps,
+        timeout,
+        language,
+        source,
+        ..Config::default()
+    })
+}
+
+/// JSON representation of a compiled program, to be cached or sent
+pub fn program_to_json(program: &Program) -> Json {
+    Json::Object(vec![
+        (String::from("ast"), node_to_json(&program.ast)),
+        (String::from("config"), config_to_json(&program.config)),
+    ])
+}
+
+/// Rebuild a compiled program from its JSON representation
+pub fn program_from_json(json: &Json) -> Result<Program, String> {
+    Ok(Program {
+        ast: node_from_json(json_member(json, "ast")?)?,
+        config: config_from_json(json_member(json, "config")?)?,
+    })
+}
+
+/// JSON representation of the tape of the VM and its pointer, to resume a
+/// program later
+///
+/// The streams, the profile and the hooks of the state are left out.
+pub fn state_to_json(state: &State) -> Json {
+    Json::Object(vec![
+        (
+            String::from("memory"),
+            Json::Array(
+                state
+                    .memory
+                    .iter()
+                    .map(|cell| Json::Number(*cell as i64))
+                    .collect(),
+            ),
+        ),
+        (String::from("index"), Json::Number(state.index as i64)),
+        (String::from("highest"), Json::Number(state.highest as i64)),
+        (String::from("written"), Json::Number(state.written as i64)),
+        (String::from("storage"), Json::Number(state.storage as i64)),
+        (
+            String::from("procedures"),
+            Json::Object(
+                state
+                    .procedures
+                    .iter()
+                    .map(|(id, body)| (id.to_string(), node_to_json(body)))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+/// Restore the tape and the pointer of a state from their JSON
+/// representation, keeping its streams
+pub fn state_from_json(json: &Json, state: &mut State) -> Result<(), String> {
+    let memory = match json_member(json, "memory")? {
+        Json::Array(cells) => cells
+            .iter()
+            .map(|cell| json_integer(cell).map(|cell| cell as u32))
+            .collect::<Result<Vec<_>, _>>()?,
+        memory => return Err(format!("expected an array of cells, got {:?}", memory)),
+    };
+    let index = json_integer(json_member(json, "index")?)? as usize;
+    if index >= memory.len() {
+        return Err(format!(
+            "pointer {} out of a tape of {} cells",
+            index,
+            memory.len()
+        ));
+    }
+    let mut procedures = BTreeMap::new();
+    match json_member(json, "procedures")? {
+        Json::Object(members) => {
+            for (id, body) in members {
+                let id = id
+                    .parse()
+                    .map_err(|_| format!("invalid procedure {:?}", id))?;
+                procedures.insert(id, Arc::new(node_from_json(body)?));
+            }
+        }
+        json => return Err(format!("expected an object of procedures, got {:?}", json)),
+    }
+
+    state.highest = json_integer(json_member(json, "highest")?)? as usize;
+    state.written = json_integer(json_member(json, "written")?)? as u64;
+    state.memory = memory;
+    state.index = index;
+    state.procedures = procedures;
+    // The tapes saved before Extended Brainfuck have no storage
+    state.storage = match json_member(json, "storage") {
+        Ok(storage) => json_integer(storage)? as u32,
+        Err(_) => 0,
+    };
+    Ok(())
+}
+
+/// JSON values of any serde format, for the implementations of programs and
+/// tapes
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::convert::TryFrom;
+
+    use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+    use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+    use super::*;
+
+    impl Serialize for Json {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Json::Null => serializer.serialize_unit(),
+                Json::Bool(value) => serializer.serialize_bool(*value),
+                Json::Number(value) => serializer.serialize_i64(*value),
+                Json::String(value) => serializer.serialize_str(value),
+                Json::Array(values) => {
+                    let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                    for value in values {
+                        seq.serialize_element(value)?;
+                    }
+                    seq.end()
+                }
+                Json::Object(members) => {
+                    let mut map = serializer.serialize_map(Some(members.len()))?;
+                    for (key, value) in members {
+                        map.serialize_entry(key, value)?;
+                    }
+                    map.end()
+                }
+            }
+        }
+    }
+
+    struct JsonVisitor;
+
+    impl<'de> Visitor<'de> for JsonVisitor {
+        type Value = Json;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a JSON value with integer numbers")
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Json, E> {
+            Ok(Json::Null)
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Json, E> {
+            Ok(Json::Null)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Json, D::Error> {
+            Json::deserialize(deserializer)
+        }
+
+        fn visit_bool<E: de::Error>(self, value: bool) -> Result<Json, E> {
+            Ok(Json::Bool(value))
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Json, E> {
+            Ok(Json::Number(value))
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Json, E> {
+            match i64::try_from(value) {
+                Ok(value) => Ok(Json::Number(value)),
+                Err(_) => Err(E::custom(format!("{} is too large", value))),
+            }
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Json, E> {
+            Ok(Json::String(String::from(value)))
+        }
+
+        fn visit_string<E: de::Error>(self, value: String) -> Result<Json, E> {
+            Ok(Json::String(value))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+            let mut values = Vec::new();
+            while let Some(value) = seq.next_element()? {
+                values.push(value);
+            }
+            Ok(Json::Array(values))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Json, A::Error> {
+            let mut members = Vec::new();
+            while let Some(member) = map.next_entry()? {
+                members.push(member);
+            }
+            Ok(Json::Object(members))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Json {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Json, D::Error> {
+            deserializer.deserialize_any(JsonVisitor)
+        }
+    }
+
+    impl Serialize for Program {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            program_to_json(self).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Program {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Program, D::Error> {
+            program_from_json(&Json::deserialize(deserializer)?).map_err(de::Error::custom)
+        }
+    }
+
+    /// The tape, the pointer and the procedures of the state, as in
+    /// `state_to_json`
+    impl Serialize for State {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            state_to_json(self).serialize(serializer)
+        }
+    }
+
+    /// A state reading the standard input and writing the standard output,
+    /// on the tape of the snapshot
+    impl<'de> Deserialize<'de> for State {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<State, D::Error> {
+            let json = Json::deserialize(deserializer)?;
+            let mut state = State::new(&Config {
+                tape_size: 0,
+                ..Config::default()
+            });
+            state_from_json(&json, &mut state).map_err(de::Error::custom)?;
+            Ok(state)
+        }
+    }
+}
@@ -0,0 +1,481 @@
+//! Tokens of the sources, in brainfuck, pbrain, Extended Brainfuck or a
+//! dialect of words
+
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+use thiserror::Error;
+
+use crate::ast::Extended;
+use crate::prelude::*;
+
+/// A brainfuck token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token {
+    Incr,               // "+"
+    Decr,               // "-"
+    MoveLeft,           // "<"
+    MoveRight,          // ">"
+    Write,              // "."
+    Read,               // ","
+    LoopBegin,          // "["
+    LoopEnd,            // "]"
+    ProcedureBegin,     // "(" of pbrain
+    ProcedureEnd,       // ")" of pbrain
+    Call,               // ":" of pbrain
+    Extended(Extended), // "$!}{~^&|" of Extended Brainfuck Type I
+}
+
+/// Tokens replaced by the words of a dialect, in the order of its words
+const DIALECT_TOKENS: [Token; 8] = [
+    Token::MoveRight,
+    Token::MoveLeft,
+    Token::Incr,
+    Token::Decr,
+    Token::Write,
+    Token::Read,
+    Token::LoopBegin,
+    Token::LoopEnd,
+];
+
+/// Language of the sources: brainfuck, its pbrain or Extended Brainfuck Type
+/// I extensions, or a dialect replacing its commands by words
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    Classic,
+    Pbrain,
+    Extended, // Extended Brainfuck Type I, its code ending at the first "@"
+    Words(&'static Dialect),
+}
+
+impl Language {
+    /// Text of a token in the sources
+    pub fn token_text(self, token: Token) -> &'static str {
+        if let Language::Words(dialect) = self {
+            if let Some(index) = DIALECT_TOKENS.iter().position(|t| *t == token) {
+                return dialect.words[index];
+            }
+        }
+        match token {
+            Token::Incr => "+",
+            Token::Decr => "-",
+            Token::MoveLeft => "<",
+            Token::MoveRight => ">",
+            Token::Write => ".",
+            Token::Read => ",",
+            Token::LoopBegin => "[",
+            Token::LoopEnd => "]",
+            Token::ProcedureBegin => "(",
+            Token::ProcedureEnd => ")",
+            Token::Call => ":",
+            Token::Extended(command) => command.text(),
+        }
+    }
+
+    /// Length of a token at the start of a text of the sources, where the
+    /// spaces of a word may be any whitespace
+    pub fn token_length(self, text: &str, token: Token) -> usize {
+        let word = self.token_text(token);
+        match self {
+            Language::Words(_) => match_word(text, word).unwrap_or(word.len()),
+            _ => word.len(),
+        }
+    }
+}
+
+/// Parse a source string and extract tokens, with their byte offset
+///
+/// A first line starting with "#!" is skipped, as it runs the source as a
+/// script.
+pub fn parse_source(source: &str) -> impl Iterator<Item = (usize, Token)> + '_ {
+    let skipped = shebang(source).len();
+    source[skipped..]
+        .char_indices()
+        .filter_map(move |(offset, c)| {
+            let token = match c {
+                '+' => Token::Incr,
+                '-' => Token::Decr,
+                '<' => Token::MoveLeft,
+                '>' => Token::MoveRight,
+                '.' => Token::Write,
+                ',' => Token::Read,
+                '[' => Token::LoopBegin,
+                ']' => Token::LoopEnd,
+                _ => return None,
+            };
+            Some((skipped + offset, token))
+        })
+}
+
+/// Parse a source in some language, and extract tokens, with their byte
+/// offset
+pub fn parse_source_in(source: &str, language: Language) -> Vec<(usize, Token)> {
+    match language {
+        Language::Classic => parse_source(source).collect(),
+        Language::Pbrain => {
+            let skipped = shebang(source).len();
+            let procedures = source[skipped..].char_indices().filter_map(|(offset, c)| {
+                let token = match c {
+                    '(' => Token::ProcedureBegin,
+                    ')' => Token::ProcedureEnd,
+                    ':' => Token::Call,
+                    _ => return None,
+                };
+                Some((skipped + offset, token))
+            });
+            let mut tokens: Vec<_> = parse_source(source).chain(procedures).collect();
+            tokens.sort_by_key(|(offset, _)| *offset);
+            tokens
+        }
+        Language::Extended => {
+            let skipped = shebang(source).len();
+            let end = source[skipped..]
+                .find('@')
+                .map_or(source.len(), |index| skipped + index);
+            let extended = source[skipped..end]
+                .char_indices()
+                .filter_map(|(offset, c)| {
+                    Some((skipped + offset, Token::Extended(Extended::from_char(c)?)))
+                });
+            let mut tokens: Vec<_> = parse_source(&source[..end]).chain(extended).collect();
+            tokens.sort_by_key(|(offset, _)| *offset);
+            tokens
+        }
+        Language::Words(dialect) => parse_words(source, dialect),
+    }
+}
+
+/// Tokens of a source read from a stream, with their byte offset, for the
+/// sources too large to be read as a whole
+///
+/// The commands of brainfuck, pbrain and Extended Brainfuck are single ASCII
+/// bytes, found without decoding the text. The words of a dialect need the whole source,
+/// read on the first token.
+#[cfg(feature = "std")]
+pub struct ReadTokens<R> {
+    reader: R,
+    language: Language,
+    buffer: Box<[u8]>,
+    start: usize,                                 // Index of the next byte of the buffer
+    end: usize,                                   // Length of the data of the buffer
+    offset: usize,                                // Offset of the start of the buffer in the source
+    line_start: bool,                             // Whether the next byte starts the source
+    in_shebang: bool,                             // Whether the bytes are in the "#!" line
+    ended: bool,                                  // Whether the "@" of Extended Brainfuck was read
+    words: Option<vec::IntoIter<(usize, Token)>>, // Tokens of a dialect
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> ReadTokens<R> {
+    /// Tokens of the source of a reader, in some language
+    pub fn new(reader: R, language: Language) -> Self {
+        ReadTokens {
+            reader,
+            language,
+            buffer: vec![0; 64 * 1024].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            offset: 0,
+            line_start: true,
+            in_shebang: false,
+            ended: false,
+            words: None,
+        }
+    }
+
+    /// Tokens of a dialect, from the whole source
+    fn read_words(&mut self, dialect: &Dialect) -> io::Result<vec::IntoIter<(usize, Token)>> {
+        let mut source = String::new();
+        self.reader.read_to_string(&mut source)?;
+        Ok(parse_words(&source, dialect).into_iter())
+    }
+
+    /// Next byte of the source, with its offset
+    fn next_byte(&mut self) -> io::Result<Option<(usize, u8)>> {
+        while self.start == self.end {
+            self.offset += self.end;
+            self.start = 0;
+            self.end = match self.reader.read(&mut self.buffer) {
+                Ok(length) => length,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+            if self.end == 0 {
+                return Ok(None);
+            }
+        }
+        let byte = self.buffer[self.start];
+        self.start += 1;
+        Ok(Some((self.offset + self.start - 1, byte)))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for ReadTokens<R> {
+    type Item = io::Result<(usize, Token)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Language::Words(dialect) = self.language {
+            if self.words.is_none() {
+                match self.read_words(dialect) {
+                    Ok(words) => self.words = Some(words),
+                    Err(error) => return Some(Err(error)),
+                }
+            }
+            return self.words.as_mut()?.next().map(Ok);
+        }
+
+        while !self.ended {
+            let (offset, byte) = match self.next_byte() {
+                Ok(Some(byte)) => byte,
+                Ok(None) => return None,
+                Err(error) => return Some(Err(error)),
+            };
+            // A "#!" first line is skipped, as in shebang
+            if self.line_start && byte == b'#' {
+                match self.next_byte() {
+                    Ok(Some((_, b'!'))) => self.in_shebang = true,
+                    Ok(Some(_)) => self.start -= 1,
+                    Ok(None) => {}
+                    Err(error) => return Some(Err(error)),
+                }
+            }
+            self.line_start = false;
+            if self.in_shebang {
+                self.in_shebang = byte != b'\n';
+                continue;
+            }
+            let token = match (byte, self.language) {
+                (b'+', _) => Token::Incr,
+                (b'-', _) => Token::Decr,
+                (b'<', _) => Token::MoveLeft,
+                (b'>', _) => Token::MoveRight,
+                (b'.', _) => Token::Write,
+                (b',', _) => Token::Read,
+                (b'[', _) => Token::LoopBegin,
+                (b']', _) => Token::LoopEnd,
+                (b'(', Language::Pbrain) => Token::ProcedureBegin,
+                (b')', Language::Pbrain) => Token::ProcedureEnd,
+                (b':', Language::Pbrain) => Token::Call,
+                (b'@', Language::Extended) => {
+                    self.ended = true;
+                    continue;
+                }
+                (byte, Language::Extended) => match Extended::from_char(byte as char) {
+                    Some(command) => Token::Extended(command),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            return Some(Ok((offset, token)));
+        }
+        None
+    }
+}
+
+/// Length of a word at the start of a text, a space of the word matching any
+/// whitespace
+fn match_word(text: &str, word: &str) -> Option<usize> {
+    let mut length = 0;
+    for (i, part) in word.split(' ').enumerate() {
+        if i > 0 {
+            let rest = &text[length..];
+            let spaces = rest.len() - rest.trim_start().len();
+            if spaces == 0 {
+                return None;
+            }
+            length += spaces;
+        }
+        if !text[length..].starts_with(part) {
+            return None;
+        }
+        length += part.len();
+    }
+    Some(length)
+}
+
+/// Parse a source written in a dialect, where the longest word wins and a
+/// space in a word stands for any whitespace
+fn parse_words(source: &str, dialect: &Dialect) -> Vec<(usize, Token)> {
+    let mut tokens = Vec::new();
+    let mut offset = shebang(source).len();
+    while let Some(c) = source[offset..].chars().next() {
+        let longest = dialect
+            .words
+            .iter()
+            .enumerate()
+            .filter_map(|(i, word)| Some((match_word(&source[offset..], word)?, i)))
+            .max();
+        match longest {
+            Some((length, i)) => {
+                tokens.push((offset, DIALECT_TOKENS[i]));
+                offset += length;
+            }
+            None => offset += c.len_utf8(),
+        }
+    }
+    tokens
+}
+
+/// The comments of a source in some language, the text between its
+/// commands, with their byte offset
+pub fn parse_comments(source: &str, language: Language) -> Vec<(usize, &str)> {
+    let mut comments = Vec::new();
+    let mut push = |start: usize, end: usize| {
+        if end > start {
+            comments.push((start, &source[start..end]));
+        }
+    };
+    let mut start = shebang(source).len();
+    for (offset, token) in parse_source_in(source, language) {
+        push(start, offset);
+        start = offset + language.token_length(&source[offset..], token);
+    }
+    push(start, source.len());
+    comments
+}
+
+/// The labels given in the comments of a source as "@label: name", with the
+/// offset of the first command after them
+///
+/// The names are made of letters, digits and underscores, which aren't
+/// commands. The colon may be left out, as it is a command of pbrain.
+pub fn parse_labels(source: &str, language: Language) -> Vec<(String, usize)> {
+    let tokens = parse_source_in(source, language);
+    let mut labels = Vec::new();
+    for (offset, comment) in parse_comments(source, language) {
+        for (index, directive) in comment.match_indices("@label") {
+            let rest = &comment[index + directive.len()..];
+            if !rest.starts_with(|c: char| c == ':' || c.is_whitespace()) {
+                continue;
+            }
+            let rest = rest.strip_prefix(':').unwrap_or(rest).trim_start();
+            let length = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let command = tokens.iter().find(|(start, _)| *start > offset + index);
+            if let (1.., Some((command, _))) = (length, command) {
+                labels.push((String::from(&rest[..length]), *command));
+            }
+        }
+    }
+    labels
+}
+
+/// The "#!" line at the start of a source, if any
+pub fn shebang(source: &str) -> &str {
+    if source.starts_with("#!") {
+        source.split_inclusive('\n').next().unwrap()
+    } else {
+        ""
+    }
+}
+
+/// Check that the brackets of a source match, or give the offset of one which
+/// doesn't
+pub fn check_brackets(source: &str) -> Result<(), ParseError> {
+    check_tokens(parse_source(source))
+}
+
+/// Deepest nesting of the loops and the procedures of a source, which the
+/// passes and the backends walking its syntax tree recursively fit in the
+/// 2 MiB stack of the threads spawned by std, even in debug builds
+pub const MAX_NESTING: usize = 256;
+
+/// Check that the brackets of some tokens match, the procedures of pbrain
+/// nesting with the loops, or give the offset of one which doesn't
+pub fn check_tokens(tokens: impl IntoIterator<Item = (usize, Token)>) -> Result<(), ParseError> {
+    let mut stack = vec![];
+    for (offset, token) in tokens {
+        match token {
+            Token::LoopBegin | Token::ProcedureBegin if stack.len() == MAX_NESTING => {
+                return Err(ParseError::TooDeep(offset))
+            }
+            Token::LoopBegin | Token::ProcedureBegin => stack.push((offset, token)),
+            Token::LoopEnd | Token::ProcedureEnd => {
+                let begin = match token {
+                    Token::LoopEnd => Token::LoopBegin,
+                    _ => Token::ProcedureBegin,
+                };
+                match stack.pop() {
+                    Some((_, token)) if token == begin => {}
+                    _ => return Err(ParseError::Unmatched(offset)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match stack.last() {
+        Some((offset, _)) => Err(ParseError::Unmatched(*offset)),
+        None => Ok(()),
+    }
+}
+
+/// An error in the structure of a source
+#[derive(Clone, Copy, Debug, PartialEq, Error)]
+pub enum ParseError {
+    #[error("unmatched bracket at byte {0}")]
+    Unmatched(usize), // Offset of a bracket or a parenthesis without its pair
+    #[error("brackets nested too deeply at byte {0}")]
+    TooDeep(usize), // Offset of the first bracket or parenthesis nested past MAX_NESTING
+}
+
+/// A brainfuck dialect where each command is replaced by a word
+#[derive(Debug, PartialEq)]
+pub struct Dialect {
+    pub words: [&'static str; 8], // Words for `><+-.,[]`, in this order
+    pub per_line: usize,          // Number of words on each line
+}
+
+/// Ook!, for orangutans
+pub const OOK: Dialect = Dialect {
+    words: [
+        "Ook. Ook?",
+        "Ook? Ook.",
+        "Ook. Ook.",
+        "Ook! Ook!",
+        "Ook! Ook.",
+        "Ook. Ook!",
+        "Ook! Ook?",
+        "Ook? Ook!",
+    ],
+    per_line: 8,
+};
+
+/// Blub, for fish
+pub const BLUB: Dialect = Dialect {
+    words: [
+        "Blub. Blub?",
+        "Blub? Blub.",
+        "Blub. Blub.",
+        "Blub! Blub!",
+        "Blub! Blub.",
+        "Blub. Blub!",
+        "Blub! Blub?",
+        "Blub? Blub!",
+    ],
+    per_line: 8,
+};
+
+/// Alphuck, with single letters
+pub const ALPHUCK: Dialect = Dialect {
+    words: ["a", "c", "e", "i", "j", "o", "p", "s"],
+    per_line: 40,
+};
+
+/// Pikalang, with the words of Pikachu
+pub const PIKALANG: Dialect = Dialect {
+    words: [
+        "pipi", "pichu", "pi", "ka", "pikachu", "pikapi", "pika", "chu",
+    ],
+    per_line: 12,
+};
+
+/// The dialects, by name
+pub const DIALECTS: &[(&str, &Dialect)] = &[
+    ("ook", &OOK),
+    ("blub", &BLUB),
+    ("alphuck", &ALPHUCK),
+    ("pikalang", &PIKALANG),
+];
@@ -0,0 +1,411 @@
+//! A brainfuck compiler: the sources are tokenized by `lexer`, built into
+//! the syntax tree of `ast`, rewritten by `opt`, and then run by `vm` or
+//! compiled to another language by `codegen`.
+//!
+//! `Compiler` goes through these stages in one call, into a `Program` that
+//! can be run, serialized or given to a backend.
+//!
+//! The VM is generic over the values of its cells, defined in `cell`, and
+//! `engine` runs it on asynchronous streams, and `symbolic` on symbolic
+//! input, to find the inputs leading to some events.
+//!
+//! Without the `std` feature, only `lexer`, `ast`, `opt`, `cell`, `vm`,
+//! `engine` and `symbolic` are built, on `core` and `alloc`, for embedded
+//! targets. The `json`, `lint` and `codegen` modules have features of their
+//! own, as have the heaviest backends, all enabled by the `cli` feature of
+//! the command line, whose subcommands are in `cli`. Its servers, for the
+//! editors, Jupyter and WebSockets, and its full-screen debugger take the
+//! `server` and `tui` features on top of it.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod ast;
+pub mod cell;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+pub mod engine;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod lexer;
+#[cfg(feature = "lint")]
+pub mod lint;
+pub mod opt;
+pub mod symbolic;
+pub mod vm;
+
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+use core::str::FromStr;
+use core::time::Duration;
+#[cfg(feature = "codegen")]
+use std::io::Write;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+#[cfg(feature = "std")]
+use thiserror::Error;
+
+#[cfg(feature = "std")]
+use crate::ast::build_ast_from;
+use crate::ast::{build_ast, shift_spans, Node, NodeKind, SourceMap, Span};
+use crate::cell::CellValue;
+#[cfg(feature = "codegen")]
+use crate::codegen::{write_ast_json, Backend, CodegenError};
+use crate::engine::{AsyncByteRead, AsyncByteWrite, AsyncInterpreter};
+#[cfg(feature = "std")]
+use crate::lexer::ReadTokens;
+use crate::lexer::{parse_source_in, Language, ParseError};
+use crate::opt::{optimize_ast, optimize_program};
+use crate::prelude::*;
+use crate::vm::{Bounds, Cell, Eof, Interpreter, Profile, RuntimeError};
+
+/// The items of the std prelude coming from alloc, for the modules built
+/// without std
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::String;
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+}
+
+/// Build the optimized AST of a brainfuck source
+pub fn compile_source(source: &str) -> Result<Node, ParseError> {
+    compile_source_at(source, 2)
+}
+
+/// Build the AST of a source at some optimization level, 0 keeping each
+/// command as it is
+pub fn compile_source_at(source: &str, level: u8) -> Result<Node, ParseError> {
+    compile_source_in(source, Language::Classic, level)
+}
+
+/// Build the AST of a source in some language, at some optimization level
+pub fn compile_source_in(source: &str, language: Language, level: u8) -> Result<Node, ParseError> {
+    let ast = build_ast(parse_source_in(source, language))?;
+    Ok(match level {
+        0 => ast,
+        _ => optimize_ast(&ast, level),
+    })
+}
+
+/// An error compiling a source with a backend
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+pub enum CompileError {
+    #[error("{0}")]
+    Parse(#[from] ParseError), // The source isn't a valid program
+    #[cfg(feature = "codegen")]
+    #[error("{0}")]
+    Codegen(#[from] CodegenError), // The backend can't write the program
+    #[error("{0}")]
+    Io(#[from] io::Error), // The source can't be read
+}
+
+/// Compile a source in the language and at the optimization level of the
+/// configuration, writing it with a backend
+#[cfg(feature = "codegen")]
+pub fn compile(
+    source: &str,
+    config: &Config,
+    backend: &dyn Backend,
+    write: &mut dyn Write,
+) -> Result<(), CompileError> {
+    let program = Compiler::new().config(config.clone()).compile(source)?;
+    Ok(program.emit(backend, write)?)
+}
+
+/// Settings of the compilation of the sources into programs
+#[derive(Clone, Debug)]
+pub struct Compiler {
+    config: Config,
+    optimize: fn(&Node, &Config) -> Node, // The optimizer for the cells of the programs
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler {
+            config: Config::default(),
+            optimize: optimize_program::<u32>,
+        }
+    }
+}
+
+impl Compiler {
+    /// A compiler of classic brainfuck, at the default optimization level
+    pub fn new() -> Self {
+        Compiler::default()
+    }
+
+    /// Start from a whole configuration, given to the backends
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Language of the sources
+    pub fn dialect(mut self, language: Language) -> Self {
+        self.config.language = language;
+        self
+    }
+
+    /// Optimization level, 0 keeping each command as it is
+    ///
+    /// Level 3 runs the start of the programs at compile time, up to their
+    /// first input, so they must run on a blank tape, instead of following
+    /// other programs on the same tape.
+    pub fn opt_level(mut self, level: u8) -> Self {
+        self.config.opt_level = level;
+        self
+    }
+
+    /// Type of the cells the programs run on, for the optimizer to leave
+    /// out the rewrites which rely on wrapping when they don't wrap
+    ///
+    /// ```
+    /// use brainfuck::cell::BigUint;
+    /// use brainfuck::vm::InterpreterBuilder;
+    ///
+    /// // "-" stops at 0, so "-+" doesn't cancel out
+    /// let program = brainfuck::Compiler::new().cells::<BigUint>().compile("-+").unwrap();
+    /// let mut big = InterpreterBuilder::<BigUint>::new().build();
+    /// program.run(&mut big).unwrap();
+    /// assert_eq!(big.state().memory[0].to_string(), "1");
+    /// ```
+    pub fn cells<C: CellValue>(mut self) -> Self {
+        self.optimize = optimize_program::<C>;
+        self
+    }
+
+    /// Optimize an AST at the level of the configuration
+    fn optimize(&self, ast: Node) -> Node {
+        match self.config.opt_level {
+            0 => ast,
+            _ => (self.optimize)(&ast, &self.config),
+        }
+    }
+
+    /// Build the program of a source
+    pub fn compile(&self, source: &str) -> Result<Program, ParseError> {
+        let ast = self.optimize(build_ast(parse_source_in(source, self.config.language))?);
+        let mut config = self.config.clone();
+        config.source = Some(String::from(source));
+        Ok(Program { ast, config })
+    }
+
+    /// Build the program of a source read from a stream, token by token,
+    /// without keeping its text
+    ///
+    /// The configuration of the program has no source, for the backends
+    /// and the messages which quote it.
+    #[cfg(feature = "std")]
+    pub fn compile_reader(&self, reader: impl Read) -> Result<Program, CompileError> {
+        let tokens = ReadTokens::new(reader, self.config.language).map(|token| Ok(token?));
+        let ast = self.optimize(build_ast_from::<CompileError>(tokens)?);
+        Ok(Program {
+            ast,
+            config: self.config.clone(),
+        })
+    }
+}
+
+/// A compiled program, with the configuration it was compiled with
+///
+/// Programs are `Send` and `Sync`, and never change as they run: the state of
+/// a run is all in its interpreter. A program compiled once, in an `Arc`,
+/// runs in many threads at once, each with its own interpreter.
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub ast: Node,
+    pub config: Config,
+}
+
+impl Program {
+    /// Splice programs into one, each running after the previous one on the
+    /// same tape, with the configuration of the first
+    ///
+    /// The programs after the first one mustn't be compiled at level 3,
+    /// which assumes a blank tape.
+    ///
+    /// The sources are joined by newlines, and the spans of each program
+    /// follow the sources of the previous ones. The program has a source
+    /// when all of them have one.
+    ///
+    /// ```
+    /// let greet: brainfuck::Program = "++++++++[>+++++++++<-]>.".parse().unwrap();
+    /// let next: brainfuck::Program = "+.".parse().unwrap();
+    /// let program = brainfuck::Program::sequence(&[greet, next]);
+    /// assert_eq!(program.run_to_string(b"").unwrap(), "HI");
+    /// ```
+    pub fn sequence(programs: &[Program]) -> Program {
+        let mut config = programs
+            .first()
+            .map_or_else(Config::default, |program| program.config.clone());
+        let mut nodes = Vec::new();
+        let mut source = String::new();
+        let mut known = true;
+        for (index, program) in programs.iter().enumerate() {
+            if index > 0 {
+                source.push('\n');
+            }
+            nodes.push(shift_spans(&program.ast, source.len()));
+            match &program.config.source {
+                Some(text) => source.push_str(text),
+                None => known = false,
+            }
+        }
+
+        // The maps and the counts of the first source don't fit the others
+        config.line_directives = None;
+        config.profile = None;
+        let ast = Node::new(NodeKind::Block(nodes), Span::new(0, source.len()));
+        config.source = known.then_some(source);
+        Program { ast, config }
+    }
+
+    /// This program followed by another one, on the same tape
+    pub fn concat(&self, other: &Program) -> Program {
+        Program::sequence(&[self.clone(), other.clone()])
+    }
+
+    /// Run the program in an interpreter, on its current tape
+    pub fn run<C: CellValue>(&self, interpreter: &mut Interpreter<C>) -> Result<(), RuntimeError> {
+        interpreter.run(&self.ast)
+    }
+
+    /// Run the program on a blank tape, with its configuration, reading
+    /// `input` and returning its output
+    ///
+    /// The output is bounded by the `max_output` of the configuration.
+    ///
+    /// ```
+    /// use brainfuck::vm::Eof;
+    /// use brainfuck::{Compiler, Config};
+    ///
+    /// let config = Config { eof: Eof::Zero, ..Config::default() };
+    /// let program = Compiler::new().config(config).compile(",[.,]").unwrap();
+    /// assert_eq!(program.run_with_input(b"echo").unwrap(), b"echo");
+    /// ```
+    pub fn run_with_input(&self, input: &[u8]) -> Result<Vec<u8>, RuntimeError> {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let written = output.clone();
+        let mut input = Vec::from(input).into_iter();
+        let mut interpreter = Interpreter::builder()
+            .config(self.config.clone())
+            .on_input(move || input.next())
+            .on_output(move |byte| written.borrow_mut().push(byte))
+            .build();
+        self.run(&mut interpreter)?;
+        Ok(output.take())
+    }
+
+    /// Run the program as `run_with_input`, returning its output as text,
+    /// with the bytes which aren't UTF-8 replaced
+    ///
+    /// ```
+    /// let program: brainfuck::Program = "++++++++[>++++++++<-]>+.+.".parse().unwrap();
+    /// assert_eq!(program.run_to_string(b"").unwrap(), "AB");
+    /// ```
+    pub fn run_to_string(&self, input: &[u8]) -> Result<String, RuntimeError> {
+        let output = self.run_with_input(input)?;
+        Ok(String::from_utf8(output)
+            .unwrap_or_else(|error| String::from_utf8_lossy(error.as_bytes()).into_owned()))
+    }
+
+    /// Run the program in an asynchronous engine, on its current tape
+    pub async fn run_async<R, W, C>(
+        &self,
+        engine: &mut AsyncInterpreter<R, W, C>,
+    ) -> Result<(), RuntimeError>
+    where
+        R: AsyncByteRead,
+        W: AsyncByteWrite,
+        C: CellValue,
+    {
+        engine.run(&self.ast).await
+    }
+
+    /// Write the program with a backend
+    #[cfg(feature = "codegen")]
+    pub fn emit(&self, backend: &dyn Backend, write: &mut dyn Write) -> Result<(), CodegenError> {
+        backend.emit(&self.ast, &self.config, write)
+    }
+
+    /// Write the AST of the program as JSON, as read back by
+    /// `json::node_from_json`
+    #[cfg(feature = "codegen")]
+    pub fn serialize(&self, write: &mut dyn Write) -> Result<(), CodegenError> {
+        write_ast_json(&self.ast, &self.config, write)
+    }
+}
+
+/// The canonical brainfuck of the program, which `parse` compiles back
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.ast.fmt(f)
+    }
+}
+
+/// Compile a classic brainfuck source with the default settings
+///
+/// ```
+/// let program: brainfuck::Program = "++[->+<]".parse().unwrap();
+/// assert_eq!(program.to_string(), "++[->+<]");
+/// assert_eq!(program.to_string().parse::<brainfuck::Program>().unwrap().to_string(), "++[->+<]");
+/// ```
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, ParseError> {
+        Compiler::new().compile(source)
+    }
+}
+
+/// Settings shared by the VM and the backends
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub eof: Eof,
+    pub tape_size: usize,
+    pub cell: Cell,
+    pub bounds: Bounds,
+    pub line_directives: Option<SourceMap>, // Map generated code back to this source
+    pub source: Option<String>,             // Brainfuck source of the program, when known
+    pub profile: Option<Profile>,           // Execution counts from a previous run
+    pub codegen_checks: bool,               // Check the pointer in the generated code
+    pub opt_level: u8,                      // Optimization level of the compiled sources
+    pub max_output: Option<u64>,            // Bytes the VM lets a program write
+    pub max_steps: Option<u64>,             // Nodes the VM runs in a call of run_ast
+    pub timeout: Option<Duration>,          // Time the VM takes in a call of run_ast, with std
+    pub language: Language,                 // Language of the sources
+}
+
+impl Config {
+    /// Index of the cell at some offset of `index`, on a circular tape
+    pub fn wrap(&self, index: usize, offset: isize) -> usize {
+        (index as isize + offset).rem_euclid(self.tape_size as isize) as usize
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            eof: Eof::Unchanged,
+            tape_size: 30000,
+            cell: Cell::U8,
+            bounds: Bounds::Unchecked,
+            line_directives: None,
+            source: None,
+            profile: None,
+            codegen_checks: false,
+            opt_level: 2,
+            max_output: None,
+            max_steps: None,
+            timeout: None,
+            language: Language::Classic,
+        }
+    }
+}
@@ -0,0 +1,587 @@
+//! Likely bugs of the programs, found without running them
+
+use std::collections::HashMap;
+
+use crate::ast::{Extended, Node, NodeKind, Span};
+use crate::lexer::{parse_source_in, Language, Token};
+use crate::vm::Bounds;
+use crate::Config;
+
+/// How a lint is reported
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LintLevel {
+    Allow, // Not reported
+    Warn,  // Reported as a warning
+    Deny,  // Reported as an error, failing the command
+}
+
+/// The lints, with their default level
+pub const LINTS: &[(&str, LintLevel)] = &[
+    ("dead-loop", LintLevel::Warn),
+    ("endless-loop", LintLevel::Warn),
+    ("nonterminating-loop", LintLevel::Warn),
+    ("pointer-underflow-possible", LintLevel::Warn),
+    ("pointer-overflow-possible", LintLevel::Warn),
+    ("useless-sequence", LintLevel::Warn),
+    ("unused-cells", LintLevel::Warn),
+    ("unbalanced-suspects", LintLevel::Allow),
+];
+
+/// A likely bug of a program, found without running it
+pub struct Lint {
+    pub name: &'static str, // One of LINTS
+    pub span: Span,
+    pub message: String,
+}
+
+impl Lint {
+    fn new(name: &'static str, span: Span, message: &str) -> Self {
+        Lint {
+            name,
+            span,
+            message: String::from(message),
+        }
+    }
+}
+
+/// What is known of the tape before a node, for the lints
+#[derive(Clone, Copy)]
+struct Facts {
+    position: Option<isize>, // Index of the current cell, when it is known
+    zero: bool,              // The current cell is zero
+    blank: bool,             // All the cells are zero
+}
+
+/// Run all the lints on a program, and on its source when it is known, in
+/// the order of the source
+pub fn lint(ast: &Node, config: &Config) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut facts = Facts {
+        position: Some(0),
+        zero: true,
+        blank: true,
+    };
+    lint_ast(ast, config, &mut facts, &mut lints);
+    lints.extend(nonterminating_loops(ast, config));
+    lints.extend(unused_cells(ast));
+    if let Some(source) = &config.source {
+        lints.extend(useless_sequences(source, config.language));
+    }
+    lints.sort_by_key(|lint| lint.span.start);
+    lints
+}
+
+fn lint_ast(ast: &Node, config: &Config, facts: &mut Facts, lints: &mut Vec<Lint>) {
+    let mut lint = |name, message| lints.push(Lint::new(name, ast.span, message));
+    let bounds_lint = |position: isize| match config.bounds {
+        Bounds::Unchecked if position < 0 => Some("pointer-underflow-possible"),
+        Bounds::Unchecked if position >= config.tape_size as isize => {
+            Some("pointer-overflow-possible")
+        }
+        _ => None,
+    };
+    let unknown = Facts {
+        position: None,
+        zero: false,
+        blank: false,
+    };
+
+    // Loops, rewritten or not, don't run on a zero cell
+    if facts.zero
+        && matches!(
+            ast.kind,
+            NodeKind::Loop(_) | NodeKind::Clear | NodeKind::Mul(_)
+        )
+    {
+        lint(
+            "dead-loop",
+            "loop never runs, as the cell is always zero here",
+        );
+        return;
+    }
+    match &ast.kind {
+        NodeKind::Incr(_) | NodeKind::Read => {
+            facts.zero = false;
+            facts.blank = false;
+        }
+        NodeKind::Write | NodeKind::Extended(Extended::Store) => {}
+        NodeKind::Extended(_) => {
+            facts.zero = false;
+            facts.blank = false;
+        }
+        NodeKind::Move(val) => {
+            facts.position = facts.position.map(|position| position + val);
+            if let Some(name) = facts.position.and_then(bounds_lint) {
+                lint(name, "pointer out of the tape");
+                facts.position = None;
+            }
+            facts.zero = facts.blank;
+        }
+        NodeKind::Clear => facts.zero = true,
+        NodeKind::Mul(targets) => {
+            let position = facts.position;
+            if let Some(name) = targets
+                .iter()
+                .find_map(|(offset, _)| bounds_lint(position? + offset))
+            {
+                lint(name, "pointer out of the tape");
+            }
+            facts.zero = true;
+            facts.blank = false;
+        }
+        NodeKind::Loop(body) => {
+            if loop_never_ends(body) {
+                lint(
+                    "endless-loop",
+                    "loop never ends once entered, as its cell doesn't change",
+                );
+            }
+            match net_move(body) {
+                Some(0) | None => {}
+                Some(offset) => lint(
+                    "unbalanced-suspects",
+                    &format!("loop moves the pointer by {} on each iteration", offset),
+                ),
+            }
+            let mut inner = Facts {
+                zero: false,
+                blank: false,
+                ..*facts
+            };
+            lint_ast(body, config, &mut inner, lints);
+            // Only the loops coming back to their cell keep the pointer known
+            if inner.position != facts.position {
+                facts.position = None;
+            }
+            facts.zero = true;
+            facts.blank = false;
+        }
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                lint_ast(node, config, facts, lints);
+            }
+        }
+        // A procedure runs on any tape, and a call can change anything
+        NodeKind::Procedure(body) => {
+            let mut inner = unknown;
+            lint_ast(body, config, &mut inner, lints);
+        }
+        NodeKind::Call => *facts = unknown,
+    }
+}
+
+/// Whether a loop body comes back to the cell of the loop without changing
+/// it, so that the loop never ends once entered
+fn loop_never_ends(body: &Node) -> bool {
+    let nodes = match &body.kind {
+        NodeKind::Block(nodes) => nodes.as_slice(),
+        _ => std::slice::from_ref(body),
+    };
+
+    let mut offset = 0;
+    for node in nodes.iter() {
+        match &node.kind {
+            NodeKind::Incr(_) | NodeKind::Read | NodeKind::Clear if offset == 0 => return false,
+            NodeKind::Incr(_) | NodeKind::Read | NodeKind::Clear | NodeKind::Write => {}
+            NodeKind::Move(val) => offset += val,
+            NodeKind::Mul(targets) => {
+                if offset == 0 || targets.iter().any(|(target, _)| offset + target == 0) {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+    offset == 0
+}
+
+/// Move of the pointer by a node, when it doesn't depend on the tape
+fn net_move(ast: &Node) -> Option<isize> {
+    match &ast.kind {
+        NodeKind::Move(val) => Some(*val),
+        NodeKind::Loop(body) => match net_move(body) {
+            Some(0) => Some(0),
+            _ => None,
+        },
+        NodeKind::Block(nodes) => nodes.iter().map(net_move).sum(),
+        NodeKind::Call => None,
+        _ => Some(0),
+    }
+}
+
+/// The values a cell may have, from `min` to `max` included
+#[derive(Clone, Copy, PartialEq)]
+struct Interval {
+    min: u64,
+    max: u64,
+}
+
+const ZERO: Interval = Interval { min: 0, max: 0 };
+
+/// What the cells may be, by their offset from the pointer
+#[derive(Clone)]
+struct Intervals {
+    cells: HashMap<isize, Interval>,
+    rest: Interval, // The cells not in `cells`
+    max: u64,       // Largest value of a cell
+}
+
+impl Intervals {
+    /// Any value of a cell
+    fn any(&self) -> Interval {
+        Interval {
+            min: 0,
+            max: self.max,
+        }
+    }
+
+    /// Nothing known of the cells
+    fn forget(&mut self) {
+        self.cells.clear();
+        self.rest = self.any();
+    }
+
+    fn get(&self, offset: isize) -> Interval {
+        self.cells.get(&offset).copied().unwrap_or(self.rest)
+    }
+
+    fn set(&mut self, offset: isize, interval: Interval) {
+        self.cells.insert(offset, interval);
+    }
+
+    /// Add to the current cell, which wraps around
+    fn add(&mut self, amount: isize) {
+        let modulus = self.max + 1;
+        let amount = (amount as i64).rem_euclid(modulus as i64) as u64;
+        let Interval { min, max } = self.get(0);
+        let interval = match (min + amount, max + amount) {
+            (min, max) if max < modulus => Interval { min, max },
+            (min, max) if min >= modulus => Interval {
+                min: min - modulus,
+                max: max - modulus,
+            },
+            _ => self.any(),
+        };
+        self.set(0, interval);
+    }
+
+    /// Move the pointer
+    fn shift(&mut self, val: isize) {
+        self.cells = self
+            .cells
+            .drain()
+            .map(|(offset, interval)| (offset - val, interval))
+            .collect();
+    }
+}
+
+/// What an iteration of a loop does to the tape, from the cell of the loop
+#[derive(Default)]
+struct Summary {
+    offset: Option<isize>, // Offset of the pointer at the end, when it is known
+    delta: Option<isize>,  // Change of the cell of the loop, when it is known
+    changed: Vec<isize>,   // Offsets of the cells changed
+}
+
+/// Summarize a loop body, from its cell
+fn summarize(body: &Node) -> Summary {
+    // `once` is false in the inner loops, whose changes may run any number
+    // of times
+    fn follow(node: &Node, once: bool, summary: &mut Summary) {
+        let offset = match summary.offset {
+            Some(offset) => offset,
+            None => return,
+        };
+        let mut change = |offset: isize, delta: Option<isize>| {
+            summary.changed.push(offset);
+            if offset == 0 {
+                summary.delta = summary.delta.zip(delta).map(|(a, b)| a + b);
+            }
+        };
+        match &node.kind {
+            NodeKind::Incr(val) => change(offset, Some(*val).filter(|_| once)),
+            NodeKind::Read | NodeKind::Clear => change(offset, None),
+            NodeKind::Extended(Extended::Store) => {}
+            NodeKind::Extended(_) => change(offset, None),
+            NodeKind::Mul(targets) => {
+                change(offset, None);
+                for (target, _) in targets.iter() {
+                    change(offset + target, None);
+                }
+            }
+            NodeKind::Write | NodeKind::Procedure(_) => {}
+            NodeKind::Move(val) => summary.offset = Some(offset + val),
+            NodeKind::Loop(body) => {
+                follow(body, false, summary);
+                if summary.offset != Some(offset) {
+                    summary.offset = None;
+                }
+            }
+            NodeKind::Block(nodes) => {
+                for node in nodes.iter() {
+                    follow(node, once, summary);
+                }
+            }
+            NodeKind::Call => summary.offset = None,
+        }
+    }
+
+    let mut summary = Summary {
+        offset: Some(0),
+        delta: Some(0),
+        changed: Vec::new(),
+    };
+    follow(body, true, &mut summary);
+    if summary.offset != Some(0) {
+        summary.delta = None;
+    }
+    summary
+}
+
+/// Loops entered on any input, whose cell changes by the same amount on
+/// each iteration without ever reaching zero, from the intervals of the
+/// cells and the summaries of the loops
+fn nonterminating_loops(ast: &Node, config: &Config) -> Vec<Lint> {
+    fn follow(ast: &Node, tape: &mut Intervals, lints: &mut Vec<Lint>) {
+        match &ast.kind {
+            NodeKind::Incr(val) => tape.add(*val),
+            NodeKind::Read => tape.set(0, tape.any()),
+            NodeKind::Write | NodeKind::Extended(Extended::Store) => {}
+            NodeKind::Extended(_) => tape.set(0, tape.any()),
+            NodeKind::Move(val) => tape.shift(*val),
+            NodeKind::Clear => tape.set(0, ZERO),
+            NodeKind::Mul(targets) => {
+                if tape.get(0) != ZERO {
+                    for (offset, _) in targets.iter() {
+                        tape.set(*offset, tape.any());
+                    }
+                }
+                tape.set(0, ZERO);
+            }
+            NodeKind::Loop(body) => {
+                let cell = tape.get(0);
+                if cell == ZERO {
+                    return;
+                }
+                let summary = summarize(body);
+                if let Some(delta) = summary.delta.filter(|_| cell.min > 0) {
+                    // The cell only goes through the multiples of `step`
+                    // from zero, those of the greatest power of two dividing
+                    // the delta
+                    let modulus = tape.max + 1;
+                    let delta = (delta as i64).rem_euclid(modulus as i64) as u64;
+                    let step = match delta {
+                        0 => modulus,
+                        delta => 1 << delta.trailing_zeros(),
+                    };
+                    if cell.min.div_ceil(step) * step > cell.max && !loop_never_ends(body) {
+                        lints.push(Lint::new(
+                            "nonterminating-loop",
+                            ast.span,
+                            &format!(
+                                "loop never ends, as its cell changes by {} on each \
+                                 iteration and never reaches zero",
+                                summary.delta.unwrap()
+                            ),
+                        ));
+                    }
+                }
+
+                // The body runs on the tapes of all the iterations
+                match summary.offset {
+                    Some(0) => {
+                        for offset in summary.changed.iter() {
+                            tape.set(*offset, tape.any());
+                        }
+                    }
+                    _ => tape.forget(),
+                }
+                let nonzero = match tape.get(0) {
+                    Interval { min: 0, max } => Interval { min: 1, max },
+                    interval => interval,
+                };
+                let mut inner = tape.clone();
+                inner.set(0, nonzero);
+                follow(body, &mut inner, lints);
+                tape.set(0, ZERO);
+            }
+            NodeKind::Block(nodes) => {
+                for node in nodes.iter() {
+                    follow(node, tape, lints);
+                }
+            }
+            // A procedure runs on any tape, and a call can change anything
+            NodeKind::Procedure(body) => {
+                let mut inner = tape.clone();
+                inner.forget();
+                follow(body, &mut inner, lints);
+            }
+            NodeKind::Call => tape.forget(),
+        }
+    }
+
+    let mut tape = Intervals {
+        cells: HashMap::new(),
+        rest: ZERO,
+        max: config.cell.max() as u64,
+    };
+    let mut lints = Vec::new();
+    follow(ast, &mut tape, &mut lints);
+    lints
+}
+
+/// Cells changed but never read, tested or written afterwards, when the
+/// pointer can be followed through the whole program
+fn unused_cells(ast: &Node) -> Vec<Lint> {
+    // First change of each cell, and the cells used after their change
+    fn follow(
+        ast: &Node,
+        position: &mut isize,
+        changes: &mut HashMap<isize, Span>,
+        used: &mut Vec<isize>,
+    ) -> bool {
+        match &ast.kind {
+            NodeKind::Incr(_) | NodeKind::Read => {
+                changes.entry(*position).or_insert(ast.span);
+            }
+            NodeKind::Write | NodeKind::Extended(Extended::Store) => used.push(*position),
+            NodeKind::Extended(Extended::Load) => {
+                changes.entry(*position).or_insert(ast.span);
+            }
+            // The shifts and the bitwise commands read the cell they change
+            NodeKind::Extended(_) => {
+                used.push(*position);
+                changes.entry(*position).or_insert(ast.span);
+            }
+            NodeKind::Move(val) => *position += val,
+            NodeKind::Clear => {}
+            NodeKind::Mul(targets) => {
+                used.push(*position);
+                for (offset, _) in targets.iter() {
+                    changes.entry(*position + offset).or_insert(ast.span);
+                }
+            }
+            NodeKind::Loop(body) => {
+                used.push(*position);
+                let start = *position;
+                if !follow(body, position, changes, used) || *position != start {
+                    return false;
+                }
+            }
+            NodeKind::Block(nodes) => {
+                for node in nodes.iter() {
+                    if !follow(node, position, changes, used) {
+                        return false;
+                    }
+                }
+            }
+            NodeKind::Procedure(_) | NodeKind::Call => return false,
+        }
+        true
+    }
+
+    let mut changes = HashMap::new();
+    let mut used = Vec::new();
+    if !follow(ast, &mut 0, &mut changes, &mut used) {
+        return Vec::new();
+    }
+    changes
+        .into_iter()
+        .filter(|(cell, _)| *cell >= 0 && !used.contains(cell))
+        .map(|(cell, span)| {
+            Lint::new(
+                "unused-cells",
+                span,
+                &format!("cell {} is changed but never used", cell),
+            )
+        })
+        .collect()
+}
+
+/// Runs of increments or moves going both ways, which partly cancel out
+fn useless_sequences(source: &str, language: Language) -> Vec<Lint> {
+    let tokens = parse_source_in(source, language);
+    let mut lints = Vec::new();
+    let mut rest = tokens.as_slice();
+    while let Some((_, token)) = rest.first() {
+        let pair = match token {
+            Token::Incr | Token::Decr => [Token::Incr, Token::Decr],
+            Token::MoveLeft | Token::MoveRight => [Token::MoveLeft, Token::MoveRight],
+            _ => {
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let length = rest
+            .iter()
+            .take_while(|(_, token)| pair.contains(token))
+            .count();
+        let (run, next) = rest.split_at(length);
+        if pair
+            .iter()
+            .all(|direction| run.iter().any(|(_, token)| token == direction))
+        {
+            let (last, token) = run[length - 1];
+            let end = last + language.token_length(&source[last..], token);
+            lints.push(Lint::new(
+                "useless-sequence",
+                Span::new(run[0].0, end),
+                "commands going both ways cancel out",
+            ));
+        }
+        rest = next;
+    }
+    lints
+}
+
+/// Apply the safe rewrites of some lints to a source, keeping its comments
+/// and layout: the cancelling commands of the useless sequences, and the
+/// commands of the dead loops, are removed. Returns the new source, and the
+/// number of lints fixed.
+pub fn fix_source(source: &str, language: Language, lints: &[Lint]) -> (String, usize) {
+    let tokens = parse_source_in(source, language);
+    let within = |span: Span| {
+        tokens
+            .iter()
+            .filter(move |(offset, _)| span.start <= *offset && *offset < span.end)
+    };
+
+    let mut removed = Vec::new();
+    let mut fixed = 0;
+    for lint in lints.iter() {
+        match lint.name {
+            "dead-loop" => removed.extend(within(lint.span)),
+            "useless-sequence" => {
+                // Keep the first commands going the way of the whole run
+                let net: isize = within(lint.span)
+                    .map(|(_, token)| match token {
+                        Token::Incr | Token::MoveRight => 1,
+                        _ => -1,
+                    })
+                    .sum();
+                let mut kept = 0;
+                for &(offset, token) in within(lint.span) {
+                    let forward = matches!(token, Token::Incr | Token::MoveRight);
+                    if net != 0 && forward == (net > 0) && kept < net.abs() {
+                        kept += 1;
+                    } else {
+                        removed.push((offset, token));
+                    }
+                }
+            }
+            _ => continue,
+        }
+        fixed += 1;
+    }
+    removed.sort_by_key(|(offset, _)| *offset);
+    removed.dedup();
+
+    let mut fixed_source = String::with_capacity(source.len());
+    let mut start = 0;
+    for (offset, token) in removed.into_iter() {
+        fixed_source.push_str(&source[start..offset]);
+        start = offset + language.token_length(&source[offset..], token);
+    }
+    fixed_source.push_str(&source[start..]);
+    (fixed_source, fixed)
+}
@@ -0,0 +1,423 @@
+//! Rewrites of the syntax tree into faster equivalent programs
+
+use crate::ast::{fold_children, Folder, Node, NodeKind, Span};
+use crate::cell::CellValue;
+use crate::prelude::*;
+use crate::vm::Bounds;
+use crate::Config;
+
+/// Extract the (offset, factor) pairs of a loop body made only of increments
+/// and moves, which comes back to its starting cell and decrements or
+/// increments it by one
+///
+/// Without wrapping, the counter must be decremented, and the increments of
+/// each cell must have the same sign, as the decrements stop at 0.
+fn multiply_loop_targets(body: &Node, wraps: bool) -> Option<Vec<(isize, isize)>> {
+    let nodes = match &body.kind {
+        NodeKind::Block(nodes) => nodes.as_slice(),
+        _ => core::slice::from_ref(body),
+    };
+
+    let mut offset = 0;
+    let mut deltas: Vec<(isize, isize)> = vec![];
+    for node in nodes.iter() {
+        match &node.kind {
+            NodeKind::Incr(val) => match deltas.iter_mut().find(|(o, _)| *o == offset) {
+                Some((_, delta)) if !wraps && delta.signum() * val.signum() < 0 => return None,
+                Some((_, delta)) => *delta += val,
+                None => deltas.push((offset, *val)),
+            },
+            NodeKind::Move(val) => offset += val,
+            _ => return None,
+        }
+    }
+    if offset != 0 {
+        return None;
+    }
+
+    // Each iteration must move the loop counter one step closer to zero
+    let counter = deltas
+        .iter()
+        .find(|(o, _)| *o == 0)
+        .map_or(0, |(_, delta)| *delta);
+    let sign = match counter {
+        -1 => 1,
+        1 if wraps => -1,
+        _ => return None,
+    };
+
+    Some(
+        deltas
+            .into_iter()
+            .filter(|(o, delta)| *o != 0 && *delta != 0)
+            .map(|(o, delta)| (o, sign * delta))
+            .collect(),
+    )
+}
+
+/// Rewrite a run of increments and moves into one increment of each cell it
+/// changes, visited from one end to the other, and a move to where it ends
+///
+/// Without wrapping, the increments of a cell in opposite directions are
+/// kept apart, in their order.
+fn gather_offsets(run: &[Node], wraps: bool) -> Vec<Node> {
+    let mut offset = 0;
+    let mut cells: Vec<(isize, Vec<Node>)> = vec![];
+    for node in run.iter() {
+        match node.kind {
+            NodeKind::Incr(val) => {
+                let increments = match cells.iter_mut().find(|(o, _)| *o == offset) {
+                    Some((_, increments)) => increments,
+                    None => {
+                        cells.push((offset, vec![]));
+                        &mut cells.last_mut().unwrap().1
+                    }
+                };
+                match increments.last_mut() {
+                    Some(Node {
+                        kind: NodeKind::Incr(last_val),
+                        span,
+                    }) if wraps || last_val.signum() * val.signum() >= 0 => {
+                        *last_val += val;
+                        *span = span.merge(node.span);
+                    }
+                    _ => increments.push(node.clone()),
+                }
+            }
+            NodeKind::Move(val) => offset += val,
+            _ => unreachable!("not an increment or a move"),
+        }
+    }
+
+    // Visit the cells upwards or downwards, whichever takes the shorter
+    // path from where the run starts to where it ends
+    cells.sort_by_key(|(o, _)| *o);
+    let (low, high) = match (cells.first(), cells.last()) {
+        (Some((low, _)), Some((high, _))) => (*low, *high),
+        _ => (offset, offset),
+    };
+    if low.abs() + (high - offset).abs() > high.abs() + (low - offset).abs() {
+        cells.reverse();
+    }
+    // The moves take empty spans, before the increments they lead to, as
+    // the profiles count the nodes by span
+    let end = Span::new(run[run.len() - 1].span.end, run[run.len() - 1].span.end);
+    let mut nodes = vec![];
+    let mut position = 0;
+    for (o, increments) in cells {
+        // Increments adding up to 0 cancel out
+        let increments = increments
+            .into_iter()
+            .filter(|node| !matches!(node.kind, NodeKind::Incr(0)));
+        let mut increments = increments.peekable();
+        if increments.peek().is_none() {
+            continue;
+        }
+        if o != position {
+            let start = increments.peek().unwrap().span.start;
+            let span = Span::new(start, start);
+            nodes.push(Node::new(NodeKind::Move(o - position), span));
+            position = o;
+        }
+        nodes.extend(increments);
+    }
+    if offset != position {
+        nodes.push(Node::new(NodeKind::Move(offset - position), end));
+    }
+    nodes
+}
+
+/// The rewrites of an optimization level
+struct Optimizer {
+    level: u8,
+    wraps: bool, // Whether the cells wrap around, instead of stopping at 0
+}
+
+impl Optimizer {
+    /// Gather the offsets of the runs of increments and moves of a block,
+    /// when it takes fewer nodes
+    fn gather_runs(&self, nodes: Vec<Node>) -> Vec<Node> {
+        let mut gathered = vec![];
+        let mut run = vec![];
+        // A last None ends the last run
+        for node in nodes.into_iter().map(Some).chain(Some(None)) {
+            if let Some(
+                node @ Node {
+                    kind: NodeKind::Incr(_) | NodeKind::Move(_),
+                    ..
+                },
+            ) = node
+            {
+                run.push(node);
+                continue;
+            }
+            if !run.is_empty() {
+                match gather_offsets(&run, self.wraps) {
+                    nodes if nodes.len() < run.len() => gathered.extend(nodes),
+                    _ => gathered.append(&mut run),
+                }
+                run.clear();
+            }
+            gathered.extend(node);
+        }
+        gathered
+    }
+}
+
+impl Folder for Optimizer {
+    fn fold(&mut self, node: &Node) -> Node {
+        let node = fold_children(self, node);
+        match node.kind {
+            NodeKind::Incr(0) | NodeKind::Move(0) => Node::new(NodeKind::Block(vec![]), node.span),
+            NodeKind::Loop(body) => {
+                // Try to replace the loop by a clear or a multiply
+                let targets = match self.level {
+                    0 | 1 => None,
+                    _ => multiply_loop_targets(&body, self.wraps),
+                };
+                let kind = match targets {
+                    Some(targets) if targets.is_empty() => NodeKind::Clear,
+                    Some(targets) => NodeKind::Mul(targets),
+                    None => NodeKind::Loop(body),
+                };
+                Node::new(kind, node.span)
+            }
+            NodeKind::Block(nodes) => {
+                let mut new_nodes: Vec<Node> = vec![];
+                for opt_node in nodes {
+                    match (new_nodes.last_mut(), &opt_node.kind) {
+                        // A decrement stopping at 0 doesn't cancel an
+                        // increment
+                        (
+                            Some(Node {
+                                kind: NodeKind::Incr(last_val),
+                                ..
+                            }),
+                            NodeKind::Incr(val),
+                        ) if !self.wraps && last_val.signum() * val.signum() < 0 => {
+                            new_nodes.push(opt_node)
+                        }
+                        // Try to merge incr nodes, and move nodes
+                        (
+                            Some(Node {
+                                kind: NodeKind::Incr(last_val),
+                                span,
+                            }),
+                            NodeKind::Incr(val),
+                        )
+                        | (
+                            Some(Node {
+                                kind: NodeKind::Move(last_val),
+                                span,
+                            }),
+                            NodeKind::Move(val),
+                        ) => {
+                            *last_val += val;
+                            *span = span.merge(opt_node.span);
+                        }
+                        _ => new_nodes.push(opt_node),
+                    }
+                }
+
+                // Gather the increments of the runs going back and forth
+                if self.level >= 3 {
+                    new_nodes = self.gather_runs(new_nodes);
+                }
+
+                if new_nodes.len() == 1 {
+                    new_nodes.pop().unwrap()
+                } else {
+                    Node::new(NodeKind::Block(new_nodes), node.span)
+                }
+            }
+            kind => Node::new(kind, node.span),
+        }
+    }
+}
+
+/// Optimize an AST: level 1 merges the runs of increments and moves, level 2
+/// also replaces the clear and multiply loops, and level 3 also gathers the
+/// runs of increments going back and forth, by the offsets of their cells
+pub fn optimize_ast(ast: &Node, level: u8) -> Node {
+    optimize_ast_for::<u8>(ast, level)
+}
+
+/// Optimize an AST for the cells of a type, leaving out the rewrites which
+/// rely on the wrapping of the cells when they don't wrap
+pub fn optimize_ast_for<C: CellValue>(ast: &Node, level: u8) -> Node {
+    Optimizer {
+        level,
+        wraps: C::WRAPS,
+    }
+    .fold(ast)
+}
+
+/// Nodes the precomputation runs at most, loops included, before giving up
+/// on the rest of the program
+pub const PRECOMPUTE_STEPS: usize = 1 << 16;
+
+/// A run of the start of a program at compile time, on a blank tape
+#[derive(Clone)]
+struct Precompute<'a, C> {
+    config: &'a Config,
+    memory: Vec<C>, // Cells up to the highest one used
+    index: usize,
+    output: Vec<u8>,
+    steps: usize, // Nodes left to run
+}
+
+impl<C: CellValue> Precompute<'_, C> {
+    /// Index of the cell at some offset of the pointer, unless it is off the
+    /// tape
+    fn cell(&mut self, offset: isize) -> Option<usize> {
+        let index = match self.config.bounds {
+            Bounds::Wrap => self.config.wrap(self.index, offset),
+            Bounds::Unchecked => match self.index as isize + offset {
+                index if index < 0 || index as usize >= self.config.tape_size => return None,
+                index => index as usize,
+            },
+        };
+        if index >= self.memory.len() {
+            self.memory.resize(index + 1, C::default());
+        }
+        Some(index)
+    }
+
+    /// Run a node, unless it reads the input, leaves the tape, needs the
+    /// state of the procedures or the storage, or runs out of steps, leaving
+    /// the tape as it was for the nodes other than the loops
+    fn run(&mut self, node: &Node) -> bool {
+        if self.steps == 0 {
+            return false;
+        }
+        self.steps -= 1;
+        match &node.kind {
+            NodeKind::Incr(val) => {
+                let cell = &mut self.memory[self.index];
+                cell.add(*val);
+                cell.truncate(self.config.cell);
+            }
+            NodeKind::Move(val) => match self.cell(*val) {
+                Some(index) => self.index = index,
+                None => return false,
+            },
+            NodeKind::Write => self.output.push(self.memory[self.index].to_byte()),
+            NodeKind::Clear => self.memory[self.index] = C::default(),
+            NodeKind::Mul(targets) => {
+                let val = self.memory[self.index].clone();
+                if !val.is_zero() {
+                    let mut indices = vec![];
+                    for (offset, _) in targets.iter() {
+                        match self.cell(*offset) {
+                            Some(index) => indices.push(index),
+                            None => return false,
+                        }
+                    }
+                    for (index, (_, factor)) in indices.into_iter().zip(targets.iter()) {
+                        let cell = &mut self.memory[index];
+                        cell.add_mul(&val, *factor);
+                        cell.truncate(self.config.cell);
+                    }
+                }
+                self.memory[self.index] = C::default();
+            }
+            NodeKind::Loop(body) => {
+                while !self.memory[self.index].is_zero() {
+                    if !self.run(body) {
+                        return false;
+                    }
+                }
+            }
+            NodeKind::Block(nodes) => return nodes.iter().all(|node| self.run(node)),
+            NodeKind::Read | NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+                return false
+            }
+        }
+        true
+    }
+}
+
+/// Replace the start of a program running on a blank tape, up to its first
+/// input or `PRECOMPUTE_STEPS` nodes, by the output it writes and the tape it
+/// leaves
+///
+/// The values are rebuilt from 0 by increments, which wrap around, so the
+/// start of the programs on cells which don't wrap is left as it is.
+pub fn precompute<C: CellValue>(ast: &Node, config: &Config) -> Node {
+    let nodes = match &ast.kind {
+        NodeKind::Block(nodes) => nodes.as_slice(),
+        _ => core::slice::from_ref(ast),
+    };
+    let mut start = Precompute::<C> {
+        config,
+        memory: vec![C::default()],
+        index: 0,
+        output: vec![],
+        steps: PRECOMPUTE_STEPS,
+    };
+    let mut count = 0;
+    for node in nodes.iter().take_while(|_| C::WRAPS) {
+        // A loop may stop half way, so it runs on a copy of the tape
+        if let NodeKind::Loop(_) = node.kind {
+            let mut next = start.clone();
+            if !next.run(node) {
+                break;
+            }
+            start = next;
+        } else if !start.run(node) {
+            break;
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return ast.clone();
+    }
+
+    // The output is written from the first cell, then the cells are set.
+    // The writes take the span of the start, and the other nodes an empty
+    // span where it begins, as the profiles count the writes by span.
+    let span = nodes[0].span.merge(nodes[count - 1].span);
+    let node = |kind| match kind {
+        NodeKind::Write => Node::new(kind, span),
+        _ => Node::new(kind, Span::new(span.start, span.start)),
+    };
+    let mut precomputed = vec![];
+    let mut last = 0;
+    for byte in start.output.iter().map(|byte| *byte as isize) {
+        if byte != last {
+            precomputed.push(node(NodeKind::Incr(byte - last)));
+        }
+        precomputed.push(node(NodeKind::Write));
+        last = byte;
+    }
+    if last != 0 {
+        precomputed.push(node(NodeKind::Clear));
+    }
+    let mut position = 0;
+    for (index, cell) in start.memory.iter().enumerate() {
+        if !cell.is_zero() {
+            if index != position {
+                precomputed.push(node(NodeKind::Move(index as isize - position as isize)));
+                position = index;
+            }
+            precomputed.push(node(NodeKind::Incr(cell.to_u32() as isize)));
+        }
+    }
+    if start.index != position {
+        precomputed.push(node(NodeKind::Move(
+            start.index as isize - position as isize,
+        )));
+    }
+    precomputed.extend(nodes[count..].iter().cloned());
+    Node::new(NodeKind::Block(precomputed), ast.span)
+}
+
+/// Optimize the AST of a program starting on a blank tape, for the cells of
+/// a type: level 3 also runs the start of the program at compile time
+pub fn optimize_program<C: CellValue>(ast: &Node, config: &Config) -> Node {
+    let ast = optimize_ast_for::<C>(ast, config.opt_level);
+    match config.opt_level {
+        0..=2 => ast,
+        _ => precompute::<C>(&ast, config),
+    }
+}
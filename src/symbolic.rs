@@ -0,0 +1,529 @@
+//! Runs of the programs on symbolic input, to find inputs leading to some
+//! events, such as the pointer leaving the tape or a loop being entered
+//!
+//! Each byte read is a variable, with the set of values it may still have,
+//! and each cell is either known or an affine function of a single byte. A
+//! loop on such a cell splits the values of its byte between the paths
+//! entering it and the ones skipping it; when a cell would depend on two
+//! bytes, the path forks on each value of one of them. The exploration is
+//! bounded by `Limits`, so its results may only cover a part of the runs.
+
+use alloc::collections::BTreeMap;
+use core::ops::RangeInclusive;
+
+use crate::ast::{Node, NodeKind, Span};
+use crate::prelude::*;
+use crate::vm::{Bounds, Eof};
+use crate::Config;
+
+/// Bounds of the exploration of the runs
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub max_input: usize,          // Bytes read at most, the input ending after them
+    pub bytes: RangeInclusive<u8>, // Values of the bytes read
+    pub max_paths: usize,          // Paths explored at most
+    pub max_steps: u64,            // Instructions run on each path at most
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_input: 4,
+            bytes: 0..=255,
+            max_paths: 10_000,
+            max_steps: 100_000,
+        }
+    }
+}
+
+/// A way for the pointer to leave the tape
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Fault {
+    Underflow, // The pointer goes below the first cell
+    Overflow,  // The pointer goes past the last cell
+}
+
+/// What the runs on symbolic input found
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub faults: Vec<(Fault, Span, Vec<u8>)>, // Moves leaving the tape, with an input doing it
+    pub loops: Vec<(Span, Option<Vec<u8>>)>, // The loops, with an input entering them if any
+    pub paths: usize,                        // Paths explored, each fork starting one
+    pub complete: bool, // All the runs on inputs within the limits were explored
+}
+
+/// The values a byte read may still have, as a bitset
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Domain([u64; 4]);
+
+impl Domain {
+    fn new(bytes: &RangeInclusive<u8>) -> Self {
+        let mut domain = Domain([0; 4]);
+        for byte in bytes.clone() {
+            domain.0[byte as usize / 64] |= 1 << (byte % 64);
+        }
+        domain
+    }
+
+    fn values(self) -> impl Iterator<Item = u8> {
+        (0..=255).filter(move |&byte| self.0[byte as usize / 64] & (1 << (byte % 64)) != 0)
+    }
+
+    /// The values of the domain for which `keep` is true
+    fn filter(&self, keep: impl Fn(u8) -> bool) -> Self {
+        let mut domain = Domain([0; 4]);
+        for byte in self.values().filter(|&byte| keep(byte)) {
+            domain.0[byte as usize / 64] |= 1 << (byte % 64);
+        }
+        domain
+    }
+
+    fn len(&self) -> u32 {
+        self.0.iter().map(|bits| bits.count_ones()).sum()
+    }
+}
+
+/// A cell of a path
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value {
+    Known(u64),
+    Affine {
+        byte: usize,
+        factor: u64,
+        offset: u64,
+    }, // offset + factor * the byte read
+}
+
+/// A run up to some instruction, for the inputs within the domains of its
+/// bytes
+#[derive(Clone)]
+struct Path {
+    next: usize, // Index of the next step
+    pointer: isize,
+    cells: BTreeMap<isize, Value>, // The cells changed so far, the others being 0
+    bytes: Vec<Domain>,            // The bytes read so far
+    ended: bool,                   // The input ended
+    steps: u64,
+}
+
+impl Path {
+    /// The value of a cell, known when its byte has a single value left
+    fn cell(&self, index: isize, mask: u64) -> Value {
+        match self.cells.get(&index).copied().unwrap_or(Value::Known(0)) {
+            Value::Affine {
+                byte,
+                factor,
+                offset,
+            } if self.bytes[byte].len() == 1 => {
+                let value = self.bytes[byte].values().next().unwrap() as u64;
+                Value::Known(offset.wrapping_add(factor.wrapping_mul(value)) & mask)
+            }
+            value => value,
+        }
+    }
+
+    /// An input following the path: the smallest value of each byte
+    fn input(&self) -> Vec<u8> {
+        self.bytes
+            .iter()
+            .map(|domain| domain.values().next().unwrap())
+            .collect()
+    }
+}
+
+/// An instruction of a program flattened for the exploration
+enum Step {
+    Command(Node),
+    LoopBegin { end: usize, span: Span },
+    LoopEnd { begin: usize },
+    Unsupported, // Procedures of pbrain and commands of Extended Brainfuck
+}
+
+fn flatten_ast(node: &Node, steps: &mut Vec<Step>) {
+    match &node.kind {
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                flatten_ast(node, steps);
+            }
+        }
+        NodeKind::Loop(body) => {
+            let begin = steps.len();
+            steps.push(Step::Unsupported);
+            flatten_ast(body, steps);
+            let end = steps.len();
+            steps.push(Step::LoopEnd { begin });
+            steps[begin] = Step::LoopBegin {
+                end,
+                span: node.span,
+            };
+        }
+        NodeKind::Procedure(_) | NodeKind::Call | NodeKind::Extended(_) => {
+            steps.push(Step::Unsupported)
+        }
+        _ => steps.push(Step::Command(node.clone())),
+    }
+}
+
+/// Whether an input is shorter than another one, or as long and smaller
+fn shorter(input: &[u8], other: &[u8]) -> bool {
+    (input.len(), input) < (other.len(), other)
+}
+
+/// The state of an exploration
+struct Explorer<'a> {
+    steps: Vec<Step>,
+    config: &'a Config,
+    limits: &'a Limits,
+    mask: u64, // Values of the cells
+    pending: Vec<Path>,
+    faults: BTreeMap<(Fault, Span), Vec<u8>>,
+    entered: BTreeMap<Span, Option<Vec<u8>>>,
+    paths: usize,
+    complete: bool,
+}
+
+impl Explorer<'_> {
+    fn add(&self, value: Value, amount: u64) -> Value {
+        match value {
+            Value::Known(value) => Value::Known(value.wrapping_add(amount) & self.mask),
+            Value::Affine {
+                byte,
+                factor,
+                offset,
+            } => Value::Affine {
+                byte,
+                factor,
+                offset: offset.wrapping_add(amount) & self.mask,
+            },
+        }
+    }
+
+    /// The paths where a value is zero and where it isn't, the impossible
+    /// ones left out
+    fn split(&self, path: Path, value: Value) -> (Option<Path>, Option<Path>) {
+        let (byte, factor, offset) = match value {
+            Value::Known(0) => return (Some(path), None),
+            Value::Known(_) => return (None, Some(path)),
+            Value::Affine {
+                byte,
+                factor,
+                offset,
+            } => (byte, factor, offset),
+        };
+        let mask = self.mask;
+        let is_zero = |x: u8| offset.wrapping_add(factor.wrapping_mul(x as u64)) & mask == 0;
+        let with = |domain: Domain| {
+            (domain.len() > 0).then(|| {
+                let mut path = path.clone();
+                path.bytes[byte] = domain;
+                path
+            })
+        };
+        let domain = path.bytes[byte];
+        (
+            with(domain.filter(is_zero)),
+            with(domain.filter(|x| !is_zero(x))),
+        )
+    }
+
+    /// Keep the shortest input found for a fault
+    fn fault(&mut self, fault: Fault, span: Span, path: &Path) {
+        let input = path.input();
+        let entry = self
+            .faults
+            .entry((fault, span))
+            .or_insert_with(|| input.clone());
+        if shorter(&input, entry) {
+            *entry = input;
+        }
+    }
+
+    /// Keep the shortest input found entering a loop
+    fn enter(&mut self, span: Span, path: Option<&Path>) {
+        let entry = self.entered.entry(span).or_insert(None);
+        match (path.map(Path::input), entry.as_ref()) {
+            (Some(input), Some(kept)) if !shorter(&input, kept) => {}
+            (Some(input), _) => *entry = Some(input),
+            (None, _) => {}
+        }
+    }
+
+    /// The index of the cell at an offset of the pointer, or the way it
+    /// leaves the tape
+    fn index(&self, pointer: isize, offset: isize) -> Result<isize, Fault> {
+        let index = pointer + offset;
+        let size = self.config.tape_size as isize;
+        match self.config.bounds {
+            Bounds::Wrap => Ok(index.rem_euclid(size)),
+            Bounds::Unchecked if index < 0 => Err(Fault::Underflow),
+            Bounds::Unchecked if index >= size => Err(Fault::Overflow),
+            Bounds::Unchecked => Ok(index),
+        }
+    }
+
+    /// Run a path until it ends, fails or forks, the other paths of the
+    /// forks being left pending
+    fn run(&mut self, mut path: Path) {
+        loop {
+            if path.steps == self.limits.max_steps {
+                self.complete = false;
+                return;
+            }
+            path.steps += 1;
+            let value = path.cell(path.pointer, self.mask);
+            match self.steps.get(path.next) {
+                None => return,
+                Some(Step::Unsupported) => {
+                    self.complete = false;
+                    return;
+                }
+                Some(Step::LoopBegin { end, span }) => {
+                    let (end, span) = (*end, *span);
+                    let (zero, nonzero) = self.split(path, value);
+                    self.enter(span, nonzero.as_ref());
+                    if let Some(mut zero) = zero {
+                        zero.next = end + 1;
+                        self.pending.push(zero);
+                    }
+                    match nonzero {
+                        Some(nonzero) => path = nonzero,
+                        None => return,
+                    }
+                }
+                Some(Step::LoopEnd { begin }) => {
+                    let begin = *begin;
+                    let (zero, nonzero) = self.split(path, value);
+                    if let Some(mut nonzero) = nonzero {
+                        nonzero.next = begin;
+                        self.pending.push(nonzero);
+                    }
+                    match zero {
+                        Some(zero) => path = zero,
+                        None => return,
+                    }
+                }
+                Some(Step::Command(node)) => {
+                    let node = node.clone();
+                    if !self.command(&node, &mut path, value) {
+                        return;
+                    }
+                }
+            }
+            path.next += 1;
+        }
+    }
+
+    /// Run a command on a path, returning whether the path goes on
+    fn command(&mut self, node: &Node, path: &mut Path, value: Value) -> bool {
+        let pointer = path.pointer;
+        match &node.kind {
+            NodeKind::Incr(val) => {
+                let value = self.add(value, *val as i64 as u64);
+                path.cells.insert(pointer, value);
+            }
+            NodeKind::Move(val) => match self.index(pointer, *val) {
+                Ok(index) => path.pointer = index,
+                Err(fault) => {
+                    self.fault(fault, node.span, path);
+                    return false;
+                }
+            },
+            NodeKind::Write => {}
+            NodeKind::Read => {
+                // The input may end before each byte
+                let mut ended = path.clone();
+                ended.ended = true;
+                match self.config.eof {
+                    Eof::Unchanged => {}
+                    Eof::Zero => {
+                        ended.cells.insert(pointer, Value::Known(0));
+                    }
+                    Eof::MinusOne => {
+                        ended.cells.insert(pointer, Value::Known(self.mask));
+                    }
+                }
+                if path.ended || path.bytes.len() == self.limits.max_input {
+                    *path = ended;
+                } else {
+                    ended.next += 1;
+                    self.pending.push(ended);
+                    let byte = path.bytes.len();
+                    path.bytes.push(Domain::new(&self.limits.bytes));
+                    let value = Value::Affine {
+                        byte,
+                        factor: 1,
+                        offset: 0,
+                    };
+                    path.cells.insert(pointer, value);
+                }
+            }
+            NodeKind::Clear => {
+                let (_, nonzero) = self.split(path.clone(), value);
+                self.enter(node.span, nonzero.as_ref());
+                path.cells.insert(pointer, Value::Known(0));
+            }
+            NodeKind::Mul(targets) => {
+                let (zero, nonzero) = self.split(path.clone(), value);
+                self.enter(node.span, nonzero.as_ref());
+                let faulty = targets
+                    .iter()
+                    .find_map(|(offset, _)| self.index(pointer, *offset).err());
+                match (faulty, nonzero) {
+                    // The targets are only reached on a nonzero cell
+                    (Some(fault), Some(nonzero)) => {
+                        self.fault(fault, node.span, &nonzero);
+                        match zero {
+                            Some(zero) => *path = zero,
+                            None => return false,
+                        }
+                    }
+                    (Some(_), None) => {}
+                    (None, _) => {
+                        // The path forks before changing any target
+                        let mut changes = Vec::new();
+                        for (offset, factor) in targets.iter() {
+                            let index = self.index(pointer, *offset).unwrap();
+                            let target = path.cell(index, self.mask);
+                            match self.mul_add(target, value, *factor as i64 as u64) {
+                                Some(target) => changes.push((index, target)),
+                                None => return self.fork_byte(path, value),
+                            }
+                        }
+                        path.cells.extend(changes);
+                    }
+                }
+                path.cells.insert(pointer, Value::Known(0));
+            }
+            NodeKind::Loop(_)
+            | NodeKind::Block(_)
+            | NodeKind::Procedure(_)
+            | NodeKind::Call
+            | NodeKind::Extended(_) => unreachable!("not a command"),
+        }
+        true
+    }
+
+    /// `target + factor * value`, unless it depends on two bytes
+    fn mul_add(&self, target: Value, value: Value, factor: u64) -> Option<Value> {
+        let mask = self.mask;
+        let (byte, value_factor, value_offset) = match value {
+            Value::Known(value) => return Some(self.add(target, value.wrapping_mul(factor))),
+            Value::Affine {
+                byte,
+                factor,
+                offset,
+            } => (byte, factor, offset),
+        };
+        let (target_factor, target_offset) = match target {
+            Value::Known(target) => (0, target),
+            Value::Affine {
+                byte: target_byte,
+                factor,
+                offset,
+            } if target_byte == byte => (factor, offset),
+            Value::Affine { .. } => return None,
+        };
+        Some(Value::Affine {
+            byte,
+            factor: target_factor.wrapping_add(value_factor.wrapping_mul(factor)) & mask,
+            offset: target_offset.wrapping_add(value_offset.wrapping_mul(factor)) & mask,
+        })
+    }
+
+    /// Replace a path by one for each value of the byte of a cell, for the
+    /// cells to depend on a single byte, the instruction running again
+    fn fork_byte(&mut self, path: &Path, value: Value) -> bool {
+        if let Value::Affine { byte, .. } = value {
+            for x in path.bytes[byte].values() {
+                let mut fork = path.clone();
+                fork.bytes[byte] = fork.bytes[byte].filter(|y| y == x);
+                fork.steps -= 1;
+                self.pending.push(fork);
+            }
+        }
+        false
+    }
+}
+
+/// Explore the runs of a program on the inputs within some limits
+///
+/// ```
+/// use brainfuck::compile_source;
+/// use brainfuck::symbolic::{explore, Fault, Limits};
+/// use brainfuck::Config;
+///
+/// // The pointer leaves the tape when the byte read is "a"
+/// let source = format!(">+<,{}[[-]>-<]>[<<]", "-".repeat(97));
+/// let ast = compile_source(&source).unwrap();
+/// let analysis = explore(&ast, &Config::default(), &Limits::default());
+/// assert_eq!(analysis.faults[0].0, Fault::Underflow);
+/// assert_eq!(analysis.faults[0].2, b"a");
+/// assert!(analysis.complete);
+/// ```
+pub fn explore(ast: &Node, config: &Config, limits: &Limits) -> Analysis {
+    let mut steps = Vec::new();
+    flatten_ast(ast, &mut steps);
+    let mut explorer = Explorer {
+        steps,
+        config,
+        limits,
+        mask: config.cell.max() as u64,
+        pending: vec![Path {
+            next: 0,
+            pointer: 0,
+            cells: BTreeMap::new(),
+            bytes: Vec::new(),
+            ended: false,
+            steps: 0,
+        }],
+        faults: BTreeMap::new(),
+        entered: BTreeMap::new(),
+        paths: 0,
+        complete: true,
+    };
+    while let Some(path) = explorer.pending.pop() {
+        if explorer.paths == limits.max_paths {
+            explorer.complete = false;
+            break;
+        }
+        explorer.paths += 1;
+        explorer.run(path);
+    }
+
+    // The loops never reached are never entered either
+    let mut loops = Vec::new();
+    loop_spans(ast, &mut loops);
+    let entered = explorer.entered;
+    Analysis {
+        faults: explorer
+            .faults
+            .into_iter()
+            .map(|((fault, span), input)| (fault, span, input))
+            .collect(),
+        loops: loops
+            .into_iter()
+            .map(|span| (span, entered.get(&span).cloned().flatten()))
+            .collect(),
+        paths: explorer.paths,
+        complete: explorer.complete,
+    }
+}
+
+/// The spans of the loops of an AST, optimized or not, in the order of the
+/// source
+fn loop_spans(node: &Node, spans: &mut Vec<Span>) {
+    match &node.kind {
+        NodeKind::Loop(body) => {
+            spans.push(node.span);
+            loop_spans(body, spans);
+        }
+        NodeKind::Clear | NodeKind::Mul(_) => spans.push(node.span),
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                loop_spans(node, spans);
+            }
+        }
+        NodeKind::Procedure(body) => loop_spans(body, spans),
+        _ => {}
+    }
+}
@@ -0,0 +1,1201 @@
+//! The interpreter of the programs
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::io::BufRead;
+#[cfg(feature = "std")]
+use std::io::IsTerminal;
+#[cfg(feature = "std")]
+use std::io::Read;
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(feature = "codegen")]
+use std::process::{Command, Stdio};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::ast::{walk, Extended, Node, NodeKind, Span, Visitor};
+use crate::cell::CellValue;
+use crate::prelude::*;
+use crate::Config;
+
+/// Behavior of the read instruction on end of input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Eof {
+    Unchanged, // Leave the cell unchanged
+    Zero,      // Set the cell to 0
+    MinusOne,  // Set the cell to -1
+}
+
+/// Width of the memory cells, which wrap around on overflow
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cell {
+    U8,
+    U16,
+    U32,
+}
+
+impl Cell {
+    /// Largest value of a cell
+    pub fn max(self) -> u32 {
+        match self {
+            Cell::U8 => u8::MAX as u32,
+            Cell::U16 => u16::MAX as u32,
+            Cell::U32 => u32::MAX,
+        }
+    }
+}
+
+/// Behavior of the data pointer past the ends of the tape
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bounds {
+    Unchecked, // Leaving the tape is an error, undefined in the generated code
+    Wrap,      // The tape is circular
+}
+
+/// Execution counts of the nodes of a program, by span
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub counts: BTreeMap<Span, u64>,
+}
+
+impl Profile {
+    /// Number of executions of the node at a span
+    pub fn count(&self, span: Span) -> u64 {
+        self.counts.get(&span).copied().unwrap_or(0)
+    }
+
+    /// Number of executions of the hottest node
+    pub fn max(&self) -> u64 {
+        self.counts.values().copied().max().unwrap_or(0)
+    }
+}
+
+/// Totals of a profiled run
+#[derive(Default)]
+pub struct RunStats {
+    pub instructions: u64, // Executed nodes, a multiply loop counting as one
+    pub iterations: u64,   // Runs of the loop bodies
+    pub reads: u64,        // Executed reads, including those at the end of the input
+    pub writes: u64,
+}
+
+/// Add up the execution counts of the nodes of an AST
+///
+/// The nodes sharing a span, such as the moves of the start precomputed by
+/// the optimizer, count their executions once.
+pub fn run_stats(ast: &Node, profile: &Profile, stats: &mut RunStats) {
+    struct Stats<'a> {
+        profile: &'a Profile,
+        stats: &'a mut RunStats,
+        counted: BTreeSet<Span>,
+    }
+    impl Visitor for Stats<'_> {
+        fn visit(&mut self, node: &Node) {
+            if let NodeKind::Block(_) = node.kind {
+                return walk(self, node);
+            }
+            let count = match self.counted.insert(node.span) {
+                true => self.profile.count(node.span),
+                false => 0,
+            };
+            match &node.kind {
+                NodeKind::Loop(body) => self.stats.iterations += first_count(body, self.profile),
+                NodeKind::Read => self.stats.reads += count,
+                NodeKind::Write => self.stats.writes += count,
+                _ => {}
+            }
+            self.stats.instructions += count;
+            walk(self, node);
+        }
+    }
+
+    // A body runs as many times as its first node
+    fn first_count(body: &Node, profile: &Profile) -> u64 {
+        match &body.kind {
+            NodeKind::Block(nodes) => nodes.first().map_or(0, |node| first_count(node, profile)),
+            _ => profile.count(body.span),
+        }
+    }
+
+    Stats {
+        profile,
+        stats,
+        counted: BTreeSet::new(),
+    }
+    .visit(ast);
+}
+
+/// Execution counts of the commands of an AST, in the order of the source,
+/// each bracket of a loop or a procedure counting the times it was reached
+///
+/// Without optimizations, each command is a byte of the source, so the
+/// commands which never ran can be pointed at.
+pub fn coverage(ast: &Node, profile: &Profile) -> Vec<(Span, u64)> {
+    struct Coverage<'a> {
+        profile: &'a Profile,
+        counts: Vec<(Span, u64)>,
+    }
+    impl Visitor for Coverage<'_> {
+        fn visit(&mut self, node: &Node) {
+            let count = self.profile.count(node.span);
+            let span = node.span;
+            match &node.kind {
+                NodeKind::Block(_) => walk(self, node),
+                NodeKind::Loop(_) | NodeKind::Procedure(_) => {
+                    self.counts
+                        .push((Span::new(span.start, span.start + 1), count));
+                    walk(self, node);
+                    self.counts.push((Span::new(span.end - 1, span.end), count));
+                }
+                _ => self.counts.push((span, count)),
+            }
+        }
+    }
+
+    let mut coverage = Coverage {
+        profile,
+        counts: Vec::new(),
+    };
+    coverage.visit(ast);
+    coverage.counts
+}
+
+/// State of the brainfuck VM, on a tape of `u32` cells by default
+pub struct State<C = u32> {
+    pub memory: Vec<C>,
+    pub index: usize,
+    pub highest: usize, // Highest index of the cells used so far
+    pub written: u64,   // Bytes written by the program so far
+    pub steps: u64,     // Nodes run by the last call of run_ast
+    #[cfg(feature = "std")]
+    pub deadline: Option<Instant>, // End of the timeout of the last call of run_ast
+    pub profile: Option<Profile>, // Count the executed nodes, when set
+    pub hook: Option<Box<StepHook<C>>>, // Called before each node, with the tape and the pointer
+    pub loop_enter: Option<Box<LoopHook<C>>>, // Called when a loop is reached
+    pub loop_exit: Option<Box<LoopHook<C>>>, // Called when a loop ends
+    pub access: Option<Box<AccessHook<C>>>, // Called on each read or write of a cell
+    pub input: Box<dyn ByteRead>, // Read by ","
+    pub output: Box<dyn ByteWrite>, // Written by "."
+    pub procedures: BTreeMap<u32, Arc<Node>>, // Bodies of the procedures of pbrain
+    pub storage: C,     // Storage register of Extended Brainfuck
+    pub interrupt: Option<Arc<AtomicBool>>, // Stops run_ast at the next iteration or call, when set
+}
+
+/// A function called by the VM before running a node
+pub type StepHook<C = u32> = dyn FnMut(&Node, &[C], usize);
+
+/// A function called by the VM around a loop, with its span, the tape and
+/// the pointer
+pub type LoopHook<C = u32> = dyn FnMut(Span, &[C], usize);
+
+/// An access of a cell of the tape by the program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,  // Its value is tested, written or names a procedure
+    Write, // Its value is set
+}
+
+/// A function called by the VM on each access of a cell, with the index of
+/// the cell, its value after the access and the span of the node accessing it
+pub type AccessHook<C = u32> = dyn FnMut(Access, usize, &C, Span);
+
+impl State {
+    /// A blank tape, sized by the configuration
+    pub fn new(config: &Config) -> Self {
+        State::with_cells(config)
+    }
+}
+
+impl<C: CellValue> State<C> {
+    /// A blank tape of some type of cells, sized by the configuration
+    pub fn with_cells(config: &Config) -> Self {
+        State {
+            memory: vec![C::default(); config.tape_size],
+            index: 0,
+            highest: 0,
+            written: 0,
+            steps: 0,
+            #[cfg(feature = "std")]
+            deadline: None,
+            procedures: BTreeMap::new(),
+            storage: C::default(),
+            profile: None,
+            hook: None,
+            loop_enter: None,
+            loop_exit: None,
+            access: None,
+            interrupt: None,
+            #[cfg(feature = "std")]
+            input: Box::new(stdin_reader(PROMPT)),
+            #[cfg(feature = "std")]
+            output: Box::new(io::stdout()),
+            #[cfg(not(feature = "std"))]
+            input: Box::new(&[][..]),
+            #[cfg(not(feature = "std"))]
+            output: Box::new(Sink),
+        }
+    }
+
+    /// Start over on a blank tape, keeping its allocation, the streams and
+    /// the hooks
+    ///
+    /// The pointer, the counters, the procedures and the storage are cleared,
+    /// and the profile, when set, starts from 0. A state reset between runs
+    /// saves allocating a new tape for each, in the benchmarks or in a server
+    /// running many programs.
+    ///
+    /// ```
+    /// use brainfuck::vm::{run_ast, State};
+    /// use brainfuck::{compile_source, Config};
+    ///
+    /// let config = Config::default();
+    /// let mut state = State::new(&config);
+    /// state.output = Box::new(Vec::new());
+    /// run_ast(&compile_source(">+++").unwrap(), &mut state, &config).unwrap();
+    /// state.reset();
+    /// assert_eq!((state.index, state.memory[1]), (0, 0));
+    /// ```
+    pub fn reset(&mut self) {
+        self.memory.fill(C::default());
+        self.index = 0;
+        self.highest = 0;
+        self.written = 0;
+        self.procedures.clear();
+        self.storage = C::default();
+        if let Some(profile) = &mut self.profile {
+            profile.counts.clear();
+        }
+    }
+}
+
+/// Error of the streams of the programs
+#[cfg(feature = "std")]
+pub use std::io::Error as IoError;
+
+/// Error of the streams of the programs
+#[cfg(not(feature = "std"))]
+#[derive(Debug, Error)]
+#[error("input or output error")]
+pub struct IoError;
+
+/// Stream read by ",", one byte at a time
+pub trait ByteRead {
+    /// The next byte, or None at the end of the input
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError>;
+}
+
+/// Stream written by "."
+pub trait ByteWrite {
+    /// Write a byte, maybe buffered
+    fn write_byte(&mut self, byte: u8) -> Result<(), IoError>;
+
+    /// Write out the buffered bytes
+    fn flush_bytes(&mut self) -> Result<(), IoError>;
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + ?Sized> ByteRead for R {
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError> {
+        let mut buffer = [0];
+        Ok(match self.read(&mut buffer)? {
+            1 => Some(buffer[0]),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write + ?Sized> ByteWrite for W {
+    fn write_byte(&mut self, byte: u8) -> Result<(), IoError> {
+        self.write_all(&[byte])
+    }
+
+    fn flush_bytes(&mut self) -> Result<(), IoError> {
+        self.flush()
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl ByteRead for &[u8] {
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError> {
+        Ok(self.split_first().map(|(byte, rest)| {
+            *self = rest;
+            *byte
+        }))
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl ByteWrite for Vec<u8> {
+    fn write_byte(&mut self, byte: u8) -> Result<(), IoError> {
+        self.push(byte);
+        Ok(())
+    }
+
+    fn flush_bytes(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+/// An input read from a function, returning None at its end
+pub struct FnInput<F>(pub F);
+
+impl<F: FnMut() -> Option<u8>> ByteRead for FnInput<F> {
+    fn read_byte(&mut self) -> Result<Option<u8>, IoError> {
+        Ok((self.0)())
+    }
+}
+
+/// An output given to a function, a byte at a time
+pub struct FnOutput<F>(pub F);
+
+impl<F: FnMut(u8)> ByteWrite for FnOutput<F> {
+    fn write_byte(&mut self, byte: u8) -> Result<(), IoError> {
+        (self.0)(byte);
+        Ok(())
+    }
+
+    fn flush_bytes(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+/// An output throwing the bytes away
+#[cfg(not(feature = "std"))]
+pub struct Sink;
+
+#[cfg(not(feature = "std"))]
+impl ByteWrite for Sink {
+    fn write_byte(&mut self, _byte: u8) -> Result<(), IoError> {
+        Ok(())
+    }
+
+    fn flush_bytes(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+/// Prompt of the standard input of the VM, when it is a terminal
+#[cfg(feature = "std")]
+pub const PROMPT: &str = "> ";
+
+/// The standard input, read a line at a time after a prompt when it is a
+/// terminal, so that the line can be edited before the program gets it
+#[cfg(feature = "std")]
+pub fn stdin_reader(prompt: &str) -> Box<dyn Read> {
+    if io::stdin().is_terminal() {
+        Box::new(PromptedStdin {
+            prompt: String::from(prompt),
+            line: io::Cursor::new(Vec::new()),
+        })
+    } else {
+        Box::new(io::stdin())
+    }
+}
+
+/// The standard input of a terminal, with a prompt before each line
+#[cfg(feature = "std")]
+struct PromptedStdin {
+    prompt: String,
+    line: io::Cursor<Vec<u8>>, // Rest of the last line read
+}
+
+#[cfg(feature = "std")]
+impl Read for PromptedStdin {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.line.position() as usize == self.line.get_ref().len() {
+            // The prompt goes to the standard error, to keep the output clean
+            let mut stderr = io::stderr();
+            stderr.write_all(self.prompt.as_bytes())?;
+            stderr.flush()?;
+            let mut line = Vec::new();
+            io::stdin().lock().read_until(b'\n', &mut line)?;
+            self.line = io::Cursor::new(line);
+        }
+        self.line.read(buffer)
+    }
+}
+
+/// An output kept in memory, to read back what the VM wrote
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(feature = "std")]
+impl SharedBuffer {
+    /// The bytes written so far
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// The bytes written since the last take, leaving the buffer empty
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Write for SharedBuffer {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buffer);
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Where the input of a program comes from
+#[cfg(feature = "std")]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+#[cfg(feature = "std")]
+impl Input {
+    /// A reader of the input, for the VM, prompting for the lines of a
+    /// terminal
+    pub fn reader(&self, prompt: &str) -> io::Result<Box<dyn Read>> {
+        Ok(match self {
+            Input::Stdin => stdin_reader(prompt),
+            Input::File(path) => Box::new(io::BufReader::new(File::open(path)?)),
+            Input::Bytes(bytes) => Box::new(io::Cursor::new(bytes.clone())),
+        })
+    }
+
+    /// Give the input to a command, returning the bytes to write on its
+    /// piped stdin, if any
+    #[cfg(feature = "codegen")]
+    pub(crate) fn feed(&self, command: &mut Command) -> io::Result<Option<&[u8]>> {
+        match self {
+            Input::Stdin => Ok(None),
+            Input::File(path) => {
+                command.stdin(File::open(path)?);
+                Ok(None)
+            }
+            Input::Bytes(bytes) => {
+                command.stdin(Stdio::piped());
+                Ok(Some(bytes))
+            }
+        }
+    }
+}
+
+/// An error stopping the brainfuck VM
+#[derive(Debug, Error)]
+pub enum RuntimeError {
+    #[error("pointer out of the tape at byte {} of the source", .0.start)]
+    OutOfTape(Span), // Span of the node moving the pointer out of the tape
+    #[error("output limit reached at byte {} of the source", .0.start)]
+    OutputLimit(Span), // Span of the write past the limit of the output
+    #[error("step limit reached at byte {} of the source", .0.start)]
+    StepLimit(Span), // Span of the node past the limit of the steps
+    #[error("timed out at byte {} of the source", .0.start)]
+    Timeout(Span), // Span of the loop or the call running past the timeout
+    #[error("call of an undefined procedure at byte {} of the source", .0.start)]
+    UnknownProcedure(Span), // Span of the call of a procedure of pbrain never defined
+    #[error("interrupted at byte {} of the source", .0.start)]
+    Interrupted(Span), // Span of the loop or the call stopped by the interrupt flag
+    #[error("loops and calls nested too deeply at byte {} of the source", .0.start)]
+    TooDeep(Span), // Span of the loop or the call nested past MAX_DEPTH
+    #[error("{0}")]
+    Io(#[from] IoError), // Error of the input or the output of the program
+}
+
+/// Index of the cell at some offset of the current one, for the node at
+/// `span`
+///
+/// Leaving an unchecked tape stops the VM with the offset of the node in the
+/// source, like the generated code with `--codegen-checks`.
+fn offset_index<C>(
+    state: &State<C>,
+    offset: isize,
+    span: Span,
+    config: &Config,
+) -> Result<usize, RuntimeError> {
+    match config.bounds {
+        Bounds::Unchecked => {
+            let index = state.index as isize + offset;
+            if index < 0 || index as usize >= state.memory.len() {
+                return Err(RuntimeError::OutOfTape(span));
+            }
+            Ok(index as usize)
+        }
+        Bounds::Wrap => Ok(config.wrap(state.index, offset)),
+    }
+}
+
+/// Tell the access hook of a state, if any, about an access of a cell
+fn access<C>(state: &mut State<C>, access: Access, index: usize, span: Span) {
+    if let Some(hook) = &mut state.access {
+        hook(access, index, &state.memory[index], span);
+    }
+}
+
+/// Run a command of the VM, which isn't a loop, a block or a procedure
+fn run_command<C: CellValue>(
+    node: &Node,
+    state: &mut State<C>,
+    config: &Config,
+) -> Result<(), RuntimeError> {
+    match &node.kind {
+        NodeKind::Incr(val) => {
+            let cell = &mut state.memory[state.index];
+            cell.add(*val);
+            cell.truncate(config.cell);
+            access(state, Access::Write, state.index, node.span);
+        }
+        NodeKind::Move(val) => {
+            state.index = offset_index(state, *val, node.span, config)?;
+            state.highest = state.highest.max(state.index);
+        }
+        NodeKind::Write => {
+            if config.max_output.is_some_and(|max| state.written >= max) {
+                return Err(RuntimeError::OutputLimit(node.span));
+            }
+            let byte = state.memory[state.index].to_byte();
+            access(state, Access::Read, state.index, node.span);
+            state.output.write_byte(byte)?;
+            state.written += 1;
+        }
+        NodeKind::Read => {
+            state.output.flush_bytes()?;
+            match state.input.read_byte()? {
+                Some(byte) => state.memory[state.index] = C::from_byte(byte),
+                None => match config.eof {
+                    Eof::Unchanged => return Ok(()),
+                    Eof::Zero => state.memory[state.index] = C::default(),
+                    Eof::MinusOne => {
+                        let cell = &mut state.memory[state.index];
+                        *cell = C::default();
+                        cell.add(-1);
+                        cell.truncate(config.cell);
+                    }
+                },
+            }
+            access(state, Access::Write, state.index, node.span);
+        }
+        NodeKind::Clear => {
+            state.memory[state.index] = C::default();
+            access(state, Access::Write, state.index, node.span);
+        }
+        NodeKind::Mul(targets) => {
+            let val = state.memory[state.index].clone();
+            access(state, Access::Read, state.index, node.span);
+            // The loop doesn't run, and doesn't move, on a cell at 0
+            for (offset, factor) in targets.iter().filter(|_| !val.is_zero()) {
+                let index = offset_index(state, *offset, node.span, config)?;
+                state.highest = state.highest.max(index);
+                let cell = &mut state.memory[index];
+                cell.add_mul(&val, *factor);
+                cell.truncate(config.cell);
+                access(state, Access::Write, index, node.span);
+            }
+            state.memory[state.index] = C::default();
+            access(state, Access::Write, state.index, node.span);
+        }
+        NodeKind::Extended(Extended::Store) => {
+            state.storage = state.memory[state.index].clone();
+            access(state, Access::Read, state.index, node.span);
+        }
+        NodeKind::Extended(Extended::Load) => {
+            state.memory[state.index] = state.storage.clone();
+            access(state, Access::Write, state.index, node.span);
+        }
+        NodeKind::Extended(command) => {
+            let cell = &mut state.memory[state.index];
+            cell.bitwise(*command, &state.storage);
+            cell.truncate(config.cell);
+            access(state, Access::Write, state.index, node.span);
+        }
+        NodeKind::Loop(_) | NodeKind::Block(_) | NodeKind::Procedure(_) | NodeKind::Call => {
+            unreachable!("not a command")
+        }
+    }
+
+    Ok(())
+}
+
+/// Stop a run, when the interrupt flag of its state is set or when its
+/// timeout is over
+fn check_interrupt<C>(state: &State<C>, span: Span) -> Result<(), RuntimeError> {
+    match &state.interrupt {
+        Some(flag) if flag.load(Ordering::Relaxed) => return Err(RuntimeError::Interrupted(span)),
+        _ => {}
+    }
+    #[cfg(feature = "std")]
+    if state
+        .deadline
+        .is_some_and(|deadline| Instant::now() >= deadline)
+    {
+        return Err(RuntimeError::Timeout(span));
+    }
+    Ok(())
+}
+
+/// Count a step of a run, stopping it past the `max_steps` of the
+/// configuration
+fn count_step<C>(state: &mut State<C>, span: Span, config: &Config) -> Result<(), RuntimeError> {
+    state.steps += 1;
+    match config.max_steps {
+        Some(max) if state.steps > max => Err(RuntimeError::StepLimit(span)),
+        _ => Ok(()),
+    }
+}
+
+/// Deepest nesting of the loops and the calls of procedures in a run, each
+/// level taking a few frames of the native stack, well within the 2 MiB of
+/// the threads spawned by std even in debug builds
+pub const MAX_DEPTH: usize = 1024;
+
+/// Run an AST in the brainfuck VM
+///
+/// The interrupt flag of the state, and the timeout of the configuration,
+/// are checked at each iteration of a loop and at each call, which any
+/// endless run goes through. The nodes run, and the iterations of the loops,
+/// count as the steps bounded by its `max_steps`. Loops and calls nested
+/// deeper than `MAX_DEPTH`, such as an endless recursion, stop the run with
+/// `RuntimeError::TooDeep`.
+pub fn run_ast<C: CellValue>(
+    node: &Node,
+    state: &mut State<C>,
+    config: &Config,
+) -> Result<(), RuntimeError> {
+    state.steps = 0;
+    #[cfg(feature = "std")]
+    {
+        state.deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+    }
+    run_nested(node, state, config, 0)
+}
+
+/// Run a node at some depth of nesting of the loops and the calls
+///
+/// The loops and the calls run in functions of their own, keeping the frame
+/// of each level of the recursion small.
+fn run_nested<C: CellValue>(
+    node: &Node,
+    state: &mut State<C>,
+    config: &Config,
+    depth: usize,
+) -> Result<(), RuntimeError> {
+    match &node.kind {
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                run_nested(node, state, config, depth)?;
+            }
+            Ok(())
+        }
+        NodeKind::Loop(_) | NodeKind::Call if depth == MAX_DEPTH => {
+            Err(RuntimeError::TooDeep(node.span))
+        }
+        NodeKind::Loop(body) => run_loop(node, body, state, config, depth + 1),
+        NodeKind::Call => run_call(node, state, config, depth + 1),
+        _ => run_leaf(node, state, config),
+    }
+}
+
+/// Count a node in the profile, and call the hook, before running it
+fn enter<C>(node: &Node, state: &mut State<C>) {
+    if let Some(profile) = &mut state.profile {
+        *profile.counts.entry(node.span).or_insert(0) += 1;
+    }
+    if let Some(hook) = &mut state.hook {
+        hook(node, &state.memory, state.index);
+    }
+}
+
+fn run_loop<C: CellValue>(
+    node: &Node,
+    body: &Node,
+    state: &mut State<C>,
+    config: &Config,
+    depth: usize,
+) -> Result<(), RuntimeError> {
+    enter(node, state);
+    if let Some(hook) = &mut state.loop_enter {
+        hook(node.span, &state.memory, state.index);
+    }
+    loop {
+        access(state, Access::Read, state.index, node.span);
+        if state.memory[state.index].is_zero() {
+            break;
+        }
+        count_step(state, node.span, config)?;
+        check_interrupt(state, node.span)?;
+        run_nested(body, state, config, depth)?;
+    }
+    if let Some(hook) = &mut state.loop_exit {
+        hook(node.span, &state.memory, state.index);
+    }
+    Ok(())
+}
+
+fn run_call<C: CellValue>(
+    node: &Node,
+    state: &mut State<C>,
+    config: &Config,
+    depth: usize,
+) -> Result<(), RuntimeError> {
+    enter(node, state);
+    access(state, Access::Read, state.index, node.span);
+    let id = state.memory[state.index].to_u32();
+    let body = state
+        .procedures
+        .get(&id)
+        .cloned()
+        .ok_or(RuntimeError::UnknownProcedure(node.span))?;
+    count_step(state, node.span, config)?;
+    check_interrupt(state, node.span)?;
+    run_nested(&body, state, config, depth)
+}
+
+/// Run a node without children, or define a procedure
+fn run_leaf<C: CellValue>(
+    node: &Node,
+    state: &mut State<C>,
+    config: &Config,
+) -> Result<(), RuntimeError> {
+    enter(node, state);
+    count_step(state, node.span, config)?;
+    match &node.kind {
+        NodeKind::Procedure(body) => {
+            access(state, Access::Read, state.index, node.span);
+            let id = state.memory[state.index].to_u32();
+            state.procedures.insert(id, Arc::clone(body));
+            Ok(())
+        }
+        _ => run_command(node, state, config),
+    }
+}
+
+/// An instruction of a program flattened for the stepper
+#[derive(Clone, Debug)]
+enum Step {
+    Command(Node),                        // A node run by run_command
+    LoopBegin { end: usize, span: Span }, // Skip to after `end` on a cell at 0
+    LoopEnd { begin: usize, span: Span }, // Go back to after `begin` on a nonzero cell
+    Procedure { end: usize, span: Span }, // Define the procedure up to `end`, and skip it
+    Return,                               // End of the body of a procedure
+    Call(Span),
+}
+
+fn flatten_ast(node: &Node, steps: &mut Vec<Step>) {
+    match &node.kind {
+        NodeKind::Block(nodes) => {
+            for node in nodes.iter() {
+                flatten_ast(node, steps);
+            }
+        }
+        NodeKind::Loop(body) => {
+            let begin = steps.len();
+            steps.push(Step::LoopBegin {
+                end: 0,
+                span: node.span,
+            });
+            flatten_ast(body, steps);
+            let end = steps.len();
+            steps.push(Step::LoopEnd {
+                begin,
+                span: node.span,
+            });
+            steps[begin] = Step::LoopBegin {
+                end,
+                span: node.span,
+            };
+        }
+        NodeKind::Procedure(body) => {
+            let begin = steps.len();
+            steps.push(Step::Procedure {
+                end: 0,
+                span: node.span,
+            });
+            flatten_ast(body, steps);
+            let end = steps.len();
+            steps.push(Step::Return);
+            steps[begin] = Step::Procedure {
+                end,
+                span: node.span,
+            };
+        }
+        NodeKind::Call => steps.push(Step::Call(node.span)),
+        _ => steps.push(Step::Command(node.clone())),
+    }
+}
+
+/// A program run one node at a time, for the embedders which can't hand
+/// the control over to the VM until the program ends
+///
+/// The nodes are counted in the profile of the state, but its hook isn't
+/// called: the caller sees each step.
+#[derive(Clone, Debug)]
+pub struct Stepper {
+    steps: Vec<Step>,
+    next: usize,                      // Index of the next step
+    returns: Vec<usize>,              // Steps after the calls of procedures
+    procedures: BTreeMap<u32, usize>, // First step of each procedure
+}
+
+impl Stepper {
+    /// A stepper at the start of a program
+    pub fn new(ast: &Node) -> Self {
+        let mut steps = Vec::new();
+        flatten_ast(ast, &mut steps);
+        Stepper {
+            steps,
+            next: 0,
+            returns: Vec::new(),
+            procedures: BTreeMap::new(),
+        }
+    }
+
+    /// Whether the program ended
+    pub fn finished(&self) -> bool {
+        self.next == self.steps.len()
+    }
+
+    /// Span of the node run by the next step, if any
+    pub fn next_span(&self) -> Option<Span> {
+        match self.steps.get(self.next)? {
+            Step::Command(node) => Some(node.span),
+            Step::LoopBegin { span, .. } | Step::Procedure { span, .. } | Step::Call(span) => {
+                Some(*span)
+            }
+            Step::LoopEnd { span, .. } => Some(*span),
+            Step::Return => None,
+        }
+    }
+
+    /// Offset in the source of the command run by the next step, the
+    /// closing bracket at the end of an iteration of a loop
+    pub fn next_offset(&self) -> Option<usize> {
+        match self.steps.get(self.next)? {
+            Step::LoopEnd { span, .. } => Some(span.end - 1),
+            _ => self.next_span().map(|span| span.start),
+        }
+    }
+
+    /// Node run by the next step, when it isn't a part of a loop or of a
+    /// procedure
+    pub(crate) fn next_command(&self) -> Option<&Node> {
+        match self.steps.get(self.next)? {
+            Step::Command(node) => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Run the next node, returning whether the program goes on
+    pub fn step<C: CellValue>(
+        &mut self,
+        state: &mut State<C>,
+        config: &Config,
+    ) -> Result<bool, RuntimeError> {
+        let id = |state: &State<C>| state.memory[state.index].to_u32();
+        let is_zero = |state: &State<C>| state.memory[state.index].is_zero();
+        let count = |state: &mut State<C>, span: Span| {
+            if let Some(profile) = &mut state.profile {
+                *profile.counts.entry(span).or_insert(0) += 1;
+            }
+        };
+        match self.steps.get(self.next) {
+            None => return Ok(false),
+            Some(Step::Command(node)) => {
+                count(state, node.span);
+                run_command(node, state, config)?;
+                self.next += 1;
+            }
+            Some(Step::LoopBegin { end, span }) => {
+                count(state, *span);
+                access(state, Access::Read, state.index, *span);
+                self.next = if is_zero(state) {
+                    end + 1
+                } else {
+                    self.next + 1
+                };
+            }
+            Some(Step::LoopEnd { begin, span }) => {
+                access(state, Access::Read, state.index, *span);
+                self.next = if is_zero(state) {
+                    self.next + 1
+                } else {
+                    begin + 1
+                };
+            }
+            Some(Step::Procedure { end, span }) => {
+                count(state, *span);
+                access(state, Access::Read, state.index, *span);
+                self.procedures.insert(id(state), self.next + 1);
+                self.next = end + 1;
+            }
+            Some(Step::Return) => self.next = self.returns.pop().unwrap_or(self.steps.len()),
+            Some(Step::Call(span)) => {
+                count(state, *span);
+                access(state, Access::Read, state.index, *span);
+                let first = *self
+                    .procedures
+                    .get(&id(state))
+                    .ok_or(RuntimeError::UnknownProcedure(*span))?;
+                if self.returns.len() == MAX_DEPTH {
+                    return Err(RuntimeError::TooDeep(*span));
+                }
+                self.returns.push(self.next + 1);
+                self.next = first;
+            }
+        }
+        Ok(!self.finished())
+    }
+}
+
+/// A brainfuck VM and its tape, for programs embedding it
+pub struct Interpreter<C = u32> {
+    config: Config,
+    state: State<C>,
+}
+
+impl Interpreter {
+    /// Configure an interpreter, starting from the default settings
+    ///
+    /// `InterpreterBuilder::<C>::new()` configures one on another type of
+    /// cells.
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::new()
+    }
+}
+
+impl<C: CellValue> Interpreter<C> {
+    /// Run a program, on the tape left by the previous ones
+    pub fn run(&mut self, ast: &Node) -> Result<(), RuntimeError> {
+        run_ast(ast, &mut self.state, &self.config)?;
+        Ok(self.state.output.flush_bytes()?)
+    }
+
+    /// Settings of the interpreter
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Tape, pointer and streams of the interpreter
+    pub fn state(&self) -> &State<C> {
+        &self.state
+    }
+
+    /// Tape, pointer and streams of the interpreter, to change between runs
+    pub fn state_mut(&mut self) -> &mut State<C> {
+        &mut self.state
+    }
+
+    /// Start over on a blank tape, keeping the streams, for the next run
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
+}
+
+/// Settings of an interpreter, the others keeping their default
+pub struct InterpreterBuilder<C = u32> {
+    config: Config,
+    input: Option<Box<dyn ByteRead>>, // The standard input by default, or nothing without std
+    output: Option<Box<dyn ByteWrite>>, // The standard output by default, or a sink without std
+    hook: Option<Box<StepHook<C>>>,
+    loop_enter: Option<Box<LoopHook<C>>>,
+    loop_exit: Option<Box<LoopHook<C>>>,
+    access: Option<Box<AccessHook<C>>>,
+}
+
+impl<C: CellValue> Default for InterpreterBuilder<C> {
+    fn default() -> Self {
+        InterpreterBuilder {
+            config: Config::default(),
+            input: None,
+            output: None,
+            hook: None,
+            loop_enter: None,
+            loop_exit: None,
+            access: None,
+        }
+    }
+}
+
+impl<C: CellValue> InterpreterBuilder<C> {
+    /// Settings of an interpreter on cells of type `C`, starting from the
+    /// default ones
+    pub fn new() -> Self {
+        InterpreterBuilder::default()
+    }
+
+    /// Start from a whole configuration
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Number of cells of the tape
+    pub fn tape_size(mut self, tape_size: usize) -> Self {
+        self.config.tape_size = tape_size;
+        self
+    }
+
+    /// Width of the cells, for the types of cells wider than it
+    pub fn cell(mut self, cell: Cell) -> Self {
+        self.config.cell = cell;
+        self
+    }
+
+    /// Value read by "," at the end of the input
+    pub fn eof(mut self, eof: Eof) -> Self {
+        self.config.eof = eof;
+        self
+    }
+
+    /// Behaviour of the pointer moving out of the tape
+    pub fn bounds(mut self, bounds: Bounds) -> Self {
+        self.config.bounds = bounds;
+        self
+    }
+
+    /// Bytes the programs may write before failing
+    pub fn max_output(mut self, max_output: u64) -> Self {
+        self.config.max_output = Some(max_output);
+        self
+    }
+
+    /// Steps each run may take before failing
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.config.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Time each run may take before failing, checked at the iterations of
+    /// the loops and at the calls
+    #[cfg(feature = "std")]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Stream read by ","
+    pub fn input(mut self, input: impl ByteRead + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    /// Stream written by "."
+    pub fn output(mut self, output: impl ByteWrite + 'static) -> Self {
+        self.output = Some(Box::new(output));
+        self
+    }
+
+    /// Function giving the bytes read by ",", None at the end of the input
+    pub fn on_input(self, read: impl FnMut() -> Option<u8> + 'static) -> Self {
+        self.input(FnInput(read))
+    }
+
+    /// Function getting the bytes written by "."
+    pub fn on_output(self, write: impl FnMut(u8) + 'static) -> Self {
+        self.output(FnOutput(write))
+    }
+
+    /// Function called before each instruction, with its node, the tape and
+    /// the pointer
+    ///
+    /// The optimized loops, such as "[-]", are single instructions.
+    pub fn on_instruction(mut self, hook: impl FnMut(&Node, &[C], usize) + 'static) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Function called when a loop is reached, before its condition
+    pub fn on_loop_enter(mut self, hook: impl FnMut(Span, &[C], usize) + 'static) -> Self {
+        self.loop_enter = Some(Box::new(hook));
+        self
+    }
+
+    /// Function called when a loop ends, its cell being 0
+    pub fn on_loop_exit(mut self, hook: impl FnMut(Span, &[C], usize) + 'static) -> Self {
+        self.loop_exit = Some(Box::new(hook));
+        self
+    }
+
+    /// Function called on each read or write of a cell, with the access, the
+    /// index and the value of the cell after it, and the span of the node
+    ///
+    /// The loops read their cell each time their condition is tested.
+    pub fn on_access(mut self, hook: impl FnMut(Access, usize, &C, Span) + 'static) -> Self {
+        self.access = Some(Box::new(hook));
+        self
+    }
+
+    /// An interpreter on a blank tape
+    pub fn build(self) -> Interpreter<C> {
+        let mut state = State::with_cells(&self.config);
+        if let Some(input) = self.input {
+            state.input = input;
+        }
+        if let Some(output) = self.output {
+            state.output = output;
+        }
+        state.hook = self.hook;
+        state.loop_enter = self.loop_enter;
+        state.loop_exit = self.loop_exit;
+        state.access = self.access;
+        Interpreter {
+            config: self.config,
+            state,
+        }
+    }
+}
+
+/// Write a hexdump of some cells of the tape, after the position of the
+/// pointer
+#[cfg(feature = "std")]
+pub fn dump_memory(
+    state: &State,
+    config: &Config,
+    cells: core::ops::Range<usize>,
+    write: &mut dyn Write,
+) -> io::Result<()> {
+    let (digits, per_row) = match config.cell {
+        Cell::U8 => (2, 16),
+        Cell::U16 => (4, 8),
+        Cell::U32 => (8, 8),
+    };
+    write.write_all(format!("pointer at cell {}\n", state.index).as_bytes())?;
+    let memory = &state.memory[cells.start.min(cells.end)..cells.end];
+    for (row, values) in memory.chunks(per_row).enumerate() {
+        let mut line = format!("{:08x} ", cells.start + row * per_row);
+        for value in values.iter() {
+            line.push_str(&format!(" {:0digits$x}", value, digits = digits));
+        }
+        // Bytes are also shown as ASCII text
+        if config.cell == Cell::U8 {
+            let padding = (per_row - values.len()) * 3;
+            let text: String = values
+                .iter()
+                .map(|&value| match value as u8 {
+                    byte @ 0x20..=0x7e => byte as char,
+                    _ => '.',
+                })
+                .collect();
+            line.push_str(&format!("{:padding$}  |{}|", "", text, padding = padding));
+        }
+        line.push('\n');
+        write.write_all(line.as_bytes())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1848 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use brainfuck::ast::{Node, NodeKind, Span};
+use brainfuck::codegen::{runner, runs_here, CodegenError, Registry, Runner};
+use brainfuck::json::{self, node_from_json, node_to_json, parse_json};
+use brainfuck::lexer::{ParseError, MAX_NESTING};
+use brainfuck::lint::lint;
+use brainfuck::vm::{Bounds, Cell, Eof};
+use brainfuck::{Compiler, Config};
+
+/// Compile a brainfuck program to Rust, build it with rustc and run it
+fn run_rust_output(name: &str, source: &str, input: &[u8]) -> Vec<u8> {
+    let dir = env::temp_dir().join(format!("brainfuck-test-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("program.bf");
+    let rust_path = dir.join("program.rs");
+    let binary_path = dir.join("program");
+    fs::write(&source_path, source).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("build")
+        .arg(&source_path)
+        .arg(&rust_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc)
+        .args(["--edition", "2021", "-D", "warnings", "-o"])
+        .arg(&binary_path)
+        .arg(&rust_path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "generated Rust doesn't compile cleanly:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut child = Command::new(&binary_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+    output.stdout
+}
+
+#[test]
+fn rust_backend_compiles_and_runs() {
+    let hello_world = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/hello_world.bf");
+    let hello_world = fs::read_to_string(hello_world).unwrap();
+    assert_eq!(
+        run_rust_output("hello", &hello_world, b""),
+        b"Hello World!\n"
+    );
+
+    // Input, wrapping and multiply loops
+    assert_eq!(run_rust_output("cat", ",[.[-],]", b"abc"), b"abc");
+    assert_eq!(run_rust_output("wrap", "-.>+[->--<]>.", b""), [255, 254]);
+}
+
+#[test]
+fn rust_nostd_backend_compiles_and_runs() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-nostd-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("program.bf");
+    let rust_path = dir.join("bf.rs");
+    let library_path = dir.join("libbf.rlib");
+    let main_path = dir.join("main.rs");
+    let binary_path = dir.join("main");
+    fs::write(&source_path, ",[.,]+++[->++<]>.").unwrap();
+    fs::write(
+        &main_path,
+        "fn main() {\n\
+         \x20   let mut input = b\"abc\".iter().copied();\n\
+         \x20   let mut output = Vec::new();\n\
+         \x20   bf::run(|byte| output.push(byte), || input.next().unwrap_or(0));\n\
+         \x20   assert_eq!(output, b\"abc\\x06\");\n\
+         }\n",
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["build", "-t", "rs-nostd"])
+        .arg(&source_path)
+        .arg(&rust_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // The crate is built on its own, as it would be in a firmware
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(&rustc)
+        .args(["--edition", "2021", "-D", "warnings"])
+        .args(["--crate-type", "rlib", "--crate-name", "bf", "-o"])
+        .arg(&library_path)
+        .arg(&rust_path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "generated Rust doesn't compile cleanly:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let mut extern_bf = std::ffi::OsString::from("bf=");
+    extern_bf.push(&library_path);
+    let status = Command::new(&rustc)
+        .args(["--edition", "2021", "--extern"])
+        .arg(extern_bf)
+        .arg("-o")
+        .arg(&binary_path)
+        .arg(&main_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(Command::new(&binary_path).status().unwrap().success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Run the brainfuck binary with some arguments and input
+fn run_brainfuck(args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    output.stdout
+}
+
+/// Build a program for a target and run the output with the tools of the
+/// target, or give None when they are missing
+fn run_built(target: &str, args: &[&str], source: &str, input: &[u8]) -> Option<Vec<u8>> {
+    static BUILDS: AtomicUsize = AtomicUsize::new(0);
+    let dir = env::temp_dir().join(format!(
+        "brainfuck-test-{}-{}-{}",
+        target,
+        std::process::id(),
+        BUILDS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("program.bf");
+    let output_path = dir.join(format!("program.{}", target));
+    fs::write(&source_path, source).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["build", "-t", target])
+        .args(args)
+        .arg(&source_path)
+        .arg(&output_path)
+        .status()
+        .unwrap();
+    assert!(status.success(), "can't build {:?} to {}", source, target);
+
+    let mut run = match target {
+        "py" => Command::new("python3"),
+        _ => unreachable!("no runner for {}", target),
+    };
+    let child = run
+        .arg(&output_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn();
+    let output = child.ok().map(|mut child| {
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        child.wait_with_output().unwrap()
+    });
+    fs::remove_dir_all(&dir).unwrap();
+
+    let output = output?;
+    assert!(output.status.success(), "{:?} fails in {}", source, target);
+    Some(output.stdout)
+}
+
+#[test]
+fn python_backend_reads_like_the_interpreter() {
+    let source = ",.,.+.";
+    for eof in ["unchanged", "zero", "minus-one"] {
+        let expected = run_brainfuck(&["run", "--eof", eof, "-c", source], b"a");
+        match run_built("py", &["--eof", eof], source, b"a") {
+            Some(output) => assert_eq!(output, expected, "with --eof {}", eof),
+            None => eprintln!("python3 is missing, skipping the py backend"),
+        }
+    }
+
+    // Python has a limit on the nesting of the loops
+    let source = format!("+{}-.{}", "[".repeat(20), "]".repeat(20));
+    if let Some(output) = run_built("py", &[], &source, b"") {
+        assert_eq!(output, [0]);
+    }
+    let source_path =
+        env::temp_dir().join(format!("brainfuck-test-nested-{}.bf", std::process::id()));
+    fs::write(&source_path, format!("[{}]", source)).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["build", "-t", "py"])
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("nests up to 20 loops"));
+    fs::remove_file(&source_path).unwrap();
+}
+
+/// Build a program with the commands of the runner of its target and run it,
+/// or give None when the tools of the target are missing
+fn run_with_runner(target: &str, mut runner: Runner, input: &[u8]) -> Option<Vec<u8>> {
+    for command in runner.build.iter_mut() {
+        let output = match command.output() {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+            output => output.unwrap(),
+        };
+        assert!(
+            output.status.success(),
+            "the {} program doesn't build:\n{}",
+            target,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let child = runner
+        .run
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+        child => child.unwrap(),
+    };
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "the {} program fails", target);
+    Some(output.stdout)
+}
+
+#[test]
+fn backends_run_like_the_interpreter() {
+    // The examples, and a read past the end of the input in each mode
+    let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data");
+    let mut programs: Vec<(String, &str, &[&str], &[u8])> = vec![
+        (String::from("cat.bf"), "unchanged", &[], b"cat\n"),
+        (String::from("hello_world.bf"), "unchanged", &[], b""),
+        (String::from("rot13.bf"), "unchanged", &[], b"Hello, World!"),
+        (String::from("sierpinski.bf"), "unchanged", &[], b""),
+    ];
+    for (source, _, _, _) in programs.iter_mut() {
+        *source = fs::read_to_string(data.join(&*source)).unwrap();
+    }
+    for eof in ["unchanged", "zero", "minus-one"] {
+        programs.push((String::from(",.,.+."), eof, &[], b"a"));
+    }
+
+    // Wider cells and a circular tape, in the backends taking them
+    let wide = "++++++++[>++++++++<-]>[<++++>-]<[>+++++++++++++++++++++++++++++++++.<[-]]";
+    for width in ["16", "32"] {
+        let tape: &[&str] = match width {
+            "16" => &["--cell-width", "16"],
+            _ => &["--cell-width", "32", "--bounds", "wrap"],
+        };
+        programs.push((String::from(wide), "unchanged", tape, b""));
+        programs.push((String::from(",.+.,-."), "minus-one", tape, b"a"));
+    }
+    let wrap: &[&str] = &["--bounds", "wrap", "--tape-size", "16"];
+    programs.push((String::from("<+++[>--<-]>.<<.>>>>[-]."), "zero", wrap, b""));
+    let programs: Vec<_> = programs
+        .into_iter()
+        .map(|(source, eof, tape, input)| {
+            let mut args = vec!["run", "--eof", eof];
+            args.extend(tape);
+            args.extend(["-c", &source]);
+            let expected = run_brainfuck(&args, input);
+            let mut config = Config {
+                eof: match eof {
+                    "unchanged" => Eof::Unchanged,
+                    "zero" => Eof::Zero,
+                    _ => Eof::MinusOne,
+                },
+                ..Config::default()
+            };
+            for option in tape.chunks(2) {
+                match option {
+                    ["--cell-width", "16"] => config.cell = Cell::U16,
+                    ["--cell-width", _] => config.cell = Cell::U32,
+                    ["--bounds", _] => config.bounds = Bounds::Wrap,
+                    ["--tape-size", size] => config.tape_size = size.parse().unwrap(),
+                    _ => unreachable!("no option {:?}", option),
+                }
+            }
+            let program = Compiler::new().config(config).compile(&source).unwrap();
+            (program, input, expected)
+        })
+        .collect();
+    let programs = Arc::new(programs);
+
+    // Each target on its own thread, the compilers being slow to start
+    let registry = Registry::default();
+    let runs: Vec<_> = registry
+        .targets()
+        .map(|target| target.name.clone())
+        .filter(|target| runs_here(target))
+        .map(|target| {
+            let programs = Arc::clone(&programs);
+            thread::spawn(move || {
+                let dir = env::temp_dir().join(format!(
+                    "brainfuck-test-backend-{}-{}",
+                    target,
+                    std::process::id()
+                ));
+                fs::create_dir_all(&dir).unwrap();
+                for (program, input, expected) in programs.iter() {
+                    let runner = match runner(&program.ast, &program.config, &target, &dir) {
+                        Ok(runner) => runner,
+                        // Some targets leave the cell unchanged on end of input,
+                        // and some only take 8-bit cells on an unchecked tape
+                        Err(CodegenError::Unsupported(_)) => continue,
+                        Err(error) => panic!("can't write the {} program: {}", target, error),
+                    };
+                    match run_with_runner(&target, runner, input) {
+                        Some(output) => assert_eq!(
+                            String::from_utf8_lossy(&output),
+                            String::from_utf8_lossy(expected),
+                            "{} runs {:?} differently with {:?} cells and {:?} bounds",
+                            target,
+                            program.config.eof,
+                            program.config.cell,
+                            program.config.bounds
+                        ),
+                        None => {
+                            eprintln!("the tools of {} are missing, skipping it", target);
+                            break;
+                        }
+                    }
+                }
+                fs::remove_dir_all(&dir).unwrap();
+            })
+        })
+        .collect();
+    for run in runs {
+        run.join().unwrap();
+    }
+}
+
+#[test]
+fn c_tapes_are_not_on_the_stack() {
+    // 64 MiB of cells, past the 8 MiB stacks of Linux
+    let config = Config {
+        cell: Cell::U32,
+        tape_size: 1 << 24,
+        ..Config::default()
+    };
+    let program = Compiler::new()
+        .config(config)
+        .compile("+++[>+++++++++++<-]>.")
+        .unwrap();
+    let dir = env::temp_dir().join(format!("brainfuck-test-c-tape-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let runner = runner(&program.ast, &program.config, "c", &dir).unwrap();
+    match run_with_runner("c", runner, b"") {
+        Some(output) => assert_eq!(output, b"!"),
+        None => eprintln!("the C compiler is missing, skipping the c backend"),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+
+    // The library allocates its tape on each run, and frees it
+    let mut library = Vec::new();
+    Registry::default()
+        .get("c-lib")
+        .unwrap()
+        .backend
+        .emit(&program.ast, &program.config, &mut library)
+        .unwrap();
+    let library = String::from_utf8(library).unwrap();
+    assert!(library.contains("calloc(16777216, sizeof(uint32_t))"));
+    assert!(library.contains("free(memory);"));
+}
+
+#[test]
+fn c_maps_back_to_the_source_with_line_directives() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-line-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("program.bf");
+    let c_path = dir.join("program.c");
+    fs::write(&source_path, "Print an A\n++++++++[>++++++++<-]\n>+.\n").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["build", "--line-directives"])
+        .arg(&source_path)
+        .arg(&c_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Each statement follows the line and the bytes of its node
+    let c = fs::read_to_string(&c_path).unwrap();
+    let directive = |line, start, end| {
+        format!(
+            "#line {} \"{}\" // bytes {}..{}\n",
+            line,
+            source_path.display(),
+            start,
+            end
+        )
+    };
+    assert!(c.contains(&directive(2, 11, 19)));
+    assert!(c.contains(&directive(2, 19, 32)));
+    assert!(c.contains(&directive(3, 35, 36)));
+    assert!(!c.contains("#line 1 "));
+
+    // The debug information of the C compiler points to the source
+    match Command::new("cc")
+        .args(["-g", "-S", "-o", "-"])
+        .arg(&c_path)
+        .output()
+    {
+        Ok(output) => {
+            assert!(output.status.success());
+            assert!(String::from_utf8_lossy(&output.stdout).contains("program.bf\""));
+        }
+        Err(_) => eprintln!("the C compiler is missing, skipping the debug information"),
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn checked_c_stops_like_the_interpreter() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-checks-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("program.bf");
+    let c_path = dir.join("program.c");
+    let binary_path = dir.join("program");
+    fs::write(&source_path, "+.>+.<<.").unwrap();
+    let build = |checks: &[&str]| {
+        let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .arg("build")
+            .args(checks)
+            .arg(&source_path)
+            .arg(&c_path)
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        fs::read_to_string(&c_path).unwrap()
+    };
+    assert!(!build(&[]).contains("out_of_tape"));
+    assert!(build(&["--codegen-checks"])
+        .contains("    index += -2;\n    if (index >= 30000) out_of_tape(5);\n"));
+
+    let output = match Command::new("cc")
+        .arg("-o")
+        .arg(&binary_path)
+        .arg(&c_path)
+        .output()
+    {
+        Ok(output) => output,
+        Err(_) => return eprintln!("the C compiler is missing, skipping the checked C"),
+    };
+    assert!(output.status.success());
+    let output = Command::new(&binary_path).output().unwrap();
+    let expected = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("run")
+        .arg(&source_path)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, expected.stdout);
+    assert_eq!(output.stdout, b"\x01\x01");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "pointer out of the tape at byte 5 of the source\n"
+    );
+    assert_eq!(expected.status.code(), Some(4));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn shared_libraries_export_bf_run() {
+    if Command::new("cc").arg("--version").output().is_err() {
+        return eprintln!("the C compiler is missing, skipping the so backend");
+    }
+    let dir = env::temp_dir().join(format!("brainfuck-test-so-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("program.bf");
+    let library_path = dir.join("libprogram.so");
+    let main_path = dir.join("main.c");
+    let binary_path = dir.join("main");
+    fs::write(&source_path, ",.,.,.").unwrap();
+    fs::write(
+        &main_path,
+        "#include <stddef.h>\n\
+         #include <stdint.h>\n\
+         #include <stdio.h>\n\
+         int bf_run(const uint8_t * input, size_t len, uint8_t * out, size_t cap);\n\
+         int main(void) {\n\
+         \x20   uint8_t out[4];\n\
+         \x20   int length = bf_run((const uint8_t *)\"abc\", 3, out, sizeof(out));\n\
+         \x20   fwrite(out, 1, length, stdout);\n\
+         \x20   printf(\" %d\\n\", bf_run((const uint8_t *)\"abc\", 3, out, 2));\n\
+         \x20   return 0;\n\
+         }\n",
+    )
+    .unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("build")
+        .arg(&source_path)
+        .arg(&library_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let mut rpath = std::ffi::OsString::from("-Wl,-rpath,");
+    rpath.push(&dir);
+    let output = Command::new("cc")
+        .arg("-o")
+        .arg(&binary_path)
+        .arg(&main_path)
+        .arg(&library_path)
+        .arg(rpath)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "the library doesn't link:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The output is returned in the buffer, up to its capacity
+    let output = Command::new(&binary_path).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"abc -1\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Little-endian integers in the headers of the executables
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[test]
+fn executables_have_the_headers_of_their_system() {
+    let program = Compiler::new().compile("++[>+<-]>.").unwrap();
+    let registry = Registry::default();
+    let emit = |target: &str| {
+        let mut code = Vec::new();
+        registry
+            .get(target)
+            .unwrap()
+            .backend
+            .emit(&program.ast, &program.config, &mut code)
+            .unwrap();
+        code
+    };
+
+    // A console program of Windows on x86-64, in PE32+
+    let pe = emit("pe");
+    assert_eq!(&pe[..2], b"MZ");
+    let header = u32_at(&pe, 0x3c) as usize;
+    assert_eq!(&pe[header..header + 4], b"PE\0\0");
+    assert_eq!(u16_at(&pe, header + 4), 0x8664);
+    assert_eq!(u16_at(&pe, header + 24), 0x20b);
+    assert_eq!(u16_at(&pe, header + 24 + 68), 3);
+    let entry = u32_at(&pe, header + 24 + 16) as usize;
+    assert!(entry > 0);
+
+    // An executable of macOS on x86-64, with its segments and its thread
+    let macho = emit("macho");
+    assert_eq!(u32_at(&macho, 0), 0xfeed_facf);
+    assert_eq!(u32_at(&macho, 4), 0x0100_0007);
+    assert_eq!(u32_at(&macho, 12), 2);
+    let (commands, size) = (u32_at(&macho, 16), u32_at(&macho, 20) as usize);
+    let mut offset = 32;
+    let mut kinds = Vec::new();
+    for _ in 0..commands {
+        kinds.push(u32_at(&macho, offset));
+        offset += u32_at(&macho, offset + 4) as usize;
+    }
+    assert_eq!(offset, 32 + size);
+    assert!(offset < macho.len());
+    assert!(kinds.contains(&0x19));
+    assert!(kinds.contains(&0x5));
+
+    // --binary-format picks the format of the bin target
+    let dir = env::temp_dir().join(format!("brainfuck-test-formats-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("program.bf");
+    fs::write(&source_path, "++[>+<-]>.").unwrap();
+    for (format, expected) in [("pe", &pe), ("macho", &macho)] {
+        let output_path = dir.join(format!("program-{}", format));
+        let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["build", "--binary-format", format])
+            .arg(&source_path)
+            .arg(&output_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(&fs::read(&output_path).unwrap(), expected, "{}", format);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sources_can_be_built_into_a_directory() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-out-dir-{}", std::process::id()));
+    let sources = dir.join("sources");
+    let out = dir.join("out");
+    fs::create_dir_all(&sources).unwrap();
+    fs::write(sources.join("one.bf"), "++++++++[>++++++++<-]>+.").unwrap();
+    fs::write(sources.join("two.bf"), "++++++++[>++++++++<-]>++.").unwrap();
+    fs::write(sources.join("notes.txt"), "[").unwrap();
+    let bad = dir.join("bad.bf");
+    fs::write(&bad, "[").unwrap();
+    let build = |args: &[&Path]| {
+        Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["build", "-t", "py", "--out-dir"])
+            .arg(&out)
+            .args(args)
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap()
+    };
+
+    // The sources of a directory, each one to a file of the target
+    let output = build(&[&sources]);
+    assert!(output.status.success());
+    let mut built: Vec<_> = fs::read_dir(&out)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    built.sort();
+    assert_eq!(built, ["one.py", "two.py"]);
+    match Command::new("python3").arg(out.join("two.py")).output() {
+        Ok(output) => assert_eq!(output.stdout, b"B"),
+        Err(_) => eprintln!("python3 is missing, skipping the built sources"),
+    }
+
+    // A failed source is reported, without stopping the others
+    fs::remove_dir_all(&out).unwrap();
+    let output = build(&[&bad, &sources.join("one.bf")]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("bad.bf:1:1: unmatched '['"));
+    assert!(out.join("one.py").exists());
+    assert!(!out.join("bad.py").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dot_graphs_show_the_optimized_program() {
+    let source = b"+++++[>+++++[>++<-]<-]>>.";
+    let dot = run_brainfuck(&["build", "-t", "dot", "-", "-"], source);
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph program {\n"));
+    assert!(dot.ends_with("}\n"));
+    // The fused loops are drawn twice outlined, in the blocks holding them
+    assert!(dot.contains("    n2 [label=\"loop\\n5..22\", shape=ellipse];\n"));
+    assert!(dot.contains("    n6 [label=\"mul [1] += 2\\n12..19\", peripheries=2];\n"));
+    assert!(dot.contains("    n3 -> n6;\n"));
+    assert!(!dot.contains("fillcolor"));
+
+    // The profile shades the nodes by their executions, without the output
+    // of its run
+    let dot = run_brainfuck(&["build", "-t", "dot", "--profile", "-", "-"], source);
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph program {\n"));
+    assert!(dot.contains(
+        "    n6 [label=\"mul [1] += 2\\n12..19\", peripheries=2, style=filled, \
+         fillcolor=\"0.000 1.000 1.000\", tooltip=\"5 executions\"];\n"
+    ));
+    assert!(dot.contains("fillcolor=\"0.000 0.387 1.000\", tooltip=\"1 executions\"];\n"));
+}
+
+#[test]
+fn pretty_brainfuck_indents_the_loops() {
+    let source = b"+++++[>+++++[>++<-]<-]>>.";
+    let pretty = run_brainfuck(&["build", "-t", "bf-pretty", "-", "-"], source);
+    assert_eq!(
+        String::from_utf8_lossy(&pretty),
+        "+++++\n\
+         [\n\
+         \x20   >+++++[->++<]<-\n\
+         ]\n\
+         >>.\n"
+    );
+
+    // Narrow lines break between the runs, each one with its length
+    let args = [
+        "build",
+        "-t",
+        "bf-pretty",
+        "--annotate-runs",
+        "--line-width",
+        "12",
+        "-",
+        "-",
+    ];
+    let pretty = run_brainfuck(&args, source);
+    assert_eq!(
+        String::from_utf8_lossy(&pretty),
+        "+++++ x5\n\
+         [\n\
+         \x20   >\n\
+         \x20   +++++ x5\n\
+         \x20   [->++<]\n\
+         \x20   < -\n\
+         ]\n\
+         >> x2 .\n"
+    );
+    // The formatted source is the same program
+    assert_eq!(run_brainfuck(&["run"], &pretty), [50]);
+}
+
+#[test]
+fn html_pages_highlight_the_source() {
+    let source = b"A & \"B\"\n+[->+<]>.";
+    let html = run_brainfuck(&["build", "-t", "html", "-", "-"], source);
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>\n"));
+    assert!(html.ends_with("</html>\n"));
+    // The comments are escaped, and the brackets point to each other
+    assert!(html.contains("<pre><span class=\"comment\">A &amp; &quot;B&quot;\n</span>"));
+    assert!(html.contains("<span id=\"c9\" class=\"loop\" data-match=\"c14\">[</span>"));
+    assert!(html.contains("<span id=\"c14\" class=\"loop\" data-match=\"c9\">]</span>"));
+    assert!(html.contains("<span id=\"c11\" class=\"move\">&gt;</span>"));
+    assert!(html.contains("querySelectorAll(\"[data-match]\")"));
+    assert!(!html.contains("title=\""));
+
+    // The profile gives the executions of each command
+    let html = run_brainfuck(&["build", "-t", "html", "--profile", "-", "-"], source);
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>\n"));
+    assert!(html.contains("<span id=\"c8\" class=\"incr\" title=\"1 executions\">+</span>"));
+    assert!(html.contains("<span id=\"c16\" class=\"io\" title=\"1 executions\">.</span>"));
+}
+
+#[test]
+fn programs_can_come_from_stdin() {
+    let program = b"++++++++[>++++++++<-]>+.+.";
+    assert_eq!(run_brainfuck(&["run"], program), b"AB");
+    assert_eq!(run_brainfuck(&["run", "-"], program), b"AB");
+    assert_eq!(
+        run_brainfuck(&["build", "-t", "bf", "-", "-"], program),
+        b"++++++++[->++++++++<]>+.+."
+    );
+
+    // Sources spanning many reads are parsed as they come
+    let mut large = b"#!/usr/bin/env brainfuck run [\n".to_vec();
+    for _ in 0..100_000 {
+        large.extend_from_slice(b"+ comment -\n");
+    }
+    large.extend_from_slice(program);
+    assert_eq!(run_brainfuck(&["run", "-"], &large), b"AB");
+}
+
+#[test]
+fn piped_input_has_no_prompt() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["run", "--eof", "zero", "--prompt", "? ", "-c", ",[.,]"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"a\nb").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(output.stdout, b"a\nb");
+    assert_eq!(output.stderr, b"");
+
+    // Pipes are read as they come, without a terminal to set in raw mode
+    assert_eq!(
+        run_brainfuck(&["run", "--raw-input", "-c", ",.,."], b"xy"),
+        b"xy"
+    );
+}
+
+#[test]
+fn programs_can_be_given_inline() {
+    let program = "++++++++[>++++++++<-]>+.,.";
+    assert_eq!(run_brainfuck(&["run", "-c", program], b"B"), b"AB");
+    assert_eq!(
+        run_brainfuck(&["run", "--eof", "zero", "-c", program], b""),
+        b"A\0"
+    );
+}
+
+#[test]
+fn program_io_can_be_redirected_with_flags() {
+    let program = ",[.,]";
+    assert_eq!(
+        run_brainfuck(
+            &["run", "--eof", "zero", "--input-str", "abc", "-c", program],
+            b""
+        ),
+        b"abc"
+    );
+
+    let input_path = env::temp_dir().join(format!("brainfuck-test-input-{}", std::process::id()));
+    fs::write(&input_path, "xyz").unwrap();
+    let input_path = input_path.to_str().unwrap();
+    assert_eq!(
+        run_brainfuck(
+            &["run", "--eof", "zero", "--input", input_path, "-c", program],
+            b""
+        ),
+        b"xyz"
+    );
+    fs::remove_file(input_path).unwrap();
+
+    // The output of the program doesn't mix with the standard output
+    let output_path = env::temp_dir().join(format!("brainfuck-test-output-{}", std::process::id()));
+    let output_path = output_path.to_str().unwrap();
+    let args = ["run", "--output-data", output_path, "-c", "-.>+[->--<]>."];
+    assert_eq!(run_brainfuck(&args, b""), b"");
+    assert_eq!(fs::read(output_path).unwrap(), [255, 254]);
+    fs::remove_file(output_path).unwrap();
+}
+
+#[test]
+fn errors_have_distinct_exit_codes() {
+    let exit_code = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        assert!(
+            !String::from_utf8_lossy(&output.stderr).contains("panicked"),
+            "{:?} panics",
+            args
+        );
+        output.status.code()
+    };
+    assert_eq!(exit_code(&["run", "--tape-size", "none"]), Some(2));
+    assert_eq!(exit_code(&["run", "-c", "[[]"]), Some(3));
+    assert_eq!(exit_code(&["run", "-c", "<"]), Some(4));
+    assert_eq!(
+        exit_code(&["run", "--max-output", "3", "-c", "+[.]"]),
+        Some(6)
+    );
+    assert_eq!(
+        exit_code(&["run", "--max-steps", "1000", "-c", "+[]"]),
+        Some(6)
+    );
+    assert_eq!(
+        exit_code(&["run", "--timeout", "0.1", "-c", "+[]"]),
+        Some(6)
+    );
+    assert_eq!(exit_code(&["run", "missing.bf"]), Some(5));
+    assert_eq!(
+        exit_code(&["build", "-t", "avr", "--codegen-checks", "-", "-"]),
+        Some(2)
+    );
+}
+
+#[test]
+fn each_command_takes_its_own_options() {
+    let brainfuck = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+    let help = brainfuck(&["build", "--help"]);
+    assert!(help.status.success());
+    let help = String::from_utf8_lossy(&help.stdout);
+    assert!(help.contains("--out-dir <dir>"));
+    assert!(help.contains("--line-width <columns>"));
+    assert!(!help.contains("--input-str"));
+
+    let help = brainfuck(&["fmt", "--help"]);
+    assert!(!String::from_utf8_lossy(&help.stdout).contains("--tape-size"));
+
+    // The options of another command are refused
+    for args in [
+        &["run", "--out-dir", "out", "-c", "."][..],
+        &["fmt", "--tape-size", "10", "-"],
+        &["check", "--fix", "--input", "in.txt", "-"],
+        &["repl", "--code", "+"],
+    ] {
+        let output = brainfuck(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("unexpected argument"));
+    }
+    let output = brainfuck(&["fmt", "--line-width", "4", "-"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn stats_tell_the_limits_hit() {
+    let stats = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["run", "--stats"])
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        (output.status.code(), stderr)
+    };
+    let (code, stderr) = stats(&["-c", "+."]);
+    assert_eq!(code, Some(0));
+    assert!(stderr.contains("bytes written                      1\n"));
+    assert!(stderr.contains("limits hit                      none\n"));
+    let (code, stderr) = stats(&["--max-output", "3", "-c", "+[.]"]);
+    assert_eq!(code, Some(6));
+    assert!(stderr.contains("limits hit              --max-output\n"));
+    assert!(stderr.contains("output limit reached"));
+    let (code, stderr) = stats(&["--max-steps", "1000", "-c", "+[]"]);
+    assert_eq!(code, Some(6));
+    assert!(stderr.contains("limits hit               --max-steps\n"));
+    assert!(stderr.contains("step limit reached"));
+}
+
+#[test]
+fn outputs_stop_at_the_max_output() {
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["run", "--max-output", "3", "-c", "+[.]"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(output.stdout, b"\x01\x01\x01");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("output limit reached"));
+}
+
+#[test]
+fn memory_can_be_dumped_after_the_run() {
+    let dump = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["run", "--dump-memory"])
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    // Up to the highest cell used, even when the pointer came back
+    let line =
+        |offset: &str, bytes: &str, text: &str| format!("{}  {:<47}  |{}|\n", offset, bytes, text);
+    assert_eq!(
+        dump(&["-c", "+++>++>+<"]),
+        (
+            Some(0),
+            format!("pointer at cell 1\n{}", line("00000000", "03 02 01", "..."))
+        )
+    );
+    assert_eq!(
+        dump(&["-c", "+++>++>+<<"]).1,
+        format!("pointer at cell 0\n{}", line("00000000", "03 02 01", "..."))
+    );
+    // Or a range of the tape, over several lines
+    let bytes = format!("03 02 01{}", " 00".repeat(13));
+    assert_eq!(
+        dump(&["--dump-range", "0..17", "-c", "+++>++>+<"]).1,
+        format!(
+            "pointer at cell 1\n{}{}",
+            line("00000000", &bytes, &".".repeat(16)),
+            line("00000010", "00", ".")
+        )
+    );
+    assert_eq!(dump(&["--dump-range", "5..2", "-c", "+"]).0, Some(2));
+}
+
+#[test]
+fn examples_are_embedded() {
+    assert_eq!(
+        run_brainfuck(&["examples", "run", "hello"], b""),
+        b"Hello World!\n"
+    );
+    assert_eq!(
+        run_brainfuck(&["examples", "run", "rot13"], b"Hello, World!"),
+        b"Uryyb, Jbeyq!"
+    );
+    assert!(
+        run_brainfuck(&["examples", "show", "mandelbrot"], b"").starts_with(b"Mandelbrot set\n")
+    );
+}
+
+#[test]
+fn programs_can_be_piped() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-pipe-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let write_program = |name: &str, source: &str| {
+        let path = dir.join(name);
+        fs::write(&path, source).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let increment = write_program("increment.bf", ",[+.[-],]");
+    let endless = write_program("endless.bf", "+[.]");
+    let first = write_program("first.bf", ",.");
+
+    assert_eq!(
+        run_brainfuck(&["pipe", &increment, &increment], b"abc"),
+        b"cde"
+    );
+    // The pipe ends with its last program
+    assert_eq!(run_brainfuck(&["pipe", &endless, &first], b""), [1]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sources_can_be_linked() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-link-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let write_program = |name: &str, source: &str| {
+        let path = dir.join(name);
+        fs::write(&path, source).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let letter = write_program("letter.bf", "++++++++[>++++++++<-]>+");
+    let print = write_program("print.bf", ".+.");
+    let underflow = write_program("underflow.bf", "<<<");
+
+    // The programs share the tape
+    assert_eq!(
+        run_brainfuck(&["run", "--link", &letter, &print], b""),
+        b"AB"
+    );
+
+    // Errors are located in the sources joined by newlines
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["run", "--link", &letter, &underflow])
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(":2:"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sources_run_as_scripts() {
+    let source_path =
+        env::temp_dir().join(format!("brainfuck-test-script-{}.bf", std::process::id()));
+    fs::write(
+        &source_path,
+        "#!/usr/bin/env -S brainfuck --eof zero\n,[.,]\n",
+    )
+    .unwrap();
+    let source_path = source_path.to_str().unwrap();
+
+    // The options of the "#!" line come before the source
+    assert_eq!(
+        run_brainfuck(&["--eof", "zero", source_path], b"abc"),
+        b"abc"
+    );
+    assert_eq!(
+        run_brainfuck(&[source_path, "--eof", "zero"], b"abc"),
+        b"abc"
+    );
+
+    // A source and an output are still built, as by the former command line
+    let output_path = source_path.replace(".bf", ".c");
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args([source_path, &output_path])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("deprecated, use 'brainfuck build"));
+    assert!(fs::read_to_string(&output_path)
+        .unwrap()
+        .contains("#include <stdio.h>"));
+
+    fs::remove_file(output_path).unwrap();
+    fs::remove_file(source_path).unwrap();
+}
+
+#[test]
+fn nesting_is_limited_for_the_passes_walking_the_tree() {
+    let nested = |depth| "+".to_string() + &"[".repeat(depth) + "-" + &"]".repeat(depth);
+
+    // The deepest program goes through each pass and each target, on the
+    // stack of a test thread
+    Compiler::new().compile(&nested(MAX_NESTING)).unwrap();
+    let program = Compiler::new()
+        .opt_level(0)
+        .compile(&nested(MAX_NESTING))
+        .unwrap();
+    assert_eq!(program.run_with_input(b"").unwrap(), b"");
+    assert!(lint(&program.ast, &program.config).is_empty());
+    let json = node_to_json(&program.ast).to_string();
+    assert_eq!(
+        node_from_json(&parse_json(&json).unwrap())
+            .unwrap()
+            .to_string(),
+        program.ast.to_string()
+    );
+    for target in Registry::default().targets() {
+        let mut output = Vec::new();
+        match target
+            .backend
+            .emit(&program.ast, &program.config, &mut output)
+        {
+            Ok(()) | Err(CodegenError::Unsupported(_)) => {}
+            Err(error) => panic!("can't write the {} program: {}", target.name, error),
+        }
+    }
+
+    // A deeper one is a parse error, instead of a stack overflow
+    let deeper = Node::new(NodeKind::Loop(Box::new(program.ast)), Span::default());
+    assert!(node_from_json(&node_to_json(&deeper)).is_err());
+    assert!(matches!(
+        Compiler::new().compile(&nested(200_000)),
+        Err(ParseError::TooDeep(offset)) if offset == 1 + MAX_NESTING
+    ));
+    for args in [&["run"][..], &["check"], &["build", "-t", "c"]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(args)
+            .args(["-", "-"].iter().take(args.len()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .env("NO_COLOR", "1")
+            .spawn()
+            .unwrap();
+        // The source is read as a stream, up to the error
+        let source = nested(200_000);
+        let _ = child.stdin.take().unwrap().write_all(source.as_bytes());
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(output.status.code(), Some(3), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("nested"));
+    }
+}
+
+#[test]
+fn dialects_select_the_language() {
+    assert_eq!(
+        run_brainfuck(
+            &[
+                "run",
+                "--dialect",
+                "pbrain",
+                "-c",
+                "++++++++[>++++++++<-]>+(.)::"
+            ],
+            b""
+        ),
+        b"AA"
+    );
+    assert_eq!(
+        run_brainfuck(
+            &[
+                "run",
+                "--dialect",
+                "extended",
+                "-c",
+                "++++++++[>++++++++<-]>+.$}.{|.@..."
+            ],
+            b""
+        ),
+        b"A A"
+    );
+
+    // An endless recursion is an error, and the backends reject the
+    // commands they can't express
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["run", "--dialect", "pbrain", "-c", "+(:):"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("nested too deeply"));
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["build", "-t", "c", "--dialect", "extended", "-", "-"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"+$").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Extended Brainfuck"));
+
+    // A program translated to a dialect reads back the same
+    let hello_world = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/hello_world.bf");
+    let hello_world = hello_world.to_str().unwrap();
+    let ook = run_brainfuck(&["build", "-t", "ook", hello_world, "-"], b"");
+    let ook = String::from_utf8(ook).unwrap();
+    assert_eq!(
+        run_brainfuck(&["run", "--dialect", "ook", "-c", &ook], b""),
+        b"Hello World!\n"
+    );
+}
+
+#[test]
+fn errors_show_the_source_line() {
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["run", "-c", "+\n+[>"])
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: <code>:2:2: unmatched '['\n  |\n2 | +[>\n  |  ^\n"
+    );
+}
+
+#[test]
+fn json_documents_nest_up_to_a_limit() {
+    let nested = |depth| "[{\"a\":".repeat(depth / 2) + "1" + &"}]".repeat(depth / 2);
+    let deepest = parse_json(&nested(json::MAX_DEPTH)).unwrap();
+    assert_eq!(deepest.to_string(), nested(json::MAX_DEPTH));
+    for depth in [json::MAX_DEPTH + 2, 200_000] {
+        let error = parse_json(&nested(depth)).unwrap_err();
+        assert!(error.contains("nested deeper"), "{}", error);
+    }
+}
+
+#[test]
+fn errors_can_be_json_lines() {
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["check", "--message-format", "json", "-c", "<\n+["])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        concat!(
+            "{\"severity\":\"error\",\"code\":null,\"message\":\"unmatched '['\",",
+            "\"path\":\"<code>\",\"span\":{\"start\":3,\"end\":4},\"line\":2,\"column\":2}\n",
+        )
+    );
+}
+
+#[test]
+fn check_warns_about_likely_bugs() {
+    let check = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .arg("check")
+            .args(args)
+            .env("NO_COLOR", "1")
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        let warnings: Vec<_> = stderr
+            .lines()
+            .filter(|line| line.starts_with("warning[") || line.starts_with("error["))
+            .map(|line| line.replace(": <code>:", " "))
+            .collect();
+        (output.status.code(), warnings)
+    };
+
+    assert_eq!(check(&["-c", "+[->+<]>."]), (Some(0), vec![]));
+    assert_eq!(
+        check(&["-c", "[.]+[>+<]>>>[-]<<<<"]),
+        (
+            Some(0),
+            vec![
+                String::from(
+                    "warning[dead-loop] 1:1: loop never runs, as the cell is always zero here"
+                ),
+                String::from(
+                    "warning[endless-loop] 1:5: loop never ends once entered, \
+                     as its cell doesn't change"
+                ),
+                String::from("warning[unused-cells] 1:7: cell 1 is changed but never used"),
+                String::from("warning[pointer-underflow-possible] 1:16: pointer out of the tape"),
+            ]
+        )
+    );
+    assert_eq!(
+        check(&["-c", "+[>,[+++]<--]++[--]"]),
+        (
+            Some(0),
+            vec![String::from(
+                "warning[nonterminating-loop] 1:2: loop never ends, as its cell changes \
+                 by -2 on each iteration and never reaches zero"
+            )]
+        )
+    );
+    assert_eq!(check(&["--deny-warnings", "-c", "<"]).0, Some(3));
+    assert_eq!(check(&["--bounds", "wrap", "-c", "<"]), (Some(0), vec![]));
+    assert_eq!(
+        check(&["--warn", "unbalanced-suspects", "--deny", "useless-sequence", "-c", "+-[>]"]),
+        (
+            Some(3),
+            vec![
+                String::from("error[useless-sequence] 1:1: commands going both ways cancel out"),
+                String::from(
+                    "warning[unbalanced-suspects] 1:3: loop moves the pointer by 1 on each iteration"
+                ),
+            ]
+        )
+    );
+    assert_eq!(
+        check(&["--allow", "dead-loop,unused-cells", "-c", "[.]+"]),
+        (Some(0), vec![])
+    );
+    assert_eq!(check(&["--allow", "no-such-lint", "-c", "."]).0, Some(2));
+}
+
+#[test]
+fn check_fixes_sources() {
+    let path = env::temp_dir().join(format!("brainfuck-test-fix-{}.bf", std::process::id()));
+    fs::write(&path, "[a comment, at the start.]\n++-+ two\n><>.<+-.\n").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["check", "--fix", path.to_str().unwrap()])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    let fixed = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(status.code(), Some(0));
+    assert_eq!(fixed, "a comment at the start\n++ two\n>.<.\n");
+}
+
+#[test]
+fn optimization_levels_give_the_same_runs() {
+    let program = "++++[>++++[>++<-]<-]>>.<<,[>++<-]>[-<+>]<.";
+    let output = run_brainfuck(&["compare", "--input-str", "Hi", "-c", program], b"");
+    let output = String::from_utf8(output).unwrap();
+    let levels: Vec<_> = output
+        .lines()
+        .skip(1)
+        .map(|line| (&line[..3], line.ends_with("  ok")))
+        .collect();
+    let instructions: Vec<u64> = output
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().nth(1).unwrap().parse().unwrap())
+        .collect();
+
+    assert_eq!(
+        levels,
+        [("-O0", true), ("-O1", true), ("-O2", true), ("-O3", true)]
+    );
+    // The start runs at compile time at -O3
+    assert!(instructions.windows(2).all(|pair| pair[1] <= pair[0]));
+    assert!(instructions[3] < instructions[2]);
+}
+
+#[test]
+fn programs_can_be_benchmarked() {
+    let output = run_brainfuck(
+        &["run", "--bench", "3", "--bench-json", "-c", "++[-]."],
+        b"",
+    );
+    let output = String::from_utf8(output).unwrap();
+
+    // The instructions of one run are counted, and the output is dropped
+    assert!(output.starts_with("{\"runs\":3,\"instructions\":3,\"min_ns\":"));
+    assert!(output.ends_with("}\n"));
+}
+
+#[test]
+fn breakpoints_can_be_labels() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["debug", "--break", "inner", "-c"])
+        .arg("+++ @label: outer\n[>+ @label inner\n<-]")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"c\nc\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    // The program runs up to the labelled command, once per iteration
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("3:1 \"<\"").count(), 3);
+    assert!(!stderr.contains("1:1"));
+}
+
+#[test]
+fn cells_can_be_watched() {
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["run", "--watch-cell", "1", "-c", "++[>+++<-]\n>."])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, [6]);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "watch: cell 1 written by \"[>+++<-]\" at <code>:1:3, value 6\n\
+         watch: cell 1 read by \".\" at <code>:2:2, value 6\n"
+    );
+}
+
+#[test]
+fn coverage_marks_the_commands_never_run() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-coverage-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let report = dir.join("coverage.txt");
+    let report_path = report.to_str().unwrap();
+    let program = "++[>+<-]\n>[<]\n[.]";
+    assert_eq!(
+        run_brainfuck(&["run", "--coverage", report_path, "-c", program], b""),
+        b""
+    );
+    assert_eq!(
+        fs::read_to_string(&report).unwrap(),
+        "        2 | ++[>+<-]\n\
+         \x20       1 | >[<]\n\
+         \x20       1 | [.]\n\
+         \x20         |  ^\n\
+         14 of 15 commands executed\n"
+    );
+
+    let report = dir.join("coverage.json");
+    let report_path = report.to_str().unwrap();
+    run_brainfuck(&["run", "--coverage", report_path, "-c", program], b"");
+    let report = fs::read_to_string(&report).unwrap();
+    assert!(report.starts_with(r#"{"source":"<code>","commands":15,"executed":14,"#));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn profiles_are_folded_by_loops() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-folded-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let folded = dir.join("run.folded");
+    let folded_path = folded.to_str().unwrap();
+    let program = "+++ @label: spin\n[->+++[>[-]<-]<]\n++[-]";
+    run_brainfuck(&["run", "--flamegraph", folded_path, "-c", program], b"");
+    assert_eq!(
+        fs::read_to_string(&folded).unwrap(),
+        "<code> 4\n\
+         <code>;spin 15\n\
+         <code>;spin;loop 2:7 36\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn traces_can_be_compared() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-traces-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let trace = |name: &str, args: &[&str]| {
+        let path = dir.join(name).to_str().unwrap().to_string();
+        let mut command = vec!["run", "--trace", &path];
+        command.extend_from_slice(args);
+        assert_eq!(run_brainfuck(&command, b""), b"\x18");
+        path
+    };
+    let program = "++++[>++[-]>+++[->++<]<<-]>>>.";
+    let unoptimized = trace("O0.trace", &["-O0", "-c", program]);
+    let optimized = trace("O2.trace", &["-O2", "-c", program]);
+    let edited = trace(
+        "edited.trace",
+        &["-O0", "-c", "++++[>+[-]>+++[->++<]<<-]>>>."],
+    );
+
+    // The optimized loops stand for the instructions they merge
+    let trace_diff = |a: &str, b: &str| {
+        Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["trace-diff", a, b])
+            .output()
+            .unwrap()
+    };
+    let output = trace_diff(&unoptimized, &optimized);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"the traces match\n");
+
+    let output = trace_diff(&unoptimized, &edited);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("O0.trace: step 9, offset 8, pointer 1, cell 2\n"));
+    assert!(stdout.ends_with("edited.trace: step 9, offset 8, pointer 1, cell 1\n"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn traces_can_be_replayed() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-replay-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let trace = dir.join("run.trace");
+    let trace_path = trace.to_str().unwrap();
+    let program = ">,[.>,]<[<]>[++.>]";
+    let args = ["--input-str", "abc", "-c", program];
+    assert_eq!(
+        run_brainfuck(&[&["run", "--trace", trace_path], &args[..]].concat(), b""),
+        b"abccde"
+    );
+
+    // The bytes read come from the trace
+    let replay = |args: &[&str], commands: &[u8]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["replay", "--trace", trace_path])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(commands).unwrap();
+        child.wait_with_output().unwrap()
+    };
+    let output = replay(&["-c", program], b"");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"abccde");
+
+    let output = replay(&["--to-step", "5", "-c", program], b"tape\ncontinue\n");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "step 5\n1:5 \">\"  pointer 1, cell 97\n(debug)  0 *97  0  0  0  0  0  0  0  0 \n(debug) "
+    );
+
+    // Without the merged increments, the steps differ
+    let output = replay(&["-O0", "-c", program], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().ends_with(
+        "trace: step 21, offset 15, pointer 1, cell 99\n\
+         replay: step 21, offset 14, pointer 1, cell 98\n"
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_runs_leave_a_core() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-core-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let core = dir.join("program.core");
+    let core = core.to_str().unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["run", "--core", core, "-c", "+++[>+>++<<-]\n<<"])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(4));
+
+    assert_eq!(
+        String::from_utf8(run_brainfuck(&["inspect", "--range", "0..4", core], b"")).unwrap(),
+        "<code>:2:1: pointer out of the tape\n\
+         highest cell 2, 0 bytes written\n\
+         last instructions:\n\
+         \x20 1:1 \"+++\"  pointer 0, cell 0\n\
+         \x20 1:4 \"[>+>++<<-]\"  pointer 0, cell 3\n\
+         \x20 2:1 \"<<\"  pointer 0, cell 0\n\
+         pointer at cell 0\n\
+         00000000  00 03 06 00                                      |....|\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tape_can_be_animated() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-animate-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let svg = dir.join("tape.svg");
+    let gif = dir.join("tape.gif");
+    for path in [&svg, &gif] {
+        let args = ["run", "-O0", "--animate", path.to_str().unwrap()];
+        let args = [&args[..], &["--animate-every", "4", "-c", "++[>+++<-]>."]].concat();
+        assert_eq!(run_brainfuck(&args, b""), [6]);
+    }
+
+    // A frame every 4 of the 17 instructions, and one at the end
+    let svg = fs::read_to_string(&svg).unwrap();
+    assert_eq!(svg.matches("<g ").count(), 6);
+    assert!(svg.contains(">step 16</text>"));
+    assert!(svg.contains(">step 17</text>"));
+    let gif = fs::read(&gif).unwrap();
+    assert!(gif.starts_with(b"GIF89a") && gif.ends_with(b"\x3b"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bounds_can_be_audited() {
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["run", "--audit-bounds", "--tape-size", "20"])
+        .args(["-c", "++++++++++[>+<-]>[>>+<[>]<<-]"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "loop 1:11-1:16  cells 0 to 1\n\
+         loop 1:18-1:29  cells 0 to 4\n\
+         loop 1:23-1:25  cells 1 to 4\n\
+         pointer from cell 0 to cell 4, of 20\n\
+         warning: the pointer reaches cell 4, 15 cells from the end of the tape\n"
+    );
+}
+
+#[test]
+fn outline_shows_the_loops() {
+    let program = "++[>++[>+<-]<-]\n>>[<]";
+    assert_eq!(
+        String::from_utf8(run_brainfuck(&["outline", "--profile", "-c", program], b"")).unwrap(),
+        "loop 1:3-1:15  depth 1, cell 0, pointer +0 per iteration, 1 run, 2 iterations\n\
+         \x20 loop 1:7-1:12  depth 2, cell 1, pointer +0 per iteration, 2 runs, 4 iterations\n\
+         loop 2:3-2:5  depth 1, cell 2, pointer -1 per iteration, 1 run, 1 iteration\n"
+    );
+}
+
+#[test]
+fn symbolic_finds_the_inputs_leaving_the_tape() {
+    let symbolic = |program: &str| {
+        Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+            .args(["symbolic", "--max-input", "2", "-c", program])
+            .output()
+            .unwrap()
+    };
+    let output = symbolic(",[>,]<<");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "underflow at 1:6 with input \"\"\n\
+         loop 1:2-1:5 entered with input \"\\x01\"\n\
+         6 paths explored\n"
+    );
+
+    // Without a fault, the loops never entered stand out
+    let output = symbolic(",----------[>+<[-]]>[-]>[+]");
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .ends_with("loop 1:25-1:27 never entered\n3 paths explored\n"));
+}
+
+#[test]
+fn watch_reruns_the_saved_sources() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-watch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("program.bf");
+    fs::write(&source_path, "++++++++[>++++++++<-]>+.").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("watch")
+        .arg(&source_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .env("NO_COLOR", "1")
+        .spawn()
+        .unwrap();
+
+    // The lines of the standard error, as they come
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in stderr.lines() {
+            let _ = sender.send(line.unwrap());
+        }
+    });
+    let wait_for = |expected: &str| loop {
+        let line = lines
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap_or_else(|_| panic!("watch never printed {:?}", expected));
+        if line.contains(expected) {
+            break;
+        }
+    };
+
+    wait_for("--- done");
+    fs::write(&source_path, "++++++++[>++++++++<-]>++.").unwrap();
+    wait_for("--- done");
+    // The errors are reported, and the next save is run again
+    fs::write(&source_path, "[").unwrap();
+    wait_for("unmatched '['");
+    fs::write(&source_path, "++++++++[>++++++++<-]>+++.").unwrap();
+    wait_for("--- done");
+
+    child.kill().unwrap();
+    let mut stdout = Vec::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_end(&mut stdout)
+        .unwrap();
+    child.wait().unwrap();
+    assert_eq!(stdout, b"ABC");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn repl_keeps_the_tape_between_the_lines() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-repl-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("two.bf"), "++").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("repl")
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .env("NO_COLOR", "1")
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"+++>++\n>+<\n:ptr\n:tape\n[\n:reset\n:load two.bf\n+.\n:tape\n:nope\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    // Each line runs on the tape left by the previous ones, up to :reset
+    let line = |bytes: &str, text: &str| format!("00000000  {:<47}  |{}|\n", bytes, text);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "> \n> \n> 1\n> pointer at cell 1\n{}> > > \n> \x03\n> pointer at cell 0\n{}> > \n",
+            line("03 02 01", "..."),
+            line("03", ".")
+        )
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error: unmatched '[' at column 1\n"));
+    assert!(stderr.ends_with("commands: :tape, :ptr, :reset, :load <file>\n"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn selftest_passes() {
+    // The backends needing tools are skipped, to keep the test fast
+    let output = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("selftest")
+        .env("CC", "/nonexistent")
+        .env("RUSTC", "/nonexistent")
+        .env("PATH", "")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout.starts_with("interpreter -O0  ok, "));
+    assert!(stdout.contains("\nc                skipped, "));
+    assert!(stdout.contains("\npy               skipped, "));
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        assert!(stdout.contains("\nelf              ok, 9 passed, 4 unsupported\n"));
+    }
+}
+
+#[test]
+fn project_config_gives_default_options() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-config-{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("bf.toml"), "eof = \"minus-one\" # for the tests\n").unwrap();
+    let source_path = dir.join("src/program.bf");
+    fs::write(&source_path, ",.").unwrap();
+    let source_path = source_path.to_str().unwrap();
+
+    assert_eq!(run_brainfuck(&["run", source_path], b""), [255]);
+    assert_eq!(
+        run_brainfuck(&["run", "--eof", "zero", source_path], b""),
+        [0]
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn optimization_levels_behave_the_same() {
+    let programs = [
+        "++++++[>++++++<-]>[>+>++>+++<<<-]>>.>.[-]<<[[-]>]",
+        "-.>+[->--<]>.",
+        ">+>++<<+>-<+>>+.<.<.",
+    ];
+    for program in programs.iter() {
+        let expected = run_brainfuck(&["run", "-c", program], b"");
+        for level in ["-O0", "-O1", "-O3"].iter() {
+            assert_eq!(
+                run_brainfuck(&["run", level, "-c", program], b""),
+                expected,
+                "{} changes the behavior of {:?}",
+                level,
+                program
+            );
+        }
+    }
+}
+
+#[test]
+fn obfuscated_programs_behave_the_same() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-obfuscate-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let hello_world = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/hello_world.bf");
+    let hello_world = fs::read_to_string(hello_world).unwrap();
+    let programs = [
+        (hello_world.as_str(), &b""[..]),
+        (",[.[-],]", b"abc"),
+        ("-.>+[->--<]>.", b""),
+        ("++++++[>++++++<-]>[>+>++>+++<<<-]>>.>.[-]<<[[-]>]", b""),
+    ];
+
+    for (i, (program, input)) in programs.iter().enumerate() {
+        let source_path = dir.join(format!("program{}.bf", i));
+        fs::write(&source_path, program).unwrap();
+        let source_path = source_path.to_str().unwrap();
+        let expected = run_brainfuck(&["run", source_path], input);
+
+        for seed in 0..8 {
+            let obfuscated_path = dir.join(format!("program{}-{}.bf", i, seed));
+            let obfuscated_path = obfuscated_path.to_str().unwrap();
+            let seed = seed.to_string();
+            run_brainfuck(
+                &[
+                    "build",
+                    "--obfuscate",
+                    "--seed",
+                    &seed,
+                    source_path,
+                    obfuscated_path,
+                ],
+                b"",
+            );
+            assert_eq!(
+                run_brainfuck(&["run", obfuscated_path], input),
+                expected,
+                "seed {} changes the behavior of {:?}",
+                seed,
+                program
+            );
+        }
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,591 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::io::{self, Cursor, Read};
+use std::mem;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use brainfuck::ast::{fold_children, walk, Folder, Node, NodeKind, Span, Visitor};
+use brainfuck::cell::BigUint;
+use brainfuck::engine::{AsyncByteRead, AsyncInterpreter, Ready};
+use brainfuck::lexer::{parse_source_in, Language, ParseError, ReadTokens};
+use brainfuck::symbolic::{explore, Limits};
+use brainfuck::vm::{
+    run_stats, Access, Bounds, Cell, Eof, Interpreter, InterpreterBuilder, IoError, Profile,
+    RunStats, RuntimeError, SharedBuffer, State, Stepper, MAX_DEPTH,
+};
+use brainfuck::{
+    compile_source, compile_source_at, compile_source_in, CompileError, Compiler, Config, Program,
+};
+
+#[test]
+fn interpreters_call_back_the_embedders() {
+    let program = Compiler::new().compile(",[>+<-]>[.-]").unwrap();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let (written, read) = (events.clone(), events.clone());
+    let (entered, exited, counted) = (events.clone(), events.clone(), events.clone());
+    let mut input = vec![3];
+    let mut interpreter = Interpreter::builder()
+        .on_input(move || {
+            read.borrow_mut().push(String::from("read"));
+            input.pop()
+        })
+        .on_output(move |byte| written.borrow_mut().push(format!("write {}", byte)))
+        .on_loop_enter(move |span, memory, index| {
+            let event = format!("enter {} on {}", span.start, memory[index]);
+            entered.borrow_mut().push(event)
+        })
+        .on_loop_exit(move |span, _, index| {
+            exited
+                .borrow_mut()
+                .push(format!("exit {} at {}", span.start, index))
+        })
+        .on_instruction(move |node, _, _| {
+            if node.span.start == 0 {
+                counted.borrow_mut().push(String::from("first"))
+            }
+        })
+        .build();
+    program.run(&mut interpreter).unwrap();
+
+    // The first loop is a single multiplication
+    assert_eq!(
+        *events.borrow(),
+        [
+            "first",
+            "read",
+            "enter 8 on 3",
+            "write 3",
+            "write 2",
+            "write 1",
+            "exit 8 at 1",
+        ]
+    );
+}
+
+#[test]
+fn interpreters_report_the_accesses_of_the_cells() {
+    let program = Compiler::new().opt_level(0).compile("+[>++<-]>.").unwrap();
+    let accesses = Rc::new(RefCell::new(Vec::new()));
+    let watched = accesses.clone();
+    let mut interpreter = Interpreter::builder()
+        .on_output(|_| {})
+        .on_access(move |access, index, value, span| {
+            if index == 1 {
+                watched.borrow_mut().push((access, *value, span.start));
+            }
+        })
+        .build();
+    program.run(&mut interpreter).unwrap();
+
+    assert_eq!(
+        *accesses.borrow(),
+        [
+            (Access::Write, 1, 3),
+            (Access::Write, 2, 4),
+            (Access::Read, 2, 9),
+        ]
+    );
+}
+
+#[test]
+fn interpreters_are_generic_over_the_cells() {
+    // 16^10, multiplied a cell at a time, then 16^10 - 1
+    let source = "+".repeat(16) + &"[>++++++++++++++++<-]>".repeat(9);
+    let program = Compiler::new().compile(&source).unwrap();
+    let minus_one = Compiler::new().compile("-").unwrap();
+
+    let mut wide = Interpreter::builder().cell(Cell::U32).build();
+    program.run(&mut wide).unwrap();
+    assert_eq!(wide.state().memory[9], 0);
+    minus_one.run(&mut wide).unwrap();
+    assert_eq!(wide.state().memory[9], u32::MAX);
+
+    let mut narrow = InterpreterBuilder::<u8>::new().build();
+    program.run(&mut narrow).unwrap();
+    minus_one.run(&mut narrow).unwrap();
+    assert_eq!(narrow.state().memory[9], u8::MAX);
+
+    let mut big = InterpreterBuilder::<BigUint>::new().build();
+    program.run(&mut big).unwrap();
+    assert_eq!(big.state().memory[9].to_string(), "1099511627776");
+    minus_one.run(&mut big).unwrap();
+    assert_eq!(big.state().memory[9].to_string(), "1099511627775");
+    assert_eq!(big.state().memory[8], BigUint::default());
+    minus_one.run(&mut big).unwrap();
+    assert_eq!(big.state().memory[9], BigUint::from(1099511627774u64));
+}
+
+#[test]
+fn extended_commands_use_the_storage() {
+    // 'A', then 'A' >> 1, then ('A' >> 1 << 1) | 'A'
+    let source = "++++++++[>++++++++<-]>+.$}.{|.~.@ the data after the code";
+    let program = Compiler::new()
+        .dialect(Language::Extended)
+        .compile(source)
+        .unwrap();
+    let mut narrow = InterpreterBuilder::<u8>::new().output(Vec::new()).build();
+    program.run(&mut narrow).unwrap();
+    assert_eq!(narrow.state().memory[1], !b'A');
+    assert_eq!(narrow.state().storage, b'A');
+
+    let mut big = InterpreterBuilder::<BigUint>::new()
+        .output(Vec::new())
+        .build();
+    program.run(&mut big).unwrap();
+    assert_eq!(big.state().memory[1], BigUint::from(0b0111110u8));
+}
+
+#[test]
+fn endless_recursions_stop_the_run() {
+    // The sources don't nest that deeply, but the trees built by hand may
+    let nested = |depth| {
+        let mut node = Node::new(NodeKind::Incr(-1), Span::default());
+        for _ in 0..depth {
+            node = Node::new(NodeKind::Loop(Box::new(node)), Span::default());
+        }
+        let incr = Node::new(NodeKind::Incr(1), Span::default());
+        Program {
+            ast: Node::new(NodeKind::Block(vec![incr, node]), Span::default()),
+            config: Config::default(),
+        }
+    };
+    let mut interpreter = Interpreter::builder().build();
+    nested(MAX_DEPTH).run(&mut interpreter).unwrap();
+    assert!(matches!(
+        nested(MAX_DEPTH + 1).run(&mut interpreter),
+        Err(RuntimeError::TooDeep(_))
+    ));
+
+    // Each call nests in a loop, both taking a frame of the native stack
+    for source in ["+(:):", "+(>+[:]):"] {
+        let program = Compiler::new()
+            .dialect(Language::Pbrain)
+            .compile(source)
+            .unwrap();
+        let mut interpreter = Interpreter::builder().build();
+        assert!(matches!(
+            program.run(&mut interpreter),
+            Err(RuntimeError::TooDeep(_))
+        ));
+
+        let mut stepper = Stepper::new(&program.ast);
+        let mut state = State::new(&Config::default());
+        assert!(matches!(
+            (0..).try_for_each(|_| stepper.step(&mut state, &Config::default()).map(|_| ())),
+            Err(RuntimeError::TooDeep(_))
+        ));
+    }
+}
+
+#[test]
+fn endless_runs_stop_at_the_limits() {
+    let program = |source| Program {
+        ast: compile_source_at(source, 0).unwrap(),
+        config: Config::default(),
+    };
+    let mut interpreter = Interpreter::builder().max_steps(4).build();
+    program("+-+-").run(&mut interpreter).unwrap();
+    assert!(matches!(
+        program("+-+-+").run(&mut interpreter),
+        Err(RuntimeError::StepLimit(_))
+    ));
+    // The iterations of the loops count as steps, the empty ones too
+    assert!(matches!(
+        program("+[]").run(&mut interpreter),
+        Err(RuntimeError::StepLimit(_))
+    ));
+
+    let mut interpreter = Interpreter::builder()
+        .timeout(Duration::from_millis(50))
+        .build();
+    assert!(matches!(
+        program("+[]").run(&mut interpreter),
+        Err(RuntimeError::Timeout(_))
+    ));
+    program("+[-]").run(&mut interpreter).unwrap();
+}
+
+#[test]
+fn outputs_stop_at_their_limit() {
+    let program = |source| Program {
+        ast: compile_source_at(source, 0).unwrap(),
+        config: Config::default(),
+    };
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .max_output(3)
+        .output(output.clone())
+        .build();
+    program("+...").run(&mut interpreter).unwrap();
+    assert_eq!(output.contents(), [1, 1, 1]);
+
+    // The bytes before the limit are written, the run stops on the next "."
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .max_output(3)
+        .output(output.clone())
+        .build();
+    match program("+[.]").run(&mut interpreter) {
+        Err(RuntimeError::OutputLimit(span)) => assert_eq!(span, Span { start: 2, end: 3 }),
+        result => panic!("the output isn't bounded: {:?}", result),
+    }
+    assert_eq!(output.contents(), [1, 1, 1]);
+}
+
+#[test]
+fn visitors_and_folders_reach_the_nested_nodes() {
+    let ast = compile_source_in("+(.[.>]):[.<]", Language::Pbrain, 0).unwrap();
+
+    // Count the writes, and the loops past which they are
+    #[derive(Default)]
+    struct Writes {
+        depth: usize,
+        deepest: usize,
+        count: usize,
+    }
+    impl Visitor for Writes {
+        fn visit(&mut self, node: &Node) {
+            match node.kind {
+                NodeKind::Write => {
+                    self.count += 1;
+                    self.deepest = self.deepest.max(self.depth);
+                }
+                NodeKind::Loop(_) | NodeKind::Procedure(_) => {
+                    self.depth += 1;
+                    walk(self, node);
+                    self.depth -= 1;
+                }
+                _ => walk(self, node),
+            }
+        }
+    }
+    let mut writes = Writes::default();
+    writes.visit(&ast);
+    assert_eq!((writes.count, writes.deepest), (3, 2));
+
+    // Read in place of each write, leaving the rest of the AST as it was
+    struct Reads;
+    impl Folder for Reads {
+        fn fold(&mut self, node: &Node) -> Node {
+            match node.kind {
+                NodeKind::Write => Node::new(NodeKind::Read, node.span),
+                _ => fold_children(self, node),
+            }
+        }
+    }
+    let folded = Reads.fold(&ast);
+    assert_eq!(folded.to_string(), "+(,[,>]):[,<]");
+    let mut writes = Writes::default();
+    writes.visit(&folded);
+    assert_eq!(writes.count, 0);
+    assert_eq!(Reads.fold(&folded).to_string(), folded.to_string());
+}
+
+#[test]
+fn unbounded_cells_run_the_same_at_each_optimization_level() {
+    // The decrements stop at 0, so "--+" doesn't decrement the cell, and
+    // neither does ">-<>+" gathered by offset
+    let source = "+++[->--+<]>.>-+.>-<->+.";
+    let outputs: Vec<Vec<u8>> = [0, 1, 2, 3]
+        .iter()
+        .map(|&level| {
+            let program = Compiler::new()
+                .opt_level(level)
+                .cells::<BigUint>()
+                .compile(source)
+                .unwrap();
+            let output = Rc::new(RefCell::new(Vec::new()));
+            let written = output.clone();
+            let mut big = InterpreterBuilder::<BigUint>::new()
+                .on_output(move |byte| written.borrow_mut().push(byte))
+                .build();
+            program.run(&mut big).unwrap();
+            output.take()
+        })
+        .collect();
+
+    assert_eq!(outputs[0], [1, 1, 1]);
+    assert_eq!(outputs[1], outputs[0]);
+    assert_eq!(outputs[2], outputs[0]);
+    assert_eq!(outputs[3], outputs[0]);
+}
+
+#[test]
+fn level_3_gathers_the_offsets_and_runs_the_start_at_compile_time() {
+    // The increments of each cell are gathered, from the closer end
+    let ast = compile_source_at(",>+<+>+<-<+>.", 3).unwrap();
+    assert_eq!(ast.to_string(), ",<+>>++<.");
+
+    // The start is replaced by its output and its tape, up to the input
+    let source = "++++++++[>++++++++<-]>+.+.<,.";
+    let program = Compiler::new().opt_level(3).compile(source).unwrap();
+    let expected = format!("{}.+.[-]>{}<,.", "+".repeat(65), "+".repeat(66));
+    assert_eq!(program.ast.to_string(), expected);
+    assert!(Compiler::new().opt_level(3).compile("+[]").is_ok());
+
+    // The profiles count the nodes replacing the start once each
+    let mut interpreter = InterpreterBuilder::<u32>::new()
+        .input(Cursor::new(b"x".to_vec()))
+        .output(Vec::new())
+        .build();
+    interpreter.state_mut().profile = Some(Profile::default());
+    program.run(&mut interpreter).unwrap();
+    let mut stats = RunStats::default();
+    run_stats(
+        &program.ast,
+        interpreter.state().profile.as_ref().unwrap(),
+        &mut stats,
+    );
+    assert_eq!((stats.instructions, stats.writes, stats.reads), (10, 3, 1));
+
+    let wrapping = Config {
+        bounds: Bounds::Wrap,
+        tape_size: 10,
+        ..Config::default()
+    };
+    let wide = Config {
+        cell: Cell::U16,
+        ..Config::default()
+    };
+    let cases = [
+        (Config::default(), source, &b"x"[..]),
+        (Config::default(), "+[>+<+]>.<<+", b""),
+        (wrapping, "<-[-<+>]<<.>+>,.", b"y"),
+        (wide, "-[->+<]>[->+>+<<]>.>>+[<]+.", b""),
+    ];
+    for (config, source, input) in cases.iter() {
+        let run = |level| {
+            let config = Config {
+                opt_level: level,
+                ..config.clone()
+            };
+            let program = Compiler::new().config(config).compile(source).unwrap();
+            // The errors are located at the nodes merged by the optimizer
+            program
+                .run_with_input(input)
+                .map_err(|error| mem::discriminant(&error))
+        };
+        for level in 1..=3 {
+            assert_eq!(
+                run(level),
+                run(0),
+                "-O{} runs {:?} differently",
+                level,
+                source
+            );
+        }
+    }
+}
+
+// A source read a byte at a time, failing past its end if it's cut
+struct Trickle<'a> {
+    source: &'a [u8],
+    cut: bool,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.source.split_first() {
+            Some((byte, rest)) if !buf.is_empty() => {
+                buf[0] = *byte;
+                self.source = rest;
+                Ok(1)
+            }
+            None if self.cut => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "cut")),
+            _ => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn streamed_sources_compile_as_the_whole_ones() {
+    let sources = [
+        ("#!/usr/bin/env brainfuck\n#+[->+<]>.", Language::Classic),
+        ("+(>#[-]<):,#!.", Language::Pbrain),
+        ("#!\n++$}.@ the data [ after the code", Language::Extended),
+    ];
+    for (source, language) in sources {
+        let trickle = || Trickle {
+            source: source.as_bytes(),
+            cut: false,
+        };
+        let tokens: Vec<_> = ReadTokens::new(trickle(), language)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(tokens, parse_source_in(source, language), "{:?}", source);
+
+        let compiler = Compiler::new().dialect(language);
+        assert_eq!(
+            compiler.compile_reader(trickle()).unwrap().ast.to_string(),
+            compiler.compile(source).unwrap().ast.to_string()
+        );
+    }
+
+    let compiler = Compiler::new();
+    let source = Trickle {
+        source: b"+[.[-]",
+        cut: false,
+    };
+    assert!(matches!(
+        compiler.compile_reader(source),
+        Err(CompileError::Parse(ParseError::Unmatched(1)))
+    ));
+    let source = Trickle {
+        source: b"+[-]",
+        cut: true,
+    };
+    match compiler.compile_reader(source) {
+        Err(CompileError::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof),
+        result => panic!("the error of the reader is lost: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn programs_run_in_many_threads_at_once() {
+    // Write the input, each byte incremented
+    let program = Arc::new(Compiler::new().compile(",[+.,]").unwrap());
+    let runs: Vec<_> = (1..=8u8)
+        .map(|n| {
+            let program = Arc::clone(&program);
+            thread::spawn(move || {
+                let output = SharedBuffer::default();
+                let mut interpreter = Interpreter::builder()
+                    .eof(Eof::Zero)
+                    .input(Cursor::new(vec![n; 1000]))
+                    .output(output.clone())
+                    .build();
+                program.run(&mut interpreter).unwrap();
+                output.contents()
+            })
+        })
+        .collect();
+    for (run, n) in runs.into_iter().zip(1..) {
+        assert_eq!(run.join().unwrap(), vec![n + 1; 1000]);
+    }
+}
+
+// An input pending before each of its bytes
+struct Slow(Vec<u8>, bool);
+
+impl AsyncByteRead for Slow {
+    fn poll_read_byte(&mut self, cx: &mut Context) -> Poll<Result<Option<u8>, IoError>> {
+        self.1 = !self.1;
+        if self.1 {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(self.0.pop()))
+        }
+    }
+}
+
+fn is_send<T: Send>(value: T) -> T {
+    value
+}
+
+#[test]
+fn engines_yield_to_the_executor() {
+    // Read three bytes, then write 10 * 10 * 10, in 1569 steps
+    let program = Compiler::new()
+        .opt_level(0)
+        .compile(",>,>,>++++++++++[>++++++++++[>++++++++++<-]<-]>>.")
+        .unwrap();
+    let interpreter = Interpreter::builder().cell(Cell::U32).build();
+    let mut engine =
+        AsyncInterpreter::new(interpreter, Slow(vec![3, 2, 1], false), Ready(Vec::new()))
+            .yield_every(100);
+
+    let mut run = Box::pin(is_send(program.run_async(&mut engine)));
+    let mut context = Context::from_waker(Waker::noop());
+    let mut pending = 0;
+    while run.as_mut().poll(&mut context).is_pending() {
+        pending += 1;
+    }
+    drop(run);
+
+    // Three reads, and a yield every 100 steps
+    assert_eq!(pending, 3 + 15);
+    assert_eq!(engine.memory()[..6], [1, 2, 3, 0, 0, 1000]);
+    assert_eq!(engine.output().0, [(1000 % 256) as u8]);
+}
+
+#[cfg(feature = "futures-io")]
+#[test]
+fn engines_run_on_the_streams_of_the_executors() {
+    // A slice and a vector are the AsyncRead and AsyncWrite of futures-io
+    let program = Compiler::new().compile(",[+.,]").unwrap();
+    let interpreter = Interpreter::builder().eof(Eof::Zero).build();
+    let mut engine = AsyncInterpreter::new(interpreter, &b"HAL"[..], Vec::new());
+    let mut run = Box::pin(program.run_async(&mut engine));
+    let mut context = Context::from_waker(Waker::noop());
+    assert!(matches!(
+        run.as_mut().poll(&mut context),
+        Poll::Ready(Ok(()))
+    ));
+    drop(run);
+    assert_eq!(engine.output(), b"IBM");
+}
+
+#[test]
+fn symbolic_runs_find_the_inputs_entering_the_loops() {
+    // The last loop is only entered when the byte read is "+"
+    let source = format!(">+<,{}[[-]>-<]>[-]", "-".repeat(43));
+    let ast = compile_source(&source).unwrap();
+    let limits = Limits {
+        max_input: 1,
+        bytes: b' '..=b'~',
+        ..Limits::default()
+    };
+    let analysis = explore(&ast, &Config::default(), &limits);
+    assert!(analysis.faults.is_empty());
+    assert!(analysis.complete);
+    assert_eq!(analysis.loops[0].1.as_deref(), Some(&b""[..]));
+    assert_eq!(analysis.loops[2].1.as_deref(), Some(&b"+"[..]));
+
+    // Without the "+", it never is
+    let limits = Limits {
+        bytes: b'0'..=b'9',
+        ..limits
+    };
+    let analysis = explore(&ast, &Config::default(), &limits);
+    assert_eq!(analysis.loops[2].1, None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn programs_and_tapes_round_trip_through_serde() {
+    use brainfuck::json::{program_to_json, Json};
+    use brainfuck::vm::State;
+
+    // The layouts are the ones of the JSON module
+    let program = Compiler::new().compile(",[.,]+").unwrap();
+    let text = serde_json::to_string(&program).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Json>(&text).unwrap(),
+        program_to_json(&program)
+    );
+
+    // Resume the program on a snapshot of its tape
+    let program: brainfuck::Program = serde_json::from_str(&text).unwrap();
+    let mut interpreter = Interpreter::builder()
+        .eof(Eof::Zero)
+        .input(Cursor::new(b"ab".to_vec()))
+        .output(SharedBuffer::default())
+        .build();
+    program.run(&mut interpreter).unwrap();
+    let snapshot = serde_json::to_string(interpreter.state()).unwrap();
+    let state: State = serde_json::from_str(&snapshot).unwrap();
+    assert_eq!(state.memory[..2], [1, 0]);
+    assert_eq!(state.written, 2);
+
+    let error = serde_json::from_str::<State>(r#"{"memory":[0],"index":1}"#);
+    assert!(error
+        .err()
+        .unwrap()
+        .to_string()
+        .contains("pointer 1 out of"));
+}
@@ -0,0 +1,548 @@
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Run the brainfuck binary with some arguments and input
+fn run_brainfuck(args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    output.stdout
+}
+
+/// Messages of the Debug Adapter or Language Server Protocol, each after its
+/// Content-Length header
+fn framed(messages: &[String]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for message in messages.iter() {
+        write!(
+            input,
+            "Content-Length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        )
+        .unwrap();
+    }
+    input
+}
+
+#[test]
+fn debug_adapter_stops_at_breakpoints() {
+    let hello_world = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data/hello_world.bf");
+    let hello_world = hello_world.to_str().unwrap().replace('\\', "\\\\");
+    let requests = [
+        String::from(r#"{"seq":1,"type":"request","command":"initialize"}"#),
+        format!(
+            r#"{{"seq":2,"type":"request","command":"launch","arguments":{{"program":"{}"}}}}"#,
+            hello_world
+        ),
+        format!(
+            r#"{{"seq":3,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[{{"line":5}}]}}}}"#,
+            hello_world
+        ),
+        String::from(r#"{"seq":4,"type":"request","command":"configurationDone"}"#),
+        String::from(
+            r#"{"seq":5,"type":"request","command":"variables","arguments":{"variablesReference":1}}"#,
+        ),
+        String::from(
+            r#"{"seq":6,"type":"request","command":"setBreakpoints","arguments":{"breakpoints":[]}}"#,
+        ),
+        String::from(r#"{"seq":7,"type":"request","command":"continue"}"#),
+        String::from(r#"{"seq":8,"type":"request","command":"disconnect"}"#),
+    ];
+    let output = String::from_utf8(run_brainfuck(&["dap"], &framed(&requests))).unwrap();
+
+    // The first breakpoint is on the first command of line 5
+    assert!(output.contains(r#"{"verified":true,"line":5,"column":9}"#));
+    assert!(output.contains(r#""reason":"breakpoint""#));
+    assert!(output.contains(r#"{"name":"[1]","value":"4","variablesReference":0}"#));
+    assert!(output.contains(r#""output":"Hello World!\n""#));
+    assert!(output.contains(r#""event":"terminated""#));
+}
+
+#[test]
+fn language_server_checks_and_formats() {
+    let open = |text: &str| {
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///a.bf","text":"{}"}}}}}}"#,
+            text
+        )
+    };
+    let at = |id: u32, method: &str, character: u32| {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"textDocument/{}","params":{{"textDocument":{{"uri":"file:///a.bf"}},"position":{{"line":0,"character":{}}},"options":{{}}}}}}"#,
+            id, method, character
+        )
+    };
+    let messages = [
+        String::from(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#),
+        open("+["),
+        open("++[->+++<]>.  +-"),
+        at(2, "hover", 4),
+        at(3, "documentHighlight", 10),
+        at(4, "formatting", 0),
+        String::from(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+    ];
+    let output = String::from_utf8(run_brainfuck(&["lsp"], &framed(&messages))).unwrap();
+
+    assert!(output.contains(r#""severity":1,"source":"brainfuck","message":"unmatched '['""#));
+    assert!(output.contains(r#""code":"useless-sequence""#));
+    assert!(output.contains("`[->+++<]` adds the cell 3 times to the cell at +1"));
+    assert!(output.contains(
+        r#"[{"range":{"start":{"line":0,"character":2},"end":{"line":0,"character":3}}},{"range":{"start":{"line":0,"character":9},"end":{"line":0,"character":10}}}]"#
+    ));
+    assert!(output.contains(r#""newText":"++[->+++<]>.\n""#));
+}
+
+/// Connect to a ZeroMQ socket of the Jupyter kernel, as a peer of some type
+fn zmtp_connect(port: u16, socket_type: &str) -> TcpStream {
+    // The kernel may not be listening yet
+    let mut stream = (0..100)
+        .find_map(|_| {
+            TcpStream::connect(("127.0.0.1", port))
+                .map_err(|_| thread::sleep(Duration::from_millis(50)))
+                .ok()
+        })
+        .unwrap();
+    let mut greeting = [0; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    stream.write_all(&greeting).unwrap();
+    let mut ready = vec![4, 0];
+    ready.extend_from_slice(b"\x05READY\x0bSocket-Type");
+    ready.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    ready.extend_from_slice(socket_type.as_bytes());
+    ready[1] = ready.len() as u8 - 2;
+    stream.write_all(&ready).unwrap();
+    stream.read_exact(&mut greeting).unwrap();
+    assert_eq!(&greeting[12..16], b"NULL");
+    // The READY of the kernel is skipped, for the next bytes to be messages
+    let mut ready = [0; 2];
+    stream.read_exact(&mut ready).unwrap();
+    stream.read_exact(&mut vec![0; ready[1] as usize]).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream
+}
+
+/// Signature of the parts of a Jupyter message, empty without a key
+fn jupyter_signature(key: &[u8], parts: &[&[u8]]) -> String {
+    if key.is_empty() {
+        return String::new();
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    for part in parts {
+        mac.update(part);
+    }
+    let digest = mac.finalize().into_bytes();
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Send a Jupyter message on a socket of the kernel, signed with a key
+fn jupyter_send(stream: &mut TcpStream, key: &[u8], msg_type: &str, content: &str) {
+    let header = format!(
+        r#"{{"msg_id":"{0}","session":"test","username":"test","msg_type":"{0}","version":"5.3"}}"#,
+        msg_type
+    );
+    let parts = [header.as_bytes(), b"{}", b"{}", content.as_bytes()];
+    let signature = jupyter_signature(key, &parts);
+    let mut frames = vec![b"<IDS|MSG>" as &[u8], signature.as_bytes()];
+    frames.extend(parts);
+    for (i, frame) in frames.iter().enumerate() {
+        let more = (i + 1 < frames.len()) as u8;
+        stream.write_all(&[more, frame.len() as u8]).unwrap();
+        stream.write_all(frame).unwrap();
+    }
+}
+
+/// Receive a Jupyter message from a socket of the kernel, as its header and
+/// its content, checking its signature
+fn jupyter_receive(stream: &mut TcpStream, key: &[u8]) -> (String, String) {
+    let mut frames = Vec::new();
+    loop {
+        let mut flags = [0; 2];
+        stream.read_exact(&mut flags).unwrap();
+        let mut frame = vec![0; flags[1] as usize];
+        if flags[0] & 2 != 0 {
+            let mut length = [0; 8];
+            length[0] = flags[1];
+            stream.read_exact(&mut length[1..]).unwrap();
+            frame = vec![0; u64::from_be_bytes(length) as usize];
+        }
+        stream.read_exact(&mut frame).unwrap();
+        if flags[0] & 4 == 0 {
+            frames.push(String::from_utf8(frame).unwrap());
+        }
+        if flags[0] & 5 == 0 {
+            let delimiter = frames
+                .iter()
+                .position(|frame| frame == "<IDS|MSG>")
+                .unwrap();
+            let parts: Vec<&[u8]> = frames[delimiter + 2..]
+                .iter()
+                .map(String::as_bytes)
+                .collect();
+            assert_eq!(frames[delimiter + 1], jupyter_signature(key, &parts));
+            return (frames[delimiter + 2].clone(), frames[delimiter + 5].clone());
+        }
+    }
+}
+
+#[test]
+fn jupyter_kernel_runs_cells() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-jupyter-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let ports: Vec<u16> = (0..5)
+        .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+        .collect::<Vec<_>>()
+        .iter()
+        .map(|listener| listener.local_addr().unwrap().port())
+        .collect();
+    let connection = dir.join("connection.json");
+    fs::write(
+        &connection,
+        format!(
+            r#"{{"transport":"tcp","ip":"127.0.0.1","shell_port":{},"iopub_port":{},"stdin_port":{},"control_port":{},"hb_port":{},"key":"","signature_scheme":"hmac-sha256"}}"#,
+            ports[0], ports[1], ports[2], ports[3], ports[4]
+        ),
+    )
+    .unwrap();
+    // Without a key, the kernel only starts when told to
+    let refused = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("jupyter-kernel")
+        .arg(&connection)
+        .output()
+        .unwrap();
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("--unsigned"));
+    let mut kernel = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["jupyter-kernel", "--persistent-tape", "--unsigned"])
+        .arg(&connection)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut iopub = zmtp_connect(ports[1], "SUB");
+    let mut shell = zmtp_connect(ports[0], "DEALER");
+    // The kernel publishes once the subscription is seen, which the
+    // notebooks wait for by asking the kernel info
+    iopub
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    loop {
+        jupyter_send(&mut shell, b"", "kernel_info_request", "{}");
+        let (header, content) = jupyter_receive(&mut shell, b"");
+        assert!(header.contains(r#""msg_type":"kernel_info_reply""#));
+        assert!(content.contains(r#""implementation":"brainfuck""#));
+        let mut flags = [0];
+        if iopub.peek(&mut flags).is_ok() {
+            break;
+        }
+    }
+    iopub
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+
+    // The tape is kept from a cell to the next
+    jupyter_send(
+        &mut shell,
+        b"",
+        "execute_request",
+        r#"{"code":"++++++++[>++++++++<-]>"}"#,
+    );
+    jupyter_receive(&mut shell, b"");
+    jupyter_send(&mut shell, b"", "execute_request", r#"{"code":"+.+."}"#);
+    let (header, content) = jupyter_receive(&mut shell, b"");
+    assert!(header.contains(r#""msg_type":"execute_reply""#));
+    assert_eq!(
+        content,
+        r#"{"status":"ok","execution_count":2,"user_expressions":{},"payload":[]}"#
+    );
+    let stream = (0..100)
+        .map(|_| jupyter_receive(&mut iopub, b""))
+        .find(|(header, _)| header.contains(r#""msg_type":"stream""#))
+        .unwrap();
+    assert_eq!(stream.1, r#"{"name":"stdout","text":"AB"}"#);
+
+    // The lines are published as they end
+    jupyter_send(
+        &mut shell,
+        b"",
+        "execute_request",
+        r#"{"code":".>++++++++++.<."}"#,
+    );
+    jupyter_receive(&mut shell, b"");
+    let streams: Vec<_> = (0..100)
+        .map(|_| jupyter_receive(&mut iopub, b""))
+        .filter(|(header, _)| header.contains(r#""msg_type":"stream""#))
+        .take(2)
+        .map(|(_, content)| content)
+        .collect();
+    assert_eq!(
+        streams,
+        [
+            r#"{"name":"stdout","text":"B\n"}"#,
+            r#"{"name":"stdout","text":"B"}"#
+        ]
+    );
+
+    jupyter_send(&mut shell, b"", "execute_request", r#"{"code":"<<<."}"#);
+    let (_, content) = jupyter_receive(&mut shell, b"");
+    assert_eq!(
+        content,
+        r#"{"status":"error","execution_count":4,"ename":"Error","evalue":"1:1: pointer out of the tape","traceback":["1:1: pointer out of the tape"]}"#
+    );
+
+    // A cell looping forever is stopped by an interrupt
+    jupyter_send(&mut shell, b"", "execute_request", r#"{"code":"+[]"}"#);
+    let mut control = zmtp_connect(ports[3], "DEALER");
+    jupyter_send(&mut control, b"", "interrupt_request", "{}");
+    let (_, content) = jupyter_receive(&mut shell, b"");
+    assert_eq!(
+        content,
+        r#"{"status":"error","execution_count":5,"ename":"Error","evalue":"1:2: interrupted","traceback":["1:2: interrupted"]}"#
+    );
+    let (header, _) = jupyter_receive(&mut control, b"");
+    assert!(header.contains(r#""msg_type":"interrupt_reply""#));
+
+    jupyter_send(
+        &mut control,
+        b"",
+        "shutdown_request",
+        r#"{"restart":false}"#,
+    );
+    let (header, _) = jupyter_receive(&mut control, b"");
+    assert!(header.contains(r#""msg_type":"shutdown_reply""#));
+    assert!(kernel.wait().unwrap().success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn jupyter_kernel_checks_the_signatures() {
+    let dir = env::temp_dir().join(format!("brainfuck-test-signed-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let ports: Vec<u16> = (0..5)
+        .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+        .collect::<Vec<_>>()
+        .iter()
+        .map(|listener| listener.local_addr().unwrap().port())
+        .collect();
+    let connection = dir.join("connection.json");
+    let write_connection = |shell_port: u32| {
+        fs::write(
+            &connection,
+            format!(
+                r#"{{"transport":"tcp","ip":"127.0.0.1","shell_port":{},"iopub_port":{},"stdin_port":{},"control_port":{},"hb_port":{},"key":"secret","signature_scheme":"hmac-sha256"}}"#,
+                shell_port, ports[1], ports[2], ports[3], ports[4]
+            ),
+        )
+        .unwrap();
+    };
+    write_connection(70000);
+    let refused = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("jupyter-kernel")
+        .arg(&connection)
+        .output()
+        .unwrap();
+    assert_eq!(refused.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("invalid shell_port 70000"));
+
+    write_connection(ports[0] as u32);
+    let mut kernel = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .arg("jupyter-kernel")
+        .arg(&connection)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // The messages signed with another key, or not signed, are dropped
+    let mut shell = zmtp_connect(ports[0], "DEALER");
+    jupyter_send(&mut shell, b"", "comm_info_request", "{}");
+    jupyter_send(&mut shell, b"guess", "history_request", "{}");
+    jupyter_send(&mut shell, b"secret", "kernel_info_request", "{}");
+    let (header, _) = jupyter_receive(&mut shell, b"secret");
+    assert!(header.contains(r#""msg_type":"kernel_info_reply""#));
+
+    let mut control = zmtp_connect(ports[3], "DEALER");
+    jupyter_send(
+        &mut control,
+        b"secret",
+        "shutdown_request",
+        r#"{"restart":false}"#,
+    );
+    let (header, _) = jupyter_receive(&mut control, b"secret");
+    assert!(header.contains(r#""msg_type":"shutdown_reply""#));
+    assert!(kernel.wait().unwrap().success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Read a frame of a WebSocket, from the server and so unmasked, as its
+/// opcode and its payload
+fn websocket_receive(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head).unwrap();
+    assert!(head[1] < 126);
+    let mut payload = vec![0; head[1] as usize];
+    stream.read_exact(&mut payload).unwrap();
+    (head[0] & 0x0f, payload)
+}
+
+/// Write a frame of a WebSocket, masked as the clients do
+fn websocket_send(stream: &mut TcpStream, opcode: u8, payload: &[u8]) {
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(
+        payload
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4]),
+    );
+    stream.write_all(&frame).unwrap();
+}
+
+/// Start a server of a program on WebSockets, returning its address
+fn serve(args: &[&str]) -> (Child, String) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let address = format!("127.0.0.1:{}", port);
+    let server = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(["serve", "--listen", &address])
+        .args(args)
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    (server, address)
+}
+
+/// Connect to a server, once it listens
+fn websocket_connect(address: &str) -> TcpStream {
+    let stream = (0..100)
+        .find_map(|_| {
+            TcpStream::connect(address)
+                .map_err(|_| thread::sleep(Duration::from_millis(50)))
+                .ok()
+        })
+        .unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream
+}
+
+/// Open a WebSocket on a server, checking the answer of its handshake
+fn websocket_open(address: &str) -> TcpStream {
+    // The key and its answer are the ones of the example of RFC 6455
+    let mut client = websocket_connect(address);
+    client
+        .write_all(
+            b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+              Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+              Sec-WebSocket-Version: 13\r\n\r\n",
+        )
+        .unwrap();
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        client.read_exact(&mut byte).unwrap();
+        response.push(byte[0]);
+    }
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 101 "));
+    assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    client
+}
+
+#[test]
+fn programs_can_be_served_on_websockets() {
+    let (mut server, address) = serve(&["-c", "++++++++++.,[+.,]<"]);
+    let mut client = websocket_open(&address);
+
+    // The output is sent before each read, and each message is read
+    assert_eq!(websocket_receive(&mut client), (2, b"\n".to_vec()));
+    websocket_send(&mut client, 1, b"ab");
+    assert_eq!(websocket_receive(&mut client), (2, b"b".to_vec()));
+    assert_eq!(websocket_receive(&mut client), (2, b"c".to_vec()));
+    websocket_send(&mut client, 2, b"\0");
+    assert_eq!(
+        websocket_receive(&mut client),
+        (1, b"<code>:1:18: pointer out of the tape".to_vec())
+    );
+    assert_eq!(
+        websocket_receive(&mut client),
+        (8, 1011u16.to_be_bytes().to_vec())
+    );
+    websocket_send(&mut client, 8, &1011u16.to_be_bytes());
+
+    // The frames too big, or not masked, close the connection
+    let mut client = websocket_open(&address);
+    assert_eq!(websocket_receive(&mut client), (2, b"\n".to_vec()));
+    client.write_all(&[0x82, 0xff]).unwrap();
+    client.write_all(&(2u64 << 20).to_be_bytes()).unwrap();
+    assert_eq!(
+        websocket_receive(&mut client),
+        (8, 1009u16.to_be_bytes().to_vec())
+    );
+    let mut client = websocket_open(&address);
+    assert_eq!(websocket_receive(&mut client), (2, b"\n".to_vec()));
+    client.write_all(&[0x82, 1, b'a']).unwrap();
+    assert_eq!(
+        websocket_receive(&mut client),
+        (8, 1002u16.to_be_bytes().to_vec())
+    );
+
+    // Other requests are refused, as are the requests with too many headers
+    let mut client = websocket_connect(&address);
+    client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+    let mut client = websocket_connect(&address);
+    client
+        .write_all(format!("GET / HTTP/1.1\r\n{}", "X: y\r\n".repeat(101)).as_bytes())
+        .unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    // The runs are stopped past their steps
+    let (mut server, address) = serve(&["--max-steps", "1000", "-c", "+[]"]);
+    let mut client = websocket_open(&address);
+    assert_eq!(
+        websocket_receive(&mut client),
+        (1, b"<code>:1:2: step limit reached".to_vec())
+    );
+    assert_eq!(
+        websocket_receive(&mut client),
+        (8, 1011u16.to_be_bytes().to_vec())
+    );
+    server.kill().unwrap();
+    server.wait().unwrap();
+}
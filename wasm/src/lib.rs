@@ -0,0 +1,88 @@
+//! Exports of the brainfuck compiler and interpreter to JavaScript, through
+//! wasm-bindgen
+//!
+//! `compile` parses and optimizes a source into a `Machine`, which runs a
+//! node at a time with `step` while `tape_view` shows its cells, so a web
+//! page runs brainfuck without a server.
+
+use std::io;
+
+use brainfuck::vm::{SharedBuffer, State, Stepper};
+use brainfuck::{Compiler, Program};
+use js_sys::Uint32Array;
+use wasm_bindgen::prelude::*;
+
+/// A compiled program, and its run
+#[wasm_bindgen]
+pub struct Machine {
+    program: Program,
+    state: State,
+    stepper: Stepper,
+    output: SharedBuffer,
+}
+
+/// Compile a brainfuck source at some optimization level, from 0 to 3,
+/// ready to run on some input
+#[wasm_bindgen]
+pub fn compile(source: &str, opt_level: u8, input: &[u8]) -> Result<Machine, JsError> {
+    let program = Compiler::new()
+        .opt_level(opt_level.min(3))
+        .compile(source)
+        .map_err(|error| JsError::new(&error.to_string()))?;
+    let mut machine = Machine {
+        state: State::new(&program.config),
+        stepper: Stepper::new(&program.ast),
+        program,
+        output: SharedBuffer::default(),
+    };
+    machine.reset(input);
+    Ok(machine)
+}
+
+#[wasm_bindgen]
+impl Machine {
+    /// Start the program over, on a blank tape reading some input
+    pub fn reset(&mut self, input: &[u8]) {
+        self.output = SharedBuffer::default();
+        self.state.reset();
+        self.state.input = Box::new(io::Cursor::new(input.to_vec()));
+        self.state.output = Box::new(self.output.clone());
+        self.stepper = Stepper::new(&self.program.ast);
+    }
+
+    /// Run the next node, returning whether the program goes on
+    pub fn step(&mut self) -> Result<bool, JsError> {
+        self.stepper
+            .step(&mut self.state, &self.program.config)
+            .map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    /// Cells of the tape, seen in the memory of the module without a copy
+    ///
+    /// The view is only valid until the next call into the module, which may
+    /// move the tape or grow the memory, so it is taken again at each step.
+    pub fn tape_view(&self) -> Uint32Array {
+        // Safety: no allocation happens before the view is handed to
+        // JavaScript, which may only use it until its next call
+        unsafe { Uint32Array::view(self.tape()) }
+    }
+
+    /// Index of the cell under the pointer
+    #[wasm_bindgen(getter)]
+    pub fn pointer(&self) -> usize {
+        self.state.index
+    }
+
+    /// Output of the program since it started
+    #[wasm_bindgen(getter)]
+    pub fn output(&self) -> Vec<u8> {
+        self.output.contents()
+    }
+}
+
+impl Machine {
+    /// Cells of the tape
+    pub fn tape(&self) -> &[u32] {
+        &self.state.memory
+    }
+}
@@ -0,0 +1,143 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use brainfuck_wasm::compile;
+
+/// Whether the standard library of wasm32 is installed
+fn wasm32_installed() -> bool {
+    let output = Command::new("rustc").args(["--print", "sysroot"]).output();
+    match output {
+        Ok(output) => Path::new(String::from_utf8_lossy(&output.stdout).trim())
+            .join("lib/rustlib/wasm32-unknown-unknown")
+            .exists(),
+        Err(_) => false,
+    }
+}
+
+/// Tell that a test is skipped, past the capture of the output of the tests
+fn skip(test: &str, reason: &str) {
+    let _ = writeln!(io::stderr(), "{} skipped: {}", test, reason);
+}
+
+/// Output of a command, or None when it isn't installed
+fn run(command: &mut Command) -> Option<String> {
+    let output = match command.output() {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+        output => output.unwrap(),
+    };
+    assert!(
+        output.status.success(),
+        "{:?} fails:\n{}",
+        command,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(String::from_utf8(output.stdout).unwrap())
+}
+
+const SCRIPT: &str = r#"
+const { compile } = require("./pkg/brainfuck_wasm.js");
+
+const machine = compile(",[.,]++", 2, new TextEncoder().encode("hi\0"));
+let steps = 0;
+while (machine.step()) {
+  steps++;
+}
+console.log(new TextDecoder().decode(machine.output), steps);
+console.log(Array.from(machine.tape_view().slice(0, 2)), machine.pointer);
+
+machine.reset(new TextEncoder().encode("!"));
+for (let i = 0; i < 3; i++) {
+  machine.step();
+}
+console.log(new TextDecoder().decode(machine.output));
+
+for (const [source, input] of [["+[.]]", ""], ["<", ""]]) {
+  try {
+    const machine = compile(source, 0, new TextEncoder().encode(input));
+    while (machine.step()) {}
+  } catch (error) {
+    console.log(error.message);
+  }
+}
+"#;
+
+#[test]
+fn machines_step_through_programs() {
+    let mut machine = compile(",[.,]++", 2, b"hi\0").ok().unwrap();
+    let mut steps = 0;
+    while machine.step().ok().unwrap() {
+        steps += 1;
+    }
+    assert_eq!((machine.output(), steps), (b"hi".to_vec(), 8));
+    assert_eq!(machine.tape()[..2], [2, 0]);
+    assert_eq!(machine.pointer(), 0);
+
+    // Start over, and stop at the first write
+    machine.reset(b"!");
+    for _ in 0..3 {
+        machine.step().ok().unwrap();
+    }
+    assert_eq!(machine.output(), b"!");
+}
+
+#[test]
+fn exports_run_in_node() {
+    if !wasm32_installed() {
+        return skip("exports_run_in_node", "the wasm32 target is missing");
+    }
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("brainfuck-wasm");
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo"));
+    run(Command::new(cargo)
+        .args([
+            "build",
+            "--target",
+            "wasm32-unknown-unknown",
+            "--manifest-path",
+        ])
+        .arg(manifest)
+        .arg("--target-dir")
+        .arg(dir.join("target")))
+    .unwrap();
+
+    // The exports of wasm-bindgen, before their JavaScript bindings, which
+    // end with a hash of the crate
+    let wasm = dir.join("target/wasm32-unknown-unknown/debug/brainfuck_wasm.wasm");
+    let exports = run(Command::new("node").arg("-e").arg(format!(
+        "const module = new WebAssembly.Module(require('fs').readFileSync({:?}));\
+         console.log(WebAssembly.Module.exports(module).map((e) => e.name).join(' '))",
+        wasm
+    )));
+    let exports = match exports {
+        Some(exports) => exports,
+        None => return skip("exports_run_in_node", "node is missing"),
+    };
+    for name in ["compile", "machine_step", "machine_tape_view"].iter() {
+        let prefix = format!("{}_", name);
+        assert!(exports
+            .split_whitespace()
+            .any(|export| export.starts_with(&prefix)));
+    }
+
+    let bound = run(Command::new("wasm-bindgen")
+        .args(["--target", "nodejs", "--out-dir"])
+        .arg(dir.join("pkg"))
+        .arg(&wasm));
+    if bound.is_none() {
+        return skip("exports_run_in_node", "wasm-bindgen is missing");
+    }
+    fs::write(dir.join("test.js"), SCRIPT).unwrap();
+    let output = match run(Command::new("node").arg(dir.join("test.js"))) {
+        Some(output) => output,
+        None => return skip("exports_run_in_node", "node is missing"),
+    };
+    assert_eq!(
+        output,
+        "hi 8\n[ 2, 0 ] 0\n!\nunmatched bracket at byte 4\n\
+         pointer out of the tape at byte 0 of the source\n"
+    );
+}